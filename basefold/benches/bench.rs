@@ -2,27 +2,31 @@ extern crate criterion;
 use criterion::*;
 
 use basefold::{prover::Prover, verifier::Verifier};
-use util::{
-    algebra::{
-        coset::Coset,
-        field::{mersenne61_ext::Mersenne61Ext, MyField},
-        polynomial::MultilinearPolynomial,
-    },
-    random_oracle::RandomOracle,
+use util::algebra::{
+    coset::Coset,
+    field::{mersenne61_ext::Mersenne61Ext, MyField},
+    polynomial::MultilinearPolynomial,
 };
 
-use util::{CODE_RATE, SECURITY_BITS, STEP};
+use util::parallel::for_each_pool;
+use util::pcs::PcsParams;
+use util::security::Regime;
+
+// BaseFold's soundness is only proven up to the unique decoding radius.
+fn unique_decoding_params<T: MyField>(variable_num: usize) -> PcsParams {
+    PcsParams::default().with_security::<T>(variable_num, 100, Regime::UniqueDecoding)
+}
+
 fn commit<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = unique_decoding_params::<T>(variable_num);
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(
-        variable_num,
-        (SECURITY_BITS as f32 / (2.0 / (1.0 + 0.5_f32.powi(CODE_RATE as i32))).log2()).ceil()
-            as usize,
-    );
 
     criterion.bench_function(
         &format!("basefold {} commit {}", T::FIELD_NAME, variable_num),
@@ -30,7 +34,7 @@ fn commit<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
             b.iter_batched(
                 || polynomial.clone(),
                 |p| {
-                    let prover = Prover::new(variable_num, &interpolate_cosets, p, &oracle, STEP);
+                    let prover = Prover::new(variable_num, &interpolate_cosets, p, params);
                     let _commit = prover.commit_polynomial();
                 },
                 BatchSize::SmallInput,
@@ -47,20 +51,21 @@ fn bench_commit(c: &mut Criterion) {
 }
 
 fn open<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = unique_decoding_params::<T>(variable_num);
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(
-        variable_num,
-        (SECURITY_BITS as f32 / (2.0 / (1.0 + 0.5_f32.powi(CODE_RATE as i32))).log2()).ceil()
-            as usize,
-    );
-    let prover = Prover::new(variable_num, &interpolate_cosets, polynomial, &oracle, STEP);
+    let prover = Prover::new(variable_num, &interpolate_cosets, polynomial, params);
     let commit = prover.commit_polynomial();
-    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle, STEP);
-    let point = verifier.get_open_point();
+    let point = (0..variable_num)
+        .map(|_| T::random_element())
+        .collect::<Vec<_>>();
+    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &point, params);
 
     criterion.bench_function(
         &format!("basefold {} open {}", T::FIELD_NAME, variable_num),
@@ -87,20 +92,27 @@ fn bench_open(c: &mut Criterion) {
 }
 
 fn verify<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = unique_decoding_params::<T>(variable_num);
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(
+    let mut prover = Prover::new(
         variable_num,
-        (SECURITY_BITS as f32 / (2.0 / (1.0 + 0.5_f32.powi(CODE_RATE as i32))).log2()).ceil()
-            as usize,
+        &interpolate_cosets,
+        polynomial.clone(),
+        params,
     );
-    let mut prover = Prover::new(variable_num, &interpolate_cosets, polynomial, &oracle, STEP);
     let commit = prover.commit_polynomial();
-    let mut verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle, STEP);
-    let point = verifier.get_open_point();
+    let point = (0..variable_num)
+        .map(|_| T::random_element())
+        .collect::<Vec<_>>();
+    let evaluation = polynomial.evaluate(&point);
+    let mut verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &point, params);
     prover.send_evaluation(&mut verifier, &point);
     prover.prove(&point);
     prover.commit_foldings(&mut verifier);
@@ -110,7 +122,7 @@ fn verify<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
         &format!("basefold {} verify {}", T::FIELD_NAME, variable_num),
         move |b| {
             b.iter(|| {
-                assert!(verifier.verify(&proof));
+                assert!(verifier.verify(&proof, evaluation).is_ok());
             })
         },
     );
@@ -123,10 +135,56 @@ fn bench_verify(c: &mut Criterion) {
     }
 }
 
+// Prover scaling by thread count; proofs are identical for every pool size.
+fn bench_open_threads(c: &mut Criterion) {
+    let variable_num = 16;
+    let params = unique_decoding_params::<Mersenne61Ext>(variable_num);
+    let polynomial = MultilinearPolynomial::<Mersenne61Ext>::random_polynomial(variable_num);
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        Mersenne61Ext::from_int(1),
+    )];
+    for i in 1..variable_num + 1 {
+        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+    }
+    let point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
+    for_each_pool(|threads, pool| {
+        c.bench_function(
+            &format!("basefold prove {:02} threads {}", variable_num, threads),
+            |b| {
+                b.iter(|| {
+                    pool.install(|| {
+                        let mut prover = Prover::new(
+                            variable_num,
+                            &interpolate_cosets,
+                            polynomial.clone(),
+                            params,
+                        );
+                        let commit = prover.commit_polynomial();
+                        let mut verifier = Verifier::new(
+                            variable_num,
+                            &interpolate_cosets,
+                            commit,
+                            &point,
+                            params,
+                        );
+                        prover.send_evaluation(&mut verifier, &point);
+                        prover.prove(&point);
+                        prover.commit_foldings(&mut verifier);
+                        let _ = prover.query();
+                    })
+                })
+            },
+        );
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_commit, bench_open, bench_verify
+    targets = bench_commit, bench_open, bench_verify, bench_open_threads
 }
 
 criterion_main!(benches);
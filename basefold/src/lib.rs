@@ -1,52 +1,402 @@
+use std::mem::size_of;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use util::{
+    algebra::{field::MyField, polynomial::Polynomial},
+    merkle_tree::MerkleCap,
+    query_result::QueryResult,
+    serialization::{
+        caps_size, deserialize_with, field_sequence_size, serialize_with, Reader, Serializable,
+        SerializationError, Writer, LENGTH_SIZE, NONCE_SIZE, VERSION_SIZE,
+    },
+};
+
+pub mod pcs;
 pub mod prover;
 pub mod verifier;
 
+// Non-interactive transcript of an opening: everything `commit_foldings`
+// and `query` would hand to the verifier.
+#[derive(Clone)]
+pub struct Proof<T: MyField> {
+    pub evaluation: T,
+    pub merkle_root: Vec<MerkleCap>,
+    pub sumcheck_values: Vec<(T, T, T)>,
+    pub final_poly: Polynomial<T>,
+    pub query_result: Vec<QueryResult<T>>,
+    pub pow_nonce: u64,
+}
+
+impl<T: MyField> Proof<T> {
+    // Exact length of `encode`.
+    pub fn size(&self) -> usize {
+        VERSION_SIZE
+            + size_of::<T>()
+            + caps_size(&self.merkle_root)
+            + LENGTH_SIZE
+            + self.sumcheck_values.len() * 3 * size_of::<T>()
+            + field_sequence_size::<T>(self.final_poly.coefficients().len())
+            + self
+                .query_result
+                .iter()
+                .fold(LENGTH_SIZE, |acc, x| acc + x.proof_size())
+            + NONCE_SIZE
+    }
+}
+
+impl<T: MyField> Serializable for Proof<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_field(&self.evaluation);
+        writer.write_caps(&self.merkle_root);
+        writer.write_length(self.sumcheck_values.len());
+        for (x_0, x_1, x_2) in &self.sumcheck_values {
+            writer.write_field(x_0);
+            writer.write_field(x_1);
+            writer.write_field(x_2);
+        }
+        writer.write_polynomial(&self.final_poly);
+        writer.write_all(&self.query_result);
+        writer.write_u64(self.pow_nonce);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let evaluation = reader.read_field()?;
+        let merkle_root = reader.read_caps()?;
+        let sumcheck_values = (0..reader.read_length(3 * size_of::<T>())?)
+            .map(|_| {
+                Ok((
+                    reader.read_field()?,
+                    reader.read_field()?,
+                    reader.read_field()?,
+                ))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Proof {
+            evaluation,
+            merkle_root,
+            sumcheck_values,
+            final_poly: reader.read_polynomial()?,
+            query_result: reader.read_all()?,
+            pow_nonce: reader.read_u64()?,
+        })
+    }
+}
+
+impl<T: MyField> Serialize for Proof<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for Proof<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem::size_of;
 
-    use crate::{prover::Prover, verifier::Verifier};
+    use crate::{pcs::BaseFold, prover::Prover, verifier::Verifier, Proof};
     use csv::Writer;
+    use util::security::Regime;
     use util::{
         algebra::{
             coset::Coset,
-            field::{mersenne61_ext::Mersenne61Ext, MyField},
-            polynomial::MultilinearPolynomial,
+            field::{goldilocks64ext::Goldilocks64Ext, mersenne61_ext::Mersenne61Ext, MyField},
+            polynomial::{MultilinearPolynomial, Polynomial},
         },
-        merkle_tree::MERKLE_ROOT_SIZE,
-        random_oracle::RandomOracle,
+        error::VerificationError,
+        merkle_tree::{Blake3Algorithm, HashChoice, MerkleHasher, MERKLE_ROOT_SIZE},
+        parallel::for_each_pool,
+        pcs::{Commitment, MultilinearPCS, PcsParams, SetupParams},
+        schedule::FoldingSchedule,
+        serialization::{Serializable, SerializationError, NONCE_SIZE},
     };
-    use util::{CODE_RATE, SECURITY_BITS, STEP};
 
     fn output_proof_size(variable_num: usize) -> usize {
         let total_round = variable_num;
+        // BaseFold's soundness is only proven up to the unique decoding radius.
+        let params = PcsParams::default().with_security::<Mersenne61Ext>(
+            variable_num,
+            100,
+            Regime::UniqueDecoding,
+        );
+        let schedule = params.folding(total_round);
         let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
         let mut interpolate_cosets = vec![Coset::new(
-            1 << (variable_num + CODE_RATE),
+            1 << (variable_num + params.code_rate),
             Mersenne61Ext::from_int(1),
         )];
         for i in 1..variable_num + 1 {
             interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
         }
-        let oracle = RandomOracle::new(
-            total_round,
-            (SECURITY_BITS as f32 / (2.0 / (1.0 + 0.5_f32.powi(CODE_RATE as i32))).log2()).ceil()
-                as usize,
-        );
-        let mut prover = Prover::new(total_round, &interpolate_cosets, polynomial, &oracle, STEP);
+        let mut prover = Prover::new(total_round, &interpolate_cosets, polynomial.clone(), params);
         let commit = prover.commit_polynomial();
-        let mut verifier = Verifier::new(total_round, &interpolate_cosets, commit, &oracle, STEP);
-        let point = verifier.get_open_point();
+        let point = (0..total_round)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let mut verifier = Verifier::new(total_round, &interpolate_cosets, commit, &point, params);
         prover.send_evaluation(&mut verifier, &point);
         prover.prove(&point);
         prover.commit_foldings(&mut verifier);
         let proof = prover.query();
-        assert!(verifier.verify(&proof));
+        let evaluation = polynomial.evaluate(&point);
+        assert_eq!(
+            verifier.verify(&proof, evaluation + Mersenne61Ext::from_int(1)),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        assert_eq!(verifier.verify(&proof, evaluation), Ok(()));
         proof
             .iter()
             .map(|x: &util::query_result::QueryResult<Mersenne61Ext>| x.proof_size())
             .sum::<usize>()
-            + variable_num * (MERKLE_ROOT_SIZE + size_of::<Mersenne61Ext>() * 3)
+            + (schedule.rounds() - 1) * MERKLE_ROOT_SIZE
+            + schedule.total() * size_of::<Mersenne61Ext>() * 3
+            + NONCE_SIZE
+    }
+
+    fn check_pcs_interface<T: MyField>(hash: HashChoice) {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 8,
+                hash,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = BaseFold::<T>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial.clone());
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, polynomial.evaluate(&point));
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+        assert_eq!(
+            pcs.verify(&commit, &point, &(eval + T::from_int(1)), &proof),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        let mut other_point = point.clone();
+        other_point[0] += T::from_int(1);
+        assert!(pcs.verify(&commit, &other_point, &eval, &proof).is_err());
+        assert_eq!(
+            pcs.verify(&commit, &point[1..], &eval, &proof),
+            Err(VerificationError::MalformedProof("open point"))
+        );
+        let sha256 = BaseFold::<T>::setup(SetupParams {
+            pcs: PcsParams {
+                hash: HashChoice::Sha256,
+                ..params.pcs
+            },
+            ..params
+        });
+        assert_eq!(
+            sha256.verify(&commit, &point, &eval, &proof),
+            Err(VerificationError::ParameterMismatch)
+        );
+    }
+
+    #[test]
+    fn pcs_interface() {
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Goldilocks64Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Poseidon2);
+    }
+
+    #[test]
+    fn serialization() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = BaseFold::<Mersenne61Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let commit: Commitment =
+            bincode::deserialize(&bincode::serialize(&commit).unwrap()).unwrap();
+        let (eval, proof) = pcs.open(&data, &point);
+
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), proof.size());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&trailing).err(),
+            Some(SerializationError::TrailingBytes(1))
+        );
+        let mut non_canonical = bytes.clone();
+        // The evaluation, right after the version byte.
+        non_canonical[1..1 + size_of::<Mersenne61Ext>()].fill(0xff);
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&non_canonical).err(),
+            Some(SerializationError::NonCanonicalField)
+        );
+
+        let proof: Proof<Mersenne61Ext> =
+            bincode::deserialize(&bincode::serialize(&proof).unwrap()).unwrap();
+        assert_eq!(proof.encode(), bytes);
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+    }
+
+    // Digest of a fixed opening made by the sequential build. The parallel
+    // build must reproduce it byte for byte in every pool size.
+    const SEQUENTIAL_PROOF: &str =
+        "16a0c69d25f57ce0eff63f07bee7970981bcf7f16e098b106ea8117af734c627";
+
+    #[test]
+    fn proof_matches_sequential_fixture() {
+        let params = SetupParams {
+            variable_num: 12,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = BaseFold::<Goldilocks64Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::new(
+            (0..1u64 << params.variable_num)
+                .map(|i| Goldilocks64Ext::from_int(i * i + 1))
+                .collect(),
+        );
+        let point = (0..params.variable_num as u64)
+            .map(|i| Goldilocks64Ext::from_int(3 * i + 7))
+            .collect::<Vec<_>>();
+        let prove = || {
+            let (commit, data) = pcs.commit(polynomial.clone());
+            let mut bytes = commit.encode();
+            bytes.extend(pcs.open(&data, &point).1.encode());
+            Blake3Algorithm::hash(&bytes)
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        };
+        for_each_pool(|_, pool| assert_eq!(pool.install(prove), SEQUENTIAL_PROOF));
+    }
+
+    #[test]
+    fn zero_polynomial() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = BaseFold::<Mersenne61Ext>::setup(params);
+        let polynomial =
+            MultilinearPolynomial::new(vec![Mersenne61Ext::from_int(0); 1 << params.variable_num]);
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, Mersenne61Ext::from_int(0));
+        let proof = Proof::<Mersenne61Ext>::decode(&proof.encode()).unwrap();
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+    }
+
+    #[test]
+    fn folding_schedules() {
+        let variable_num = 5;
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        // uniform steps, most of them leaving a remainder round
+        let uniform = (1..variable_num + 1).map(|step| PcsParams { step, ..params });
+        let scheduled = FoldingSchedule::all(variable_num)
+            .into_iter()
+            .map(|schedule| PcsParams {
+                schedule: Some(schedule),
+                ..params
+            });
+        for pcs in uniform.chain(scheduled) {
+            let pcs = BaseFold::<Mersenne61Ext>::setup(SetupParams { variable_num, pcs });
+            let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+            let point = (0..variable_num)
+                .map(|_| Mersenne61Ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = pcs.commit(polynomial);
+            let (eval, proof) = pcs.open(&data, &point);
+            assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+        }
+    }
+
+    #[test]
+    fn early_termination() {
+        let variable_num = 6;
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        for final_poly_bits in 0..variable_num {
+            for step in 1..3 {
+                let pcs = BaseFold::<Mersenne61Ext>::setup(SetupParams {
+                    variable_num,
+                    pcs: PcsParams {
+                        step,
+                        final_poly_bits,
+                        ..params
+                    },
+                });
+                let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+                let point = (0..variable_num)
+                    .map(|_| Mersenne61Ext::random_element())
+                    .collect::<Vec<_>>();
+                let (commit, data) = pcs.commit(polynomial);
+                let (eval, mut proof) = pcs.open(&data, &point);
+                assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+
+                proof.final_poly = Polynomial::random_polynomial((1 << final_poly_bits) + 1);
+                assert_eq!(
+                    pcs.verify(&commit, &point, &eval, &proof),
+                    Err(VerificationError::MalformedProof("final polynomial"))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn merkle_caps() {
+        let variable_num = 8;
+        for cap_bits in 0..8 {
+            let pcs = BaseFold::<Mersenne61Ext>::setup(SetupParams {
+                variable_num,
+                pcs: PcsParams {
+                    query_num: 10,
+                    grinding_bits: 0,
+                    step: 2,
+                    cap_bits,
+                    ..PcsParams::default()
+                },
+            });
+            let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+            let point = (0..variable_num)
+                .map(|_| Mersenne61Ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = pcs.commit(polynomial);
+            assert!(commit.merkle_root.len() <= 1 << cap_bits);
+            let (eval, mut proof) = pcs.open(&data, &point);
+            assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+
+            proof.merkle_root[0][0][0] ^= 1;
+            assert!(pcs.verify(&commit, &point, &eval, &proof).is_err());
+        }
     }
 
     #[test]
@@ -0,0 +1,82 @@
+use util::{
+    algebra::{coset::Coset, field::MyField, polynomial::MultilinearPolynomial},
+    error::VerificationError,
+    pcs::{Commitment, MultilinearPCS, SetupParams},
+};
+
+use crate::{prover::Prover, verifier::Verifier, Proof};
+
+pub struct BaseFold<T: MyField> {
+    params: SetupParams,
+    interpolate_cosets: Vec<Coset<T>>,
+}
+
+impl<T: MyField> MultilinearPCS<T> for BaseFold<T> {
+    type Params = SetupParams;
+    type Polynomial = MultilinearPolynomial<T>;
+    type Evaluation = T;
+    type Commitment = Commitment;
+    type ProverData = Prover<T>;
+    type Proof = Proof<T>;
+
+    fn setup(params: SetupParams) -> Self {
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (params.variable_num + params.pcs.code_rate),
+            T::from_int(1),
+        )];
+        for i in 1..params.variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        BaseFold {
+            params,
+            interpolate_cosets,
+        }
+    }
+
+    fn commit(&self, poly: MultilinearPolynomial<T>) -> (Commitment, Prover<T>) {
+        let prover = Prover::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            poly,
+            self.params.pcs,
+        );
+        (prover.commit_polynomial(), prover)
+    }
+
+    fn open(&self, data: &Prover<T>, point: &[T]) -> (T, Proof<T>) {
+        let proof = data.clone().generate_proof(point.to_vec());
+        (proof.evaluation, proof)
+    }
+
+    fn verify(
+        &self,
+        commitment: &Commitment,
+        point: &[T],
+        eval: &T,
+        proof: &Proof<T>,
+    ) -> Result<(), VerificationError> {
+        let schedule = self.params.pcs.folding(self.params.variable_num);
+        if proof.merkle_root.len() + 1 != schedule.rounds() {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        let mut verifier = Verifier::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            commitment.clone(),
+            point,
+            self.params.pcs,
+        );
+        verifier.set_evalutation(proof.evaluation);
+        for (i, root) in proof.merkle_root.iter().enumerate() {
+            let leave_number =
+                self.interpolate_cosets[schedule.offset(i + 1)].size() >> schedule.step(i + 1);
+            verifier.receive_folding_root(leave_number, root.clone());
+        }
+        for value in &proof.sumcheck_values {
+            verifier.receive_sumcheck_value(*value);
+        }
+        verifier.set_final_poly(proof.final_poly.clone());
+        verifier.set_pow_nonce(proof.pow_nonce);
+        verifier.verify(&proof.query_result, *eval)
+    }
+}
@@ -5,12 +5,17 @@ use util::{
         polynomial::{EqMultilinear, MultilinearPolynomial, Polynomial},
     },
     interpolation::InterpolateValue,
-    merkle_tree::MERKLE_ROOT_SIZE,
+    pcs::{Commitment, PcsParams},
     query_result::QueryResult,
-    random_oracle::RandomOracle,
+    schedule::FoldingSchedule,
+    transcript::Transcript,
 };
 
-use crate::verifier::Verifier;
+use crate::{verifier::Verifier, Proof};
+use util::{cfg_into_iter, cfg_iter_mut};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 #[derive(Clone)]
 pub struct Prover<T: MyField> {
@@ -20,9 +25,11 @@ pub struct Prover<T: MyField> {
     interpolations: Vec<InterpolateValue<T>>,
     hypercube_interpolation: Vec<T>,
     sumcheck_value: Vec<(T, T, T)>,
-    oracle: RandomOracle<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    schedule: FoldingSchedule,
     final_poly: Option<Polynomial<T>>,
-    step: usize,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> Prover<T> {
@@ -30,31 +37,40 @@ impl<T: MyField> Prover<T> {
         total_round: usize,
         interpolate_cosets: &Vec<Coset<T>>,
         polynomial: MultilinearPolynomial<T>,
-        oracle: &RandomOracle<T>,
-        step: usize,
+        params: PcsParams,
     ) -> Self {
+        let schedule = params.folding(total_round);
+        let interpolation = InterpolateValue::new(
+            params.tree_hash::<T>(),
+            interpolate_cosets[0].fft(polynomial.coefficients().clone()),
+            1 << schedule.step(0),
+        );
+        let mut transcript = Transcript::new(b"basefold");
+        transcript.append_cap(&interpolation.commit());
         Prover {
             total_round,
             interpolate_cosets: interpolate_cosets.clone(),
-            interpolations: vec![InterpolateValue::new(
-                interpolate_cosets[0].fft(polynomial.coefficients().clone()),
-                1 << step,
-            )],
+            interpolations: vec![interpolation],
             hypercube_interpolation: polynomial.evaluate_hypercube(),
             polynomial,
             sumcheck_value: vec![],
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            schedule,
             final_poly: None,
-            step,
+            pow_nonce: 0,
         }
     }
 
-    pub fn commit_polynomial(&self) -> [u8; MERKLE_ROOT_SIZE] {
-        self.interpolations[0].commit()
+    pub fn commit_polynomial(&self) -> Commitment {
+        Commitment {
+            merkle_root: self.interpolations[0].commit(),
+            params: self.params,
+        }
     }
 
     pub fn commit_foldings(&self, verifier: &mut Verifier<T>) {
-        for i in 1..self.total_round / self.step {
+        for i in 1..self.schedule.rounds() {
             let interpolation = &self.interpolations[i];
             verifier.receive_folding_root(interpolation.leave_num(), interpolation.commit());
         }
@@ -62,47 +78,56 @@ impl<T: MyField> Prover<T> {
             verifier.receive_sumcheck_value(i.clone());
         }
         verifier.set_final_poly(self.final_poly.clone().unwrap());
+        verifier.set_pow_nonce(self.pow_nonce);
+    }
+
+    pub fn send_evaluation(&mut self, verifier: &mut Verifier<T>, point: &Vec<T>) {
+        verifier.set_evalutation(self.evaluate(point));
     }
 
-    pub fn send_evaluation(&self, verifier: &mut Verifier<T>, point: &Vec<T>) {
-        verifier.set_evalutation(self.polynomial.evaluate(point));
+    fn evaluate(&mut self, point: &Vec<T>) -> T {
+        let evaluation = self.polynomial.evaluate(point);
+        self.transcript.append_fields(point);
+        self.transcript.append_field(&evaluation);
+        evaluation
     }
 
     fn evaluation_next_domain(&self, round: usize, challenges: Vec<T>) -> Vec<T> {
-        let mut get_folding_value = self.interpolations[round].value.clone();
-        for j in 0..self.step {
-            let len = self.interpolate_cosets[round * self.step + j].size();
-            let coset = &self.interpolate_cosets[round * self.step + j];
-            let mut tmp_folding_value = vec![];
+        let mut get_folding_value = self.interpolations[round].value.to_vec();
+        let offset = self.schedule.offset(round);
+        for j in 0..self.schedule.step(round) {
+            let len = self.interpolate_cosets[offset + j].size();
+            let coset = &self.interpolate_cosets[offset + j];
             let challenge = challenges[j];
-            for i in 0..(len / 2) {
-                let x = get_folding_value[i];
-                let nx = get_folding_value[i + len / 2];
-                let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
-                tmp_folding_value.push(new_v * T::inverse_2());
-            }
-            get_folding_value = tmp_folding_value;
+            get_folding_value = cfg_into_iter!(0..(len / 2))
+                .map(|i| {
+                    let x = get_folding_value[i];
+                    let nx = get_folding_value[i + len / 2];
+                    let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
+                    new_v * T::inverse_2()
+                })
+                .collect();
         }
         get_folding_value
     }
 
     fn sumcheck_next_domain(hypercube_interpolation: &mut Vec<T>, m: usize, challenge: T) {
-        for i in 0..m {
-            hypercube_interpolation[i] *= T::from_int(1) - challenge;
-            let tmp = hypercube_interpolation[i + m] * challenge;
-            hypercube_interpolation[i] += tmp;
-        }
+        let (lo, hi) = hypercube_interpolation.split_at_mut(m);
+        cfg_iter_mut!(lo).zip(&hi[..m]).for_each(|(x, &y)| {
+            *x *= T::from_int(1) - challenge;
+            *x += y * challenge;
+        });
     }
 
     pub fn prove(&mut self, point: &Vec<T>) {
         let mut poly_hypercube = self.hypercube_interpolation.clone();
         let mut eq_hypercube = EqMultilinear::new(point.clone()).evaluate_hypercube();
-        for i in 0..self.total_round / self.step {
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
             let mut challenges = vec![];
             // step 1 calculate the sumcheck part and sends to v
-            for j in 0..self.step {
-                let m = 1 << (self.total_round - (i * self.step + j) - 1);
-                let challenge = self.oracle.folding_challenges[i * self.step + j];
+            for j in 0..self.schedule.step(i) {
+                let m = 1 << (self.total_round - (self.schedule.offset(i) + j) - 1);
                 let (sum_0, sum_1, sum_2) = (0..m).into_iter().fold(
                     (T::from_int(0), T::from_int(0), T::from_int(0)),
                     |acc, x| {
@@ -118,6 +143,8 @@ impl<T: MyField> Prover<T> {
                     },
                 );
                 self.sumcheck_value.push((sum_0, sum_1, sum_2));
+                self.transcript.append_fields(&[sum_0, sum_1, sum_2]);
+                let challenge = self.transcript.challenge();
 
                 Self::sumcheck_next_domain(&mut poly_hypercube, m, challenge);
                 Self::sumcheck_next_domain(&mut eq_hypercube, m, challenge);
@@ -126,30 +153,41 @@ impl<T: MyField> Prover<T> {
 
             // step 2 calculate the folding phase
             let next_evalutation = self.evaluation_next_domain(i, challenges);
-            if i < self.total_round / self.step - 1 {
-                self.interpolations
-                    .push(InterpolateValue::new(next_evalutation, 1 << self.step));
+            if i < rounds - 1 {
+                let interpolation = InterpolateValue::new(
+                    self.params.tree_hash::<T>(),
+                    next_evalutation,
+                    1 << self.schedule.step(i + 1),
+                );
+                self.transcript.append_cap(&interpolation.commit());
+                self.interpolations.push(interpolation);
             } else {
-                self.interpolations.push(InterpolateValue::new(
-                    next_evalutation.clone(),
-                    1 << self.step,
-                ));
-                self.final_poly = Some(Polynomial::new(
-                    self.interpolate_cosets[(i + 1) * self.step].ifft(next_evalutation),
-                ));
+                // The last layer is never committed; the coefficients of f
+                // with the folded variables fixed are sent instead.
+                let mut coefficients =
+                    self.interpolate_cosets[self.schedule.total()].ifft(next_evalutation);
+                coefficients.truncate(1 << self.params.final_poly_bits);
+                let final_poly = Polynomial::new(coefficients);
+                self.transcript.append_fields(final_poly.coefficients());
+                self.final_poly = Some(final_poly);
             }
         }
+        self.pow_nonce = self.transcript.grind(self.params.grinding_bits);
     }
 
-    pub fn query(&self) -> Vec<QueryResult<T>> {
+    pub fn query(&mut self) -> Vec<QueryResult<T>> {
         let mut res = vec![];
-        let mut leaf_indices = self.oracle.query_list.clone();
+        let mut leaf_indices = self.transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
 
-        for i in 0..self.total_round / self.step + 1 {
-            let len = self.interpolate_cosets[i * self.step].size();
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
+            let len = self.interpolate_cosets[self.schedule.offset(i)].size();
             leaf_indices = leaf_indices
                 .iter_mut()
-                .map(|v| *v % (len >> self.step))
+                .map(|v| *v % (len >> self.schedule.step(i)))
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
@@ -157,4 +195,20 @@ impl<T: MyField> Prover<T> {
         }
         res
     }
+
+    pub fn generate_proof(mut self, point: Vec<T>) -> Proof<T> {
+        let evaluation = self.evaluate(&point);
+        self.prove(&point);
+        let query_result = self.query();
+        Proof {
+            evaluation,
+            merkle_root: (1..self.schedule.rounds())
+                .map(|i| self.interpolations[i].commit())
+                .collect(),
+            sumcheck_values: self.sumcheck_value.clone(),
+            final_poly: self.final_poly.clone().unwrap(),
+            query_result,
+            pow_nonce: self.pow_nonce,
+        }
+    }
 }
@@ -1,66 +1,71 @@
-use util::algebra::polynomial::{EqMultilinear, Polynomial};
-use util::merkle_tree::MERKLE_ROOT_SIZE;
-use util::random_oracle::RandomOracle;
+use util::algebra::polynomial::{EqMultilinear, MultilinearPolynomial, Polynomial};
+use util::error::VerificationError;
+use util::pcs::{Commitment, PcsParams};
+use util::schedule::FoldingSchedule;
+use util::transcript::Transcript;
 use util::{
     algebra::{coset::Coset, field::MyField},
-    merkle_tree::MerkleTreeVerifier,
+    merkle_tree::{MerkleCap, MerkleTreeVerifier},
     query_result::QueryResult,
 };
 
 #[derive(Clone)]
 pub struct Verifier<T: MyField> {
-    total_round: usize,
     interpolate_cosets: Vec<Coset<T>>,
     polynomial_roots: Vec<MerkleTreeVerifier>,
-    oracle: RandomOracle<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    commit_params: PcsParams,
+    total_round: usize,
+    schedule: FoldingSchedule,
     final_poly: Option<Polynomial<T>>,
     sumcheck_values: Vec<(T, T, T)>,
     open_point: Vec<T>,
     evaluation: Option<T>,
-    step: usize,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> Verifier<T> {
     pub fn new(
         total_round: usize,
         coset: &Vec<Coset<T>>,
-        commit: [u8; MERKLE_ROOT_SIZE],
-        oracle: &RandomOracle<T>,
-        step: usize,
+        commit: Commitment,
+        open_point: &[T],
+        params: PcsParams,
     ) -> Self {
+        let schedule = params.folding(total_round);
+        let mut transcript = Transcript::new(b"basefold");
+        transcript.append_cap(&commit.merkle_root);
         Verifier {
-            total_round,
             interpolate_cosets: coset.clone(),
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            commit_params: commit.params,
+            total_round,
+            schedule,
             polynomial_roots: vec![MerkleTreeVerifier::new(
-                coset[0].size() / (1 << step),
-                &commit,
+                params.tree_hash::<T>(),
+                coset[0].size() / (1 << schedule.step(0)),
+                &commit.merkle_root,
             )],
             final_poly: None,
             sumcheck_values: vec![],
-            open_point: (0..total_round).map(|_| T::random_element()).collect(),
+            open_point: open_point.to_vec(),
             evaluation: None,
-            step,
+            pow_nonce: 0,
         }
     }
 
-    pub fn get_open_point(&self) -> Vec<T> {
-        self.open_point.clone()
-    }
-
     pub fn receive_sumcheck_value(&mut self, value: (T, T, T)) {
         self.sumcheck_values.push(value);
     }
 
-    pub fn receive_folding_root(
-        &mut self,
-        leave_number: usize,
-        folding_root: [u8; MERKLE_ROOT_SIZE],
-    ) {
-        self.polynomial_roots.push(MerkleTreeVerifier {
+    pub fn receive_folding_root(&mut self, leave_number: usize, folding_root: MerkleCap) {
+        self.polynomial_roots.push(MerkleTreeVerifier::new(
+            self.params.tree_hash::<T>(),
             leave_number,
-            merkle_root: folding_root,
-        });
+            &folding_root,
+        ));
     }
 
     pub fn set_evalutation(&mut self, evaluation: T) {
@@ -71,113 +76,149 @@ impl<T: MyField> Verifier<T> {
         self.final_poly = Some(poly);
     }
 
-    pub fn verify(&self, polynomial_proof: &Vec<QueryResult<T>>) -> bool {
-        let mut leaf_indices = self.oracle.query_list.clone();
-        let mut sum = self.sumcheck_values[0].0 + self.sumcheck_values[0].1;
-        for i in 0..self.total_round / self.step {
-            let domain_size = self.interpolate_cosets[i * self.step].size();
+    pub fn set_pow_nonce(&mut self, nonce: u64) {
+        self.pow_nonce = nonce;
+    }
+
+    pub fn verify(
+        &self,
+        polynomial_proof: &Vec<QueryResult<T>>,
+        evaluation: T,
+    ) -> Result<(), VerificationError> {
+        if self.commit_params != self.params {
+            return Err(VerificationError::ParameterMismatch);
+        }
+        if self.open_point.len() != self.total_round {
+            return Err(VerificationError::MalformedProof("open point"));
+        }
+        if self.evaluation.is_none() || self.final_poly.is_none() {
+            return Err(VerificationError::MalformedProof("missing evaluation"));
+        }
+        if self.final_poly.as_ref().unwrap().coefficients().len() > 1 << self.params.final_poly_bits
+        {
+            return Err(VerificationError::MalformedProof("final polynomial"));
+        }
+        if self.evaluation != Some(evaluation) {
+            return Err(VerificationError::EvaluationMismatch);
+        }
+        let rounds = self.schedule.rounds();
+        let folded = self.schedule.total();
+        if self.sumcheck_values.len() != folded || self.polynomial_roots.len() != rounds {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        if polynomial_proof.len() != rounds {
+            return Err(VerificationError::MalformedProof("query result count"));
+        }
+        let (folding_challenges, mut leaf_indices) = self.replay_transcript()?;
+        let mut sum = self.evaluation.unwrap();
+        for i in 0..rounds {
+            let step = self.schedule.step(i);
+            let offset = self.schedule.offset(i);
+            let domain_size = self.interpolate_cosets[offset].size();
             leaf_indices = leaf_indices
                 .iter_mut()
-                .map(|v| *v % (domain_size / (1 << self.step)))
+                .map(|v| *v % (domain_size / (1 << step)))
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
 
             polynomial_proof[i].verify_merkle_tree(
+                i,
                 &leaf_indices,
-                1 << self.step,
+                1 << step,
                 &self.polynomial_roots[i],
-            );
-            let folding_value = &polynomial_proof[i].proof_values;
+            )?;
 
-            for k in 0..self.step {
-                let challenge = self.oracle.folding_challenges[i * self.step + k];
-                sum =
-                    self.process_sumcheck(challenge, sum, self.sumcheck_values[i * self.step + k]);
+            for k in 0..step {
+                let round = offset + k;
+                sum = self.process_sumcheck(
+                    round,
+                    folding_challenges[round],
+                    sum,
+                    self.sumcheck_values[round],
+                )?;
             }
 
-            for k in &leaf_indices {
-                let mut x;
-                let mut nx;
-                let mut verify_values = vec![];
-                let mut verify_inds = vec![];
-                for j in 0..(1 << self.step) {
-                    // Init verify values, which is the total values in the first step
-                    let ind = k + j * domain_size / (1 << self.step);
-                    verify_values.push(folding_value[&ind]);
-                    verify_inds.push(ind);
-                }
-                for j in 0..self.step {
-                    let challenge = self.oracle.folding_challenges[i * self.step + j];
-                    let size = verify_values.len();
-                    let mut tmp_values = vec![];
-                    let mut tmp_inds = vec![];
-                    for l in 0..size / 2 {
-                        x = verify_values[l];
-                        nx = verify_values[l + size / 2];
-                        tmp_values.push(
-                            (x + nx
-                                + challenge
-                                    * (x - nx)
-                                    * self.interpolate_cosets[i * self.step + j]
-                                        .element_inv_at(verify_inds[l]))
-                                * T::inverse_2(),
-                        );
-                        tmp_inds.push(verify_inds[l]);
-                    }
-                    verify_values = tmp_values;
-                    verify_inds = tmp_inds;
+            // Each step also halves, hence the extra factor of 2^-step.
+            let scale = T::inverse_2().pow(step);
+            let cosets = &self.interpolate_cosets[offset..];
+            let challenges = &folding_challenges[offset..offset + step];
+            for (position, k) in leaf_indices.iter().enumerate() {
+                let v = polynomial_proof[i].fold(position, cosets, challenges) * scale;
+                let expected = if i == rounds - 1 {
+                    let point = self.interpolate_cosets[offset + step].element_at(*k);
+                    self.final_poly.as_ref().unwrap().evaluation_at(point)
+                } else {
+                    polynomial_proof[i + 1].entry(*k, self.polynomial_roots[i + 1].leave_number)?
+                };
+                if v != expected {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *k,
+                    });
                 }
-                // // ----------------------
-                // for k in 0..self.step {
-                //     let challenge = self.oracle.folding_challenges[i*self.step + k];
-                //     let x = folding_value[j];
-                //     let nx = folding_value[&(j + domain_size / 2)];
-                //     let v =
-                //         x + nx + challenge * (x - nx) * self.interpolate_cosets[i].element_inv_at(*j);
-                // }
+            }
+        }
 
-                assert_eq!(verify_values.len(), 1);
-                let v = verify_values[0];
+        // The final polynomial is f(r, .) for the folding challenges r; with the
+        // open point split as (z, z') the sumcheck must end at f(r, z') * eq(z, r).
+        let mut coefficients = self.final_poly.as_ref().unwrap().coefficients().clone();
+        coefficients.resize(1 << self.params.final_poly_bits, T::from_int(0));
+        let final_value =
+            MultilinearPolynomial::new(coefficients).evaluate(&self.open_point[folded..].to_vec());
+        let eq_poly = EqMultilinear::new(self.open_point[..folded].to_vec());
+        if sum != final_value * eq_poly.evaluate(&folding_challenges) {
+            return Err(VerificationError::SumcheckMismatch { round: folded });
+        }
+        Ok(())
+    }
 
-                if i == self.total_round / self.step {
-                    let mut j = 0;
-                    while i * self.step + j < self.total_round {
-                        let challenge = self.oracle.folding_challenges[i * self.step + k];
-                        sum = self.process_sumcheck(
-                            challenge,
-                            sum,
-                            self.sumcheck_values[i * self.step + k],
-                        );
-                        j += 1;
-                    }
-                    let eq_poly = EqMultilinear::new(self.open_point.clone());
-                    assert_eq!(
-                        v,
-                        sum * T::inverse(&eq_poly.evaluate(
-                            &self.oracle.folding_challenges[0..self.total_round].to_vec()
-                        ))
-                    )
-                } else if i == self.total_round / self.step - 1 {
-                    let point = self.interpolate_cosets[(i + 1) * self.step].element_at(*k);
-                    assert_eq!(v, self.final_poly.clone().unwrap().evaluation_at(point));
-                } else {
-                    assert_eq!(v, polynomial_proof[i + 1].proof_values[k]);
-                }
+    // Derives the folding challenges and queries in the prover's order.
+    fn replay_transcript(&self) -> Result<(Vec<T>, Vec<usize>), VerificationError> {
+        let mut transcript = self.transcript.clone();
+        transcript.append_fields(&self.open_point);
+        transcript.append_field(&self.evaluation.unwrap());
+        let mut folding_challenges = vec![];
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
+            for j in 0..self.schedule.step(i) {
+                let (x_0, x_1, x_2) = self.sumcheck_values[self.schedule.offset(i) + j];
+                transcript.append_fields(&[x_0, x_1, x_2]);
+                folding_challenges.push(transcript.challenge());
             }
+            if i < rounds - 1 {
+                transcript.append_cap(&self.polynomial_roots[i + 1].merkle_cap);
+            } else {
+                transcript.append_fields(self.final_poly.as_ref().unwrap().coefficients());
+            }
+        }
+        if !transcript.check_grinding(self.params.grinding_bits, self.pow_nonce) {
+            return Err(VerificationError::InsufficientWork);
         }
-        true
+        let query_list = transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
+        Ok((folding_challenges, query_list))
     }
 
-    fn process_sumcheck(&self, challenge: T, last_sum: T, sumcheck_values: (T, T, T)) -> T {
+    fn process_sumcheck(
+        &self,
+        round: usize,
+        challenge: T,
+        last_sum: T,
+        sumcheck_values: (T, T, T),
+    ) -> Result<T, VerificationError> {
         let x_0 = sumcheck_values.0;
         let x_1 = sumcheck_values.1;
         let x_2 = sumcheck_values.2;
-        assert_eq!(last_sum, x_0 + x_1);
+        if last_sum != x_0 + x_1 {
+            return Err(VerificationError::SumcheckMismatch { round });
+        }
         let sum =
             x_0 * (T::from_int(1) - challenge) * (T::from_int(2) - challenge) * T::inverse_2()
                 + x_1 * challenge * (T::from_int(2) - challenge)
                 + x_2 * challenge * (challenge - T::from_int(1)) * T::inverse_2();
-        sum
+        Ok(sum)
     }
 }
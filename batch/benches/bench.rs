@@ -2,31 +2,32 @@ extern crate criterion;
 use criterion::*;
 
 use batch::{prover::Prover, verifier::Verifier};
-use util::{
-    algebra::{
-        coset::Coset,
-        field::{mersenne61_ext::Mersenne61Ext, MyField},
-        polynomial::MultilinearPolynomial,
-    },
-    random_oracle::RandomOracle,
+use util::algebra::{
+    coset::Coset,
+    field::{mersenne61_ext::Mersenne61Ext, MyField},
+    polynomial::MultilinearPolynomial,
 };
 
-use util::{CODE_RATE, SECURITY_BITS};
+use util::parallel::for_each_pool;
+use util::pcs::PcsParams;
 
 fn open<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let params = PcsParams::default();
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
     let polynomials = (0..variable_num)
         .rev()
         .map(|x| MultilinearPolynomial::random_polynomial(x + 1))
         .collect();
-    let prover = Prover::new(variable_num, &interpolate_cosets, polynomials, &oracle);
-    let commit = prover.commit_polynomial();
-    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle);
-    let point = verifier.get_open_point();
+    let prover = Prover::new(variable_num, &interpolate_cosets, polynomials, params);
+    let point = (0..variable_num)
+        .map(|_| T::random_element())
+        .collect::<Vec<_>>();
 
     criterion.bench_function(&format!("batch open {}", variable_num), move |b| {
         b.iter_batched(
@@ -46,26 +47,32 @@ fn bench_open(c: &mut Criterion) {
 }
 
 fn verify<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let params = PcsParams::default();
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
     let polynomials = (0..variable_num)
         .rev()
         .map(|x| MultilinearPolynomial::random_polynomial(x + 1))
         .collect();
-    let prover = Prover::new(variable_num, &interpolate_cosets, polynomials, &oracle);
+    let prover = Prover::new(variable_num, &interpolate_cosets, polynomials, params);
     let commit = prover.commit_polynomial();
-    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle);
-    let point = verifier.get_open_point();
+    let point = (0..variable_num)
+        .map(|_| T::random_element())
+        .collect::<Vec<_>>();
+    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &point, params);
     let proof = prover.generate_proof(point);
+    let evaluations = proof.evaluations();
 
     criterion.bench_function(&format!("batch verify {}", variable_num), move |b| {
         b.iter_batched(
             || (verifier.clone(), proof.clone()),
             |(v, pi)| {
-                assert!(v.verify(pi));
+                assert!(v.verify(pi, &evaluations).is_ok());
             },
             BatchSize::SmallInput,
         )
@@ -78,10 +85,48 @@ fn bench_verify(c: &mut Criterion) {
     }
 }
 
+// Prover scaling by thread count; proofs are identical for every pool size.
+fn bench_open_threads(c: &mut Criterion) {
+    let variable_num = 18;
+    let params = PcsParams::default();
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        Mersenne61Ext::from_int(1),
+    )];
+    for i in 1..variable_num {
+        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+    }
+    let polynomials = (0..variable_num)
+        .rev()
+        .map(|x| MultilinearPolynomial::<Mersenne61Ext>::random_polynomial(x + 1))
+        .collect::<Vec<_>>();
+    let point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
+    for_each_pool(|threads, pool| {
+        c.bench_function(
+            &format!("batch prove {:02} threads {}", variable_num, threads),
+            |b| {
+                b.iter(|| {
+                    pool.install(|| {
+                        let prover = Prover::new(
+                            variable_num,
+                            &interpolate_cosets,
+                            polynomials.clone(),
+                            params,
+                        );
+                        let _proof = prover.generate_proof(point.clone());
+                    })
+                })
+            },
+        );
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_open, bench_verify
+    targets = bench_open, bench_verify, bench_open_threads
 }
 
 criterion_main!(benches);
@@ -1,11 +1,25 @@
 use std::mem::size_of;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use util::cfg_iter_mut;
 use util::{
-    algebra::{field::MyField, polynomial::MultilinearPolynomial},
-    merkle_tree::MERKLE_ROOT_SIZE,
+    algebra::{
+        field::MyField,
+        polynomial::{MultilinearPolynomial, Polynomial},
+    },
+    merkle_tree::MerkleCap,
+    pcs::PcsParams,
     query_result::QueryResult,
+    serialization::{
+        caps_size, deserialize_with, field_sequence_size, serialize_with, Reader, Serializable,
+        SerializationError, Writer, LENGTH_SIZE, NONCE_SIZE, VERSION_SIZE,
+    },
 };
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+pub mod pcs;
 pub mod prover;
 pub mod verifier;
 
@@ -30,11 +44,11 @@ impl<T: MyField> DeepEval<T> {
         assert_eq!(len, 1 << point.len());
         for v in point.into_iter() {
             len >>= 1;
-            for i in 0..len {
-                poly_hypercube[i] *= T::from_int(1) - v;
-                let tmp = poly_hypercube[i + len] * v;
-                poly_hypercube[i] += tmp;
-            }
+            let (lo, hi) = poly_hypercube.split_at_mut(len);
+            cfg_iter_mut!(lo).zip(&hi[..len]).for_each(|(x, &y)| {
+                *x *= T::from_int(1) - v;
+                *x += y * v;
+            });
         }
         poly_hypercube[0]
     }
@@ -45,11 +59,17 @@ impl<T: MyField> DeepEval<T> {
         self.else_evals.push(poly.evaluate(&point));
     }
 
-    pub fn verify(&self, challenges: &Vec<T>, out_evals: &Vec<T>) -> T {
-        let (_, challenges) = challenges.split_at(challenges.len() - self.point.len());
+    // Folds the claim through the rounds it took part in and checks it against
+    // `final_poly` at the rest of the point.
+    pub fn verify(
+        &self,
+        challenges: &Vec<T>,
+        out_evals: &Vec<T>,
+        final_poly: &MultilinearPolynomial<T>,
+    ) -> bool {
+        let folded = self.else_evals.len();
+        let (_, challenges) = challenges.split_at(challenges.len() - folded);
         let mut y_0 = self.first_eval;
-        assert_eq!(self.point.len(), self.else_evals.len());
-        assert_eq!(challenges.len(), out_evals.len() + 1);
         for ((x, eval), (i, challenge)) in self
             .point
             .iter()
@@ -62,83 +82,426 @@ impl<T: MyField> DeepEval<T> {
                 y_0 += out_evals[i] * challenge.clone() * challenge.clone();
             }
         }
-        y_0
+        y_0 == final_poly.evaluate(&self.point[folded..].to_vec())
     }
 }
 
+#[derive(Clone)]
 pub struct Commit<T: MyField> {
-    merkle_root: [u8; MERKLE_ROOT_SIZE],
+    merkle_root: MerkleCap,
+    params: PcsParams,
     deep: T,
 }
 
 #[derive(Clone)]
 pub struct Proof<T: MyField> {
-    merkle_root: Vec<[u8; MERKLE_ROOT_SIZE]>,
+    merkle_root: Vec<MerkleCap>,
     query_result: (Vec<QueryResult<T>>, Vec<QueryResult<T>>),
     deep_evals: Vec<(T, Vec<T>)>,
     shuffle_evals: Vec<T>,
     out_evals: Vec<Vec<T>>,
     evaluation: T,
-    final_value: T,
+    // g after the last round, then the committed polynomials too small to
+    // have been merged into it, all in coefficient form.
+    final_poly: Polynomial<T>,
+    unfolded_polys: Vec<Polynomial<T>>,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> Proof<T> {
-    fn size(&self) -> usize {
-        self.merkle_root.len() * MERKLE_ROOT_SIZE
+    // Claimed values of every committed polynomial at its part of the open point.
+    pub fn evaluations(&self) -> Vec<T> {
+        let mut res = vec![self.evaluation];
+        if let Some(evals) = self.out_evals.first() {
+            res.extend_from_slice(evals);
+        }
+        res
+    }
+
+    // Exact length of `encode`.
+    pub fn size(&self) -> usize {
+        VERSION_SIZE
+            + caps_size(&self.merkle_root)
             + self
                 .query_result
                 .0
                 .iter()
-                .fold(0, |acc, x| acc + x.proof_size())
+                .fold(LENGTH_SIZE, |acc, x| acc + x.proof_size())
             + self
                 .query_result
                 .1
                 .iter()
-                .fold(0, |acc, x| acc + x.proof_size())
-            + (self.deep_evals.iter().fold(0, |acc, x| acc + x.1.len())
-                + self.shuffle_evals.len()
-                + self.out_evals.iter().fold(0, |acc, x| acc + x.len())
-                + 2)
-                * size_of::<T>()
+                .fold(LENGTH_SIZE, |acc, x| acc + x.proof_size())
+            + self.deep_evals.iter().fold(LENGTH_SIZE, |acc, x| {
+                acc + size_of::<T>() + field_sequence_size::<T>(x.1.len())
+            })
+            + field_sequence_size::<T>(self.shuffle_evals.len())
+            + self.out_evals.iter().fold(LENGTH_SIZE, |acc, x| {
+                acc + field_sequence_size::<T>(x.len())
+            })
+            + size_of::<T>()
+            + field_sequence_size::<T>(self.final_poly.coefficients().len())
+            + self.unfolded_polys.iter().fold(LENGTH_SIZE, |acc, x| {
+                acc + field_sequence_size::<T>(x.coefficients().len())
+            })
+            + NONCE_SIZE
+    }
+}
+
+impl<T: MyField> Serializable for Commit<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_cap(&self.merkle_root);
+        writer.write(&self.params);
+        writer.write_field(&self.deep);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        Ok(Commit {
+            merkle_root: reader.read_cap()?,
+            params: reader.read()?,
+            deep: reader.read_field()?,
+        })
+    }
+}
+
+impl<T: MyField> Serializable for Proof<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_caps(&self.merkle_root);
+        writer.write_all(&self.query_result.0);
+        writer.write_all(&self.query_result.1);
+        writer.write_length(self.deep_evals.len());
+        for (first_eval, else_evals) in &self.deep_evals {
+            writer.write_field(first_eval);
+            writer.write_fields(else_evals);
+        }
+        writer.write_fields(&self.shuffle_evals);
+        writer.write_length(self.out_evals.len());
+        for out_evals in &self.out_evals {
+            writer.write_fields(out_evals);
+        }
+        writer.write_field(&self.evaluation);
+        writer.write_polynomial(&self.final_poly);
+        writer.write_length(self.unfolded_polys.len());
+        for poly in &self.unfolded_polys {
+            writer.write_polynomial(poly);
+        }
+        writer.write_u64(self.pow_nonce);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let merkle_root = reader.read_caps()?;
+        let query_result = (reader.read_all()?, reader.read_all()?);
+        let deep_evals = (0..reader.read_length(size_of::<T>() + LENGTH_SIZE)?)
+            .map(|_| Ok((reader.read_field()?, reader.read_fields()?)))
+            .collect::<Result<_, _>>()?;
+        let shuffle_evals = reader.read_fields()?;
+        let out_evals = (0..reader.read_length(LENGTH_SIZE)?)
+            .map(|_| reader.read_fields())
+            .collect::<Result<_, _>>()?;
+        let evaluation = reader.read_field()?;
+        let final_poly = reader.read_polynomial()?;
+        let unfolded_polys = (0..reader.read_length(LENGTH_SIZE)?)
+            .map(|_| reader.read_polynomial())
+            .collect::<Result<_, _>>()?;
+        Ok(Proof {
+            merkle_root,
+            query_result,
+            deep_evals,
+            shuffle_evals,
+            out_evals,
+            evaluation,
+            final_poly,
+            unfolded_polys,
+            pow_nonce: reader.read_u64()?,
+        })
+    }
+}
+
+impl<T: MyField> Serialize for Commit<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for Commit<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
+impl<T: MyField> Serialize for Proof<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for Proof<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::{prover::Prover, verifier::Verifier};
+    use crate::{pcs::BatchDeepFold, prover::Prover, verifier::Verifier, Commit, Proof};
     use csv::Writer;
-    use util::{
-        algebra::{
-            coset::Coset,
-            field::{mersenne61_ext::Mersenne61Ext, MyField},
-            polynomial::MultilinearPolynomial,
-        },
-        random_oracle::RandomOracle,
+    use util::algebra::{
+        coset::Coset,
+        field::{goldilocks64ext::Goldilocks64Ext, mersenne61_ext::Mersenne61Ext, MyField},
+        polynomial::{MultilinearPolynomial, Polynomial},
     };
-    use util::{CODE_RATE, SECURITY_BITS};
+    use util::error::VerificationError;
+    use util::merkle_tree::{Blake3Algorithm, HashChoice, MerkleHasher};
+    use util::parallel::for_each_pool;
+    use util::pcs::{MultilinearPCS, PcsParams, SetupParams};
+    use util::serialization::{Serializable, SerializationError};
 
     fn output_proof_size<T: MyField>(variable_num: usize) -> usize {
-        let mut interpolate_cosets =
-            vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+        let params = PcsParams::default();
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + params.code_rate),
+            T::from_int(1),
+        )];
         for i in 1..variable_num {
             interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
         }
-        let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
         let polynomials = (0..variable_num)
             .rev()
             .map(|x| MultilinearPolynomial::random_polynomial(x + 1))
-            .collect();
-        let prover = Prover::new(variable_num, &interpolate_cosets, polynomials, &oracle);
+            .collect::<Vec<_>>();
+        let prover = Prover::new(
+            variable_num,
+            &interpolate_cosets,
+            polynomials.clone(),
+            params,
+        );
         let commit = prover.commit_polynomial();
-        let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle);
-        let point = verifier.get_open_point();
+        let commit_bytes = bincode::serialize(&commit).unwrap();
+        let commit: Vec<Commit<T>> = bincode::deserialize(&commit_bytes).unwrap();
+        let point = (0..variable_num)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let evaluations = polynomials
+            .iter()
+            .enumerate()
+            .map(|(i, poly)| poly.evaluate(&point[i..].to_vec()))
+            .collect::<Vec<_>>();
+        let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &point, params);
         let proof = prover.generate_proof(point);
         let size = proof.size();
-        assert!(verifier.verify(proof));
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), size);
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Proof::<T>::decode(&trailing).err(),
+            Some(SerializationError::TrailingBytes(1))
+        );
+        let proof: Proof<T> = bincode::deserialize(&bincode::serialize(&proof).unwrap()).unwrap();
+        assert_eq!(proof.encode(), bytes);
+        let mut wrong_evaluations = evaluations.clone();
+        wrong_evaluations[0] += T::from_int(1);
+        assert_eq!(
+            verifier.clone().verify(proof.clone(), &wrong_evaluations),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        assert_eq!(verifier.verify(proof, &evaluations), Ok(()));
         size
     }
 
+    fn check_pcs_interface<T: MyField>(hash: HashChoice) {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 8,
+                hash,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = BatchDeepFold::<T>::setup(params);
+        let polynomials = (0..params.variable_num)
+            .rev()
+            .map(|x| MultilinearPolynomial::random_polynomial(x + 1))
+            .collect::<Vec<_>>();
+        let point = (0..params.variable_num)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomials.clone());
+        let (evals, proof) = pcs.open(&data, &point);
+        for (i, (poly, eval)) in polynomials.iter().zip(evals.iter()).enumerate() {
+            assert_eq!(*eval, poly.evaluate(&point[i..].to_vec()));
+        }
+        assert_eq!(pcs.verify(&commit, &point, &evals, &proof), Ok(()));
+        let mut wrong_evals = evals.clone();
+        wrong_evals[1] += T::from_int(1);
+        assert_eq!(
+            pcs.verify(&commit, &point, &wrong_evals, &proof),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        let other = BatchDeepFold::<T>::setup(SetupParams {
+            pcs: PcsParams {
+                query_num: 11,
+                ..params.pcs
+            },
+            ..params
+        });
+        assert_eq!(
+            other.verify(&commit, &point, &evals, &proof),
+            Err(VerificationError::ParameterMismatch)
+        );
+    }
+
+    #[test]
+    fn pcs_interface() {
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Goldilocks64Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Poseidon2);
+    }
+
+    #[test]
+    fn zero_polynomials() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = BatchDeepFold::<Mersenne61Ext>::setup(params);
+        let zero = Mersenne61Ext::from_int(0);
+        let polynomials = (0..params.variable_num)
+            .rev()
+            .map(|x| MultilinearPolynomial::new(vec![zero; 2 << x]))
+            .collect::<Vec<_>>();
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomials);
+        let (evals, proof) = pcs.open(&data, &point);
+        assert!(evals.iter().all(|&x| x == zero));
+        let proof = Proof::<Mersenne61Ext>::decode(&proof.encode()).unwrap();
+        assert_eq!(pcs.verify(&commit, &point, &evals, &proof), Ok(()));
+    }
+
+    // Digest of a fixed opening made by the sequential build. The parallel
+    // build must reproduce it byte for byte in every pool size.
+    const SEQUENTIAL_PROOF: &str =
+        "3bf2b347a97f509e0a01a9c4b85d3be2ce3c2857eb9de19d7d947f7cebeeb7b2";
+
+    #[test]
+    fn proof_matches_sequential_fixture() {
+        let params = SetupParams {
+            variable_num: 12,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = BatchDeepFold::<Goldilocks64Ext>::setup(params);
+        let polynomials = (0..params.variable_num as u64)
+            .rev()
+            .map(|x| {
+                MultilinearPolynomial::new(
+                    (0..2u64 << x)
+                        .map(|i| Goldilocks64Ext::from_int(i * i + x + 1))
+                        .collect(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let point = (0..params.variable_num as u64)
+            .map(|i| Goldilocks64Ext::from_int(3 * i + 7))
+            .collect::<Vec<_>>();
+        let prove = || {
+            let (commits, data) = pcs.commit(polynomials.clone());
+            let mut bytes = commits.iter().flat_map(|x| x.encode()).collect::<Vec<_>>();
+            bytes.extend(pcs.open(&data, &point).1.encode());
+            Blake3Algorithm::hash(&bytes)
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        };
+        for_each_pool(|_, pool| assert_eq!(pool.install(prove), SEQUENTIAL_PROOF));
+    }
+
+    #[test]
+    fn early_termination() {
+        let variable_num = 6;
+        for final_poly_bits in 0..variable_num {
+            let pcs = BatchDeepFold::<Mersenne61Ext>::setup(SetupParams {
+                variable_num,
+                pcs: PcsParams {
+                    query_num: 10,
+                    grinding_bits: 0,
+                    final_poly_bits,
+                    ..PcsParams::default()
+                },
+            });
+            let polynomials = (0..variable_num)
+                .rev()
+                .map(|x| MultilinearPolynomial::random_polynomial(x + 1))
+                .collect::<Vec<_>>();
+            let point = (0..variable_num)
+                .map(|_| Mersenne61Ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = pcs.commit(polynomials);
+            let (evals, proof) = pcs.open(&data, &point);
+            assert_eq!(pcs.verify(&commit, &point, &evals, &proof), Ok(()));
+            let bytes = proof.encode();
+            assert_eq!(bytes.len(), proof.size());
+            assert_eq!(
+                Proof::<Mersenne61Ext>::decode(&bytes).unwrap().encode(),
+                bytes
+            );
+
+            let mut forged = proof.clone();
+            forged.out_evals[0][variable_num - 2] += Mersenne61Ext::from_int(1);
+            let wrong_evals = forged.evaluations();
+            assert!(pcs.verify(&commit, &point, &wrong_evals, &forged).is_err());
+            let mut tampered = proof.clone();
+            tampered.final_poly = Polynomial::random_polynomial((1 << final_poly_bits) + 1);
+            assert_eq!(
+                pcs.verify(&commit, &point, &evals, &tampered),
+                Err(VerificationError::MalformedProof("final polynomial"))
+            );
+        }
+    }
+
+    #[test]
+    fn merkle_caps() {
+        let variable_num = 6;
+        for cap_bits in 0..8 {
+            let pcs = BatchDeepFold::<Mersenne61Ext>::setup(SetupParams {
+                variable_num,
+                pcs: PcsParams {
+                    query_num: 10,
+                    grinding_bits: 0,
+                    cap_bits,
+                    ..PcsParams::default()
+                },
+            });
+            let polynomials = (0..variable_num)
+                .rev()
+                .map(|x| MultilinearPolynomial::random_polynomial(x + 1))
+                .collect::<Vec<_>>();
+            let point = (0..variable_num)
+                .map(|_| Mersenne61Ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = pcs.commit(polynomials);
+            let (evals, proof) = pcs.open(&data, &point);
+            assert_eq!(pcs.verify(&commit, &point, &evals, &proof), Ok(()));
+            let bytes = proof.encode();
+            assert_eq!(bytes.len(), proof.size());
+
+            let mut forged = proof.clone();
+            forged.merkle_root[0][0][0] ^= 1;
+            assert!(pcs.verify(&commit, &point, &evals, &forged).is_err());
+        }
+    }
+
     #[test]
     fn test_proof_size() {
         let mut wtr = Writer::from_path("batch.csv").unwrap();
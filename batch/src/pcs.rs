@@ -0,0 +1,71 @@
+use util::{
+    algebra::{coset::Coset, field::MyField, polynomial::MultilinearPolynomial},
+    error::VerificationError,
+    pcs::{MultilinearPCS, SetupParams},
+};
+
+use crate::{prover::Prover, verifier::Verifier, Commit, Proof};
+
+// Commits to `variable_num` polynomials with `variable_num, variable_num - 1,
+// ..., 1` variables; the i-th one is opened at `point[i..]`. Batch always
+// folds one variable per round, so `PcsParams::step` is ignored; it stops
+// `final_poly_bits` rounds early and sends what is left in coefficient form.
+pub struct BatchDeepFold<T: MyField> {
+    params: SetupParams,
+    interpolate_cosets: Vec<Coset<T>>,
+}
+
+impl<T: MyField> MultilinearPCS<T> for BatchDeepFold<T> {
+    type Params = SetupParams;
+    type Polynomial = Vec<MultilinearPolynomial<T>>;
+    type Evaluation = Vec<T>;
+    type Commitment = Vec<Commit<T>>;
+    type ProverData = Prover<T>;
+    type Proof = Proof<T>;
+
+    fn setup(params: SetupParams) -> Self {
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (params.variable_num + params.pcs.code_rate),
+            T::from_int(1),
+        )];
+        for i in 1..params.variable_num {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        BatchDeepFold {
+            params,
+            interpolate_cosets,
+        }
+    }
+
+    fn commit(&self, poly: Vec<MultilinearPolynomial<T>>) -> (Vec<Commit<T>>, Prover<T>) {
+        let prover = Prover::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            poly,
+            self.params.pcs,
+        );
+        (prover.commit_polynomial(), prover)
+    }
+
+    fn open(&self, data: &Prover<T>, point: &[T]) -> (Vec<T>, Proof<T>) {
+        let proof = data.clone().generate_proof(point.to_vec());
+        (proof.evaluations(), proof)
+    }
+
+    fn verify(
+        &self,
+        commitment: &Vec<Commit<T>>,
+        point: &[T],
+        eval: &Vec<T>,
+        proof: &Proof<T>,
+    ) -> Result<(), VerificationError> {
+        let verifier = Verifier::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            commitment.clone(),
+            point,
+            self.params.pcs,
+        );
+        verifier.verify(proof.clone(), eval)
+    }
+}
@@ -1,15 +1,27 @@
 use util::{
-    algebra::{coset::Coset, field::MyField, polynomial::MultilinearPolynomial},
+    algebra::{
+        coset::Coset,
+        field::MyField,
+        polynomial::{MultilinearPolynomial, Polynomial},
+    },
     interpolation::InterpolateValue,
+    pcs::PcsParams,
     query_result::QueryResult,
-    random_oracle::RandomOracle,
+    transcript::Transcript,
 };
 
 use crate::{Commit, DeepEval, Proof};
+use util::cfg_into_iter;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 #[derive(Clone)]
 pub struct Prover<T: MyField> {
     total_round: usize,
+    // Rounds actually folded; the last `final_poly_bits` variables are sent
+    // in the clear.
+    rounds: usize,
     interpolate_cosets: Vec<Coset<T>>,
     function_interpolations: Vec<InterpolateValue<T>>,
     folding_interpolations: Vec<InterpolateValue<T>>,
@@ -18,8 +30,11 @@ pub struct Prover<T: MyField> {
     evals: Vec<Vec<T>>,
     polynomials: Vec<MultilinearPolynomial<T>>,
     shuffle_eval: Option<DeepEval<T>>,
-    oracle: RandomOracle<T>,
-    final_value: Option<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    final_poly: Option<Polynomial<T>>,
+    unfolded_polys: Vec<Polynomial<T>>,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> Prover<T> {
@@ -27,34 +42,49 @@ impl<T: MyField> Prover<T> {
         total_round: usize,
         interpolate_cosets: &Vec<Coset<T>>,
         polynomials: Vec<MultilinearPolynomial<T>>,
-        oracle: &RandomOracle<T>,
+        params: PcsParams,
     ) -> Self {
+        assert!(params.final_poly_bits < total_round);
+        let function_interpolations: Vec<InterpolateValue<T>> = interpolate_cosets
+            .iter()
+            .zip(polynomials.iter())
+            .enumerate()
+            .map(|(i, (set, poly))| {
+                InterpolateValue::new(
+                    params.tree_hash::<T>(),
+                    set.fft(poly.coefficients().clone()),
+                    if i == 0 { 2 } else { 1 },
+                )
+            })
+            .collect();
+        let mut transcript = Transcript::new(b"batch");
+        for interpolation in &function_interpolations {
+            transcript.append_cap(&interpolation.commit());
+        }
+        let extra_v = polynomials
+            .iter()
+            .map(|x| {
+                let v = x.evaluate_as_polynomial(transcript.challenge());
+                transcript.append_field(&v);
+                v
+            })
+            .collect();
         Prover {
             total_round,
+            rounds: total_round - params.final_poly_bits,
             interpolate_cosets: interpolate_cosets.clone(),
-            function_interpolations: interpolate_cosets
-                .iter()
-                .zip(polynomials.iter())
-                .enumerate()
-                .map(|(i, (set, poly))| {
-                    InterpolateValue::new(
-                        set.fft(poly.coefficients().clone()),
-                        if i == 0 { 2 } else { 1 },
-                    )
-                })
-                .collect(),
+            function_interpolations,
             folding_interpolations: vec![],
             deep_eval: vec![],
-            extra_v: polynomials
-                .iter()
-                .enumerate()
-                .map(|(i, x)| x.evaluate_as_polynomial(oracle.alpha[i]))
-                .collect(),
+            extra_v,
             polynomials,
             evals: vec![],
             shuffle_eval: None,
-            oracle: oracle.clone(),
-            final_value: None,
+            transcript,
+            params,
+            final_poly: None,
+            unfolded_polys: vec![],
+            pow_nonce: 0,
         }
     }
 
@@ -63,13 +93,13 @@ impl<T: MyField> Prover<T> {
             .into_iter()
             .map(|i| Commit {
                 merkle_root: self.function_interpolations[i].commit(),
+                params: self.params,
                 deep: self.extra_v[i],
             })
             .collect()
     }
 
     fn evaluation_next_domain(&self, round: usize, challenge: T) -> Vec<T> {
-        let mut res = vec![];
         let len = self.interpolate_cosets[round].size();
         let get_folding_value = if round > 0 {
             &self.folding_interpolations[round - 1].value
@@ -78,18 +108,19 @@ impl<T: MyField> Prover<T> {
         };
         let coset = &self.interpolate_cosets[round];
         let challenge_2 = challenge * challenge;
-        for i in 0..(len / 2) {
-            let x = get_folding_value[i];
-            let nx = get_folding_value[i + len / 2];
-            let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
-            res.push(if round == self.total_round - 1 {
-                new_v * T::inverse_2()
-            } else {
-                new_v * T::inverse_2()
-                    + self.function_interpolations[round + 1].value[i] * challenge_2
-            });
-        }
-        res
+        cfg_into_iter!(0..(len / 2))
+            .map(|i| {
+                let x = get_folding_value[i];
+                let nx = get_folding_value[i + len / 2];
+                let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
+                if round == self.total_round - 1 {
+                    new_v * T::inverse_2()
+                } else {
+                    new_v * T::inverse_2()
+                        + self.function_interpolations[round + 1].value[i] * challenge_2
+                }
+            })
+            .collect()
     }
 
     pub fn prove(&mut self, point: Vec<T>) {
@@ -105,12 +136,16 @@ impl<T: MyField> Prover<T> {
                 .map(|i| self.polynomials[i].evaluate(&point[i..].to_vec()))
                 .collect(),
         );
-        for i in 0..self.total_round {
-            self.shuffle_eval
-                .as_mut()
-                .unwrap()
-                .append_else_eval(&polynomial);
-            let mut alpha = self.oracle.deep[i];
+        self.transcript.append_fields(&point);
+        self.transcript
+            .append_field(&self.shuffle_eval.as_ref().unwrap().first_eval);
+        self.transcript.append_fields(&self.evals[0]);
+        for i in 0..self.rounds {
+            let shuffle_eval = self.shuffle_eval.as_mut().unwrap();
+            shuffle_eval.append_else_eval(&polynomial);
+            self.transcript
+                .append_field(shuffle_eval.else_evals.last().unwrap());
+            let mut alpha = self.transcript.challenge();
             self.deep_eval.push({
                 let deep_point = std::iter::successors(Some(alpha), |&x| Some(x * x))
                     .take(self.total_round - i)
@@ -123,36 +158,59 @@ impl<T: MyField> Prover<T> {
             });
             self.evals.push({
                 let mut res = vec![];
-                for j in (i + 1)..self.total_round {
+                // polynomials past the last round are never merged in
+                for j in (i + 1)..self.total_round.min(self.rounds + 1) {
                     alpha *= alpha;
                     res.push(self.polynomials[j].evaluate_as_polynomial(alpha));
                 }
                 res
             });
+            self.transcript
+                .append_field(&self.deep_eval.last().unwrap().first_eval);
+            self.transcript.append_fields(self.evals.last().unwrap());
             for deep in &mut self.deep_eval {
                 deep.append_else_eval(&polynomial);
+                self.transcript
+                    .append_field(deep.else_evals.last().unwrap());
             }
 
-            let challenge = self.oracle.folding_challenges[i];
-            let next_evalutation = self.evaluation_next_domain(i, challenge);
+            let challenge = self.transcript.challenge();
+            if i < self.rounds - 1 {
+                let next_evalutation = self.evaluation_next_domain(i, challenge);
+                let interpolation =
+                    InterpolateValue::new(self.params.tree_hash::<T>(), next_evalutation, 2);
+                self.transcript.append_cap(&interpolation.commit());
+                self.folding_interpolations.push(interpolation);
+            }
+            polynomial.fold_self(challenge);
             if i < self.total_round - 1 {
-                polynomial.fold_self(challenge);
                 polynomial.add_mult(&self.polynomials[i + 1], challenge * challenge);
-
-                self.folding_interpolations
-                    .push(InterpolateValue::new(next_evalutation, 2));
-            } else {
-                self.final_value = Some(next_evalutation[0]);
             }
         }
+        let final_poly = Polynomial::new(polynomial.coefficients().clone());
+        self.transcript.append_fields(final_poly.coefficients());
+        self.final_poly = Some(final_poly);
+        self.unfolded_polys = self
+            .polynomials
+            .iter()
+            .skip(self.rounds + 1)
+            .map(|x| Polynomial::new(x.coefficients().clone()))
+            .collect();
+        for poly in &self.unfolded_polys {
+            self.transcript.append_fields(poly.coefficients());
+        }
+        self.pow_nonce = self.transcript.grind(self.params.grinding_bits);
     }
 
-    pub fn query(&self) -> (Vec<QueryResult<T>>, Vec<QueryResult<T>>) {
+    pub fn query(&mut self) -> (Vec<QueryResult<T>>, Vec<QueryResult<T>>) {
         let mut folding_res = vec![];
         let mut function_res = vec![];
-        let mut leaf_indices = self.oracle.query_list.clone();
+        let mut leaf_indices = self.transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> 1,
+        );
 
-        for i in 0..self.total_round {
+        for i in 0..self.rounds {
             let len = self.interpolate_cosets[i].size();
             leaf_indices = leaf_indices.iter_mut().map(|v| *v % (len >> 1)).collect();
             leaf_indices.sort();
@@ -166,6 +224,14 @@ impl<T: MyField> Prover<T> {
                 function_res.push(self.function_interpolations[i + 1].query(&leaf_indices));
             }
         }
+        // The polynomials sent in the clear are checked against their commitments.
+        for i in self.rounds + 1..self.total_round {
+            let len = self.interpolate_cosets[i].size();
+            leaf_indices = leaf_indices.iter_mut().map(|v| *v % len).collect();
+            leaf_indices.sort();
+            leaf_indices.dedup();
+            function_res.push(self.function_interpolations[i].query(&leaf_indices));
+        }
         (folding_res, function_res)
     }
 
@@ -173,7 +239,7 @@ impl<T: MyField> Prover<T> {
         self.prove(point);
         let query_result = self.query();
         Proof {
-            merkle_root: (1..self.total_round)
+            merkle_root: (1..self.rounds)
                 .into_iter()
                 .map(|x| self.folding_interpolations[x - 1].commit())
                 .collect(),
@@ -184,9 +250,11 @@ impl<T: MyField> Prover<T> {
                 .map(|x| (x.first_eval, x.else_evals.clone()))
                 .collect(),
             shuffle_evals: self.shuffle_eval.as_ref().unwrap().else_evals.clone(),
-            final_value: self.final_value.unwrap(),
+            final_poly: self.final_poly.unwrap(),
+            unfolded_polys: self.unfolded_polys,
             evaluation: self.shuffle_eval.as_ref().unwrap().first_eval,
             out_evals: self.evals.clone(),
+            pow_nonce: self.pow_nonce,
         }
     }
 }
@@ -1,20 +1,35 @@
-use util::random_oracle::RandomOracle;
+use util::error::VerificationError;
+use util::transcript::Transcript;
 use util::{
-    algebra::{coset::Coset, field::MyField},
+    algebra::{
+        coset::Coset,
+        field::MyField,
+        polynomial::{MultilinearPolynomial, Polynomial},
+    },
     merkle_tree::MerkleTreeVerifier,
+    pcs::PcsParams,
 };
 
 use crate::{Commit, DeepEval, Proof};
 
+// Reads a polynomial sent in coefficient form as one of `variable_num` variables.
+fn multilinear<T: MyField>(poly: &Polynomial<T>, variable_num: usize) -> MultilinearPolynomial<T> {
+    let mut coefficients = poly.coefficients().clone();
+    coefficients.resize(1 << variable_num, T::from_int(0));
+    MultilinearPolynomial::new(coefficients)
+}
+
 #[derive(Clone)]
 pub struct Verifier<T: MyField> {
     total_round: usize,
+    rounds: usize,
     interpolate_cosets: Vec<Coset<T>>,
     polynomial_roots: Vec<MerkleTreeVerifier>,
     folding_roots: Vec<MerkleTreeVerifier>,
     first_deep: Vec<T>,
-    oracle: RandomOracle<T>,
-    final_value: Option<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    commit_params: Vec<PcsParams>,
     shuffle_eval: Option<DeepEval<T>>,
     deep_evals: Vec<DeepEval<T>>,
     open_point: Vec<T>,
@@ -25,17 +40,30 @@ impl<T: MyField> Verifier<T> {
         total_round: usize,
         coset: &Vec<Coset<T>>,
         commit: Vec<Commit<T>>,
-        oracle: &RandomOracle<T>,
+        open_point: &[T],
+        params: PcsParams,
     ) -> Self {
+        let mut transcript = Transcript::new(b"batch");
+        for c in &commit {
+            transcript.append_cap(&c.merkle_root);
+        }
+        for c in &commit {
+            let _alpha: T = transcript.challenge();
+            transcript.append_field(&c.deep);
+        }
         Verifier {
             total_round,
+            rounds: total_round.saturating_sub(params.final_poly_bits),
             interpolate_cosets: coset.clone(),
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            commit_params: commit.iter().map(|c| c.params).collect(),
             polynomial_roots: commit
                 .iter()
                 .enumerate()
                 .map(|(i, c)| {
                     MerkleTreeVerifier::new(
+                        params.tree_hash::<T>(),
                         if i == 0 {
                             coset[i].size() / 2
                         } else {
@@ -47,26 +75,27 @@ impl<T: MyField> Verifier<T> {
                 .collect(),
             folding_roots: vec![],
             first_deep: commit.iter().map(|x| x.deep).collect(),
-            final_value: None,
             shuffle_eval: None,
             deep_evals: vec![],
-            open_point: (0..total_round).map(|_| T::random_element()).collect(),
+            open_point: open_point.to_vec(),
         }
     }
 
-    pub fn get_open_point(&self) -> Vec<T> {
-        self.open_point.clone()
-    }
-
-    pub fn verify(mut self, proof: Proof<T>) -> bool {
-        self.final_value = Some(proof.final_value);
+    // `evaluations[i]` is the claimed value of the i-th polynomial at `open_point[i..]`.
+    pub fn verify(mut self, proof: Proof<T>, evaluations: &[T]) -> Result<(), VerificationError> {
+        self.check_shape(&proof)?;
+        if proof.evaluations() != evaluations {
+            return Err(VerificationError::EvaluationMismatch);
+        }
+        let (deep, folding_challenges, query_list) = self.replay_transcript(&proof)?;
         let mut leave_number = self.interpolate_cosets[0].size() / 2;
         for merkle_root in proof.merkle_root {
             leave_number /= 2;
-            self.folding_roots.push(MerkleTreeVerifier {
-                merkle_root,
+            self.folding_roots.push(MerkleTreeVerifier::new(
+                self.params.tree_hash::<T>(),
                 leave_number,
-            });
+                &merkle_root,
+            ));
         }
         self.shuffle_eval = Some(DeepEval {
             point: self.open_point.clone(),
@@ -79,7 +108,7 @@ impl<T: MyField> Verifier<T> {
             .enumerate()
             .for_each(|(idx, (first_eval, else_evals))| {
                 self.deep_evals.push(DeepEval {
-                    point: std::iter::successors(Some(self.oracle.deep[idx]), |&x| Some(x * x))
+                    point: std::iter::successors(Some(deep[idx]), |&x| Some(x * x))
                         .take(self.total_round - idx)
                         .collect::<Vec<_>>(),
                     first_eval,
@@ -87,8 +116,9 @@ impl<T: MyField> Verifier<T> {
                 });
             });
         let (folding_proof, function_proof) = &proof.query_result;
-        let mut leaf_indices = self.oracle.query_list.clone();
-        for i in 0..self.total_round {
+        let mut leaf_indices = query_list;
+        let final_poly = multilinear(&proof.final_poly, self.params.final_poly_bits);
+        for i in 0..self.rounds {
             let domain_size = self.interpolate_cosets[i].size();
             leaf_indices = leaf_indices
                 .iter_mut()
@@ -97,58 +127,190 @@ impl<T: MyField> Verifier<T> {
             leaf_indices.sort();
             leaf_indices.dedup();
             if i == 0 {
-                function_proof[i].verify_merkle_tree(&leaf_indices, 2, &self.polynomial_roots[i]);
+                function_proof[i].verify_merkle_tree(
+                    i,
+                    &leaf_indices,
+                    2,
+                    &self.polynomial_roots[i],
+                )?;
             } else {
                 folding_proof[i - 1].verify_merkle_tree(
+                    i,
                     &leaf_indices,
                     2,
                     &self.folding_roots[i - 1],
-                );
+                )?;
             }
             if i < self.total_round - 1 {
                 function_proof[i + 1].verify_merkle_tree(
+                    i,
                     &leaf_indices,
                     1,
                     &self.polynomial_roots[i + 1],
-                );
-            } else {
-                let challenges = self.oracle.folding_challenges[0..self.total_round].to_vec();
-                assert_eq!(
-                    self.shuffle_eval
-                        .as_ref()
-                        .unwrap()
-                        .verify(&challenges, &proof.out_evals[0]),
-                    self.final_value.unwrap()
-                );
+                )?;
+            }
+            if i == self.rounds - 1 {
+                if !self.shuffle_eval.as_ref().unwrap().verify(
+                    &folding_challenges,
+                    &proof.out_evals[0],
+                    &final_poly,
+                ) {
+                    return Err(VerificationError::EvaluationMismatch);
+                }
                 for (j, de) in self.deep_evals.iter().enumerate() {
-                    assert_eq!(
-                        de.verify(&challenges, &proof.out_evals[j + 1]),
-                        self.final_value.unwrap()
-                    );
+                    if !de.verify(&folding_challenges, &proof.out_evals[j + 1], &final_poly) {
+                        return Err(VerificationError::DeepEvalMismatch { index: j });
+                    }
                 }
             }
             let folding_value = if i == 0 {
-                &function_proof[0].proof_values
+                &function_proof[0]
             } else {
-                &folding_proof[i - 1].proof_values
+                &folding_proof[i - 1]
             };
-            let challenge = self.oracle.folding_challenges[i];
-            for j in &leaf_indices {
-                let x = folding_value[j];
-                let nx = folding_value[&(j + domain_size / 2)];
-                let v =
-                    x + nx + challenge * (x - nx) * self.interpolate_cosets[i].element_inv_at(*j);
-                if i == self.total_round - 1 {
-                    assert_eq!(v * T::inverse_2(), self.final_value.unwrap());
+            let challenge = folding_challenges[i];
+            for (position, j) in leaf_indices.iter().enumerate() {
+                let mut v =
+                    folding_value.fold(position, &self.interpolate_cosets[i..], &[challenge])
+                        * T::inverse_2();
+                if i < self.total_round - 1 {
+                    v += challenge * challenge * function_proof[i + 1].leaf(position)[0];
+                }
+                // the last layer is never committed; it must be the
+                // evaluations of `final_poly`
+                let expected = if i == self.rounds - 1 {
+                    let x = self.interpolate_cosets[i].element_at(*j);
+                    proof.final_poly.evaluation_at(x * x)
                 } else {
-                    assert_eq!(
-                        v * T::inverse_2()
-                            + challenge * challenge * function_proof[i + 1].proof_values[j],
-                        folding_proof[i].proof_values[j]
-                    );
+                    folding_proof[i].entry(*j, self.folding_roots[i].leave_number)?
+                };
+                if v != expected {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *j,
+                    });
                 }
             }
         }
-        true
+        // The polynomials never merged into g are checked against their
+        // commitments and their claimed evaluations directly.
+        for (poly, i) in proof.unfolded_polys.iter().zip(self.rounds + 1..) {
+            let domain_size = self.interpolate_cosets[i].size();
+            leaf_indices = leaf_indices.iter_mut().map(|v| *v % domain_size).collect();
+            leaf_indices.sort();
+            leaf_indices.dedup();
+            function_proof[i].verify_merkle_tree(i, &leaf_indices, 1, &self.polynomial_roots[i])?;
+            for (position, j) in leaf_indices.iter().enumerate() {
+                let x = self.interpolate_cosets[i].element_at(*j);
+                if function_proof[i].leaf(position)[0] != poly.evaluation_at(x) {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *j,
+                    });
+                }
+            }
+            let poly = multilinear(poly, self.total_round - i);
+            if poly.evaluate(&self.open_point[i..].to_vec()) != proof.out_evals[0][i - 1] {
+                return Err(VerificationError::EvaluationMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_shape(&self, proof: &Proof<T>) -> Result<(), VerificationError> {
+        if self.commit_params.iter().any(|x| *x != self.params) {
+            return Err(VerificationError::ParameterMismatch);
+        }
+        if self.open_point.len() != self.total_round {
+            return Err(VerificationError::MalformedProof("open point"));
+        }
+        if self.rounds == 0 {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        let (folding_proof, function_proof) = &proof.query_result;
+        if proof.merkle_root.len() != self.rounds - 1 || folding_proof.len() != self.rounds - 1 {
+            return Err(VerificationError::MalformedProof("folding round count"));
+        }
+        if function_proof.len() != self.total_round {
+            return Err(VerificationError::MalformedProof("function query count"));
+        }
+        if proof.shuffle_evals.len() != self.rounds {
+            return Err(VerificationError::MalformedProof(
+                "shuffle evaluation count",
+            ));
+        }
+        if proof.deep_evals.len() != self.rounds
+            || proof
+                .deep_evals
+                .iter()
+                .enumerate()
+                .any(|(idx, (_, else_evals))| else_evals.len() != self.rounds - idx)
+        {
+            return Err(VerificationError::MalformedProof("DEEP evaluation count"));
+        }
+        // Only the polynomials merged in by the last round are evaluated at
+        // the DEEP points.
+        let merged = self.total_round.min(self.rounds + 1);
+        if proof.out_evals.len() != self.rounds + 1
+            || proof.out_evals[0].len() != self.total_round - 1
+            || proof.out_evals[1..]
+                .iter()
+                .enumerate()
+                .any(|(idx, evals)| evals.len() + idx + 1 != merged)
+        {
+            return Err(VerificationError::MalformedProof("out evaluation count"));
+        }
+        if proof.final_poly.coefficients().len() > 1 << self.params.final_poly_bits
+            || proof.unfolded_polys.len() != self.total_round - merged
+            || proof
+                .unfolded_polys
+                .iter()
+                .zip(merged..)
+                .any(|(poly, i)| poly.coefficients().len() > 1 << (self.total_round - i))
+        {
+            return Err(VerificationError::MalformedProof("final polynomial"));
+        }
+        Ok(())
+    }
+
+    // Derives the DEEP points, folding challenges and queries in the prover's order.
+    fn replay_transcript(
+        &mut self,
+        proof: &Proof<T>,
+    ) -> Result<(Vec<T>, Vec<T>, Vec<usize>), VerificationError> {
+        let mut deep = vec![];
+        let mut folding_challenges = vec![];
+        self.transcript.append_fields(&self.open_point);
+        self.transcript.append_field(&proof.evaluation);
+        self.transcript.append_fields(&proof.out_evals[0]);
+        for i in 0..self.rounds {
+            self.transcript.append_field(&proof.shuffle_evals[i]);
+            deep.push(self.transcript.challenge());
+            self.transcript.append_field(&proof.deep_evals[i].0);
+            self.transcript.append_fields(&proof.out_evals[i + 1]);
+            for (idx, (_, else_evals)) in proof.deep_evals[..i + 1].iter().enumerate() {
+                self.transcript.append_field(&else_evals[i - idx]);
+            }
+            folding_challenges.push(self.transcript.challenge());
+            if i < self.rounds - 1 {
+                self.transcript.append_cap(&proof.merkle_root[i]);
+            }
+        }
+        self.transcript
+            .append_fields(proof.final_poly.coefficients());
+        for poly in &proof.unfolded_polys {
+            self.transcript.append_fields(poly.coefficients());
+        }
+        if !self
+            .transcript
+            .check_grinding(self.params.grinding_bits, proof.pow_nonce)
+        {
+            return Err(VerificationError::InsufficientWork);
+        }
+        let query_list = self.transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> 1,
+        );
+        Ok((deep, folding_challenges, query_list))
     }
 }
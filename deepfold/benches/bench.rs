@@ -1,30 +1,32 @@
 extern crate criterion;
 use criterion::*;
 
-use deepfold::{prover::Prover, verifier::Verifier};
-use util::{
-    algebra::{
-        coset::Coset,
-        field::{mersenne61_ext::Mersenne61Ext, MyField},
-        polynomial::MultilinearPolynomial,
-    },
-    random_oracle::RandomOracle,
+use deepfold::{prover::Prover, verifier::Verifier, DeepEval};
+use util::algebra::{
+    coset::Coset,
+    field::{mersenne61_ext::Mersenne61Ext, MyField},
+    polynomial::MultilinearPolynomial,
 };
 
-use util::{CODE_RATE, SECURITY_BITS, STEP};
+use util::parallel::for_each_pool;
+use util::pcs::PcsParams;
+use util::storage::Storage;
 fn commit<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = PcsParams::default();
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
 
     criterion.bench_function(&format!("deepfold commit {:02}", variable_num), move |b| {
         b.iter_batched(
             || polynomial.clone(),
             |p| {
-                let prover = Prover::new(variable_num, &interpolate_cosets, p, &oracle, STEP);
+                let prover = Prover::new(variable_num, &interpolate_cosets, p, params);
                 let _commit = prover.commit_polynomial();
             },
             BatchSize::SmallInput,
@@ -39,16 +41,19 @@ fn bench_commit(c: &mut Criterion) {
 }
 
 fn open<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = PcsParams::default();
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
-    let prover = Prover::new(variable_num, &interpolate_cosets, polynomial, &oracle, STEP);
-    let commit = prover.commit_polynomial();
-    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle, STEP);
-    let point = verifier.get_open_point();
+    let prover = Prover::new(variable_num, &interpolate_cosets, polynomial, params);
+    let point = (0..variable_num)
+        .map(|_| T::random_element())
+        .collect::<Vec<_>>();
 
     criterion.bench_function(&format!("deepfold open {:02}", variable_num), move |b| {
         b.iter_batched(
@@ -68,23 +73,34 @@ fn bench_open(c: &mut Criterion) {
 }
 
 fn verify<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = PcsParams::default();
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
-    let prover = Prover::new(variable_num, &interpolate_cosets, polynomial, &oracle, STEP);
+    let prover = Prover::new(
+        variable_num,
+        &interpolate_cosets,
+        polynomial.clone(),
+        params,
+    );
     let commit = prover.commit_polynomial();
-    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle, STEP);
-    let point = verifier.get_open_point();
+    let point = (0..variable_num)
+        .map(|_| T::random_element())
+        .collect::<Vec<_>>();
+    let evaluation = polynomial.evaluate(&point);
+    let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &point, params);
     let proof = prover.generate_proof(point);
 
     criterion.bench_function(&format!("deepfold verify {:02}", variable_num), move |b| {
         b.iter_batched(
             || (verifier.clone(), proof.clone()),
             |(v, pi)| {
-                assert!(v.verify(pi));
+                assert!(v.verify(pi, evaluation).is_ok());
             },
             BatchSize::SmallInput,
         )
@@ -97,10 +113,121 @@ fn bench_verify(c: &mut Criterion) {
     }
 }
 
+// Prover scaling by thread count; proofs are identical for every pool size.
+fn bench_open_threads(c: &mut Criterion) {
+    let variable_num = 20;
+    let params = PcsParams::default();
+    let polynomial = MultilinearPolynomial::<Mersenne61Ext>::random_polynomial(variable_num);
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        Mersenne61Ext::from_int(1),
+    )];
+    for i in 1..variable_num + 1 {
+        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+    }
+    let point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
+    for_each_pool(|threads, pool| {
+        c.bench_function(
+            &format!("deepfold prove {:02} threads {}", variable_num, threads),
+            |b| {
+                b.iter(|| {
+                    pool.install(|| {
+                        let prover = Prover::new(
+                            variable_num,
+                            &interpolate_cosets,
+                            polynomial.clone(),
+                            params,
+                        );
+                        let _proof = prover.generate_proof(point.clone());
+                    })
+                })
+            },
+        );
+    });
+}
+
+fn fold<T: MyField>(poly_hypercube: &mut Vec<T>, challenge: T) {
+    let len = poly_hypercube.len() / 2;
+    let (lo, hi) = poly_hypercube.split_at_mut(len);
+    for (x, &y) in lo.iter_mut().zip(hi.iter()) {
+        *x += (y - *x) * challenge;
+    }
+    poly_hypercube.truncate(len);
+}
+
+// The former bookkeeping: every evaluation folds its own copy of the hypercube.
+fn fold_at<T: MyField>(point: &[T], mut poly_hypercube: Vec<T>) -> T {
+    for &v in point {
+        fold(&mut poly_hypercube, v);
+    }
+    poly_hypercube[0]
+}
+
+// One DEEP point per round, as in a proof with step 1, over the whole run.
+fn deep_evals<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let polynomial = MultilinearPolynomial::<T>::random_polynomial(variable_num);
+    let hypercube = polynomial.evaluate_hypercube();
+    let points = (0..variable_num)
+        .map(|r| {
+            (0..variable_num - r)
+                .map(|_| T::random_element())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let challenges = (0..variable_num)
+        .map(|_| T::random_element())
+        .collect::<Vec<_>>();
+
+    criterion.bench_function(
+        &format!("deepfold deep evals naive {:02}", variable_num),
+        |b| {
+            b.iter(|| {
+                let mut hypercube = hypercube.clone();
+                for round in 0..variable_num {
+                    black_box(fold_at(&points[round], hypercube.clone()));
+                    for point in &points[..round + 1] {
+                        let mut point = point[round - (variable_num - point.len())..].to_vec();
+                        point[0] += T::from_int(1);
+                        black_box(fold_at(&point, hypercube.clone()));
+                    }
+                    fold(&mut hypercube, challenges[round]);
+                }
+            })
+        },
+    );
+    criterion.bench_function(
+        &format!("deepfold deep evals shared {:02}", variable_num),
+        |b| {
+            b.iter(|| {
+                let mut hypercube = hypercube.clone();
+                let mut evals = vec![];
+                for round in 0..variable_num {
+                    evals.push(DeepEval::new(
+                        points[round].clone(),
+                        &hypercube,
+                        &Storage::Memory,
+                    ));
+                    DeepEval::append_else_evals(&mut evals, &hypercube);
+                    fold(&mut hypercube, challenges[round]);
+                }
+                black_box(evals);
+            })
+        },
+    );
+}
+
+fn bench_deep_evals(c: &mut Criterion) {
+    for i in [20, 22] {
+        deep_evals::<Mersenne61Ext>(c, i);
+    }
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_commit, bench_open, bench_verify
+    targets = bench_commit, bench_open, bench_verify, bench_open_threads, bench_deep_evals
 }
 
 criterion_main!(benches);
@@ -0,0 +1,286 @@
+use util::{
+    algebra::{
+        coset::Coset,
+        field::MyField,
+        polynomial::{MultilinearPolynomial, Polynomial},
+    },
+    interpolation::{InterpolateValue, MultiInterpolateValue},
+    pcs::PcsParams,
+    query_result::QueryResult,
+    schedule::FoldingSchedule,
+    storage::{Buffer, Storage},
+    transcript::Transcript,
+};
+
+use crate::{deep_point, fold_codeword, sumcheck_next_domain, BatchCommit, BatchProof, DeepEval};
+
+// Polynomials of one variable count, committed side by side in a single tree
+// over the coset their codewords live on.
+#[derive(Clone)]
+struct CommitGroup<T: MyField> {
+    members: Vec<usize>,
+    oracle: MultiInterpolateValue<T>,
+}
+
+// Opens k polynomials of up to `total_round` variables at m points with one
+// DeepFold run. A polynomial f with d fewer variables stands for
+// f(X^{2^d}), which ignores the first d coordinates of a point, so its
+// committed codeword already gives that polynomial's values on the big domain.
+#[derive(Clone)]
+pub struct BatchProver<T: MyField> {
+    total_round: usize,
+    interpolate_cosets: Vec<Coset<T>>,
+    polynomials: Vec<MultilinearPolynomial<T>>,
+    groups: Vec<CommitGroup<T>>,
+    transcript: Transcript,
+    params: PcsParams,
+    schedule: FoldingSchedule,
+}
+
+impl<T: MyField> BatchProver<T> {
+    pub fn new(
+        total_round: usize,
+        interpolate_cosets: &[Coset<T>],
+        polynomials: Vec<MultilinearPolynomial<T>>,
+        params: PcsParams,
+    ) -> Self {
+        let schedule = params.folding(total_round);
+        let step = schedule.step(0);
+        let variable_nums = polynomials
+            .iter()
+            .map(|x| x.variable_num())
+            .collect::<Vec<_>>();
+        let mut sizes = variable_nums.clone();
+        sizes.sort_by(|x, y| y.cmp(x));
+        sizes.dedup();
+        let groups = sizes
+            .into_iter()
+            .map(|variable_num| {
+                assert!(variable_num <= total_round && variable_num + params.code_rate >= step);
+                let members = (0..polynomials.len())
+                    .filter(|&j| variable_nums[j] == variable_num)
+                    .collect::<Vec<_>>();
+                let coset = &interpolate_cosets[total_round - variable_num];
+                let oracle = MultiInterpolateValue::new(
+                    params.tree_hash::<T>(),
+                    members
+                        .iter()
+                        .map(|&j| coset.fft(polynomials[j].coefficients().clone()))
+                        .collect(),
+                    1 << step,
+                );
+                CommitGroup { members, oracle }
+            })
+            .collect::<Vec<_>>();
+        let mut transcript = Transcript::new(b"deepfold batch");
+        for group in &groups {
+            transcript.append_cap(&group.oracle.commit());
+        }
+        for n in &variable_nums {
+            transcript.append_bytes(&(*n as u32).to_le_bytes());
+        }
+        BatchProver {
+            total_round,
+            interpolate_cosets: interpolate_cosets.to_vec(),
+            polynomials,
+            groups,
+            transcript,
+            params,
+            schedule,
+        }
+    }
+
+    pub fn commit_polynomials(&self) -> BatchCommit {
+        BatchCommit {
+            merkle_root: self.groups.iter().map(|x| x.oracle.commit()).collect(),
+            variable_nums: self.polynomials.iter().map(|x| x.variable_num()).collect(),
+            params: self.params,
+        }
+    }
+
+    fn append_deep_eval(
+        &self,
+        transcript: &mut Transcript,
+        deep_eval: &mut Vec<DeepEval<T>>,
+        round: usize,
+        hypercube_interpolation: &[T],
+    ) {
+        let deep = DeepEval::new(
+            deep_point(transcript.challenge(), self.total_round - round),
+            hypercube_interpolation,
+            &Storage::Memory,
+        );
+        transcript.append_field(&deep.first_eval);
+        deep_eval.push(deep);
+    }
+
+    // Every point has `total_round` coordinates; a polynomial with fewer
+    // variables is evaluated at the matching suffix.
+    pub fn generate_proof(&self, points: &[Vec<T>]) -> BatchProof<T> {
+        let mut transcript = self.transcript.clone();
+        let evaluations = points
+            .iter()
+            .map(|point| {
+                assert_eq!(point.len(), self.total_round);
+                self.polynomials
+                    .iter()
+                    .map(|poly| {
+                        poly.evaluate(&point[self.total_round - poly.variable_num()..].to_vec())
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for (point, evaluations) in points.iter().zip(&evaluations) {
+            transcript.append_fields(point);
+            transcript.append_fields(evaluations);
+        }
+
+        // g = sum_j alpha^j f_j over `total_round` variables
+        let alpha: T = transcript.challenge();
+        let coefficients = std::iter::successors(Some(T::from_int(1)), |&x| Some(x * alpha))
+            .take(self.polynomials.len())
+            .collect::<Vec<_>>();
+        let mut hypercube_interpolation = Buffer::from(vec![T::from_int(0); 1 << self.total_round]);
+        for (poly, &coefficient) in self.polynomials.iter().zip(&coefficients) {
+            let hypercube = poly.evaluate_hypercube();
+            for (i, v) in hypercube_interpolation.iter_mut().enumerate() {
+                *v += coefficient * hypercube[i % hypercube.len()];
+            }
+        }
+        let mut folding_value =
+            Buffer::from(vec![T::from_int(0); self.interpolate_cosets[0].size()]);
+        for group in &self.groups {
+            for (&j, codeword) in group.members.iter().zip(&group.oracle.values) {
+                for (i, v) in folding_value.iter_mut().enumerate() {
+                    *v += coefficients[j] * codeword[i % codeword.len()];
+                }
+            }
+        }
+
+        let mut deep_eval = vec![];
+        self.append_deep_eval(&mut transcript, &mut deep_eval, 0, &hypercube_interpolation);
+        let mut shuffle_eval = points
+            .iter()
+            .zip(&evaluations)
+            .map(|(point, evaluations)| {
+                DeepEval::with_eval(
+                    point.clone(),
+                    evaluations
+                        .iter()
+                        .zip(&coefficients)
+                        .fold(T::from_int(0), |acc, (&x, &c)| acc + x * c),
+                    &Storage::Memory,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let rounds = self.schedule.rounds();
+        let mut interpolations: Vec<InterpolateValue<T>> = vec![];
+        let mut final_poly = None;
+        for i in 0..rounds {
+            let mut challenges: Vec<T> = vec![];
+            let step = self.schedule.step(i);
+            for j in 0..step {
+                let round = self.schedule.offset(i) + j;
+                DeepEval::append_else_evals(
+                    shuffle_eval.iter_mut().chain(deep_eval.iter_mut()),
+                    &hypercube_interpolation,
+                );
+                for eval in shuffle_eval.iter().chain(&deep_eval) {
+                    transcript.append_field(eval.else_evals.last().unwrap());
+                }
+                challenges.push(transcript.challenge());
+
+                if round < self.total_round - 1 {
+                    let m = 1 << (self.total_round - round - 1);
+                    sumcheck_next_domain(&mut hypercube_interpolation, m, challenges[j]);
+                    if j + 1 < step {
+                        self.append_deep_eval(
+                            &mut transcript,
+                            &mut deep_eval,
+                            round + 1,
+                            &hypercube_interpolation,
+                        );
+                    }
+                }
+            }
+
+            let next_evaluation = fold_codeword(
+                &folding_value,
+                &self.interpolate_cosets[self.schedule.offset(i)..],
+                &challenges,
+                &Storage::Memory,
+            );
+            if i < rounds - 1 {
+                let interpolation = InterpolateValue::from_buffer(
+                    self.params.tree_hash::<T>(),
+                    next_evaluation.clone(),
+                    1 << self.schedule.step(i + 1),
+                );
+                transcript.append_cap(&interpolation.commit());
+                interpolations.push(interpolation);
+            } else {
+                // only the low coefficients survive folding down to
+                // `final_poly_bits` variables
+                let mut coefficients =
+                    self.interpolate_cosets[self.schedule.total()].ifft(next_evaluation.to_vec());
+                coefficients.truncate(1 << self.params.final_poly_bits);
+                let poly = Polynomial::new(coefficients);
+                transcript.append_fields(poly.coefficients());
+                final_poly = Some(poly);
+            }
+            folding_value = next_evaluation;
+            let folded = self.schedule.offset(i + 1);
+            if folded < self.schedule.total() {
+                self.append_deep_eval(
+                    &mut transcript,
+                    &mut deep_eval,
+                    folded,
+                    &hypercube_interpolation,
+                );
+            }
+        }
+
+        let pow_nonce = transcript.grind(self.params.grinding_bits);
+        let mut leaf_indices = transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
+        let commit_query = self
+            .groups
+            .iter()
+            .map(|group| {
+                let len = group.oracle.leave_num();
+                let mut indices = leaf_indices.iter().map(|v| v % len).collect::<Vec<_>>();
+                indices.sort();
+                indices.dedup();
+                group.oracle.query(&indices)
+            })
+            .collect();
+        let mut query_result: Vec<QueryResult<T>> = vec![];
+        for (i, interpolation) in interpolations.iter().enumerate() {
+            let len = self.interpolate_cosets[self.schedule.offset(i + 1)].size();
+            leaf_indices = leaf_indices
+                .iter_mut()
+                .map(|v| *v % (len >> self.schedule.step(i + 1)))
+                .collect();
+            leaf_indices.sort();
+            leaf_indices.dedup();
+            query_result.push(interpolation.query(&leaf_indices));
+        }
+
+        BatchProof {
+            evaluations,
+            commit_query,
+            merkle_root: interpolations.iter().map(|x| x.commit()).collect(),
+            query_result,
+            deep_evals: deep_eval
+                .into_iter()
+                .map(|x| (x.first_eval, x.else_evals))
+                .collect(),
+            shuffle_evals: shuffle_eval.into_iter().map(|x| x.else_evals).collect(),
+            final_poly: final_poly.unwrap(),
+            pow_nonce,
+        }
+    }
+}
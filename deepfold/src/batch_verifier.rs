@@ -0,0 +1,308 @@
+use util::error::VerificationError;
+use util::transcript::Transcript;
+use util::{
+    algebra::{coset::Coset, field::MyField},
+    merkle_tree::MerkleTreeVerifier,
+    pcs::PcsParams,
+    query_result::fold,
+    schedule::FoldingSchedule,
+};
+
+use crate::{deep_point, final_multilinear, BatchCommit, BatchProof, DeepEval};
+
+#[derive(Clone)]
+pub struct BatchVerifier<T: MyField> {
+    total_round: usize,
+    interpolate_cosets: Vec<Coset<T>>,
+    commit: BatchCommit,
+    transcript: Transcript,
+    params: PcsParams,
+    schedule: FoldingSchedule,
+    open_points: Vec<Vec<T>>,
+}
+
+// Challenges replayed from the transcript.
+struct Challenges<T: MyField> {
+    alpha: T,
+    folding: Vec<T>,
+    deep: Vec<T>,
+    query_list: Vec<usize>,
+}
+
+impl<T: MyField> BatchVerifier<T> {
+    pub fn new(
+        total_round: usize,
+        coset: &[Coset<T>],
+        commit: BatchCommit,
+        open_points: &[Vec<T>],
+        params: PcsParams,
+    ) -> Self {
+        let mut transcript = Transcript::new(b"deepfold batch");
+        for root in &commit.merkle_root {
+            transcript.append_cap(root);
+        }
+        for n in &commit.variable_nums {
+            transcript.append_bytes(&(*n as u32).to_le_bytes());
+        }
+        BatchVerifier {
+            total_round,
+            interpolate_cosets: coset.to_vec(),
+            commit,
+            transcript,
+            params,
+            schedule: params.folding(total_round),
+            open_points: open_points.to_vec(),
+        }
+    }
+
+    // `evaluations[l][j]` is the claimed value of polynomial j at point l.
+    pub fn verify(
+        &self,
+        proof: BatchProof<T>,
+        evaluations: &[Vec<T>],
+    ) -> Result<(), VerificationError> {
+        if proof.evaluations != evaluations {
+            return Err(VerificationError::EvaluationMismatch);
+        }
+        self.check_shape(&proof)?;
+        let challenges = self.replay_transcript(&proof)?;
+        let rounds = self.schedule.rounds();
+
+        let mut coefficients = vec![T::from_int(1)];
+        for _ in 1..self.commit.variable_nums.len() {
+            coefficients.push(*coefficients.last().unwrap() * challenges.alpha);
+        }
+        let final_poly = final_multilinear(&proof.final_poly, self.params.final_poly_bits);
+        for (point, (evaluations, else_evals)) in self
+            .open_points
+            .iter()
+            .zip(proof.evaluations.iter().zip(proof.shuffle_evals))
+        {
+            let shuffle_eval = DeepEval::claimed(
+                point.clone(),
+                evaluations
+                    .iter()
+                    .zip(&coefficients)
+                    .fold(T::from_int(0), |acc, (&x, &c)| acc + x * c),
+                else_evals,
+            );
+            if !shuffle_eval.verify(&challenges.folding, &final_poly) {
+                return Err(VerificationError::EvaluationMismatch);
+            }
+        }
+        for (index, (first_eval, else_evals)) in proof.deep_evals.into_iter().enumerate() {
+            let deep_eval = DeepEval::claimed(
+                deep_point(challenges.deep[index], self.total_round - index),
+                first_eval,
+                else_evals,
+            );
+            if !deep_eval.verify(&challenges.folding, &final_poly) {
+                return Err(VerificationError::DeepEvalMismatch { index });
+            }
+        }
+
+        // The first layer is the combination of every committed codeword.
+        let domain_size = self.interpolate_cosets[0].size();
+        let step = self.schedule.step(0);
+        let mut leaf_indices = challenges.query_list;
+        leaf_indices.sort();
+        leaf_indices.dedup();
+        // Entry s of leaf `leaf_indices[k]` sits at `(k << step) + s`.
+        let mut folding_value = vec![T::from_int(0); leaf_indices.len() << step];
+        for (g, variable_num) in self.commit.group_sizes().into_iter().enumerate() {
+            let members = (0..self.commit.variable_nums.len())
+                .filter(|&j| self.commit.variable_nums[j] == variable_num)
+                .collect::<Vec<_>>();
+            let len = self.interpolate_cosets[self.total_round - variable_num].size();
+            let mut indices = leaf_indices
+                .iter()
+                .map(|v| v % (len >> step))
+                .collect::<Vec<_>>();
+            indices.sort();
+            indices.dedup();
+            let query = &proof.commit_query[g];
+            query.verify_merkle_tree_multi(
+                0,
+                &indices,
+                1 << step,
+                members.len(),
+                &MerkleTreeVerifier::new(
+                    self.params.tree_hash::<T>(),
+                    len >> step,
+                    &self.commit.merkle_root[g],
+                ),
+            )?;
+            for (position, k) in leaf_indices.iter().enumerate() {
+                for s in 0..(1 << step) {
+                    let ind = (k + s * (domain_size >> step)) % len;
+                    let (leaf, offset) = (ind % (len >> step), ind / (len >> step));
+                    for (c, &j) in members.iter().enumerate() {
+                        folding_value[(position << step) + s] +=
+                            coefficients[j] * query.get(leaf, (c << step) + offset)?;
+                    }
+                }
+            }
+        }
+
+        for i in 0..rounds {
+            let (offset, step) = (self.schedule.offset(i), self.schedule.step(i));
+            let domain_size = self.interpolate_cosets[offset].size();
+            leaf_indices = leaf_indices
+                .iter_mut()
+                .map(|v| *v % (domain_size >> step))
+                .collect();
+            leaf_indices.sort();
+            leaf_indices.dedup();
+            if i > 0 {
+                proof.query_result[i - 1].verify_merkle_tree(
+                    i,
+                    &leaf_indices,
+                    1 << step,
+                    &MerkleTreeVerifier::new(
+                        self.params.tree_hash::<T>(),
+                        domain_size >> step,
+                        &proof.merkle_root[i - 1],
+                    ),
+                )?;
+            }
+
+            // Every folding step also halves.
+            let scale = T::inverse_2().pow(step);
+            let cosets = &self.interpolate_cosets[offset..];
+            let folding = &challenges.folding[offset..offset + step];
+            for (position, k) in leaf_indices.iter().enumerate() {
+                let value = if i == 0 {
+                    let leaf = folding_value[position << step..(position + 1) << step].to_vec();
+                    fold(leaf, *k, cosets, folding)
+                } else {
+                    proof.query_result[i - 1].fold(position, cosets, folding)
+                } * scale;
+                let expected = if i + 1 == rounds {
+                    let point = self.interpolate_cosets[self.schedule.total()].element_at(*k);
+                    proof.final_poly.evaluation_at(point)
+                } else {
+                    let next = self.schedule.offset(i + 1);
+                    let leaves = self.interpolate_cosets[next].size() >> self.schedule.step(i + 1);
+                    proof.query_result[i].entry(*k, leaves)?
+                };
+                if value != expected {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *k,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_shape(&self, proof: &BatchProof<T>) -> Result<(), VerificationError> {
+        if self.commit.params != self.params {
+            return Err(VerificationError::ParameterMismatch);
+        }
+        if self.open_points.iter().any(|x| x.len() != self.total_round) {
+            return Err(VerificationError::MalformedProof("open point"));
+        }
+        let rounds = self.schedule.rounds();
+        let groups = self.commit.group_sizes();
+        if self.commit.merkle_root.len() != groups.len()
+            || groups.first().is_some_and(|&n| n > self.total_round)
+            || groups
+                .last()
+                .is_some_and(|&n| n + self.params.code_rate < self.schedule.step(0))
+        {
+            return Err(VerificationError::MalformedProof("commitment shape"));
+        }
+        if proof.evaluations.len() != self.open_points.len()
+            || proof
+                .evaluations
+                .iter()
+                .any(|x| x.len() != self.commit.variable_nums.len())
+        {
+            return Err(VerificationError::MalformedProof("evaluation count"));
+        }
+        if proof.commit_query.len() != groups.len() {
+            return Err(VerificationError::MalformedProof("query result count"));
+        }
+        if proof.merkle_root.len() != rounds - 1 || proof.query_result.len() != rounds - 1 {
+            return Err(VerificationError::MalformedProof("merkle root count"));
+        }
+        if proof.shuffle_evals.len() != self.open_points.len()
+            || proof
+                .shuffle_evals
+                .iter()
+                .any(|x| x.len() != self.schedule.total())
+        {
+            return Err(VerificationError::MalformedProof(
+                "shuffle evaluation count",
+            ));
+        }
+        if proof.deep_evals.len() != self.schedule.total()
+            || proof
+                .deep_evals
+                .iter()
+                .enumerate()
+                .any(|(idx, (_, else_evals))| else_evals.len() != self.schedule.total() - idx)
+        {
+            return Err(VerificationError::MalformedProof("DEEP evaluation count"));
+        }
+        if proof.final_poly.coefficients().len() > 1 << self.params.final_poly_bits {
+            return Err(VerificationError::MalformedProof("final polynomial degree"));
+        }
+        Ok(())
+    }
+
+    // Derives every challenge by absorbing the proof in the prover's order.
+    fn replay_transcript(&self, proof: &BatchProof<T>) -> Result<Challenges<T>, VerificationError> {
+        let mut transcript = self.transcript.clone();
+        for (point, evaluations) in self.open_points.iter().zip(&proof.evaluations) {
+            transcript.append_fields(point);
+            transcript.append_fields(evaluations);
+        }
+        let alpha = transcript.challenge();
+        let mut deep = vec![transcript.challenge()];
+        transcript.append_field(&proof.deep_evals[0].0);
+        let mut folding = vec![];
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
+            let step = self.schedule.step(i);
+            for j in 0..step {
+                let round = self.schedule.offset(i) + j;
+                for else_evals in &proof.shuffle_evals {
+                    transcript.append_field(&else_evals[round]);
+                }
+                for (idx, (_, else_evals)) in proof.deep_evals[..round + 1].iter().enumerate() {
+                    transcript.append_field(&else_evals[round - idx]);
+                }
+                folding.push(transcript.challenge());
+                if round < self.total_round - 1 && j + 1 < step {
+                    deep.push(transcript.challenge());
+                    transcript.append_field(&proof.deep_evals[round + 1].0);
+                }
+            }
+            if i + 1 < rounds {
+                transcript.append_cap(&proof.merkle_root[i]);
+            } else {
+                transcript.append_fields(proof.final_poly.coefficients());
+            }
+            let folded = self.schedule.offset(i + 1);
+            if folded < self.schedule.total() {
+                deep.push(transcript.challenge());
+                transcript.append_field(&proof.deep_evals[folded].0);
+            }
+        }
+        if !transcript.check_grinding(self.params.grinding_bits, proof.pow_nonce) {
+            return Err(VerificationError::InsufficientWork);
+        }
+        let query_list = transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
+        Ok(Challenges {
+            alpha,
+            folding,
+            deep,
+            query_list,
+        })
+    }
+}
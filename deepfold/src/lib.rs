@@ -1,55 +1,202 @@
 use std::mem::size_of;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use util::{
-    algebra::{field::MyField, polynomial::Polynomial},
-    merkle_tree::MERKLE_ROOT_SIZE,
+    algebra::{
+        coset::Coset,
+        field::MyField,
+        polynomial::{MultilinearPolynomial, Polynomial},
+    },
+    merkle_tree::MerkleCap,
+    pcs::PcsParams,
     query_result::QueryResult,
+    serialization::{
+        caps_size, deserialize_with, field_sequence_size, serialize_with, Reader, Serializable,
+        SerializationError, Writer, LENGTH_SIZE, NONCE_SIZE, VERSION_SIZE,
+    },
+    storage::{Buffer, Storage},
 };
+use util::{cfg_into_iter, cfg_iter_mut};
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+pub mod batch_prover;
+pub mod batch_verifier;
+pub mod pcs;
 pub mod prover;
 pub mod verifier;
 
+fn deep_point<T: MyField>(alpha: T, len: usize) -> Vec<T> {
+    std::iter::successors(Some(alpha), |&x| Some(x * x))
+        .take(len)
+        .collect()
+}
+
+fn sumcheck_next_domain<T: MyField>(
+    hypercube_interpolation: &mut Buffer<T>,
+    m: usize,
+    challenge: T,
+) {
+    let (lo, hi) = hypercube_interpolation.split_at_mut(m);
+    cfg_iter_mut!(lo).zip(&hi[..m]).for_each(|(x, &y)| {
+        *x *= T::from_int(1) - challenge;
+        *x += y * challenge;
+    });
+    hypercube_interpolation.truncate(m);
+}
+
+// Folds a whole codeword over `cosets[0]` once per challenge.
+fn fold_codeword<T: MyField>(
+    values: &[T],
+    cosets: &[Coset<T>],
+    challenges: &[T],
+    storage: &Storage,
+) -> Buffer<T> {
+    let mut res: Option<Buffer<T>> = None;
+    for (coset, &challenge) in cosets.iter().zip(challenges) {
+        let get_folding_value = res.as_deref().unwrap_or(values);
+        let len = coset.size();
+        let next = Buffer::from_fn(storage, len / 2, |i| {
+            let x = get_folding_value[i];
+            let nx = get_folding_value[i + len / 2];
+            let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
+            new_v * T::inverse_2()
+        });
+        res = Some(next);
+    }
+    res.unwrap_or_else(|| Buffer::from_fn(storage, values.len(), |i| values[i]))
+}
+
+// The final polynomial's coefficients read as a multilinear polynomial in
+// `variable_num` variables; trailing zero coefficients are not sent.
+fn final_multilinear<T: MyField>(
+    final_poly: &Polynomial<T>,
+    variable_num: usize,
+) -> MultilinearPolynomial<T> {
+    let mut coefficients = final_poly.coefficients().clone();
+    coefficients.resize(1 << variable_num, T::from_int(0));
+    MultilinearPolynomial::new(coefficients)
+}
+
+// Hypercube entries are summed this many at a time per task.
+const EVAL_BLOCK: usize = 1 << 12;
+
+// Evaluates the hypercube at (q0, tail) for every (q0, eq(., tail)) in one
+// pass; each entry pair is read once however many points there are.
+fn evaluate_tails<T: MyField>(poly_hypercube: &[T], tails: &[(T, &[T])]) -> Vec<T> {
+    let half = poly_hypercube.len() / 2;
+    let (lo, hi) = poly_hypercube.split_at(half);
+    let zero = T::from_int(0);
+    let sums = cfg_into_iter!(0..half.div_ceil(EVAL_BLOCK))
+        .map(|k| {
+            let mut acc = vec![zero; tails.len()];
+            for i in k * EVAL_BLOCK..std::cmp::min((k + 1) * EVAL_BLOCK, half) {
+                let (x, d) = (lo[i], hi[i] - lo[i]);
+                for (acc, &(q0, eq)) in acc.iter_mut().zip(tails) {
+                    *acc += eq[i] * (x + q0 * d);
+                }
+            }
+            acc
+        })
+        .collect::<Vec<_>>();
+    (0..tails.len())
+        .map(|e| sums.iter().fold(zero, |acc, x| acc + x[e]))
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct DeepEval<T: MyField> {
     point: Vec<T>,
     first_eval: T,
     else_evals: Vec<T>,
+    // Prover only: eq(., point[i..]) for the suffixes still to be used, the
+    // shortest first; the last one serves the next evaluation and is dropped.
+    // Together they are as large as the hypercube, so they are placed in the
+    // prover's storage.
+    suffix_eqs: Vec<Buffer<T>>,
 }
 
 impl<T: MyField> DeepEval<T> {
-    pub fn new(point: Vec<T>, poly_hypercube: Vec<T>) -> Self {
+    pub fn new(point: Vec<T>, poly_hypercube: &[T], storage: &Storage) -> Self {
+        assert_eq!(poly_hypercube.len(), 1 << point.len());
+        let mut res = Self::with_eval(point, T::from_int(0), storage);
+        res.first_eval = res.evaluate_other(poly_hypercube);
+        res
+    }
+
+    // An evaluation whose value at `point` is already known.
+    pub fn with_eval(point: Vec<T>, first_eval: T, storage: &Storage) -> Self {
+        let mut suffix_eqs = vec![Buffer::from(vec![T::from_int(1)])];
+        for &p in point[1..].iter().rev() {
+            let last = suffix_eqs.last().unwrap();
+            let half = last.len();
+            let next = Buffer::from_fn(storage, 2 * half, |i| {
+                let t = last[i % half] * p;
+                if i < half {
+                    last[i] - t
+                } else {
+                    t
+                }
+            });
+            suffix_eqs.push(next);
+        }
         DeepEval {
-            point: point.clone(),
-            first_eval: Self::evaluatioin_at(point, poly_hypercube),
+            point,
+            first_eval,
             else_evals: vec![],
+            suffix_eqs,
         }
     }
 
-    fn evaluatioin_at(point: Vec<T>, mut poly_hypercube: Vec<T>) -> T {
-        let mut len = poly_hypercube.len();
-        assert_eq!(len, 1 << point.len());
-        for v in point.into_iter() {
-            len >>= 1;
-            for i in 0..len {
-                poly_hypercube[i] *= T::from_int(1) - v;
-                let tmp = poly_hypercube[i + len] * v;
-                poly_hypercube[i] += tmp;
-            }
+    // Another polynomial's value at `point`, reusing this point's eq tables;
+    // only possible before any else evaluation is appended.
+    fn evaluate_other(&self, poly_hypercube: &[T]) -> T {
+        assert!(self.else_evals.is_empty());
+        evaluate_tails(
+            poly_hypercube,
+            &[(self.point[0], &self.suffix_eqs.last().unwrap()[..])],
+        )[0]
+    }
+
+    // The verifier side, which only checks claimed values.
+    fn claimed(point: Vec<T>, first_eval: T, else_evals: Vec<T>) -> Self {
+        DeepEval {
+            point,
+            first_eval,
+            else_evals,
+            suffix_eqs: vec![],
         }
-        poly_hypercube[0]
     }
 
-    pub fn append_else_eval(&mut self, poly_hypercube: Vec<T>) {
-        let mut point = self.point[self.else_evals.len()..].to_vec();
-        point[0] += T::from_int(1);
-        self.else_evals
-            .push(Self::evaluatioin_at(point, poly_hypercube));
+    // Appends the next else evaluation of every given point, all in one pass
+    // over `poly_hypercube`.
+    pub fn append_else_evals<'a>(
+        evals: impl IntoIterator<Item = &'a mut DeepEval<T>>,
+        poly_hypercube: &[T],
+    ) {
+        let mut evals = evals.into_iter().collect::<Vec<_>>();
+        let tails = evals
+            .iter()
+            .map(|eval| {
+                let q0 = eval.point[eval.else_evals.len()] + T::from_int(1);
+                (q0, &eval.suffix_eqs.last().unwrap()[..])
+            })
+            .collect::<Vec<_>>();
+        let values = evaluate_tails(poly_hypercube, &tails);
+        for (eval, value) in evals.iter_mut().zip(values) {
+            eval.else_evals.push(value);
+            eval.suffix_eqs.pop();
+        }
     }
 
-    pub fn verify(&self, challenges: &Vec<T>) -> T {
-        let (_, challenges) = challenges.split_at(challenges.len() - self.point.len());
+    // Folds the claimed values with the last challenges, one per else
+    // evaluation, and checks the result against `final_poly`, the polynomial
+    // left after folding, at the rest of `point`.
+    pub fn verify(&self, challenges: &Vec<T>, final_poly: &MultilinearPolynomial<T>) -> bool {
+        let folded = self.else_evals.len();
+        let (_, challenges) = challenges.split_at(challenges.len() - folded);
         let mut y_0 = self.first_eval;
-        assert_eq!(self.point.len(), self.else_evals.len());
         for ((x, eval), challenge) in self
             .point
             .iter()
@@ -59,72 +206,1233 @@ impl<T: MyField> DeepEval<T> {
             let y_1 = eval.clone();
             y_0 += (y_1 - y_0) * (challenge.clone() - x.clone());
         }
-        y_0
+        y_0 == final_poly.evaluate(&self.point[folded..].to_vec())
     }
 }
 
+#[derive(Clone)]
 pub struct Commit<T: MyField> {
-    merkle_root: [u8; MERKLE_ROOT_SIZE],
+    merkle_root: MerkleCap,
+    params: PcsParams,
     deep: T,
+    mask: Option<MaskCommit<T>>,
+}
+
+// Hiding mode: the masking polynomial's cap and its value at the first DEEP
+// point.
+#[derive(Clone)]
+struct MaskCommit<T: MyField> {
+    merkle_root: MerkleCap,
+    deep: T,
+}
+
+// Hiding mode: the masking polynomial's value at the opened point and the
+// openings of its codeword at the queried leaves.
+#[derive(Clone)]
+struct MaskOpening<T: MyField, B: MyField> {
+    evaluation: T,
+    query: QueryResult<B>,
 }
 
 #[derive(Clone)]
-pub struct Proof<T: MyField> {
-    merkle_root: Vec<[u8; MERKLE_ROOT_SIZE]>,
+pub struct Proof<T: MyField, B: MyField = T> {
+    merkle_root: Vec<MerkleCap>,
+    // Openings of the committed codeword, then of each folded layer.
+    commit_query: QueryResult<B>,
     query_result: Vec<QueryResult<T>>,
     deep_evals: Vec<(T, Vec<T>)>,
     shuffle_evals: Vec<T>,
     evaluation: T,
-    final_value: T,
+    // What is left after folding, sent in coefficient form.
+    final_poly: Polynomial<T>,
+    pow_nonce: u64,
+    mask: Option<MaskOpening<T, B>>,
+}
+
+// One Merkle cap per distinct variable count, in decreasing order of size;
+// polynomials of equal size share a tree.
+#[derive(Clone)]
+pub struct BatchCommit {
+    merkle_root: Vec<MerkleCap>,
+    variable_nums: Vec<usize>,
+    params: PcsParams,
+}
+
+impl BatchCommit {
+    // Variable counts of the committed polynomials without repeats, largest first.
+    fn group_sizes(&self) -> Vec<usize> {
+        let mut sizes = self.variable_nums.clone();
+        sizes.sort_by(|x, y| y.cmp(x));
+        sizes.dedup();
+        sizes
+    }
+}
+
+#[derive(Clone)]
+pub struct BatchProof<T: MyField> {
+    // evaluations[l][j] is polynomial j at point l.
+    evaluations: Vec<Vec<T>>,
+    commit_query: Vec<QueryResult<T>>,
+    merkle_root: Vec<MerkleCap>,
+    query_result: Vec<QueryResult<T>>,
+    deep_evals: Vec<(T, Vec<T>)>,
+    shuffle_evals: Vec<Vec<T>>,
     final_poly: Polynomial<T>,
+    pow_nonce: u64,
 }
 
-impl<T: MyField> Proof<T> {
-    fn size(&self) -> usize {
-        self.merkle_root.len() * MERKLE_ROOT_SIZE
+impl<T: MyField> BatchProof<T> {
+    pub fn evaluations(&self) -> &Vec<Vec<T>> {
+        &self.evaluations
+    }
+
+    // Exact length of `encode`.
+    pub fn size(&self) -> usize {
+        VERSION_SIZE
+            + self.evaluations.iter().fold(LENGTH_SIZE, |acc, x| {
+                acc + field_sequence_size::<T>(x.len())
+            })
+            + self
+                .commit_query
+                .iter()
+                .fold(LENGTH_SIZE, |acc, x| acc + x.proof_size())
+            + caps_size(&self.merkle_root)
+            + self
+                .query_result
+                .iter()
+                .fold(LENGTH_SIZE, |acc, x| acc + x.proof_size())
+            + self.deep_evals.iter().fold(LENGTH_SIZE, |acc, x| {
+                acc + size_of::<T>() + field_sequence_size::<T>(x.1.len())
+            })
+            + self.shuffle_evals.iter().fold(LENGTH_SIZE, |acc, x| {
+                acc + field_sequence_size::<T>(x.len())
+            })
+            + field_sequence_size::<T>(self.final_poly.coefficients().len())
+            + NONCE_SIZE
+    }
+}
+
+impl<T: MyField, B: MyField> Proof<T, B> {
+    // Exact length of `encode`.
+    pub fn size(&self) -> usize {
+        VERSION_SIZE
+            + caps_size(&self.merkle_root)
             + self
                 .query_result
                 .iter()
-                .fold(0, |acc, x| acc + x.proof_size())
-            + (self.deep_evals.iter().fold(0, |acc, x| acc + x.1.len())
-                + self.shuffle_evals.len()
-                + 2)
-                * size_of::<T>()
+                .fold(LENGTH_SIZE + self.commit_query.proof_size(), |acc, x| {
+                    acc + x.proof_size()
+                })
+            + self.deep_evals.iter().fold(LENGTH_SIZE, |acc, x| {
+                acc + size_of::<T>() + field_sequence_size::<T>(x.1.len())
+            })
+            + field_sequence_size::<T>(self.shuffle_evals.len())
+            + size_of::<T>()
+            + field_sequence_size::<T>(self.final_poly.coefficients().len())
+            + NONCE_SIZE
+            + FLAG_SIZE
+            + self
+                .mask
+                .as_ref()
+                .map_or(0, |mask| size_of::<T>() + mask.query.proof_size())
+    }
+}
+
+// Marks whether the hiding-mode part that follows is present.
+const FLAG_SIZE: usize = 1;
+
+fn write_flag(writer: &mut Writer, flag: bool) {
+    writer.write_u8(flag as u8);
+}
+
+fn read_flag(reader: &mut Reader) -> Result<bool, SerializationError> {
+    match reader.read_u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(SerializationError::NonCanonical("hiding flag")),
+    }
+}
+
+impl<T: MyField> Serializable for Commit<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_cap(&self.merkle_root);
+        writer.write(&self.params);
+        writer.write_field(&self.deep);
+        write_flag(writer, self.mask.is_some());
+        if let Some(mask) = &self.mask {
+            writer.write_cap(&mask.merkle_root);
+            writer.write_field(&mask.deep);
+        }
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let merkle_root = reader.read_cap()?;
+        let params = reader.read()?;
+        let deep = reader.read_field()?;
+        let mask = if read_flag(reader)? {
+            Some(MaskCommit {
+                merkle_root: reader.read_cap()?,
+                deep: reader.read_field()?,
+            })
+        } else {
+            None
+        };
+        Ok(Commit {
+            merkle_root,
+            params,
+            deep,
+            mask,
+        })
+    }
+}
+
+// The committed layer's opening is written as the first query result, so a
+// proof with B = T encodes like a plain list of openings.
+impl<T: MyField, B: MyField> Serializable for Proof<T, B> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_caps(&self.merkle_root);
+        writer.write_length(self.query_result.len() + 1);
+        writer.write(&self.commit_query);
+        for query in &self.query_result {
+            writer.write(query);
+        }
+        writer.write_length(self.deep_evals.len());
+        for (first_eval, else_evals) in &self.deep_evals {
+            writer.write_field(first_eval);
+            writer.write_fields(else_evals);
+        }
+        writer.write_fields(&self.shuffle_evals);
+        writer.write_field(&self.evaluation);
+        writer.write_polynomial(&self.final_poly);
+        writer.write_u64(self.pow_nonce);
+        write_flag(writer, self.mask.is_some());
+        if let Some(mask) = &self.mask {
+            writer.write_field(&mask.evaluation);
+            writer.write(&mask.query);
+        }
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let merkle_root = reader.read_caps()?;
+        let len = reader.read_length(0)?;
+        if len == 0 {
+            return Err(SerializationError::NonCanonical("query result count"));
+        }
+        let commit_query = reader.read()?;
+        let query_result = (1..len).map(|_| reader.read()).collect::<Result<_, _>>()?;
+        let deep_evals = (0..reader.read_length(size_of::<T>() + LENGTH_SIZE)?)
+            .map(|_| Ok((reader.read_field()?, reader.read_fields()?)))
+            .collect::<Result<_, _>>()?;
+        Ok(Proof {
+            merkle_root,
+            commit_query,
+            query_result,
+            deep_evals,
+            shuffle_evals: reader.read_fields()?,
+            evaluation: reader.read_field()?,
+            final_poly: reader.read_polynomial()?,
+            pow_nonce: reader.read_u64()?,
+            mask: if read_flag(reader)? {
+                Some(MaskOpening {
+                    evaluation: reader.read_field()?,
+                    query: reader.read()?,
+                })
+            } else {
+                None
+            },
+        })
+    }
+}
+
+const VARIABLE_NUM_SIZE: usize = 4;
+
+impl Serializable for BatchCommit {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_caps(&self.merkle_root);
+        writer.write_length(self.variable_nums.len());
+        for &n in &self.variable_nums {
+            writer.write_u32(u32::try_from(n).expect("variable count does not fit in u32"));
+        }
+        writer.write(&self.params);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let merkle_root = reader.read_caps()?;
+        let variable_nums = (0..reader.read_length(VARIABLE_NUM_SIZE)?)
+            .map(|_| Ok(reader.read_u32()? as usize))
+            .collect::<Result<_, _>>()?;
+        Ok(BatchCommit {
+            merkle_root,
+            variable_nums,
+            params: reader.read()?,
+        })
+    }
+}
+
+impl<T: MyField> Serializable for BatchProof<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_length(self.evaluations.len());
+        for evaluations in &self.evaluations {
+            writer.write_fields(evaluations);
+        }
+        writer.write_all(&self.commit_query);
+        writer.write_caps(&self.merkle_root);
+        writer.write_all(&self.query_result);
+        writer.write_length(self.deep_evals.len());
+        for (first_eval, else_evals) in &self.deep_evals {
+            writer.write_field(first_eval);
+            writer.write_fields(else_evals);
+        }
+        writer.write_length(self.shuffle_evals.len());
+        for shuffle_evals in &self.shuffle_evals {
+            writer.write_fields(shuffle_evals);
+        }
+        writer.write_polynomial(&self.final_poly);
+        writer.write_u64(self.pow_nonce);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let evaluations = (0..reader.read_length(LENGTH_SIZE)?)
+            .map(|_| reader.read_fields())
+            .collect::<Result<_, _>>()?;
+        let commit_query = reader.read_all()?;
+        let merkle_root = reader.read_caps()?;
+        let query_result = reader.read_all()?;
+        let deep_evals = (0..reader.read_length(size_of::<T>() + LENGTH_SIZE)?)
+            .map(|_| Ok((reader.read_field()?, reader.read_fields()?)))
+            .collect::<Result<_, _>>()?;
+        let shuffle_evals = (0..reader.read_length(LENGTH_SIZE)?)
+            .map(|_| reader.read_fields())
+            .collect::<Result<_, _>>()?;
+        Ok(BatchProof {
+            evaluations,
+            commit_query,
+            merkle_root,
+            query_result,
+            deep_evals,
+            shuffle_evals,
+            final_poly: reader.read_polynomial()?,
+            pow_nonce: reader.read_u64()?,
+        })
+    }
+}
+
+impl<T: MyField> Serialize for Commit<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for Commit<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
+impl<T: MyField, B: MyField> Serialize for Proof<T, B> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField, B: MyField> Deserialize<'de> for Proof<T, B> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
+impl Serialize for BatchCommit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BatchCommit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
+impl<T: MyField> Serialize for BatchProof<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for BatchProof<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{prover::Prover, verifier::Verifier};
+    use crate::{
+        batch_prover::BatchProver, batch_verifier::BatchVerifier, pcs::DeepFold, prover::Prover,
+        sumcheck_next_domain, verifier::Verifier, BatchCommit, BatchProof, Commit, DeepEval, Proof,
+        FLAG_SIZE,
+    };
     use csv::Writer;
-    use util::{
-        algebra::{
-            coset::Coset,
-            field::{m31_ext::M31ext, MyField},
-            polynomial::MultilinearPolynomial,
+    use std::mem::size_of;
+    use util::algebra::{
+        coset::Coset,
+        field::{
+            goldilocks64::Goldilocks64, goldilocks64ext::Goldilocks64Ext, m31_ext::M31ext, MyField,
         },
-        random_oracle::RandomOracle,
+        polynomial::{MultilinearPolynomial, Polynomial},
     };
-    use util::{CODE_RATE, SECURITY_BITS, STEP};
+    use util::error::VerificationError;
+    use util::merkle_tree::{Blake3Algorithm, HashChoice, MerkleHasher};
+    use util::parallel::for_each_pool;
+    use util::pcs::{MultilinearPCS, PcsParams, SetupParams};
+    use util::schedule::FoldingSchedule;
+    use util::serialization::{Serializable, SerializationError, NONCE_SIZE};
+    use util::storage::Storage;
+
+    // Few queries and no grinding, for the tests that only check behaviour.
+    fn test_params() -> PcsParams {
+        PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        }
+    }
 
     fn output_proof_size<T: MyField>(variable_num: usize) -> usize {
+        let params = PcsParams::default();
         let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
-        let mut interpolate_cosets =
-            vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + params.code_rate),
+            T::from_int(1),
+        )];
         for i in 1..variable_num + 1 {
             interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
         }
-        let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
-        let prover = Prover::new(variable_num, &interpolate_cosets, polynomial, &oracle, STEP);
-        let commit = prover.commit_polynomial();
-        let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &oracle, STEP);
-        let point = verifier.get_open_point();
+        let prover = Prover::new(variable_num, &interpolate_cosets, polynomial, params);
+        let commit = Commit::decode(&prover.commit_polynomial().encode()).unwrap();
+        let point = (0..variable_num)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &point, params);
         let proof = prover.generate_proof(point);
+        let evaluation = proof.evaluation;
         let size = proof.size();
-        assert!(verifier.verify(proof));
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), size);
+        let proof = Proof::decode(&bytes).unwrap();
+        assert_eq!(proof.encode(), bytes);
+        assert_eq!(verifier.verify(proof, evaluation), Ok(()));
         size
     }
 
+    #[test]
+    fn serialization() {
+        let variable_num = 8;
+        let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + test_params().code_rate),
+            M31ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let prover = Prover::new(
+            variable_num,
+            &interpolate_cosets,
+            polynomial.clone(),
+            test_params(),
+        );
+        let commit = prover.commit_polynomial();
+        let commit_bytes = bincode::serialize(&commit).unwrap();
+        let commit: Commit<M31ext> = bincode::deserialize(&commit_bytes).unwrap();
+        let point = (0..variable_num)
+            .map(|_| M31ext::random_element())
+            .collect::<Vec<_>>();
+        let verifier = Verifier::new(
+            variable_num,
+            &interpolate_cosets,
+            commit,
+            &point,
+            test_params(),
+        );
+        let proof = prover.generate_proof(point.clone());
+        let evaluation = polynomial.evaluate(&point);
+
+        let bytes = proof.encode();
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Proof::<M31ext>::decode(&trailing).err(),
+            Some(SerializationError::TrailingBytes(1))
+        );
+        let mut non_canonical = bytes.clone();
+        // The last final_poly coefficient, ahead of the nonce and mask flag.
+        let n = non_canonical.len() - NONCE_SIZE - FLAG_SIZE;
+        non_canonical[n - size_of::<M31ext>()..n].fill(0xff);
+        assert_eq!(
+            Proof::<M31ext>::decode(&non_canonical).err(),
+            Some(SerializationError::NonCanonicalField)
+        );
+
+        let proof: Proof<M31ext> =
+            bincode::deserialize(&bincode::serialize(&proof).unwrap()).unwrap();
+        assert_eq!(proof.encode(), bytes);
+        assert_eq!(verifier.verify(proof, evaluation), Ok(()));
+    }
+
+    #[test]
+    fn rejects_bad_proofs() {
+        let variable_num = 8;
+        let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + test_params().code_rate),
+            M31ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let prover = Prover::new(
+            variable_num,
+            &interpolate_cosets,
+            polynomial.clone(),
+            test_params(),
+        );
+        let commit = prover.commit_polynomial();
+        let point = (0..variable_num)
+            .map(|_| M31ext::random_element())
+            .collect::<Vec<_>>();
+        let verifier = Verifier::new(
+            variable_num,
+            &interpolate_cosets,
+            commit,
+            &point,
+            test_params(),
+        );
+        let proof = prover.generate_proof(point.clone());
+        let evaluation = polynomial.evaluate(&point);
+
+        let mut truncated = proof.clone();
+        truncated.query_result.pop();
+        assert_eq!(
+            verifier.clone().verify(truncated, evaluation),
+            Err(VerificationError::MalformedProof("query result count"))
+        );
+
+        let mut tampered = proof.clone();
+        tampered.commit_query.values[0] += M31ext::from_int(1);
+        assert!(matches!(
+            verifier.clone().verify(tampered, evaluation),
+            Err(VerificationError::MerklePathInvalid { round: 0, .. })
+        ));
+
+        let mut garbage = proof.clone();
+        garbage.query_result[0].proof_bytes = vec![1, 2, 3];
+        assert!(matches!(
+            verifier.clone().verify(garbage, evaluation),
+            Err(VerificationError::MerklePathInvalid { round: 1, .. })
+        ));
+
+        let mut wrong_evaluation = proof.clone();
+        wrong_evaluation.evaluation += M31ext::from_int(1);
+        assert_eq!(
+            verifier
+                .clone()
+                .verify(wrong_evaluation.clone(), evaluation),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        assert!(verifier
+            .clone()
+            .verify(wrong_evaluation, evaluation + M31ext::from_int(1))
+            .is_err());
+
+        assert_eq!(verifier.verify(proof, evaluation), Ok(()));
+    }
+
+    #[test]
+    fn grinding() {
+        let variable_num = 8;
+        let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + test_params().code_rate),
+            M31ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let params = PcsParams {
+            grinding_bits: 12,
+            ..test_params()
+        };
+        let prover = Prover::new(
+            variable_num,
+            &interpolate_cosets,
+            polynomial.clone(),
+            params,
+        );
+        let mut commit = prover.commit_polynomial();
+        let point = (0..variable_num)
+            .map(|_| M31ext::random_element())
+            .collect::<Vec<_>>();
+        let verifier = Verifier::new(
+            variable_num,
+            &interpolate_cosets,
+            commit.clone(),
+            &point,
+            params,
+        );
+        let proof = prover.generate_proof(point.clone());
+        let evaluation = polynomial.evaluate(&point);
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), proof.size());
+        let proof = Proof::decode(&bytes).unwrap();
+        assert_eq!(verifier.verify(proof.clone(), evaluation), Ok(()));
+        // a commitment claiming more grinding than the proof was made with
+        let params = PcsParams {
+            grinding_bits: 40,
+            ..params
+        };
+        commit.params = params;
+        let verifier = Verifier::new(variable_num, &interpolate_cosets, commit, &point, params);
+        assert_eq!(
+            verifier.verify(proof, evaluation),
+            Err(VerificationError::InsufficientWork)
+        );
+    }
+
+    fn check_pcs_interface<T: MyField>(hash: HashChoice) {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                grinding_bits: 8,
+                hash,
+                ..test_params()
+            },
+        };
+        let pcs = DeepFold::<T>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial.clone());
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, polynomial.evaluate(&point));
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+        assert_eq!(
+            pcs.verify(&commit, &point, &(eval + T::from_int(1)), &proof),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        let mut other_point = point.clone();
+        other_point[0] += T::from_int(1);
+        assert!(pcs.verify(&commit, &other_point, &eval, &proof).is_err());
+        assert_eq!(
+            pcs.verify(&commit, &point[1..], &eval, &proof),
+            Err(VerificationError::MalformedProof("open point"))
+        );
+    }
+
+    #[test]
+    fn pcs_interface() {
+        check_pcs_interface::<M31ext>(HashChoice::Blake3);
+        check_pcs_interface::<Goldilocks64Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Goldilocks64Ext>(HashChoice::Poseidon2);
+    }
+
+    #[test]
+    fn zero_polynomial() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: test_params(),
+        };
+        let pcs = DeepFold::<Goldilocks64Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::new(vec![
+            Goldilocks64Ext::from_int(0);
+            1 << params.variable_num
+        ]);
+        let point = (0..params.variable_num)
+            .map(|_| Goldilocks64Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, Goldilocks64Ext::from_int(0));
+        let proof = Proof::<Goldilocks64Ext>::decode(&proof.encode()).unwrap();
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+    }
+
+    #[test]
+    fn differently_configured_schemes() {
+        let setup = |pcs| {
+            DeepFold::<M31ext>::setup(SetupParams {
+                variable_num: 8,
+                pcs,
+            })
+        };
+        let plain = setup(test_params());
+        let other = setup(PcsParams {
+            step: 2,
+            hash: HashChoice::Keccak256,
+            ..test_params()
+        });
+        let polynomial = MultilinearPolynomial::random_polynomial(8);
+        let point = (0..8).map(|_| M31ext::random_element()).collect::<Vec<_>>();
+        let (commit, data) = plain.commit(polynomial.clone());
+        let (eval, proof) = plain.open(&data, &point);
+        let (other_commit, other_data) = other.commit(polynomial);
+        let (other_eval, other_proof) = other.open(&other_data, &point);
+        assert_eq!(eval, other_eval);
+        assert_ne!(commit.merkle_root, other_commit.merkle_root);
+        assert_eq!(plain.verify(&commit, &point, &eval, &proof), Ok(()));
+        assert_eq!(
+            other.verify(&other_commit, &point, &eval, &other_proof),
+            Ok(())
+        );
+        assert_eq!(
+            other.verify(&commit, &point, &eval, &proof),
+            Err(VerificationError::ParameterMismatch)
+        );
+    }
+
+    #[test]
+    fn shared_deep_evals() {
+        let variable_num = 14;
+        let polynomial = MultilinearPolynomial::<Goldilocks64Ext>::random_polynomial(variable_num);
+        let mut hypercube = polynomial.evaluate_hypercube_into(&Storage::Memory);
+        let points = (0..3)
+            .map(|r| {
+                (0..variable_num - r)
+                    .map(|_| Goldilocks64Ext::random_element())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut evals = vec![DeepEval::new(
+            points[0].clone(),
+            &hypercube,
+            &Storage::Memory,
+        )];
+        assert_eq!(evals[0].first_eval, polynomial.evaluate(&points[0]));
+        let mut expected = vec![vec![]; points.len()];
+        for round in 0..variable_num {
+            if round > 0 && round < points.len() {
+                evals.push(DeepEval::new(
+                    points[round].clone(),
+                    &hypercube,
+                    &Storage::Memory,
+                ));
+            }
+            DeepEval::append_else_evals(&mut evals, &hypercube);
+            // the old per-evaluation fold of a private copy
+            for (point, expected) in points.iter().zip(&mut expected).take(evals.len()) {
+                let mut tail = point[round + point.len() - variable_num..].to_vec();
+                tail[0] += Goldilocks64Ext::from_int(1);
+                let mut copy = hypercube.clone();
+                for v in tail {
+                    let m = copy.len() / 2;
+                    sumcheck_next_domain(&mut copy, m, v);
+                }
+                expected.push(copy[0]);
+            }
+            let m = hypercube.len() / 2;
+            sumcheck_next_domain(&mut hypercube, m, Goldilocks64Ext::random_element());
+        }
+        for (eval, expected) in evals.iter().zip(expected) {
+            assert_eq!(eval.else_evals, expected);
+            assert!(eval.suffix_eqs.is_empty());
+        }
+    }
+
+    // Digest of a fixed opening made by the sequential build. The parallel
+    // build must reproduce it byte for byte in every pool size.
+    const SEQUENTIAL_PROOF: &str =
+        "ded507f8a25ed7a399bec69ded80849e4ca1bc11e505e9dbbca9f8f866969f9f";
+
+    #[test]
+    fn proof_matches_sequential_fixture() {
+        let variable_num = 12;
+        let polynomial = MultilinearPolynomial::new(
+            (0..1u64 << variable_num)
+                .map(|i| Goldilocks64Ext::from_int(i * i + 1))
+                .collect(),
+        );
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + test_params().code_rate),
+            Goldilocks64Ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let point = (0..variable_num as u64)
+            .map(|i| Goldilocks64Ext::from_int(3 * i + 7))
+            .collect::<Vec<_>>();
+        let prove = || {
+            let prover = Prover::new(
+                variable_num,
+                &interpolate_cosets,
+                polynomial.clone(),
+                test_params(),
+            );
+            let mut bytes = prover.commit_polynomial().encode();
+            bytes.extend(prover.generate_proof(point.clone()).encode());
+            Blake3Algorithm::hash(&bytes)
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        };
+        for_each_pool(|_, pool| assert_eq!(pool.install(prove), SEQUENTIAL_PROOF));
+    }
+
+    #[test]
+    fn mapped_storage() {
+        // large enough for the codeword, the Merkle layers and the hypercube
+        // to go to disk
+        let variable_num = 16;
+        let polynomial = MultilinearPolynomial::<Goldilocks64Ext>::random_polynomial(variable_num);
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + test_params().code_rate),
+            Goldilocks64Ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let point = (0..variable_num)
+            .map(|_| Goldilocks64Ext::random_element())
+            .collect::<Vec<_>>();
+        let memory = Prover::new(
+            variable_num,
+            &interpolate_cosets,
+            polynomial.clone(),
+            test_params(),
+        );
+        let mapped = Prover::with_storage(
+            variable_num,
+            &interpolate_cosets,
+            polynomial.clone(),
+            test_params(),
+            &Storage::Mapped(std::env::temp_dir()),
+        );
+        let commit = mapped.commit_polynomial();
+        assert_eq!(commit.encode(), memory.commit_polynomial().encode());
+        let verifier = Verifier::new(
+            variable_num,
+            &interpolate_cosets,
+            commit,
+            &point,
+            test_params(),
+        );
+        let proof = mapped.generate_proof(point.clone());
+        assert_eq!(
+            proof.encode(),
+            memory.generate_proof(point.clone()).encode()
+        );
+        assert_eq!(verifier.verify(proof, polynomial.evaluate(&point)), Ok(()));
+    }
+
+    fn check_base_field_commitment(hash: HashChoice) {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                grinding_bits: 8,
+                hash,
+                ..test_params()
+            },
+        };
+        let polynomial = MultilinearPolynomial::<Goldilocks64>::random_polynomial(8);
+        let lifted = MultilinearPolynomial::new(
+            polynomial
+                .coefficients()
+                .iter()
+                .map(|&x| Goldilocks64Ext::from(x))
+                .collect(),
+        );
+        let point = (0..params.variable_num)
+            .map(|_| Goldilocks64Ext::random_element())
+            .collect::<Vec<_>>();
+
+        let pcs = DeepFold::<Goldilocks64Ext, Goldilocks64>::setup(params);
+        let (commit, data) = pcs.commit(polynomial);
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, lifted.evaluate(&point));
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+        assert_eq!(
+            pcs.verify(
+                &commit,
+                &point,
+                &(eval + Goldilocks64Ext::from_int(1)),
+                &proof
+            ),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), proof.size());
+        assert_eq!(
+            Proof::<Goldilocks64Ext, Goldilocks64>::decode(&bytes)
+                .unwrap()
+                .encode(),
+            bytes
+        );
+
+        let ext_pcs = DeepFold::<Goldilocks64Ext>::setup(params);
+        let (ext_commit, ext_data) = ext_pcs.commit(lifted);
+        let (ext_eval, ext_proof) = ext_pcs.open(&ext_data, &point);
+        assert_eq!(ext_eval, eval);
+        assert_eq!(
+            ext_pcs.verify(&ext_commit, &point, &ext_eval, &ext_proof),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn base_field_commitment() {
+        check_base_field_commitment(HashChoice::Blake3);
+        // the commitment tree hashes Goldilocks64 leaves, the others Goldilocks64Ext
+        check_base_field_commitment(HashChoice::Poseidon2);
+    }
+
+    #[test]
+    fn hiding_opening() {
+        let variable_num = 10;
+        let total_round = variable_num + 1;
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (total_round + test_params().code_rate),
+            M31ext::from_int(1),
+        )];
+        for i in 1..total_round + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let point = (0..variable_num)
+            .map(|_| M31ext::random_element())
+            .collect::<Vec<_>>();
+        // two unrelated polynomials agreeing at `point`
+        let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+        let evaluation = polynomial.evaluate(&point);
+        let other = MultilinearPolynomial::<M31ext>::random_polynomial(variable_num);
+        let mut coefficients = other.coefficients().clone();
+        coefficients[0] += evaluation - other.evaluate(&point);
+        let other = MultilinearPolynomial::new(coefficients);
+
+        let prove = |polynomial: &MultilinearPolynomial<M31ext>| {
+            let prover = Prover::hiding(
+                total_round,
+                &interpolate_cosets,
+                polynomial.clone(),
+                test_params(),
+            );
+            let commit = Commit::decode(&prover.commit_polynomial().encode()).unwrap();
+            let proof = prover.generate_proof(point.clone());
+            let bytes = proof.encode();
+            assert_eq!(bytes.len(), proof.size());
+            let proof = Proof::<M31ext>::decode(&bytes).unwrap();
+            let verifier = Verifier::hiding(
+                total_round,
+                &interpolate_cosets,
+                commit.clone(),
+                &point,
+                test_params(),
+            );
+            assert_eq!(verifier.clone().verify(proof.clone(), evaluation), Ok(()));
+            assert_eq!(
+                verifier.verify(proof.clone(), evaluation + M31ext::from_int(1)),
+                Err(VerificationError::EvaluationMismatch)
+            );
+            // a hiding proof is not accepted as a plain one
+            let padded = [point.clone(), vec![M31ext::from_int(0)]].concat();
+            let plain = Verifier::new(
+                total_round,
+                &interpolate_cosets,
+                commit,
+                &padded,
+                test_params(),
+            );
+            assert!(plain.verify(proof.clone(), evaluation).is_err());
+            proof
+        };
+        let proof = prove(&polynomial);
+        let other_proof = prove(&other);
+        assert_eq!(proof.evaluation, other_proof.evaluation);
+        // Sizes differ only in how many queries share a leaf or a path, which
+        // the transcript decides whatever the polynomial.
+        assert_eq!(proof.merkle_root.len(), other_proof.merkle_root.len());
+        assert_eq!(proof.query_result.len(), other_proof.query_result.len());
+        assert_eq!(
+            proof.commit_query.leaf_width(),
+            other_proof.commit_query.leaf_width()
+        );
+
+        // With the queried leaves of `proof` fixed, its openings are those
+        // of `other` under some other blinding r'. The blinding r is uniform
+        // and r' - r depends only on the two polynomials, so the openings are
+        // identically distributed whichever polynomial was committed.
+        let coset = &interpolate_cosets[0];
+        let opened = &proof.commit_query;
+        let len = coset.size() / opened.leaf_width();
+        let half = other.coefficients().len();
+        let other_codeword = coset.fft(other.coefficients().clone());
+        let mut indices = vec![];
+        let mut points = vec![];
+        for (position, &k) in opened.leaf_indices().iter().enumerate() {
+            for (j, &v) in opened.leaf(position).iter().enumerate() {
+                let index = k + j * len;
+                let x = coset.element_at(index);
+                indices.push((index, v));
+                points.push((x, (v - other_codeword[index]) * x.pow(half).inverse()));
+            }
+        }
+        assert!(points.len() <= half);
+        let blinding = interpolate(&points);
+        let codeword = coset.fft([other.coefficients().clone(), blinding].concat());
+        assert!(indices.iter().all(|&(index, v)| codeword[index] == v));
+    }
+
+    // Coefficients of the polynomial of degree below `points.len()` through
+    // `points`, by Newton's divided differences.
+    fn interpolate<T: MyField>(points: &[(T, T)]) -> Vec<T> {
+        let mut differences = points.iter().map(|&(_, y)| y).collect::<Vec<_>>();
+        for level in 1..points.len() {
+            for i in (level..points.len()).rev() {
+                differences[i] = (differences[i] - differences[i - 1])
+                    * (points[i].0 - points[i - level].0).inverse();
+            }
+        }
+        let mut coefficients = vec![T::from_int(0); points.len()];
+        for i in (0..points.len()).rev() {
+            // coefficients = coefficients * (X - x_i) + differences[i]
+            for j in (1..points.len()).rev() {
+                coefficients[j] = coefficients[j - 1] - coefficients[j] * points[i].0;
+            }
+            coefficients[0] = differences[i] - coefficients[0] * points[i].0;
+        }
+        coefficients
+    }
+
+    #[test]
+    #[should_panic(expected = "more evaluations than the blinding hides")]
+    fn hiding_needs_enough_blinding() {
+        let total_round = 5;
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (total_round + test_params().code_rate),
+            M31ext::from_int(1),
+        )];
+        for i in 1..total_round + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        Prover::hiding(
+            total_round,
+            &interpolate_cosets,
+            MultilinearPolynomial::<M31ext>::random_polynomial(total_round - 1),
+            test_params(),
+        );
+    }
+
+    fn batch_opening(
+        variable_num: usize,
+        variable_nums: &[usize],
+        point_num: usize,
+        params: PcsParams,
+    ) {
+        let polynomials = variable_nums
+            .iter()
+            .map(|&n| MultilinearPolynomial::random_polynomial(n))
+            .collect::<Vec<_>>();
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + params.code_rate),
+            M31ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let prover = BatchProver::new(
+            variable_num,
+            &interpolate_cosets,
+            polynomials.clone(),
+            params,
+        );
+        let commit = BatchCommit::decode(&prover.commit_polynomials().encode()).unwrap();
+        let points = (0..point_num)
+            .map(|_| {
+                (0..variable_num)
+                    .map(|_| M31ext::random_element())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let evaluations = points
+            .iter()
+            .map(|point| {
+                polynomials
+                    .iter()
+                    .map(|poly| {
+                        poly.evaluate(&point[variable_num - poly.variable_num()..].to_vec())
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let verifier =
+            BatchVerifier::new(variable_num, &interpolate_cosets, commit, &points, params);
+        let proof = prover.generate_proof(&points);
+        assert_eq!(proof.evaluations(), &evaluations);
+
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), proof.size());
+        let proof = BatchProof::<M31ext>::decode(&bytes).unwrap();
+        assert_eq!(proof.encode(), bytes);
+        assert_eq!(verifier.verify(proof.clone(), &evaluations), Ok(()));
+
+        let mut wrong_evaluations = evaluations.clone();
+        wrong_evaluations[point_num - 1][0] += M31ext::from_int(1);
+        assert_eq!(
+            verifier.verify(proof.clone(), &wrong_evaluations),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        let mut forged = proof.clone();
+        forged.evaluations = wrong_evaluations.clone();
+        assert!(verifier.verify(forged, &wrong_evaluations).is_err());
+
+        let mut tampered = proof.clone();
+        tampered.commit_query[0].values[0] += M31ext::from_int(1);
+        assert!(matches!(
+            verifier.verify(tampered, &evaluations),
+            Err(VerificationError::MerklePathInvalid { round: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn batch_open() {
+        batch_opening(
+            8,
+            &[8, 6, 8, 3, 1],
+            3,
+            PcsParams {
+                step: 1,
+                ..test_params()
+            },
+        );
+        batch_opening(
+            7,
+            &[5, 7, 2],
+            2,
+            PcsParams {
+                step: 2,
+                ..test_params()
+            },
+        );
+    }
+
+    #[test]
+    fn folding_schedules() {
+        let variable_num = 5;
+        // uniform steps, most of them leaving a remainder round
+        let uniform = (1..variable_num + 1).map(|step| PcsParams {
+            step,
+            ..test_params()
+        });
+        let scheduled = FoldingSchedule::all(variable_num)
+            .into_iter()
+            .map(|schedule| PcsParams {
+                schedule: Some(schedule),
+                ..test_params()
+            });
+        for pcs in uniform.chain(scheduled) {
+            let scheme = DeepFold::<M31ext>::setup(SetupParams { variable_num, pcs });
+            let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+            let point = (0..variable_num)
+                .map(|_| M31ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = scheme.commit(polynomial);
+            let (eval, proof) = scheme.open(&data, &point);
+            assert_eq!(scheme.verify(&commit, &point, &eval, &proof), Ok(()));
+            batch_opening(variable_num, &[5, 3], 2, pcs);
+        }
+    }
+
+    #[test]
+    fn early_termination() {
+        let variable_num = 6;
+        for final_poly_bits in 0..variable_num {
+            for step in 1..3 {
+                let pcs = PcsParams {
+                    step,
+                    final_poly_bits,
+                    ..test_params()
+                };
+                let scheme = DeepFold::<M31ext>::setup(SetupParams { variable_num, pcs });
+                let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+                let point = (0..variable_num)
+                    .map(|_| M31ext::random_element())
+                    .collect::<Vec<_>>();
+                let (commit, data) = scheme.commit(polynomial);
+                let (eval, mut proof) = scheme.open(&data, &point);
+                assert_eq!(scheme.verify(&commit, &point, &eval, &proof), Ok(()));
+                batch_opening(variable_num, &[6, 4, 1], 2, pcs);
+
+                proof.final_poly = Polynomial::random_polynomial((1 << final_poly_bits) + 1);
+                assert_eq!(
+                    scheme.verify(&commit, &point, &eval, &proof),
+                    Err(VerificationError::MalformedProof("final polynomial"))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn merkle_caps() {
+        let variable_num = 6;
+        for cap_bits in 0..8 {
+            let pcs = PcsParams {
+                cap_bits,
+                ..test_params()
+            };
+            let scheme = DeepFold::<M31ext>::setup(SetupParams { variable_num, pcs });
+            let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+            let point = (0..variable_num)
+                .map(|_| M31ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = scheme.commit(polynomial);
+            assert!(commit.merkle_root.len() <= 1 << cap_bits);
+            let (eval, mut proof) = scheme.open(&data, &point);
+            assert_eq!(scheme.verify(&commit, &point, &eval, &proof), Ok(()));
+            let bytes = proof.encode();
+            assert_eq!(bytes.len(), proof.size());
+            batch_opening(variable_num, &[6, 4, 1], 2, pcs);
+
+            proof.merkle_root[0][0][0] ^= 1;
+            assert!(scheme.verify(&commit, &point, &eval, &proof).is_err());
+        }
+    }
+
+    #[test]
+    fn merkle_arity() {
+        let variable_num = 6;
+        for merkle_arity in [2, 4, 8] {
+            for cap_bits in [0, 2] {
+                let pcs = PcsParams {
+                    merkle_arity,
+                    cap_bits,
+                    ..test_params()
+                };
+                let scheme = DeepFold::<M31ext>::setup(SetupParams { variable_num, pcs });
+                let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+                let point = (0..variable_num)
+                    .map(|_| M31ext::random_element())
+                    .collect::<Vec<_>>();
+                let (commit, data) = scheme.commit(polynomial);
+                let (eval, proof) = scheme.open(&data, &point);
+                assert_eq!(scheme.verify(&commit, &point, &eval, &proof), Ok(()));
+                batch_opening(variable_num, &[6, 4, 1], 2, pcs);
+
+                let binary = DeepFold::<M31ext>::setup(SetupParams {
+                    variable_num,
+                    pcs: PcsParams {
+                        merkle_arity: 2,
+                        ..pcs
+                    },
+                });
+                if merkle_arity != 2 {
+                    assert_eq!(
+                        binary.verify(&commit, &point, &eval, &proof),
+                        Err(VerificationError::ParameterMismatch)
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_proof_size() {
         let mut wtr = Writer::from_path("deepfold.csv").unwrap();
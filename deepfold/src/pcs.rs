@@ -0,0 +1,80 @@
+use std::marker::PhantomData;
+
+use util::{
+    algebra::{
+        coset::Coset,
+        field::{ExtensionOf, MyField},
+        polynomial::MultilinearPolynomial,
+    },
+    error::VerificationError,
+    pcs::{MultilinearPCS, SetupParams},
+};
+
+use crate::{prover::Prover, verifier::Verifier, Commit, Proof};
+
+// `B` is the field the polynomial and its first codeword live in; it defaults
+// to the challenge field `T`. The codeword is an FFT over `B`, so `B` needs a
+// two-adic subgroup as large as the codeword: of the fields here only
+// Goldilocks64 under Goldilocks64Ext qualifies. M31 and Mersenne61 have
+// 2-adicity 1, and their polynomials are committed over the extension.
+pub struct DeepFold<T: MyField, B: MyField = T> {
+    params: SetupParams,
+    interpolate_cosets: Vec<Coset<T>>,
+    _base: PhantomData<B>,
+}
+
+impl<T: ExtensionOf<B>, B: MyField> MultilinearPCS<T> for DeepFold<T, B> {
+    type Params = SetupParams;
+    type Polynomial = MultilinearPolynomial<B>;
+    type Evaluation = T;
+    type Commitment = Commit<T>;
+    type ProverData = Prover<T, B>;
+    type Proof = Proof<T, B>;
+
+    fn setup(params: SetupParams) -> Self {
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (params.variable_num + params.pcs.code_rate),
+            T::from_int(1),
+        )];
+        for i in 1..params.variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        DeepFold {
+            params,
+            interpolate_cosets,
+            _base: PhantomData,
+        }
+    }
+
+    fn commit(&self, poly: MultilinearPolynomial<B>) -> (Commit<T>, Prover<T, B>) {
+        let prover = Prover::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            poly,
+            self.params.pcs,
+        );
+        (prover.commit_polynomial(), prover)
+    }
+
+    fn open(&self, data: &Prover<T, B>, point: &[T]) -> (T, Proof<T, B>) {
+        let proof = data.clone().generate_proof(point.to_vec());
+        (proof.evaluation, proof)
+    }
+
+    fn verify(
+        &self,
+        commitment: &Commit<T>,
+        point: &[T],
+        eval: &T,
+        proof: &Proof<T, B>,
+    ) -> Result<(), VerificationError> {
+        let verifier = Verifier::<T, B>::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            commitment.clone(),
+            point,
+            self.params.pcs,
+        );
+        verifier.verify(proof.clone(), *eval)
+    }
+}
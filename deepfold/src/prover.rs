@@ -1,196 +1,422 @@
 use util::{
     algebra::{
         coset::Coset,
-        field::MyField,
+        field::{ExtensionOf, MyField},
         polynomial::{MultilinearPolynomial, Polynomial},
     },
     interpolation::InterpolateValue,
+    pcs::PcsParams,
     query_result::QueryResult,
-    random_oracle::RandomOracle,
+    schedule::FoldingSchedule,
+    storage::{Buffer, Storage},
+    transcript::Transcript,
 };
 
-use crate::{Commit, DeepEval, Proof};
-use util::CODE_RATE;
+use crate::{
+    deep_point, fold_codeword, sumcheck_next_domain, Commit, DeepEval, MaskCommit, MaskOpening,
+    Proof,
+};
+use util::cfg_iter_mut;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
+// Hiding mode: a random polynomial committed next to the witness. Openings
+// prove `polynomial + challenge * mask`, so every revealed evaluation and
+// folded value is randomized.
 #[derive(Clone)]
-pub struct Prover<T: MyField> {
+struct Mask<T: MyField, B: MyField> {
+    commitment: InterpolateValue<B>,
+    hypercube_interpolation: Buffer<T>,
+    deep: T,
+    evaluation: T,
+    challenge: T,
+}
+
+// At most this many evaluations of the committed polynomial are revealed by a
+// proof: 2^step opened values per query in every round, the DEEP and shuffle
+// evaluations (a point per folded variable and the shuffle point, each with at
+// most one value per folded variable besides its first) and the final
+// polynomial.
+fn revealed_evaluations(params: &PcsParams, schedule: &FoldingSchedule) -> usize {
+    let queries = (0..schedule.rounds())
+        .map(|i| params.query_num << schedule.step(i))
+        .sum::<usize>();
+    queries + (schedule.total() + 1).pow(2) + (1 << params.final_poly_bits)
+}
+
+// The committed codeword lives over `B`; folding challenges and every later
+// oracle live over `T`.
+#[derive(Clone)]
+pub struct Prover<T: MyField, B: MyField = T> {
     total_round: usize,
     interpolate_cosets: Vec<Coset<T>>,
+    base_coset: Coset<B>,
+    commitment: InterpolateValue<B>,
     interpolations: Vec<InterpolateValue<T>>,
-    hypercube_interpolation: Vec<T>,
+    hypercube_interpolation: Buffer<T>,
     deep_eval: Vec<DeepEval<T>>,
     shuffle_eval: Option<DeepEval<T>>,
-    oracle: RandomOracle<T>,
-    final_value: Option<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    schedule: FoldingSchedule,
     final_poly: Option<Polynomial<T>>,
-    step: usize,
+    mask: Option<Mask<T, B>>,
+    pow_nonce: u64,
 }
 
-impl<T: MyField> Prover<T> {
+impl<T: ExtensionOf<B>, B: MyField> Prover<T, B> {
     pub fn new(
         total_round: usize,
         interpolate_cosets: &Vec<Coset<T>>,
-        polynomial: MultilinearPolynomial<T>,
-        oracle: &RandomOracle<T>,
-        step: usize,
+        polynomial: MultilinearPolynomial<B>,
+        params: PcsParams,
+    ) -> Self {
+        Self::with_storage(
+            total_round,
+            interpolate_cosets,
+            polynomial,
+            params,
+            &Storage::Memory,
+        )
+    }
+
+    // The codeword, every folded layer, their Merkle trees, the hypercube
+    // values and the DEEP evaluations' eq tables are placed in `storage`; with
+    // `Storage::Mapped` they live in files instead of memory.
+    pub fn with_storage(
+        total_round: usize,
+        interpolate_cosets: &Vec<Coset<T>>,
+        polynomial: MultilinearPolynomial<B>,
+        params: PcsParams,
+        storage: &Storage,
     ) -> Self {
-        let point = std::iter::successors(Some(oracle.deep[0]), |&x| Some(x * x))
-            .take(total_round)
-            .collect::<Vec<_>>();
-        let hypercube_interpolation = polynomial.evaluate_hypercube();
+        Self::build(
+            total_round,
+            interpolate_cosets,
+            polynomial,
+            None,
+            params,
+            storage,
+        )
+    }
+
+    // Zero-knowledge variant: `polynomial` has `total_round - 1` variables and
+    // is committed with a random extra last variable, which blinds its
+    // codeword, next to a random mask. Every tree is salted. Proofs reveal
+    // nothing about `polynomial` beyond the opened evaluation, as long as they
+    // reveal no more evaluations than the extra variable has random
+    // coefficients; any fewer values are uniformly distributed.
+    pub fn hiding(
+        total_round: usize,
+        interpolate_cosets: &Vec<Coset<T>>,
+        polynomial: MultilinearPolynomial<B>,
+        params: PcsParams,
+    ) -> Self {
+        assert_eq!(polynomial.variable_num() + 1, total_round);
+        assert!(
+            revealed_evaluations(&params, &params.folding(total_round))
+                <= polynomial.coefficients().len(),
+            "proofs would reveal more evaluations than the blinding hides"
+        );
+        let mut coefficients = polynomial.coefficients().clone();
+        coefficients.extend((0..coefficients.len()).map(|_| B::random_element()));
+        Self::build(
+            total_round,
+            interpolate_cosets,
+            MultilinearPolynomial::new(coefficients),
+            Some(MultilinearPolynomial::random_polynomial(total_round)),
+            params,
+            &Storage::Memory,
+        )
+    }
+
+    fn build(
+        total_round: usize,
+        interpolate_cosets: &Vec<Coset<T>>,
+        polynomial: MultilinearPolynomial<B>,
+        mask: Option<MultilinearPolynomial<B>>,
+        params: PcsParams,
+        storage: &Storage,
+    ) -> Self {
+        let base_coset = Coset::new(interpolate_cosets[0].size(), B::from_int(1));
+        assert!((0..2)
+            .all(|i| T::from(base_coset.element_at(i)) == interpolate_cosets[0].element_at(i)));
+        let hiding = mask.is_some();
+        let schedule = params.folding(total_round);
+        let interpolate = |polynomial: &MultilinearPolynomial<B>| {
+            let codeword = base_coset.fft_into(polynomial.coefficients(), storage);
+            let (hash, leaf_size) = (params.tree_hash::<B>(), 1 << schedule.step(0));
+            if hiding {
+                InterpolateValue::from_buffer_salted(hash, codeword, leaf_size)
+            } else {
+                InterpolateValue::from_buffer(hash, codeword, leaf_size)
+            }
+        };
+        let lift = |polynomial: &MultilinearPolynomial<B>| {
+            let hypercube = polynomial.evaluate_hypercube_into(storage);
+            Buffer::from_fn(storage, hypercube.len(), |i| T::from(hypercube[i]))
+        };
+        let commitment = interpolate(&polynomial);
+        let mask = mask.map(|mask| (interpolate(&mask), lift(&mask)));
+        let mut transcript = Transcript::new(b"deepfold");
+        transcript.append_cap(&commitment.commit());
+        if let Some((commitment, _)) = &mask {
+            transcript.append_cap(&commitment.commit());
+        }
+        let hypercube_interpolation = lift(&polynomial);
+        let deep_eval = DeepEval::new(
+            deep_point(transcript.challenge(), total_round),
+            &hypercube_interpolation,
+            storage,
+        );
+        transcript.append_field(&deep_eval.first_eval);
+        let mask = mask.map(|(commitment, hypercube_interpolation)| {
+            let deep = deep_eval.evaluate_other(&hypercube_interpolation);
+            transcript.append_field(&deep);
+            Mask {
+                commitment,
+                hypercube_interpolation,
+                deep,
+                evaluation: T::from_int(0),
+                challenge: T::from_int(0),
+            }
+        });
         Prover {
             total_round,
             interpolate_cosets: interpolate_cosets.clone(),
-            interpolations: vec![InterpolateValue::new(
-                interpolate_cosets[0].fft(polynomial.coefficients().clone()),
-                1 << step,
-            )],
-            hypercube_interpolation: hypercube_interpolation.clone(),
-            deep_eval: vec![DeepEval::new(point.clone(), hypercube_interpolation)],
+            base_coset,
+            commitment,
+            interpolations: vec![],
+            hypercube_interpolation,
+            deep_eval: vec![deep_eval],
             shuffle_eval: None,
-            oracle: oracle.clone(),
-            final_value: None,
+            transcript,
+            params,
+            schedule,
             final_poly: None,
-            step,
+            mask,
+            pow_nonce: 0,
         }
     }
 
     pub fn commit_polynomial(&self) -> Commit<T> {
         Commit {
-            merkle_root: self.interpolations[0].commit(),
+            merkle_root: self.commitment.commit(),
+            params: self.params,
             deep: self.deep_eval[0].first_eval,
+            mask: self.mask.as_ref().map(|mask| MaskCommit {
+                merkle_root: mask.commitment.commit(),
+                deep: mask.deep,
+            }),
         }
     }
 
-    fn evaluation_next_domain(&self, round: usize, challenges: &Vec<T>) -> Vec<T> {
-        let mut get_folding_value = self.interpolations[round].value.clone();
-
-        for j in 0..self.step {
-            if round * self.step + j == self.total_round {
-                break;
-            }
-            let len = self.interpolate_cosets[round * self.step + j].size();
-            let coset = &self.interpolate_cosets[round * self.step + j];
-            let challenge = challenges[j];
-            let mut tmp_folding_value = vec![];
-            for i in 0..(len / 2) {
-                let x = get_folding_value[i];
-                let nx = get_folding_value[i + len / 2];
-                let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
-                tmp_folding_value.push(new_v * T::inverse_2());
-            }
-            get_folding_value = tmp_folding_value;
+    // `step` is what the round reading this layer folds.
+    fn interpolate(&self, value: Buffer<T>, step: usize) -> InterpolateValue<T> {
+        let (hash, leaf_size) = (self.params.tree_hash::<T>(), 1 << step);
+        if self.mask.is_some() {
+            InterpolateValue::from_buffer_salted(hash, value, leaf_size)
+        } else {
+            InterpolateValue::from_buffer(hash, value, leaf_size)
         }
-        get_folding_value
     }
 
-    fn sumcheck_next_domain(hypercube_interpolation: &mut Vec<T>, m: usize, challenge: T) {
-        for i in 0..m {
-            hypercube_interpolation[i] *= T::from_int(1) - challenge;
-            let tmp = hypercube_interpolation[i + m] * challenge;
-            hypercube_interpolation[i] += tmp;
+    fn evaluation_next_domain(&self, round: usize, challenges: &Vec<T>) -> Buffer<T> {
+        let storage = self.commitment.value.storage();
+        if round > 0 {
+            return fold_codeword(
+                &self.interpolations[round - 1].value,
+                &self.interpolate_cosets[self.schedule.offset(round)..],
+                challenges,
+                &storage,
+            );
+        }
+        // The first fold lifts the base codeword into T.
+        let value = &self.commitment.value;
+        let len = value.len();
+        let get_folding_value = match &self.mask {
+            None => Buffer::from_fn(&storage, len / 2, |i| {
+                let x = value[i];
+                let nx = value[i + len / 2];
+                let new_v = T::from(x + nx)
+                    + challenges[0].mul_base((x - nx) * self.base_coset.element_inv_at(i));
+                new_v * T::inverse_2()
+            }),
+            Some(mask) => Buffer::from_fn(&storage, len / 2, |i| {
+                let masked = |k: usize| {
+                    T::from(value[k]) + mask.challenge.mul_base(mask.commitment.value[k])
+                };
+                let x = masked(i);
+                let nx = masked(i + len / 2);
+                let new_v =
+                    (x + nx) + challenges[0] * (x - nx).mul_base(self.base_coset.element_inv_at(i));
+                new_v * T::inverse_2()
+            }),
+        };
+        if challenges.len() == 1 {
+            return get_folding_value;
         }
-        hypercube_interpolation.truncate(m);
+        fold_codeword(
+            &get_folding_value,
+            &self.interpolate_cosets[1..],
+            &challenges[1..],
+            &storage,
+        )
     }
 
-    pub fn prove(&mut self, point: Vec<T>) {
+    fn append_deep_eval(&mut self, round: usize, hypercube_interpolation: &[T]) {
+        let deep_eval = DeepEval::new(
+            deep_point(self.transcript.challenge(), self.total_round - round),
+            hypercube_interpolation,
+            &self.commitment.value.storage(),
+        );
+        self.transcript.append_field(&deep_eval.first_eval);
+        self.deep_eval.push(deep_eval);
+    }
+
+    pub fn prove(&mut self, mut point: Vec<T>) {
         let mut hypercube_interpolation = self.hypercube_interpolation.clone();
-        self.shuffle_eval = Some(DeepEval::new(
+        if self.mask.is_some() {
+            // the blinding variable is opened at zero
+            point.push(T::from_int(0));
+        }
+        let mut shuffle_eval = DeepEval::new(
             point.clone(),
-            hypercube_interpolation.clone(),
-        ));
-        for i in 0..self.total_round / self.step + 1 {
+            &hypercube_interpolation,
+            &self.commitment.value.storage(),
+        );
+        self.transcript.append_fields(&point);
+        self.transcript.append_field(&shuffle_eval.first_eval);
+        if let Some(mask) = &mut self.mask {
+            mask.evaluation = shuffle_eval.evaluate_other(&mask.hypercube_interpolation);
+            self.transcript.append_field(&mask.evaluation);
+            mask.challenge = self.transcript.challenge();
+            let challenge = mask.challenge;
+            cfg_iter_mut!(hypercube_interpolation)
+                .zip(&mask.hypercube_interpolation[..])
+                .for_each(|(x, &y)| *x += challenge * y);
+            shuffle_eval.first_eval += challenge * mask.evaluation;
+            self.deep_eval[0].first_eval += challenge * mask.deep;
+        }
+        self.shuffle_eval = Some(shuffle_eval);
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
             let mut challenges: Vec<T> = vec![];
-            for j in 0..self.step {
-                if i * self.step + j == self.total_round {
-                    break;
-                }
-                challenges.push(self.oracle.folding_challenges[i * self.step + j]);
-            }
-
-            for j in 0..self.step {
-                if i * self.step + j == self.total_round {
-                    break;
-                }
-                self.shuffle_eval
-                    .as_mut()
-                    .unwrap()
-                    .append_else_eval(hypercube_interpolation.clone());
-                for deep in &mut self.deep_eval {
-                    deep.append_else_eval(hypercube_interpolation.clone());
+            let step = self.schedule.step(i);
+            for j in 0..step {
+                let round = self.schedule.offset(i) + j;
+                DeepEval::append_else_evals(
+                    self.shuffle_eval.iter_mut().chain(&mut self.deep_eval),
+                    &hypercube_interpolation,
+                );
+                for eval in self.shuffle_eval.iter().chain(&self.deep_eval) {
+                    self.transcript
+                        .append_field(eval.else_evals.last().unwrap());
                 }
+                challenges.push(self.transcript.challenge());
 
-                if i * self.step + j < self.total_round - 1 {
-                    let m = 1 << (self.total_round - (i * self.step + j) - 1);
-                    Self::sumcheck_next_domain(&mut hypercube_interpolation, m, challenges[j]);
-                    self.deep_eval.push({
-                        let deep_point = std::iter::successors(
-                            Some(self.oracle.deep[i * self.step + j + 1]),
-                            |&x| Some(x * x),
-                        )
-                        .take(self.total_round - (i * self.step + j) - 1)
-                        .collect::<Vec<_>>();
-                        DeepEval::new(deep_point.clone(), hypercube_interpolation.clone())
-                    });
+                if round < self.total_round - 1 {
+                    let m = 1 << (self.total_round - round - 1);
+                    sumcheck_next_domain(&mut hypercube_interpolation, m, challenges[j]);
+                    // at a step boundary the DEEP point waits until the new root is absorbed
+                    if j + 1 < step {
+                        self.append_deep_eval(round + 1, &hypercube_interpolation);
+                    }
                 }
             }
 
-            // let challenge = self.oracle.folding_challenges[i];
             let next_evalutation = self.evaluation_next_domain(i, &challenges);
-            if i < self.total_round / self.step - 1 {
-                self.interpolations
-                    .push(InterpolateValue::new(next_evalutation, 1 << self.step));
-            } else if i == self.total_round / self.step - 1 {
-                // todo: final_value
-                self.interpolations.push(InterpolateValue::new(
-                    next_evalutation.clone(),
-                    1 << self.step,
-                ));
-                self.final_poly = Some(Polynomial::new(
-                    self.interpolate_cosets[(i + 1) * self.step].ifft(next_evalutation),
-                ));
+            if i < rounds - 1 {
+                let interpolation = self.interpolate(next_evalutation, self.schedule.step(i + 1));
+                self.transcript.append_cap(&interpolation.commit());
+                self.interpolations.push(interpolation);
             } else {
-                assert_eq!(next_evalutation.len(), 1 << CODE_RATE);
-                self.final_value = Some(next_evalutation[0]);
+                // The last layer is checked against the polynomial it
+                // encodes, so it is neither committed nor opened.
+                let mut coefficients =
+                    self.interpolate_cosets[self.schedule.total()].ifft(next_evalutation.to_vec());
+                coefficients.truncate(1 << self.params.final_poly_bits);
+                let final_poly = Polynomial::new(coefficients);
+                self.transcript.append_fields(final_poly.coefficients());
+                self.final_poly = Some(final_poly);
+            }
+            let folded = self.schedule.offset(i + 1);
+            if folded < self.schedule.total() {
+                self.append_deep_eval(folded, &hypercube_interpolation);
             }
         }
+        self.pow_nonce = self.transcript.grind(self.params.grinding_bits);
     }
 
-    pub fn query(&self) -> Vec<QueryResult<T>> {
+    // Openings of the committed codeword, of the mask in hiding mode, and of
+    // every committed folded layer.
+    pub fn query(&mut self) -> (QueryResult<B>, Option<QueryResult<B>>, Vec<QueryResult<T>>) {
         let mut res = vec![];
-        let mut leaf_indices = self.oracle.query_list.clone();
+        let mut leaf_indices = self.transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
+        leaf_indices.sort();
+        leaf_indices.dedup();
+        let (commit_query, mask_query) = match &self.mask {
+            Some(mask) => {
+                let mut queries = InterpolateValue::query_many(
+                    &[&self.commitment, &mask.commitment],
+                    &leaf_indices,
+                );
+                let mask_query = queries.pop();
+                (queries.pop().unwrap(), mask_query)
+            }
+            None => (self.commitment.query(&leaf_indices), None),
+        };
 
-        for i in 0..self.total_round / self.step + 1 {
-            let len = self.interpolate_cosets[i * self.step].size();
+        let rounds = self.schedule.rounds();
+        for i in 1..rounds {
+            let len = self.interpolate_cosets[self.schedule.offset(i)].size();
             leaf_indices = leaf_indices
                 .iter_mut()
-                .map(|v| *v % (len >> self.step))
+                .map(|v| *v % (len >> self.schedule.step(i)))
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
-            res.push(self.interpolations[i].query(&leaf_indices));
+            res.push(self.interpolations[i - 1].query(&leaf_indices));
         }
-        res
+        (commit_query, mask_query, res)
     }
 
-    pub fn generate_proof(mut self, point: Vec<T>) -> Proof<T> {
+    pub fn generate_proof(mut self, point: Vec<T>) -> Proof<T, B> {
         self.prove(point);
-        let query_result = self.query();
+        let (commit_query, mask_query, query_result) = self.query();
+        let shuffle_eval = self.shuffle_eval.as_ref().unwrap();
+        // the shuffle evaluation is of the masked polynomial
+        let (evaluation, mask) = match (&self.mask, mask_query) {
+            (Some(mask), Some(query)) => (
+                shuffle_eval.first_eval - mask.challenge * mask.evaluation,
+                Some(MaskOpening {
+                    evaluation: mask.evaluation,
+                    query,
+                }),
+            ),
+            _ => (shuffle_eval.first_eval, None),
+        };
         Proof {
-            merkle_root: (1..self.total_round / self.step)
+            merkle_root: (0..self.schedule.rounds() - 1)
                 .into_iter()
                 .map(|x| self.interpolations[x].commit())
                 .collect(),
+            commit_query,
             query_result,
             deep_evals: self
                 .deep_eval
                 .iter()
                 .map(|x| (x.first_eval, x.else_evals.clone()))
                 .collect(),
-            shuffle_evals: self.shuffle_eval.as_ref().unwrap().else_evals.clone(),
-            final_value: self.final_value.unwrap(),
+            shuffle_evals: shuffle_eval.else_evals.clone(),
             final_poly: self.final_poly.unwrap(),
-            evaluation: self.shuffle_eval.as_ref().unwrap().first_eval,
+            pow_nonce: self.pow_nonce,
+            evaluation,
+            mask,
         }
     }
 }
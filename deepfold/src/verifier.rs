@@ -1,167 +1,360 @@
+use std::marker::PhantomData;
+
 use util::algebra::polynomial::Polynomial;
-use util::random_oracle::RandomOracle;
+use util::error::VerificationError;
+use util::transcript::Transcript;
 use util::{
-    algebra::{coset::Coset, field::MyField},
+    algebra::{
+        coset::Coset,
+        field::{ExtensionOf, MyField},
+    },
     merkle_tree::MerkleTreeVerifier,
-    query_result::QueryResult,
+    pcs::PcsParams,
+    query_result::{fold, QueryResult},
+    schedule::FoldingSchedule,
 };
 
-use crate::{Commit, DeepEval, Proof};
+use crate::{deep_point, final_multilinear, Commit, DeepEval, MaskCommit, Proof};
 
 #[derive(Clone)]
-pub struct Verifier<T: MyField> {
+pub struct Verifier<T: MyField, B: MyField = T> {
     total_round: usize,
     interpolate_cosets: Vec<Coset<T>>,
     polynomial_roots: Vec<MerkleTreeVerifier>,
     first_deep: T,
-    oracle: RandomOracle<T>,
-    final_value: Option<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    // What the commitment claims it was made under.
+    commit_params: PcsParams,
+    schedule: FoldingSchedule,
+    folding_challenges: Vec<T>,
+    deep: Vec<T>,
+    query_list: Vec<usize>,
     final_poly: Option<Polynomial<T>>,
     shuffle_eval: Option<DeepEval<T>>,
     deep_evals: Vec<DeepEval<T>>,
     open_point: Vec<T>,
-    step: usize,
+    hiding: bool,
+    mask: Option<MaskCommit<T>>,
+    mask_challenge: T,
+    _base: PhantomData<B>,
 }
 
-impl<T: MyField> Verifier<T> {
+impl<T: ExtensionOf<B>, B: MyField> Verifier<T, B> {
     pub fn new(
         total_round: usize,
         coset: &Vec<Coset<T>>,
         commit: Commit<T>,
-        oracle: &RandomOracle<T>,
-        step: usize,
+        open_point: &[T],
+        params: PcsParams,
+    ) -> Self {
+        Self::build(total_round, coset, commit, open_point, params, false)
+    }
+
+    // Checks proofs from `Prover::hiding`; `open_point` has `total_round - 1`
+    // coordinates, the blinding variable is opened at zero.
+    pub fn hiding(
+        total_round: usize,
+        coset: &Vec<Coset<T>>,
+        commit: Commit<T>,
+        open_point: &[T],
+        params: PcsParams,
+    ) -> Self {
+        Self::build(total_round, coset, commit, open_point, params, true)
+    }
+
+    fn build(
+        total_round: usize,
+        coset: &Vec<Coset<T>>,
+        commit: Commit<T>,
+        open_point: &[T],
+        params: PcsParams,
+        hiding: bool,
     ) -> Self {
+        let mut open_point = open_point.to_vec();
+        if hiding {
+            open_point.push(T::from_int(0));
+        }
+        let mut transcript = Transcript::new(b"deepfold");
+        transcript.append_cap(&commit.merkle_root);
+        if let Some(mask) = &commit.mask {
+            transcript.append_cap(&mask.merkle_root);
+        }
+        let deep = transcript.challenge();
+        transcript.append_field(&commit.deep);
+        if let Some(mask) = &commit.mask {
+            transcript.append_field(&mask.deep);
+        }
+        let schedule = params.folding(total_round);
         Verifier {
             total_round,
             interpolate_cosets: coset.clone(),
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            commit_params: commit.params,
+            schedule,
+            folding_challenges: vec![],
+            deep: vec![deep],
+            query_list: vec![],
             polynomial_roots: vec![MerkleTreeVerifier::new(
-                coset[0].size() / (1 << step), // todo: 2?
+                params.tree_hash::<B>(),
+                coset[0].size() >> schedule.step(0),
                 &commit.merkle_root,
             )],
             first_deep: commit.deep,
-            final_value: None,
             final_poly: None,
             shuffle_eval: None,
             deep_evals: vec![],
-            open_point: (0..total_round).map(|_| T::random_element()).collect(),
-            step,
+            open_point,
+            hiding,
+            mask: commit.mask,
+            mask_challenge: T::from_int(0),
+            _base: PhantomData,
         }
     }
 
-    pub fn get_open_point(&self) -> Vec<T> {
-        self.open_point.clone()
-    }
-
-    pub fn verify(mut self, proof: Proof<T>) -> bool {
-        self.final_value = Some(proof.final_value);
+    pub fn verify(mut self, proof: Proof<T, B>, evaluation: T) -> Result<(), VerificationError> {
+        if proof.evaluation != evaluation {
+            return Err(VerificationError::EvaluationMismatch);
+        }
+        self.check_shape(&proof)?;
+        self.replay_transcript(&proof)?;
         self.final_poly = Some(proof.final_poly);
-        let mut leave_number = self.interpolate_cosets[0].size() / (1 << self.step);
-        for merkle_root in proof.merkle_root {
-            leave_number /= 1 << self.step;
-            self.polynomial_roots.push(MerkleTreeVerifier {
-                merkle_root,
+        // in hiding mode every evaluation below is of `polynomial + challenge * mask`
+        let (evaluation, first_deep) = match (&self.mask, &proof.mask) {
+            (Some(mask), Some(opening)) => (
+                proof.evaluation + self.mask_challenge * opening.evaluation,
+                self.first_deep + self.mask_challenge * mask.deep,
+            ),
+            _ => (proof.evaluation, self.first_deep),
+        };
+        for (i, merkle_root) in proof.merkle_root.into_iter().enumerate() {
+            let leave_number = self.interpolate_cosets[self.schedule.offset(i + 1)].size()
+                >> self.schedule.step(i + 1);
+            self.polynomial_roots.push(MerkleTreeVerifier::new(
+                self.params.tree_hash::<T>(),
                 leave_number,
-            });
+                &merkle_root,
+            ));
+        }
+        self.shuffle_eval = Some(DeepEval::claimed(
+            self.open_point.clone(),
+            evaluation,
+            proof.shuffle_evals,
+        ));
+        if first_deep != proof.deep_evals[0].0 {
+            return Err(VerificationError::DeepEvalMismatch { index: 0 });
         }
-        self.shuffle_eval = Some(DeepEval {
-            point: self.open_point.clone(),
-            first_eval: proof.evaluation,
-            else_evals: proof.shuffle_evals,
-        });
-        assert_eq!(self.first_deep, proof.deep_evals[0].0);
         proof
             .deep_evals
             .into_iter()
             .enumerate()
             .for_each(|(idx, (first_eval, else_evals))| {
-                self.deep_evals.push(DeepEval {
-                    point: std::iter::successors(Some(self.oracle.deep[idx]), |&x| Some(x * x))
-                        .take(self.total_round - idx)
-                        .collect::<Vec<_>>(),
+                self.deep_evals.push(DeepEval::claimed(
+                    deep_point(self.deep[idx], self.total_round - idx),
                     first_eval,
                     else_evals,
-                });
+                ));
             });
-        self._verify(&proof.query_result)
+        self._verify(
+            &proof.commit_query,
+            proof.mask.as_ref().map(|mask| &mask.query),
+            &proof.query_result,
+        )
+    }
+
+    fn check_shape(&self, proof: &Proof<T, B>) -> Result<(), VerificationError> {
+        if self.commit_params != self.params {
+            return Err(VerificationError::ParameterMismatch);
+        }
+        if self.mask.is_some() != self.hiding || proof.mask.is_some() != self.hiding {
+            return Err(VerificationError::MalformedProof("hiding mode"));
+        }
+        if self.open_point.len() != self.total_round {
+            return Err(VerificationError::MalformedProof("open point"));
+        }
+        let rounds = self.schedule.rounds();
+        let folded = self.schedule.total();
+        if proof.merkle_root.len() != rounds - 1 {
+            return Err(VerificationError::MalformedProof("merkle root count"));
+        }
+        if proof.query_result.len() != rounds - 1 {
+            return Err(VerificationError::MalformedProof("query result count"));
+        }
+        if proof.final_poly.coefficients().len() > 1 << self.params.final_poly_bits {
+            return Err(VerificationError::MalformedProof("final polynomial"));
+        }
+        if proof.shuffle_evals.len() != folded {
+            return Err(VerificationError::MalformedProof(
+                "shuffle evaluation count",
+            ));
+        }
+        if proof.deep_evals.len() != folded
+            || proof
+                .deep_evals
+                .iter()
+                .enumerate()
+                .any(|(idx, (_, else_evals))| else_evals.len() != folded - idx)
+        {
+            return Err(VerificationError::MalformedProof("DEEP evaluation count"));
+        }
+        Ok(())
+    }
+
+    // Derives every challenge by absorbing the proof in the prover's order.
+    fn replay_transcript(&mut self, proof: &Proof<T, B>) -> Result<(), VerificationError> {
+        self.transcript.append_fields(&self.open_point);
+        self.transcript.append_field(&proof.evaluation);
+        if let Some(mask) = &proof.mask {
+            self.transcript.append_field(&mask.evaluation);
+            self.mask_challenge = self.transcript.challenge();
+        }
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
+            let step = self.schedule.step(i);
+            for j in 0..step {
+                let round = self.schedule.offset(i) + j;
+                self.transcript.append_field(&proof.shuffle_evals[round]);
+                for (idx, (_, else_evals)) in proof.deep_evals[..round + 1].iter().enumerate() {
+                    self.transcript.append_field(&else_evals[round - idx]);
+                }
+                self.folding_challenges.push(self.transcript.challenge());
+                if round < self.total_round - 1 && j + 1 < step {
+                    self.deep.push(self.transcript.challenge());
+                    self.transcript.append_field(&proof.deep_evals[round + 1].0);
+                }
+            }
+            if i < rounds - 1 {
+                self.transcript.append_cap(&proof.merkle_root[i]);
+            } else {
+                self.transcript
+                    .append_fields(proof.final_poly.coefficients());
+            }
+            let folded = self.schedule.offset(i + 1);
+            if folded < self.schedule.total() {
+                self.deep.push(self.transcript.challenge());
+                self.transcript.append_field(&proof.deep_evals[folded].0);
+            }
+        }
+        if !self
+            .transcript
+            .check_grinding(self.params.grinding_bits, proof.pow_nonce)
+        {
+            return Err(VerificationError::InsufficientWork);
+        }
+        self.query_list = self.transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
+        Ok(())
     }
 
-    fn _verify(&self, polynomial_proof: &Vec<QueryResult<T>>) -> bool {
-        let mut leaf_indices = self.oracle.query_list.clone();
-        for i in 0..self.total_round / self.step {
-            let domain_size = self.interpolate_cosets[i * self.step].size();
+    fn _verify(
+        &self,
+        commit_proof: &QueryResult<B>,
+        mask_proof: Option<&QueryResult<B>>,
+        polynomial_proof: &Vec<QueryResult<T>>,
+    ) -> Result<(), VerificationError> {
+        let mut leaf_indices = self.query_list.clone();
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
+            let (offset, step) = (self.schedule.offset(i), self.schedule.step(i));
+            let domain_size = self.interpolate_cosets[offset].size();
             leaf_indices = leaf_indices
                 .iter_mut()
-                .map(|v| *v % (domain_size / (1 << self.step)))
+                .map(|v| *v % (domain_size >> step))
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
 
-            polynomial_proof[i].verify_merkle_tree(
-                &leaf_indices,
-                1 << self.step,
-                &self.polynomial_roots[i],
-            );
-
-            if i == self.total_round / self.step - 1 {
-                let challenges = self.oracle.folding_challenges[0..self.total_round].to_vec();
-                assert_eq!(
-                    self.shuffle_eval.as_ref().unwrap().verify(&challenges),
-                    self.final_value.unwrap()
-                );
-                for j in &self.deep_evals {
-                    assert_eq!(j.verify(&challenges), self.final_value.unwrap());
+            if i == 0 {
+                // the mask tree is opened at the same leaves in one proof
+                match self.mask.as_ref().zip(mask_proof) {
+                    Some((mask, mask_proof)) => QueryResult::verify_merkle_trees(
+                        &[commit_proof, mask_proof],
+                        i,
+                        &leaf_indices,
+                        1 << step,
+                        &[
+                            self.polynomial_roots[i].clone(),
+                            MerkleTreeVerifier::new(
+                                self.params.tree_hash::<B>(),
+                                self.polynomial_roots[i].leave_number,
+                                &mask.merkle_root,
+                            ),
+                        ],
+                    )?,
+                    None => commit_proof.verify_merkle_tree(
+                        i,
+                        &leaf_indices,
+                        1 << step,
+                        &self.polynomial_roots[i],
+                    )?,
                 }
+            } else {
+                polynomial_proof[i - 1].verify_merkle_tree(
+                    i,
+                    &leaf_indices,
+                    1 << step,
+                    &self.polynomial_roots[i],
+                )?;
             }
 
-            let folding_value = &polynomial_proof[i].proof_values;
-            let mut challenge = vec![];
-            for j in 0..self.step {
-                challenge.push(self.oracle.folding_challenges[i * self.step + j]);
-            }
-            for k in &leaf_indices {
-                // let x = folding_value[k];
-                // let nx = folding_value[&(k + domain_size / 2)];
-                // let v =
-                //     x + nx + challenge * (x - nx) * self.interpolate_cosets[i].element_inv_at(*k);
-                // if i == self.total_round - 1 {
-                //     assert_eq!(v * T::inverse_2(), self.final_value.unwrap());
-                // } else {
-                //     assert_eq!(v * T::inverse_2(), polynomial_proof[i + 1].proof_values[k]);
-                // }
-                let mut x;
-                let mut nx;
-                let mut verify_values = vec![];
-                let mut verify_inds = vec![];
-                for j in 0..(1 << self.step) {
-                    // Init verify values, which is the total values in the first step
-                    let ind = k + j * domain_size / (1 << self.step);
-                    verify_values.push(folding_value[&ind]);
-                    verify_inds.push(ind);
+            if i == rounds - 1 {
+                let challenges = &self.folding_challenges;
+                let final_poly = final_multilinear(
+                    self.final_poly.as_ref().unwrap(),
+                    self.params.final_poly_bits,
+                );
+                if !self
+                    .shuffle_eval
+                    .as_ref()
+                    .unwrap()
+                    .verify(challenges, &final_poly)
+                {
+                    return Err(VerificationError::EvaluationMismatch);
                 }
-                for j in 0..self.step {
-                    let size = verify_values.len();
-                    let mut tmp_values = vec![];
-                    let mut tmp_inds = vec![];
-                    for l in 0..size / 2 {
-                        x = verify_values[l];
-                        nx = verify_values[l + size / 2];
-                        tmp_values.push(
-                            (x + nx
-                                + challenge[j]
-                                    * (x - nx)
-                                    * self.interpolate_cosets[i * self.step + j]
-                                        .element_inv_at(verify_inds[l]))
-                                * T::inverse_2(),
-                        );
-                        tmp_inds.push(verify_inds[l]);
+                for (index, j) in self.deep_evals.iter().enumerate() {
+                    if !j.verify(challenges, &final_poly) {
+                        return Err(VerificationError::DeepEvalMismatch { index });
                     }
-                    verify_values = tmp_values;
-                    verify_inds = tmp_inds;
                 }
-                assert_eq!(verify_values[0], polynomial_proof[i + 1].proof_values[k]);
+            }
+
+            // Every folding step also halves.
+            let scale = T::inverse_2().pow(step);
+            let cosets = &self.interpolate_cosets[offset..];
+            let challenge = &self.folding_challenges[offset..offset + step];
+            for (position, k) in leaf_indices.iter().enumerate() {
+                let value = if i == 0 {
+                    let leaf = commit_proof.leaf(position).iter().map(|&v| T::from(v));
+                    let leaf = match mask_proof {
+                        Some(mask_proof) => leaf
+                            .zip(mask_proof.leaf(position))
+                            .map(|(v, &m)| v + self.mask_challenge.mul_base(m))
+                            .collect(),
+                        None => leaf.collect(),
+                    };
+                    fold(leaf, *k, cosets, challenge)
+                } else {
+                    polynomial_proof[i - 1].fold(position, cosets, challenge)
+                } * scale;
+                // the last layer is never committed; it must be the
+                // evaluations of `final_poly`
+                let expected = if i == rounds - 1 {
+                    let point = self.interpolate_cosets[offset + step].element_at(*k);
+                    self.final_poly.as_ref().unwrap().evaluation_at(point)
+                } else {
+                    polynomial_proof[i].entry(*k, self.polynomial_roots[i + 1].leave_number)?
+                };
+                if value != expected {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *k,
+                    });
+                }
             }
         }
-        true
+        Ok(())
     }
 }
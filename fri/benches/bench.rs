@@ -2,25 +2,27 @@ extern crate criterion;
 use criterion::*;
 
 use fri::{prover::Prover, verifier::Verifier};
-use util::{
-    algebra::{
-        coset::Coset,
-        field::{mersenne61_ext::Mersenne61Ext, MyField},
-        polynomial::Polynomial,
-    },
-    random_oracle::RandomOracle,
+use util::algebra::{
+    coset::Coset,
+    field::{mersenne61_ext::Mersenne61Ext, MyField},
+    polynomial::Polynomial,
 };
 
-use util::{CODE_RATE, SECURITY_BITS, STEP};
+use util::parallel::for_each_pool;
+use util::pcs::PcsParams;
+
 fn commit<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = PcsParams::default();
     let total_round: usize = variable_num;
     let degree = 1 << variable_num;
     let polynomial = Polynomial::random_polynomial(degree);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..total_round + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(total_round, SECURITY_BITS / CODE_RATE);
 
     criterion.bench_function(
         &format!("fri {} commit {}", T::FIELD_NAME, variable_num),
@@ -28,7 +30,7 @@ fn commit<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
             b.iter_batched(
                 || polynomial.clone(),
                 |p| {
-                    let prover = Prover::new(total_round, &interpolate_cosets, p, &oracle, STEP);
+                    let prover = Prover::new(total_round, &interpolate_cosets, p, params);
                     let _ = prover.commit_polynomial();
                 },
                 BatchSize::SmallInput,
@@ -44,18 +46,21 @@ fn bench_commit(c: &mut Criterion) {
 }
 
 fn open<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = PcsParams::default();
     let total_round = variable_num;
     let degree = 1 << variable_num;
     let polynomial = Polynomial::random_polynomial(degree);
-    let mut interpolate_cosets = vec![Coset::new(1 << (variable_num + CODE_RATE), T::from_int(1))];
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        T::from_int(1),
+    )];
     for i in 1..total_round + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(total_round, SECURITY_BITS / CODE_RATE);
-    let prover = Prover::new(total_round, &interpolate_cosets, polynomial, &oracle, STEP);
+    let prover = Prover::new(total_round, &interpolate_cosets, polynomial, params);
     let commits = prover.commit_polynomial();
-    let verifier = Verifier::new(total_round, &interpolate_cosets, commits, &oracle, STEP);
-    let point = verifier.get_open_point();
+    let point = T::random_element();
+    let verifier = Verifier::new(total_round, &interpolate_cosets, commits, point, params);
 
     criterion.bench_function(
         &format!("fri {} open {}", T::FIELD_NAME, variable_num),
@@ -80,21 +85,21 @@ fn bench_open(c: &mut Criterion) {
 }
 
 fn verify<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
+    let params = PcsParams::default();
     let total_round = variable_num;
     let degree = 1 << variable_num;
     let polynomial = Polynomial::random_polynomial(degree);
     let mut interpolate_cosets = vec![Coset::new(
-        1 << (variable_num + CODE_RATE),
+        1 << (variable_num + params.code_rate),
         Mersenne61Ext::from_int(1),
     )];
     for i in 1..total_round + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(total_round, SECURITY_BITS / CODE_RATE);
-    let mut prover = Prover::new(total_round, &interpolate_cosets, polynomial, &oracle, STEP);
+    let mut prover = Prover::new(total_round, &interpolate_cosets, polynomial, params);
     let commits = prover.commit_polynomial();
-    let mut verifier = Verifier::new(total_round, &interpolate_cosets, commits, &oracle, STEP);
-    let point = verifier.get_open_point();
+    let point = Mersenne61Ext::random_element();
+    let mut verifier = Verifier::new(total_round, &interpolate_cosets, commits, point, params);
 
     let evaluation = prover.prove(point);
     prover.commit_foldings_multi_step(&mut verifier);
@@ -104,7 +109,7 @@ fn verify<T: MyField>(criterion: &mut Criterion, variable_num: usize) {
         &format!("fri {} verify {}", T::FIELD_NAME, variable_num),
         move |b| {
             b.iter(|| {
-                assert!(verifier.verify(&interpolation_proof, evaluation));
+                assert!(verifier.verify(&interpolation_proof, evaluation).is_ok());
             })
         },
     );
@@ -116,10 +121,48 @@ fn bench_verify(c: &mut Criterion) {
     }
 }
 
+// Prover scaling by thread count.
+fn bench_open_threads(c: &mut Criterion) {
+    let variable_num = 18;
+    let params = PcsParams::default();
+    let polynomial = Polynomial::<Mersenne61Ext>::random_polynomial(1 << variable_num);
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        Mersenne61Ext::from_int(1),
+    )];
+    for i in 1..variable_num + 1 {
+        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+    }
+    let point = Mersenne61Ext::random_element();
+    for_each_pool(|threads, pool| {
+        c.bench_function(
+            &format!("fri prove {:02} threads {}", variable_num, threads),
+            |b| {
+                b.iter(|| {
+                    pool.install(|| {
+                        let mut prover = Prover::new(
+                            variable_num,
+                            &interpolate_cosets,
+                            polynomial.clone(),
+                            params,
+                        );
+                        let commit = prover.commit_polynomial();
+                        let mut verifier =
+                            Verifier::new(variable_num, &interpolate_cosets, commit, point, params);
+                        let _ = prover.prove(point);
+                        prover.commit_foldings_multi_step(&mut verifier);
+                        let _ = prover.query();
+                    })
+                })
+            },
+        );
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_commit, bench_open, bench_verify
+    targets = bench_commit, bench_open, bench_verify, bench_open_threads
 }
 
 criterion_main!(benches);
@@ -8,48 +8,228 @@ mod tests {
     use util::{
         algebra::{
             coset::Coset,
-            field::{mersenne61_ext::Mersenne61Ext, MyField},
+            field::{goldilocks64ext::Goldilocks64Ext, mersenne61_ext::Mersenne61Ext, MyField},
             polynomial::Polynomial,
         },
-        merkle_tree::MERKLE_ROOT_SIZE,
-        random_oracle::RandomOracle,
+        error::VerificationError,
+        merkle_tree::{HashChoice, MERKLE_ROOT_SIZE},
+        pcs::PcsParams,
+        schedule::FoldingSchedule,
+        serialization::NONCE_SIZE,
     };
-    use util::{CODE_RATE, SECURITY_BITS, STEP};
 
-    fn output_proof_size(variable_num: usize) -> usize {
+    fn output_proof_size<T: MyField>(variable_num: usize, params: PcsParams) -> usize {
         let degree = 1 << variable_num;
         let polynomial = Polynomial::random_polynomial(degree);
         let mut interpolate_cosets = vec![Coset::new(
-            1 << (variable_num + CODE_RATE),
-            Mersenne61Ext::from_int(1),
+            1 << (variable_num + params.code_rate),
+            T::from_int(1),
         )];
         for i in 1..variable_num + 1 {
             interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
         }
-        let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
-        let mut prover = Prover::new(variable_num, &interpolate_cosets, polynomial, &oracle, STEP);
+        let mut prover = Prover::new(variable_num, &interpolate_cosets, polynomial, params);
         let commits = prover.commit_polynomial();
-        let mut verifier = Verifier::new(variable_num, &interpolate_cosets, commits, &oracle, STEP);
-        let point = verifier.get_open_point();
+        let point = T::random_element();
+        let mut verifier = Verifier::new(variable_num, &interpolate_cosets, commits, point, params);
 
         let evaluation = prover.prove(point);
         prover.commit_foldings_multi_step(&mut verifier);
         let interpolation_proof = prover.query();
-        assert!(verifier.verify(&interpolation_proof, evaluation));
+        assert_eq!(verifier.verify(&interpolation_proof, evaluation), Ok(()));
         interpolation_proof
             .iter()
             .map(|x| x.proof_size())
             .sum::<usize>()
             + variable_num * MERKLE_ROOT_SIZE
+            + NONCE_SIZE
     }
 
     #[test]
     fn test_proof_size() {
         let mut wtr = Writer::from_path("fri.csv").unwrap();
         for i in 10..11 {
-            let proof_size = output_proof_size(i);
+            let proof_size = output_proof_size::<Mersenne61Ext>(i, PcsParams::default());
             wtr.write_record([i.to_string(), proof_size.to_string()])
                 .unwrap();
         }
     }
+
+    #[test]
+    fn goldilocks() {
+        output_proof_size::<Goldilocks64Ext>(10, PcsParams::default());
+    }
+
+    #[test]
+    fn poseidon2_trees() {
+        let params = PcsParams {
+            hash: HashChoice::Poseidon2,
+            ..PcsParams::default()
+        };
+        output_proof_size::<Mersenne61Ext>(8, params);
+        output_proof_size::<Goldilocks64Ext>(8, params);
+    }
+
+    #[test]
+    fn folding_schedules() {
+        let variable_num = 5;
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        // uniform steps, most of them leaving a remainder round
+        for step in 1..variable_num + 1 {
+            output_proof_size::<Mersenne61Ext>(variable_num, PcsParams { step, ..params });
+        }
+        for schedule in FoldingSchedule::all(variable_num) {
+            output_proof_size::<Mersenne61Ext>(
+                variable_num,
+                PcsParams {
+                    schedule: Some(schedule),
+                    ..params
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn early_termination() {
+        let variable_num = 6;
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        for final_poly_bits in 0..variable_num {
+            for step in 1..3 {
+                output_proof_size::<Mersenne61Ext>(
+                    variable_num,
+                    PcsParams {
+                        step,
+                        final_poly_bits,
+                        ..params
+                    },
+                );
+            }
+        }
+
+        let params = PcsParams {
+            final_poly_bits: 2,
+            ..params
+        };
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + params.code_rate),
+            Mersenne61Ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let mut prover = Prover::new(
+            variable_num,
+            &interpolate_cosets,
+            Polynomial::random_polynomial(1 << variable_num),
+            params,
+        );
+        let commit = prover.commit_polynomial();
+        let point = Mersenne61Ext::random_element();
+        let mut verifier = Verifier::new(variable_num, &interpolate_cosets, commit, point, params);
+        let evaluation = prover.prove(point);
+        prover.commit_foldings_multi_step(&mut verifier);
+        // one coefficient more than `final_poly_bits` allows
+        verifier.set_final_poly(Polynomial::random_polynomial(5));
+        assert_eq!(
+            verifier.verify(&prover.query(), evaluation),
+            Err(VerificationError::MalformedProof("final polynomial"))
+        );
+    }
+
+    #[test]
+    fn zero_polynomial() {
+        let variable_num = 8;
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + params.code_rate),
+            Mersenne61Ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let polynomial = Polynomial::new(vec![Mersenne61Ext::from_int(0); 1 << variable_num]);
+        assert_eq!(polynomial.coefficients(), &vec![Mersenne61Ext::from_int(0)]);
+        let mut prover = Prover::new(variable_num, &interpolate_cosets, polynomial, params);
+        let commit = prover.commit_polynomial();
+        let point = Mersenne61Ext::random_element();
+        let mut verifier = Verifier::new(variable_num, &interpolate_cosets, commit, point, params);
+        let evaluation = prover.prove(point);
+        assert_eq!(evaluation, Mersenne61Ext::from_int(0));
+        prover.commit_foldings_multi_step(&mut verifier);
+        assert_eq!(verifier.verify(&prover.query(), evaluation), Ok(()));
+    }
+
+    #[test]
+    fn merkle_caps() {
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        // caps higher than the smaller trees clamp to their leaves
+        for cap_bits in 0..8 {
+            for step in 1..3 {
+                output_proof_size::<Mersenne61Ext>(
+                    8,
+                    PcsParams {
+                        step,
+                        cap_bits,
+                        ..params
+                    },
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parameter_mismatch() {
+        let variable_num = 8;
+        let params = PcsParams {
+            query_num: 10,
+            ..PcsParams::default()
+        };
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (variable_num + params.code_rate),
+            Mersenne61Ext::from_int(1),
+        )];
+        for i in 1..variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let mut prover = Prover::new(
+            variable_num,
+            &interpolate_cosets,
+            Polynomial::random_polynomial(1 << variable_num),
+            params,
+        );
+        let commit = prover.commit_polynomial();
+        let point = Mersenne61Ext::random_element();
+        let mut verifier = Verifier::new(
+            variable_num,
+            &interpolate_cosets,
+            commit,
+            point,
+            PcsParams {
+                hash: HashChoice::Sha256,
+                ..params
+            },
+        );
+        let evaluation = prover.prove(point);
+        prover.commit_foldings_multi_step(&mut verifier);
+        assert_eq!(
+            verifier.verify(&prover.query(), evaluation),
+            Err(VerificationError::ParameterMismatch)
+        );
+    }
 }
@@ -2,23 +2,29 @@ use super::verifier::Verifier;
 use util::algebra::field::batch_inverse;
 use util::algebra::polynomial::Polynomial;
 
-use util::merkle_tree::MERKLE_ROOT_SIZE;
+use util::cfg_into_iter;
+use util::pcs::{Commitment, PcsParams};
 use util::query_result::QueryResult;
+use util::schedule::FoldingSchedule;
 use util::{
     algebra::{coset::Coset, field::MyField},
     interpolation::InterpolateValue,
-    random_oracle::RandomOracle,
+    transcript::Transcript,
 };
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
 #[derive(Clone)]
 pub struct Prover<T: MyField> {
-    total_round: usize,
+    schedule: FoldingSchedule,
     polynomial: Polynomial<T>,
     interpolate_cosets: Vec<Coset<T>>,
     interpolations: Vec<InterpolateValue<T>>,
-    oracle: RandomOracle<T>,
+    transcript: Transcript,
+    params: PcsParams,
     final_poly: Option<Polynomial<T>>,
-    step: usize,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> Prover<T> {
@@ -26,58 +32,64 @@ impl<T: MyField> Prover<T> {
         total_round: usize,
         interpolate_coset: &Vec<Coset<T>>, // L0, L1, ...
         polynomial: Polynomial<T>,
-        oracle: &RandomOracle<T>,
-        step: usize,
+        params: PcsParams,
     ) -> Prover<T> {
+        let schedule = params.folding(total_round);
         let interpolate_polynomial = InterpolateValue::new(
+            params.tree_hash::<T>(),
             interpolate_coset[0].fft(polynomial.coefficients().clone()),
-            1 << step,
+            1 << schedule.step(0),
         );
+        let mut transcript = Transcript::new(b"fri");
+        transcript.append_cap(&interpolate_polynomial.commit());
 
         Prover {
-            total_round,
+            schedule,
             polynomial,
             interpolate_cosets: interpolate_coset.clone(),
             interpolations: vec![interpolate_polynomial],
-            oracle: oracle.clone(),
+            transcript,
+            params,
             final_poly: None,
-            step: step,
+            pow_nonce: 0,
         }
     }
 
-    pub fn commit_polynomial(&self) -> [u8; MERKLE_ROOT_SIZE] {
-        self.interpolations[0].commit()
+    pub fn commit_polynomial(&self) -> Commitment {
+        Commitment {
+            merkle_root: self.interpolations[0].commit(),
+            params: self.params,
+        }
     }
 
     pub fn commit_foldings_multi_step(&self, verifier: &mut Verifier<T>) {
-        // Todo: 边缘case检测
-        for i in 1..self.total_round / self.step as usize + 1 {
+        for i in 1..self.schedule.rounds() + 1 {
             let interpolation = &self.interpolations[i];
             verifier.receive_interpolation_root(interpolation.leave_num(), interpolation.commit());
         }
         verifier.set_final_poly(self.final_poly.clone().unwrap());
+        verifier.set_pow_nonce(self.pow_nonce);
     }
 
     fn evaluation_next_domain(
         &self,
-        folding_value: &Vec<T>,
+        folding_value: &[T],
         round: usize,
         challenge: Vec<T>,
     ) -> Vec<T> {
         let mut res = vec![];
-        let mut tmp_folding_value = folding_value.clone();
+        let mut tmp_folding_value = folding_value.to_vec();
 
-        for j in 0..self.step as usize {
-            let coset = self.interpolate_cosets[self.step * round + j].clone();
-            res = vec![];
-            let mut new_v;
+        for j in 0..self.schedule.step(round) {
+            let coset = self.interpolate_cosets[self.schedule.offset(round) + j].clone();
             let len = coset.size();
-            for i in 0..(len / 2) {
-                let x = tmp_folding_value[i];
-                let nx = tmp_folding_value[i + len / 2];
-                new_v = (x + nx) + challenge[j] * (x - nx) * coset.element_inv_at(i);
-                res.push(new_v);
-            }
+            res = cfg_into_iter!(0..(len / 2))
+                .map(|i| {
+                    let x = tmp_folding_value[i];
+                    let nx = tmp_folding_value[i + len / 2];
+                    (x + nx) + challenge[j] * (x - nx) * coset.element_inv_at(i)
+                })
+                .collect();
             tmp_folding_value = res.clone();
             // Todo: init coset for every round outside bench
             // coset = coset.pow(2);
@@ -86,13 +98,12 @@ impl<T: MyField> Prover<T> {
     }
 
     pub fn prove(&mut self, point: T) -> T {
-        let mut res = None;
-        for i in 0..self.total_round / self.step as usize {
-            let mut challenge = vec![];
-            for j in 0..self.step {
-                challenge.push(self.oracle.folding_challenges[self.step * i + j])
-            }
-            // let challenge = self.oracle.folding_challenges[i];
+        let res = self.polynomial.evaluation_at(point);
+        self.transcript.append_field(&point);
+        self.transcript.append_field(&res);
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
+            let challenge = self.transcript.challenges(self.schedule.step(i));
             let next_evalutation = if i == 0 {
                 let inv = batch_inverse(
                     &self.interpolate_cosets[0]
@@ -101,47 +112,65 @@ impl<T: MyField> Prover<T> {
                         .map(|x| x - point)
                         .collect(),
                 );
-                res = Some(self.polynomial.evaluation_at(point));
-                let v = self.interpolations[0].value.clone();
+                let v = &self.interpolations[0].value;
                 // Cauchy: h(x) = (f(x)-v) * (x-z)^(-1)
                 self.evaluation_next_domain(
-                    &v.into_iter()
+                    &v.iter()
                         .zip(inv.into_iter())
-                        .map(|(x, inv)| (x - res.unwrap()) * inv)
-                        .collect(),
+                        .map(|(&x, inv)| (x - res) * inv)
+                        .collect::<Vec<_>>(),
                     i,
                     challenge,
                 )
             } else {
                 self.evaluation_next_domain(&self.interpolations[i].value, i, challenge)
             };
-            if i < self.total_round / self.step as usize - 1 {
-                self.interpolations
-                    .push(InterpolateValue::new(next_evalutation, 1 << self.step));
+            if i < rounds - 1 {
+                let interpolation = InterpolateValue::new(
+                    self.params.tree_hash::<T>(),
+                    next_evalutation,
+                    1 << self.schedule.step(i + 1),
+                );
+                self.transcript.append_cap(&interpolation.commit());
+                self.interpolations.push(interpolation);
             } else {
                 // self.final_value = Some(next_evalutation[0]);
                 // self.final_values = Some(next_evalutation);
-                self.interpolations.push(InterpolateValue::new(
+                let interpolation = InterpolateValue::new(
+                    self.params.tree_hash::<T>(),
                     next_evalutation.clone(),
-                    1 << self.step,
-                ));
-                self.final_poly = Some(Polynomial::new(
-                    self.interpolate_cosets[(i + 1) * self.step].ifft(next_evalutation.clone()),
-                ));
+                    1 << self.schedule.leaf_step(rounds, next_evalutation.len()),
+                );
+                self.transcript.append_cap(&interpolation.commit());
+                self.interpolations.push(interpolation);
+                // Only the low coefficients survive folding down to
+                // `final_poly_bits` variables.
+                let mut coefficients =
+                    self.interpolate_cosets[self.schedule.total()].ifft(next_evalutation.clone());
+                coefficients.truncate(1 << self.params.final_poly_bits);
+                let final_poly = Polynomial::new(coefficients);
+                self.transcript.append_fields(final_poly.coefficients());
+                self.final_poly = Some(final_poly);
             }
         }
-        res.unwrap()
+        self.pow_nonce = self.transcript.grind(self.params.grinding_bits);
+        res
     }
 
-    pub fn query(&self) -> Vec<QueryResult<T>> {
+    pub fn query(&mut self) -> Vec<QueryResult<T>> {
         let mut folding_res = vec![];
-        let mut leaf_indices = self.oracle.query_list.clone();
+        let mut leaf_indices = self.transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
 
-        for i in 0..self.total_round / self.step + 1 {
-            let len = self.interpolate_cosets[i * self.step].size();
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds + 1 {
+            let len = self.interpolate_cosets[self.schedule.offset(i)].size();
+            let step = self.schedule.leaf_step(i, len);
             leaf_indices = leaf_indices
                 .iter_mut()
-                .map(|v| *v % (len >> self.step))
+                .map(|v| *v % (len >> step))
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
@@ -1,148 +1,198 @@
 use util::algebra::polynomial::Polynomial;
-use util::merkle_tree::MERKLE_ROOT_SIZE;
-use util::random_oracle::RandomOracle;
+use util::error::VerificationError;
+use util::pcs::{Commitment, PcsParams};
+use util::schedule::FoldingSchedule;
+use util::transcript::Transcript;
 use util::{
     algebra::{coset::Coset, field::MyField},
-    merkle_tree::MerkleTreeVerifier,
-    query_result::QueryResult,
+    merkle_tree::{MerkleCap, MerkleTreeVerifier},
+    query_result::{fold, QueryResult},
 };
 
 #[derive(Clone)]
 pub struct Verifier<T: MyField> {
-    total_round: usize,
+    schedule: FoldingSchedule,
     interpolate_cosets: Vec<Coset<T>>,
     interpolation_roots: Vec<MerkleTreeVerifier>,
-    oracle: RandomOracle<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    commit_params: PcsParams,
     final_poly: Option<Polynomial<T>>, // used for multi-step verifier
     open_point: T,
-    step: usize,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> Verifier<T> {
     pub fn new(
         total_round: usize,
         coset: &Vec<Coset<T>>,
-        commit: [u8; MERKLE_ROOT_SIZE],
-        oracle: &RandomOracle<T>,
-        step: usize,
+        commit: Commitment,
+        open_point: T,
+        params: PcsParams,
     ) -> Self {
+        let schedule = params.folding(total_round);
+        let mut transcript = Transcript::new(b"fri");
+        transcript.append_cap(&commit.merkle_root);
         Verifier {
-            total_round,
+            schedule,
             interpolate_cosets: coset.clone(),
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            commit_params: commit.params,
             interpolation_roots: vec![MerkleTreeVerifier::new(
-                coset[0].size() / (1 << step),
-                &commit,
+                params.tree_hash::<T>(),
+                coset[0].size() / (1 << schedule.step(0)),
+                &commit.merkle_root,
             )],
             final_poly: None,
-            open_point: T::random_element(),
-            step: step,
+            open_point,
+            pow_nonce: 0,
         }
     }
 
-    pub fn get_open_point(&self) -> T {
-        self.open_point
-    }
-
     pub fn receive_interpolation_root(
         &mut self,
         leave_number: usize,
-        interpolation_root: [u8; MERKLE_ROOT_SIZE],
+        interpolation_root: MerkleCap,
     ) {
-        self.interpolation_roots.push(MerkleTreeVerifier {
+        self.interpolation_roots.push(MerkleTreeVerifier::new(
+            self.params.tree_hash::<T>(),
             leave_number,
-            merkle_root: interpolation_root,
-        });
+            &interpolation_root,
+        ));
     }
 
     pub fn set_final_poly(&mut self, poly: Polynomial<T>) {
         self.final_poly = Some(poly);
     }
 
-    pub fn verify(&self, interpolation_proof: &Vec<QueryResult<T>>, evaluation: T) -> bool {
-        let mut leaf_indices = self.oracle.query_list.clone();
-        for i in 0..self.total_round / self.step - 1 {
-            let domain_size = self.interpolate_cosets[i * self.step].size();
+    pub fn set_pow_nonce(&mut self, nonce: u64) {
+        self.pow_nonce = nonce;
+    }
+
+    // Derives the folding challenges and queries in the prover's order.
+    fn replay_transcript(&self, evaluation: T) -> Result<(Vec<T>, Vec<usize>), VerificationError> {
+        let mut transcript = self.transcript.clone();
+        transcript.append_field(&self.open_point);
+        transcript.append_field(&evaluation);
+        let mut folding_challenges = vec![];
+        for i in 0..self.schedule.rounds() {
+            folding_challenges.append(&mut transcript.challenges(self.schedule.step(i)));
+            transcript.append_cap(&self.interpolation_roots[i + 1].merkle_cap);
+        }
+        transcript.append_fields(self.final_poly.as_ref().unwrap().coefficients());
+        if !transcript.check_grinding(self.params.grinding_bits, self.pow_nonce) {
+            return Err(VerificationError::InsufficientWork);
+        }
+        let query_list = transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
+        Ok((folding_challenges, query_list))
+    }
+
+    pub fn verify(
+        &self,
+        interpolation_proof: &Vec<QueryResult<T>>,
+        evaluation: T,
+    ) -> Result<(), VerificationError> {
+        if self.commit_params != self.params {
+            return Err(VerificationError::ParameterMismatch);
+        }
+        let rounds = self.schedule.rounds();
+        if self.final_poly.is_none() || self.interpolation_roots.len() != rounds + 1 {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        if self.final_poly.as_ref().unwrap().coefficients().len() > 1 << self.params.final_poly_bits
+        {
+            return Err(VerificationError::MalformedProof("final polynomial"));
+        }
+        if interpolation_proof.len() != rounds + 1 {
+            return Err(VerificationError::MalformedProof("query result count"));
+        }
+        let (folding_challenges, mut leaf_indices) = self.replay_transcript(evaluation)?;
+        for i in 0..rounds {
+            let step = self.schedule.step(i);
+            let offset = self.schedule.offset(i);
+            let domain_size = self.interpolate_cosets[offset].size();
             leaf_indices = leaf_indices
                 .iter_mut()
-                .map(|v| *v % (domain_size >> self.step))
+                .map(|v| *v % (domain_size >> step))
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
 
             // Cauchy: verify mt
             interpolation_proof[i].verify_merkle_tree(
+                i,
                 &leaf_indices,
-                1 << self.step,
+                1 << step,
                 &self.interpolation_roots[i],
-            );
+            )?;
 
-            let mut challenge = vec![];
-            for j in 0..self.step {
-                challenge.push(self.oracle.folding_challenges[i * self.step + j]);
+            let challenge = &folding_challenges[offset..offset + step];
+            let cosets = &self.interpolate_cosets[offset..];
+            let len = domain_size >> step;
+            for (position, k) in leaf_indices.iter().enumerate() {
+                // The first oracle is the quotient (f(x) - f(z)) / (x - z).
+                let v = if i == 0 {
+                    let values = interpolation_proof[0]
+                        .leaf(position)
+                        .iter()
+                        .enumerate()
+                        .map(|(j, &v)| {
+                            let x = self.interpolate_cosets[0].element_at(k + j * len);
+                            (v - evaluation) * (x - self.open_point).inverse()
+                        })
+                        .collect();
+                    fold(values, *k, cosets, challenge)
+                } else {
+                    interpolation_proof[i].fold(position, cosets, challenge)
+                };
+                let next = &interpolation_proof[i + 1];
+                if v != next.entry(*k, self.interpolation_roots[i + 1].leave_number)? {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *k,
+                    });
+                }
             }
+        }
 
-            let get_folding_value: Box<dyn Fn(&usize) -> T> = if i == 0 {
-                Box::new(|x| {
-                    (interpolation_proof[0].proof_values[x] - evaluation)
-                        * (self.interpolate_cosets[0].element_at(*x) - self.open_point).inverse()
-                })
-            } else {
-                Box::new(|x| interpolation_proof[i].proof_values[x])
-            };
-            for k in &leaf_indices {
-                let mut x;
-                let mut nx;
-                let mut verify_values = vec![];
-                let mut verify_inds = vec![];
-                for j in 0..(1 << self.step) {
-                    // Init verify values, which is the total values in the first step
-                    let ind = k + j * domain_size / (1 << self.step);
-                    verify_values.push(get_folding_value(&ind));
-                    verify_inds.push(ind);
-                }
-                for j in 0..self.step {
-                    let size = verify_values.len();
-                    let mut tmp_values = vec![];
-                    let mut tmp_inds = vec![];
-                    for l in 0..size / 2 {
-                        x = verify_values[l];
-                        nx = verify_values[l + size / 2];
-                        tmp_values.push(
-                            x + nx
-                                + challenge[j]
-                                    * (x - nx)
-                                    * self.interpolate_cosets[i * self.step + j]
-                                        .element_inv_at(verify_inds[l]),
-                        );
-                        tmp_inds.push(verify_inds[l]);
-                    }
-                    verify_values = tmp_values;
-                    verify_inds = tmp_inds;
+        // The last committed layer must be the evaluations of final_poly.
+        let coset = &self.interpolate_cosets[self.schedule.total()];
+        let step = self.schedule.leaf_step(rounds, coset.size());
+        let len = coset.size() >> step;
+        leaf_indices = leaf_indices.iter().map(|v| *v % len).collect();
+        leaf_indices.sort();
+        leaf_indices.dedup();
+        interpolation_proof[rounds].verify_merkle_tree(
+            rounds,
+            &leaf_indices,
+            1 << step,
+            &self.interpolation_roots[rounds],
+        )?;
+        for (position, k) in leaf_indices.iter().enumerate() {
+            for (j, &value) in interpolation_proof[rounds]
+                .leaf(position)
+                .iter()
+                .enumerate()
+            {
+                let index = k + j * len;
+                let expected = self
+                    .final_poly
+                    .as_ref()
+                    .unwrap()
+                    .evaluation_at(coset.element_at(index));
+                if value != expected {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: rounds,
+                        index,
+                    });
                 }
-                assert_eq!(verify_values[0], interpolation_proof[i + 1].proof_values[k]);
             }
         }
 
-        // Cauchy: the final round
-        // let i = self.total_round / self.step;
-
-        // interpolation_proof[i].verify_merkle_tree(
-        //     &(0..self.interpolation_roots[i].leave_number).collect(),
-        //     usize::pow(2, self.step as u32),
-        //     &self.interpolation_roots[i],
-        // );
-
-        // let coset = self.interpolate_cosets[i * self.step].clone();
-        // for x in 0..coset.size() {
-        //     assert_eq!(
-        //         self.final_poly
-        //             .clone()
-        //             .unwrap()
-        //             .evaluation_at(coset.element_at(x)),
-        //         interpolation_proof[i].proof_values[&x]
-        //     )
-        // }
-        true
+        Ok(())
     }
 }
@@ -2,26 +2,30 @@ extern crate criterion;
 use criterion::*;
 
 use polyfrim::{prover::One2ManyProver, verifier::One2ManyVerifier};
-use util::{
-    algebra::{
-        coset::Coset,
-        field::{mersenne61_ext::Mersenne61Ext, MyField},
-        polynomial::MultilinearPolynomial,
-    },
-    random_oracle::RandomOracle,
+use util::algebra::{
+    coset::Coset,
+    field::{mersenne61_ext::Mersenne61Ext, MyField},
+    polynomial::MultilinearPolynomial,
 };
 
-use util::{CODE_RATE, SECURITY_BITS};
+use util::parallel::for_each_pool;
+use util::pcs::PcsParams;
+use util::security::Regime;
+
 fn commit(criterion: &mut Criterion, variable_num: usize, terminate_round: usize) {
+    let params = PcsParams {
+        final_poly_bits: terminate_round,
+        ..PcsParams::default()
+    }
+    .with_security::<Mersenne61Ext>(variable_num, 100, Regime::Conjectured);
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
     let mut interpolate_cosets = vec![Coset::new(
-        1 << (variable_num + CODE_RATE),
+        1 << (variable_num + params.code_rate),
         Mersenne61Ext::random_element(),
     )];
     for i in 1..variable_num {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
 
     criterion.bench_function(&format!("polyfrim commit {}", variable_num), move |b| {
         b.iter_batched(
@@ -31,7 +35,7 @@ fn commit(criterion: &mut Criterion, variable_num: usize, terminate_round: usize
                     variable_num - terminate_round,
                     &interpolate_cosets,
                     p,
-                    &oracle,
+                    params,
                 );
                 prover.commit_polynomial();
             },
@@ -47,30 +51,37 @@ fn bench_commit(c: &mut Criterion) {
 }
 
 fn open(criterion: &mut Criterion, variable_num: usize, terminate_round: usize) {
+    let params = PcsParams {
+        final_poly_bits: terminate_round,
+        ..PcsParams::default()
+    }
+    .with_security::<Mersenne61Ext>(variable_num, 100, Regime::Conjectured);
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
     let mut interpolate_cosets = vec![Coset::new(
-        1 << (variable_num + CODE_RATE),
+        1 << (variable_num + params.code_rate),
         Mersenne61Ext::random_element(),
     )];
     for i in 1..variable_num {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
     let prover = One2ManyProver::new(
         variable_num - terminate_round,
         &interpolate_cosets,
         polynomial,
-        &oracle,
+        params,
     );
     let commit = prover.commit_polynomial();
+    let open_point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
     let mut verifier = One2ManyVerifier::new(
         variable_num - terminate_round,
         variable_num,
         &interpolate_cosets,
         commit,
-        &oracle,
+        &open_point,
+        params,
     );
-    let open_point = verifier.get_open_point();
 
     criterion.bench_function(&format!("polyfrim open {}", variable_num), move |b| {
         b.iter_batched(
@@ -93,38 +104,48 @@ fn bench_open(c: &mut Criterion) {
 }
 
 fn verify(criterion: &mut Criterion, variable_num: usize, terminate_round: usize) {
+    let params = PcsParams {
+        final_poly_bits: terminate_round,
+        ..PcsParams::default()
+    }
+    .with_security::<Mersenne61Ext>(variable_num, 100, Regime::Conjectured);
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
     let mut interpolate_cosets = vec![Coset::new(
-        1 << (variable_num + CODE_RATE),
+        1 << (variable_num + params.code_rate),
         Mersenne61Ext::random_element(),
     )];
     for i in 1..variable_num {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
     let mut prover = One2ManyProver::new(
         variable_num - terminate_round,
         &interpolate_cosets,
-        polynomial,
-        &oracle,
+        polynomial.clone(),
+        params,
     );
     let commit = prover.commit_polynomial();
+    let open_point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
     let mut verifier = One2ManyVerifier::new(
         variable_num - terminate_round,
         variable_num,
         &interpolate_cosets,
         commit,
-        &oracle,
+        &open_point,
+        params,
     );
-    let open_point = verifier.get_open_point();
 
     prover.commit_functions(&open_point, &mut verifier);
     prover.prove();
     prover.commit_foldings(&mut verifier);
     let (folding_proof, function_proof) = prover.query();
+    let evaluation = polynomial.evaluate(&open_point);
     criterion.bench_function(&format!("polyfrim verify {}", variable_num), move |b| {
         b.iter(|| {
-            verifier.verify(&folding_proof, &function_proof);
+            assert!(verifier
+                .verify(&folding_proof, &function_proof, evaluation)
+                .is_ok());
         })
     });
 }
@@ -135,10 +156,61 @@ fn bench_verify(c: &mut Criterion) {
     }
 }
 
+// Prover scaling by thread count; proofs are identical for every pool size.
+fn bench_open_threads(c: &mut Criterion) {
+    let (variable_num, terminate_round) = (18, 1);
+    let params = PcsParams {
+        final_poly_bits: terminate_round,
+        ..PcsParams::default()
+    }
+    .with_security::<Mersenne61Ext>(variable_num, 100, Regime::Conjectured);
+    let polynomial = MultilinearPolynomial::<Mersenne61Ext>::random_polynomial(variable_num);
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        Mersenne61Ext::random_element(),
+    )];
+    for i in 1..variable_num {
+        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+    }
+    let open_point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
+    for_each_pool(|threads, pool| {
+        c.bench_function(
+            &format!("polyfrim prove {:02} threads {}", variable_num, threads),
+            |b| {
+                b.iter(|| {
+                    pool.install(|| {
+                        let mut prover = One2ManyProver::new(
+                            variable_num - terminate_round,
+                            &interpolate_cosets,
+                            polynomial.clone(),
+                            params,
+                        );
+                        let commit = prover.commit_polynomial();
+                        let mut verifier = One2ManyVerifier::new(
+                            variable_num - terminate_round,
+                            variable_num,
+                            &interpolate_cosets,
+                            commit,
+                            &open_point,
+                            params,
+                        );
+                        prover.commit_functions(&open_point, &mut verifier);
+                        prover.prove();
+                        prover.commit_foldings(&mut verifier);
+                        prover.query();
+                    })
+                })
+            },
+        );
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_commit, bench_open, bench_verify
+    targets = bench_commit, bench_open, bench_verify, bench_open_threads
 }
 
 criterion_main!(benches);
@@ -1,58 +1,438 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use util::{
+    algebra::{
+        field::MyField,
+        polynomial::{MultilinearPolynomial, Polynomial},
+    },
+    merkle_tree::MerkleCap,
+    query_result::QueryResult,
+    serialization::{
+        caps_size, deserialize_with, field_sequence_size, serialize_with, Reader, Serializable,
+        SerializationError, Writer, LENGTH_SIZE, NONCE_SIZE, VERSION_SIZE,
+    },
+};
+
+pub mod pcs;
 pub mod prover;
 pub mod verifier;
 
+// Non-interactive transcript of an opening: everything `commit_functions`,
+// `commit_foldings` and `query` would hand to the verifier. Each folding tree
+// after the first round also holds the function over the same domain.
+#[derive(Clone)]
+pub struct Proof<T: MyField> {
+    pub evaluation: MultilinearPolynomial<T>,
+    pub folding_root: Vec<MerkleCap>,
+    pub final_value: Polynomial<T>,
+    pub folding_proofs: Vec<QueryResult<T>>,
+    pub function_proof: QueryResult<T>,
+    pub pow_nonce: u64,
+}
+
+impl<T: MyField> Proof<T> {
+    // Exact length of `encode`.
+    pub fn size(&self) -> usize {
+        VERSION_SIZE
+            + field_sequence_size::<T>(self.evaluation.coefficients().len())
+            + caps_size(&self.folding_root)
+            + field_sequence_size::<T>(self.final_value.coefficients().len())
+            + self
+                .folding_proofs
+                .iter()
+                .fold(LENGTH_SIZE, |acc, x| acc + x.proof_size())
+            + self.function_proof.proof_size()
+            + NONCE_SIZE
+    }
+}
+
+impl<T: MyField> Serializable for Proof<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_fields(self.evaluation.coefficients());
+        writer.write_caps(&self.folding_root);
+        writer.write_polynomial(&self.final_value);
+        writer.write_all(&self.folding_proofs);
+        writer.write(&self.function_proof);
+        writer.write_u64(self.pow_nonce);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        // `MultilinearPolynomial::new` needs a power-of-two length.
+        let evaluation = reader.read_fields::<T>()?;
+        if !evaluation.len().is_power_of_two() {
+            return Err(SerializationError::NonCanonical("multilinear polynomial"));
+        }
+        Ok(Proof {
+            evaluation: MultilinearPolynomial::new(evaluation),
+            folding_root: reader.read_caps()?,
+            final_value: reader.read_polynomial()?,
+            folding_proofs: reader.read_all()?,
+            function_proof: reader.read()?,
+            pow_nonce: reader.read_u64()?,
+        })
+    }
+}
+
+impl<T: MyField> Serialize for Proof<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for Proof<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem::size_of;
 
-    use crate::{prover::One2ManyProver, verifier::One2ManyVerifier};
+    use crate::{pcs::PolyFrim, prover::One2ManyProver, verifier::One2ManyVerifier, Proof};
     use csv::Writer;
+    use util::security::Regime;
     use util::{
         algebra::{
             coset::Coset,
-            field::{mersenne61_ext::Mersenne61Ext, MyField},
-            polynomial::MultilinearPolynomial,
+            field::{goldilocks64ext::Goldilocks64Ext, mersenne61_ext::Mersenne61Ext, MyField},
+            polynomial::{MultilinearPolynomial, Polynomial},
         },
-        merkle_tree::MERKLE_ROOT_SIZE,
-        random_oracle::RandomOracle,
+        error::VerificationError,
+        merkle_tree::{Blake3Algorithm, HashChoice, MerkleHasher, MERKLE_ROOT_SIZE},
+        parallel::for_each_pool,
+        pcs::{Commitment, MultilinearPCS, PcsParams, SetupParams},
+        serialization::{Serializable, SerializationError, LENGTH_SIZE, NONCE_SIZE},
     };
-    use util::{CODE_RATE, SECURITY_BITS};
 
     fn output_proof_size(variable_num: usize, terminate_round: usize) -> usize {
+        let params = PcsParams {
+            final_poly_bits: terminate_round,
+            ..PcsParams::default()
+        }
+        .with_security::<Mersenne61Ext>(variable_num, 100, Regime::Conjectured);
         let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
         let mut interpolate_cosets = vec![Coset::new(
-            1 << (variable_num + CODE_RATE),
+            1 << (variable_num + params.code_rate),
             Mersenne61Ext::random_element(),
         )];
         for i in 1..variable_num {
             interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
         }
-        let oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
         let mut prover = One2ManyProver::new(
             variable_num - terminate_round,
             &interpolate_cosets,
-            polynomial,
-            &oracle,
+            polynomial.clone(),
+            params,
         );
         let commit = prover.commit_polynomial();
+        let open_point = (0..variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
         let mut verifier = One2ManyVerifier::new(
             variable_num - terminate_round,
             variable_num,
             &interpolate_cosets,
             commit,
-            &oracle,
+            &open_point,
+            params,
         );
-        let open_point = verifier.get_open_point();
 
         prover.commit_functions(&open_point, &mut verifier);
         prover.prove();
         prover.commit_foldings(&mut verifier);
         let (folding_proof, function_proof) = prover.query();
-        verifier.verify(&folding_proof, &function_proof);
+        let evaluation = polynomial.evaluate(&open_point);
+        assert_eq!(
+            verifier.verify(
+                &folding_proof,
+                &function_proof,
+                evaluation + Mersenne61Ext::from_int(1)
+            ),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        assert_eq!(
+            verifier.verify(&folding_proof, &function_proof, evaluation),
+            Ok(())
+        );
         folding_proof.iter().map(|x| x.proof_size()).sum::<usize>()
-            + function_proof.iter().map(|x| x.proof_size()).sum::<usize>()
-            + (variable_num - terminate_round) * MERKLE_ROOT_SIZE * 2
+            + function_proof.proof_size()
+            + (variable_num - terminate_round) * MERKLE_ROOT_SIZE
             + ((1 << terminate_round) + 1) * size_of::<Mersenne61Ext>() * 2
+            + NONCE_SIZE
+    }
+
+    fn check_pcs_interface<T: MyField>(hash: HashChoice) {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 8,
+                hash,
+                final_poly_bits: 1,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = PolyFrim::<T>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial.clone());
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, polynomial.evaluate(&point));
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+        assert_eq!(
+            pcs.verify(&commit, &point, &(eval + T::from_int(1)), &proof),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        let mut other_point = point.clone();
+        other_point[0] += T::from_int(1);
+        assert!(pcs.verify(&commit, &other_point, &eval, &proof).is_err());
+        assert_eq!(
+            pcs.verify(&commit, &point[1..], &eval, &proof),
+            Err(VerificationError::MalformedProof("open point"))
+        );
+        let sha256 = PolyFrim::<T>::setup(SetupParams {
+            pcs: PcsParams {
+                hash: HashChoice::Sha256,
+                ..params.pcs
+            },
+            ..params
+        });
+        assert_eq!(
+            sha256.verify(&commit, &point, &eval, &proof),
+            Err(VerificationError::ParameterMismatch)
+        );
+    }
+
+    #[test]
+    fn pcs_interface() {
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Goldilocks64Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Poseidon2);
+    }
+
+    #[test]
+    fn serialization() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                final_poly_bits: 2,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = PolyFrim::<Mersenne61Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let commit: Commitment =
+            bincode::deserialize(&bincode::serialize(&commit).unwrap()).unwrap();
+        let (eval, proof) = pcs.open(&data, &point);
+
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), proof.size());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&trailing).err(),
+            Some(SerializationError::TrailingBytes(1))
+        );
+        // The evaluation's coefficients follow the version byte and their count.
+        let n = 1 + LENGTH_SIZE;
+        let mut non_canonical = bytes.clone();
+        non_canonical[n..n + size_of::<Mersenne61Ext>()].fill(0xff);
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&non_canonical).err(),
+            Some(SerializationError::NonCanonicalField)
+        );
+        let mut ragged = bytes.clone();
+        ragged[1] -= 1;
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&ragged).err(),
+            Some(SerializationError::NonCanonical("multilinear polynomial"))
+        );
+
+        let proof: Proof<Mersenne61Ext> =
+            bincode::deserialize(&bincode::serialize(&proof).unwrap()).unwrap();
+        assert_eq!(proof.encode(), bytes);
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+    }
+
+    #[test]
+    fn malformed_rounds() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                final_poly_bits: 2,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = PolyFrim::<Mersenne61Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let (eval, proof) = pcs.open(&data, &point);
+
+        // one coefficient more than `final_poly_bits` allows
+        let mut long = proof.clone();
+        long.final_value = Polynomial::random_polynomial((1 << params.pcs.final_poly_bits) + 1);
+        assert_eq!(
+            pcs.verify(&commit, &point, &eval, &long),
+            Err(VerificationError::MalformedProof("final polynomial"))
+        );
+
+        let mut verifier = One2ManyVerifier::new(
+            0,
+            params.variable_num,
+            &vec![Coset::new(
+                1 << (params.variable_num + params.pcs.code_rate),
+                Mersenne61Ext::from_int(1),
+            )],
+            commit,
+            &point,
+            params.pcs,
+        );
+        verifier.set_evaluation(proof.evaluation);
+        verifier.set_final_value(&proof.final_value);
+        assert_eq!(
+            verifier.verify(&vec![], &proof.function_proof, eval),
+            Err(VerificationError::MalformedProof("round count"))
+        );
+    }
+
+    // Digest of a fixed opening made by the sequential build. The parallel
+    // build must reproduce it byte for byte in every pool size.
+    const SEQUENTIAL_PROOF: &str =
+        "6695a98a71e1118a3ec8c3b4de06ab26c472139b90a59a8128e8263a9f0d75f5";
+
+    #[test]
+    fn proof_matches_sequential_fixture() {
+        let params = SetupParams {
+            variable_num: 12,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = PolyFrim::<Goldilocks64Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::new(
+            (0..1u64 << params.variable_num)
+                .map(|i| Goldilocks64Ext::from_int(i * i + 1))
+                .collect(),
+        );
+        let point = (0..params.variable_num as u64)
+            .map(|i| Goldilocks64Ext::from_int(3 * i + 7))
+            .collect::<Vec<_>>();
+        let prove = || {
+            let (commit, data) = pcs.commit(polynomial.clone());
+            let mut bytes = commit.encode();
+            bytes.extend(pcs.open(&data, &point).1.encode());
+            Blake3Algorithm::hash(&bytes)
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        };
+        for_each_pool(|_, pool| assert_eq!(pool.install(prove), SEQUENTIAL_PROOF));
+    }
+
+    #[test]
+    fn zero_polynomial() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = PolyFrim::<Mersenne61Ext>::setup(params);
+        let polynomial =
+            MultilinearPolynomial::new(vec![Mersenne61Ext::from_int(0); 1 << params.variable_num]);
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, Mersenne61Ext::from_int(0));
+        let proof = Proof::<Mersenne61Ext>::decode(&proof.encode()).unwrap();
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+    }
+
+    #[test]
+    fn merkle_caps() {
+        let variable_num = 8;
+        for cap_bits in 0..8 {
+            let pcs = PolyFrim::<Mersenne61Ext>::setup(SetupParams {
+                variable_num,
+                pcs: PcsParams {
+                    query_num: 10,
+                    grinding_bits: 0,
+                    final_poly_bits: 1,
+                    cap_bits,
+                    ..PcsParams::default()
+                },
+            });
+            let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+            let point = (0..variable_num)
+                .map(|_| Mersenne61Ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = pcs.commit(polynomial);
+            assert!(commit.merkle_root.len() <= 1 << cap_bits);
+            let (eval, mut proof) = pcs.open(&data, &point);
+            assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+
+            proof.folding_root[0][0][0] ^= 1;
+            assert!(pcs.verify(&commit, &point, &eval, &proof).is_err());
+        }
+    }
+
+    #[test]
+    fn shared_folding_trees() {
+        let variable_num = 8;
+        let pcs = PolyFrim::<Mersenne61Ext>::setup(SetupParams {
+            variable_num,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                final_poly_bits: 1,
+                ..PcsParams::default()
+            },
+        });
+        let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+        let point = (0..variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let (eval, mut proof) = pcs.open(&data, &point);
+        assert_eq!(proof.folding_root.len(), variable_num - 2);
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+
+        // Offsets 2 and 3 of a shared leaf are the function column.
+        let mut swapped = proof.clone();
+        proof.folding_proofs[0].values[2] += Mersenne61Ext::from_int(1);
+        assert!(matches!(
+            pcs.verify(&commit, &point, &eval, &proof),
+            Err(VerificationError::MerklePathInvalid { round: 1, .. })
+        ));
+
+        // Swapping the function column of the first two opened leaves
+        // reveals the same values, just not at the committed positions.
+        for offset in 2..4 {
+            swapped.folding_proofs[0].values.swap(offset, 4 + offset);
+        }
+        assert!(matches!(
+            pcs.verify(&commit, &point, &eval, &swapped),
+            Err(VerificationError::MerklePathInvalid { round: 1, .. })
+        ));
     }
 
     #[test]
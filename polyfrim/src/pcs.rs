@@ -0,0 +1,91 @@
+use util::{
+    algebra::{coset::Coset, field::MyField, polynomial::MultilinearPolynomial},
+    error::VerificationError,
+    pcs::{Commitment, MultilinearPCS, SetupParams},
+    transcript::Transcript,
+};
+
+use crate::{prover::One2ManyProver, verifier::One2ManyVerifier, Proof};
+
+// The last `PcsParams::final_poly_bits` variables are sent in the clear as a
+// multilinear polynomial instead of being folded. Every round folds a single
+// variable, so `PcsParams::step` is ignored.
+pub struct PolyFrim<T: MyField> {
+    params: SetupParams,
+    interpolate_cosets: Vec<Coset<T>>,
+}
+
+impl<T: MyField> PolyFrim<T> {
+    fn total_round(&self) -> usize {
+        self.params.variable_num - self.params.pcs.final_poly_bits
+    }
+}
+
+impl<T: MyField> MultilinearPCS<T> for PolyFrim<T> {
+    type Params = SetupParams;
+    type Polynomial = MultilinearPolynomial<T>;
+    type Evaluation = T;
+    type Commitment = Commitment;
+    type ProverData = One2ManyProver<T>;
+    type Proof = Proof<T>;
+
+    // The coset shift is derived from a fixed transcript so that prover and
+    // verifier agree on it without further communication.
+    fn setup(params: SetupParams) -> Self {
+        assert!(params.pcs.final_poly_bits < params.variable_num);
+        let mut transcript = Transcript::new(b"polyfrim setup");
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (params.variable_num + params.pcs.code_rate),
+            transcript.challenge(),
+        )];
+        for i in 1..params.variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        PolyFrim {
+            params,
+            interpolate_cosets,
+        }
+    }
+
+    fn commit(&self, poly: MultilinearPolynomial<T>) -> (Commitment, One2ManyProver<T>) {
+        let prover = One2ManyProver::new(
+            self.total_round(),
+            &self.interpolate_cosets,
+            poly,
+            self.params.pcs,
+        );
+        (prover.commit_polynomial(), prover)
+    }
+
+    fn open(&self, data: &One2ManyProver<T>, point: &[T]) -> (T, Proof<T>) {
+        let proof = data.clone().generate_proof(point.to_vec());
+        let eval = proof
+            .evaluation
+            .evaluate(&point[self.total_round()..].to_vec());
+        (eval, proof)
+    }
+
+    fn verify(
+        &self,
+        commitment: &Commitment,
+        point: &[T],
+        eval: &T,
+        proof: &Proof<T>,
+    ) -> Result<(), VerificationError> {
+        let mut verifier = One2ManyVerifier::new(
+            self.total_round(),
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            commitment.clone(),
+            point,
+            self.params.pcs,
+        );
+        verifier.set_evaluation(proof.evaluation.clone());
+        for (i, root) in proof.folding_root.iter().enumerate() {
+            verifier.receive_folding_root(self.interpolate_cosets[i + 1].size() / 2, root.clone());
+        }
+        verifier.set_final_value(&proof.final_value);
+        verifier.set_pow_nonce(proof.pow_nonce);
+        verifier.verify(&proof.folding_proofs, &proof.function_proof, *eval)
+    }
+}
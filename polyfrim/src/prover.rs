@@ -1,24 +1,35 @@
 use super::verifier::One2ManyVerifier;
+use crate::Proof;
 use util::algebra::polynomial::{MultilinearPolynomial, Polynomial};
 
-use util::interpolation::InterpolateValue;
-use util::merkle_tree::MERKLE_ROOT_SIZE;
+use util::interpolation::{InterpolateValue, MultiInterpolateValue};
+use util::pcs::{Commitment, PcsParams};
 use util::query_result::QueryResult;
 // use util::query_result::QueryResult;
+use util::cfg_into_iter;
 use util::{
     algebra::{coset::Coset, field::MyField},
-    random_oracle::RandomOracle,
+    transcript::Transcript,
 };
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
 #[derive(Clone)]
 pub struct One2ManyProver<T: MyField> {
     total_round: usize,
     variable_num: usize,
     interpolate_cosets: Vec<Coset<T>>,
-    functions: Vec<InterpolateValue<T>>,
-    foldings: Vec<InterpolateValue<T>>,
-    oracle: RandomOracle<T>,
+    function: InterpolateValue<T>,
+    // f with its first i variables fixed to the open point, for i in
+    // 1..total_round. Each is moved into the folding tree over its domain.
+    functions: Vec<Vec<T>>,
+    // Column 0 is the folding, column 1 the function over the same domain.
+    foldings: Vec<MultiInterpolateValue<T>>,
+    transcript: Transcript,
+    params: PcsParams,
     final_value: Option<Polynomial<T>>,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> One2ManyProver<T> {
@@ -26,27 +37,38 @@ impl<T: MyField> One2ManyProver<T> {
         total_round: usize,
         interpolate_coset: &Vec<Coset<T>>,
         polynomial: MultilinearPolynomial<T>,
-        oracle: &RandomOracle<T>,
+        params: PcsParams,
     ) -> One2ManyProver<T> {
-        let interpolation = interpolate_coset[0].fft(polynomial.coefficients().clone());
+        let interpolation = InterpolateValue::new(
+            params.tree_hash::<T>(),
+            interpolate_coset[0].fft(polynomial.coefficients().clone()),
+            2,
+        );
+        let mut transcript = Transcript::new(b"polyfrim");
+        transcript.append_cap(&interpolation.commit());
 
         One2ManyProver {
             total_round,
             variable_num: polynomial.variable_num(),
             interpolate_cosets: interpolate_coset.clone(),
-            functions: vec![InterpolateValue::new(interpolation, 2)],
+            function: interpolation,
+            functions: vec![],
             foldings: vec![],
-            oracle: oracle.clone(),
+            transcript,
+            params,
             final_value: None,
+            pow_nonce: 0,
         }
     }
 
-    pub fn commit_polynomial(&self) -> [u8; MERKLE_ROOT_SIZE] {
-        assert_eq!(self.functions.len(), 1);
-        self.functions[0].commit()
+    pub fn commit_polynomial(&self) -> Commitment {
+        Commitment {
+            merkle_root: self.function.commit(),
+            params: self.params,
+        }
     }
 
-    fn fold(values: &Vec<T>, parameter: T, coset: &Coset<T>) -> Vec<T> {
+    fn fold(values: &[T], parameter: T, coset: &Coset<T>) -> Vec<T> {
         let len = values.len() / 2;
         let res = (0..len)
             .into_iter()
@@ -61,27 +83,32 @@ impl<T: MyField> One2ManyProver<T> {
     }
 
     pub fn commit_functions(&mut self, open_point: &Vec<T>, verifier: &mut One2ManyVerifier<T>) {
+        let evaluation = self.open_functions(open_point);
+        verifier.set_evaluation(evaluation);
+    }
+
+    fn open_functions(&mut self, open_point: &Vec<T>) -> MultilinearPolynomial<T> {
         let mut evaluation = None;
+        self.transcript.append_fields(open_point);
         for round in 0..self.total_round {
-            let next_evaluation = Self::fold(
-                &self.functions[round].value,
-                open_point[round],
-                &self.interpolate_cosets[round],
-            );
+            let values = if round == 0 {
+                &self.function.value[..]
+            } else {
+                &self.functions[round - 1][..]
+            };
+            let next_evaluation =
+                Self::fold(values, open_point[round], &self.interpolate_cosets[round]);
             if round < self.total_round - 1 {
-                self.functions
-                    .push(InterpolateValue::new(next_evaluation, 2));
+                self.functions.push(next_evaluation);
             } else {
                 let mut coefficients = self.interpolate_cosets[round + 1].ifft(next_evaluation);
                 coefficients.truncate(1 << (self.variable_num - self.total_round));
                 evaluation = Some(MultilinearPolynomial::new(coefficients));
             }
         }
-        for i in 1..self.total_round {
-            let function = &self.functions[i];
-            verifier.set_function(function.leave_num(), &function.commit());
-        }
-        verifier.set_evaluation(evaluation.unwrap());
+        let evaluation = evaluation.unwrap();
+        self.transcript.append_fields(evaluation.coefficients());
+        evaluation
     }
 
     pub fn commit_foldings(&self, verifier: &mut One2ManyVerifier<T>) {
@@ -90,54 +117,68 @@ impl<T: MyField> One2ManyProver<T> {
             verifier.receive_folding_root(interpolation.leave_num(), interpolation.commit());
         }
         verifier.set_final_value(self.final_value.as_ref().unwrap());
+        verifier.set_pow_nonce(self.pow_nonce);
     }
 
     fn evaluation_next_domain(&self, round: usize, challenge: T) -> Vec<T> {
-        let mut res = vec![];
         let len = self.interpolate_cosets[round].size();
         let get_folding_value = if round == 0 {
-            &self.functions[round]
+            &self.function.value[..]
         } else {
-            &self.foldings[round - 1]
+            &self.foldings[round - 1].values[0][..]
         };
         let coset = &self.interpolate_cosets[round];
-        for i in 0..(len / 2) {
-            let x = get_folding_value.value[i];
-            let nx = get_folding_value.value[i + len / 2];
-            let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
-            if round == 0 {
-                res.push(new_v);
-            } else {
-                let fv = &self.functions[round];
-                let x = fv.value[i];
-                let nx = fv.value[i + len / 2];
-                let new_v =
-                    (new_v * challenge + (x + nx)) * challenge + (x - nx) * coset.element_inv_at(i);
-                res.push(new_v);
-            }
-        }
-        res
+        cfg_into_iter!(0..(len / 2))
+            .map(|i| {
+                let x = get_folding_value[i];
+                let nx = get_folding_value[i + len / 2];
+                let new_v = (x + nx) + challenge * (x - nx) * coset.element_inv_at(i);
+                if round == 0 {
+                    new_v
+                } else {
+                    let fv = &self.foldings[round - 1].values[1];
+                    let x = fv[i];
+                    let nx = fv[i + len / 2];
+                    (new_v * challenge + (x + nx)) * challenge + (x - nx) * coset.element_inv_at(i)
+                }
+            })
+            .collect()
     }
 
     pub fn prove(&mut self) {
         for i in 0..self.total_round {
-            let challenge = self.oracle.folding_challenges[i];
+            let challenge = self.transcript.challenge();
             if i < self.total_round - 1 {
                 let next_evalutation = self.evaluation_next_domain(i, challenge);
-                self.foldings
-                    .push(InterpolateValue::new(next_evalutation, 2));
+                // The function over the same domain is queried at the same
+                // indices, so both share one tree. It is committed before the
+                // challenge that mixes it into the next folding.
+                let function = std::mem::take(&mut self.functions[i]);
+                let folding = MultiInterpolateValue::new(
+                    self.params.tree_hash::<T>(),
+                    vec![next_evalutation, function],
+                    2,
+                );
+                self.transcript.append_cap(&folding.commit());
+                self.foldings.push(folding);
             } else {
                 let next_evalutation = self.evaluation_next_domain(i, challenge);
                 let coefficients = self.interpolate_cosets[i + 1].ifft(next_evalutation);
-                self.final_value = Some(Polynomial::new(coefficients));
+                let final_value = Polynomial::new(coefficients);
+                self.transcript.append_fields(final_value.coefficients());
+                self.final_value = Some(final_value);
             }
         }
+        self.pow_nonce = self.transcript.grind(self.params.grinding_bits);
     }
 
-    pub fn query(&self) -> (Vec<QueryResult<T>>, Vec<QueryResult<T>>) {
+    pub fn query(&mut self) -> (Vec<QueryResult<T>>, QueryResult<T>) {
         let mut folding_res = vec![];
-        let mut functions_res = vec![];
-        let mut leaf_indices = self.oracle.query_list.clone();
+        let mut function_res = None;
+        let mut leaf_indices = self.transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> 1,
+        );
 
         for i in 0..self.total_round {
             let len = self.interpolate_cosets[i].size();
@@ -145,13 +186,26 @@ impl<T: MyField> One2ManyProver<T> {
             leaf_indices.sort();
             leaf_indices.dedup();
 
-            let query_result = self.functions[i].query(&leaf_indices);
-            functions_res.push(query_result);
-
-            if i > 0 {
+            if i == 0 {
+                function_res = Some(self.function.query(&leaf_indices));
+            } else {
                 folding_res.push(self.foldings[i - 1].query(&leaf_indices));
             }
         }
-        (folding_res, functions_res)
+        (folding_res, function_res.unwrap())
+    }
+
+    pub fn generate_proof(mut self, point: Vec<T>) -> Proof<T> {
+        let evaluation = self.open_functions(&point);
+        self.prove();
+        let (folding_proofs, function_proof) = self.query();
+        Proof {
+            evaluation,
+            folding_root: self.foldings.iter().map(|x| x.commit()).collect(),
+            final_value: self.final_value.clone().unwrap(),
+            folding_proofs,
+            function_proof,
+            pow_nonce: self.pow_nonce,
+        }
     }
 }
@@ -1,9 +1,10 @@
 use util::algebra::polynomial::{MultilinearPolynomial, Polynomial};
-use util::merkle_tree::MERKLE_ROOT_SIZE;
-use util::random_oracle::RandomOracle;
+use util::error::VerificationError;
+use util::pcs::{Commitment, PcsParams};
+use util::transcript::Transcript;
 use util::{
     algebra::{coset::Coset, field::MyField},
-    merkle_tree::MerkleTreeVerifier,
+    merkle_tree::{MerkleCap, MerkleTreeVerifier},
     query_result::QueryResult,
 };
 
@@ -12,12 +13,16 @@ pub struct One2ManyVerifier<T: MyField> {
     total_round: usize,
     log_max_degree: usize,
     interpolate_cosets: Vec<Coset<T>>,
-    function_root: Vec<MerkleTreeVerifier>,
+    function_root: MerkleTreeVerifier,
+    // Column 0 is the folding, column 1 the function over the same domain.
     folding_root: Vec<MerkleTreeVerifier>,
-    oracle: RandomOracle<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    commit_params: PcsParams,
     final_value: Option<Polynomial<T>>,
     evaluation: Option<MultilinearPolynomial<T>>,
     open_point: Vec<T>,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> One2ManyVerifier<T> {
@@ -25,65 +30,118 @@ impl<T: MyField> One2ManyVerifier<T> {
         total_round: usize,
         log_max_degree: usize,
         coset: &Vec<Coset<T>>,
-        commit: [u8; MERKLE_ROOT_SIZE],
-        oracle: &RandomOracle<T>,
+        commit: Commitment,
+        open_point: &[T],
+        params: PcsParams,
     ) -> Self {
+        let mut transcript = Transcript::new(b"polyfrim");
+        transcript.append_cap(&commit.merkle_root);
         One2ManyVerifier {
             total_round,
             log_max_degree,
             interpolate_cosets: coset.clone(),
-            function_root: vec![MerkleTreeVerifier {
-                merkle_root: commit,
-                leave_number: coset[0].size() / 2,
-            }],
+            function_root: MerkleTreeVerifier::new(
+                params.tree_hash::<T>(),
+                coset[0].size() / 2,
+                &commit.merkle_root,
+            ),
             folding_root: vec![],
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            commit_params: commit.params,
             final_value: None,
             evaluation: None,
-            open_point: (0..log_max_degree)
-                .into_iter()
-                .map(|_| T::random_element())
-                .collect(),
+            open_point: open_point.to_vec(),
+            pow_nonce: 0,
         }
     }
 
-    pub fn get_open_point(&self) -> Vec<T> {
-        self.open_point.clone()
-    }
-
     pub fn set_evaluation(&mut self, evaluation: MultilinearPolynomial<T>) {
         self.evaluation = Some(evaluation);
     }
 
-    pub fn set_function(&mut self, leave_number: usize, function_root: &[u8; MERKLE_ROOT_SIZE]) {
-        self.function_root.push(MerkleTreeVerifier {
-            merkle_root: function_root.clone(),
-            leave_number,
-        });
-    }
-
-    pub fn receive_folding_root(
-        &mut self,
-        leave_number: usize,
-        folding_root: [u8; MERKLE_ROOT_SIZE],
-    ) {
-        self.folding_root.push(MerkleTreeVerifier {
+    pub fn receive_folding_root(&mut self, leave_number: usize, folding_root: MerkleCap) {
+        self.folding_root.push(MerkleTreeVerifier::new(
+            self.params.tree_hash::<T>(),
             leave_number,
-            merkle_root: folding_root,
-        });
+            &folding_root,
+        ));
     }
 
     pub fn set_final_value(&mut self, value: &Polynomial<T>) {
-        assert!(value.degree() <= 1 << (self.log_max_degree - self.total_round));
         self.final_value = Some(value.clone());
     }
 
+    pub fn set_pow_nonce(&mut self, nonce: u64) {
+        self.pow_nonce = nonce;
+    }
+
+    // Derives the folding challenges and queries in the prover's order.
+    fn replay_transcript(&self) -> Result<(Vec<T>, Vec<usize>), VerificationError> {
+        let mut transcript = self.transcript.clone();
+        transcript.append_fields(&self.open_point);
+        transcript.append_fields(self.evaluation.as_ref().unwrap().coefficients());
+        let mut folding_challenges = vec![];
+        for i in 0..self.total_round {
+            folding_challenges.push(transcript.challenge());
+            if i < self.total_round - 1 {
+                transcript.append_cap(&self.folding_root[i].merkle_cap);
+            } else {
+                transcript.append_fields(self.final_value.as_ref().unwrap().coefficients());
+            }
+        }
+        if !transcript.check_grinding(self.params.grinding_bits, self.pow_nonce) {
+            return Err(VerificationError::InsufficientWork);
+        }
+        let query_list = transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> 1,
+        );
+        Ok((folding_challenges, query_list))
+    }
+
     pub fn verify(
         &self,
         folding_proof: &Vec<QueryResult<T>>,
-        function_proof: &Vec<QueryResult<T>>,
-    ) -> bool {
-        let mut leaf_indices = self.oracle.query_list.clone();
+        function_proof: &QueryResult<T>,
+        evaluation: T,
+    ) -> Result<(), VerificationError> {
+        if self.commit_params != self.params {
+            return Err(VerificationError::ParameterMismatch);
+        }
+        if self.open_point.len() != self.log_max_degree {
+            return Err(VerificationError::MalformedProof("open point"));
+        }
+        // the checks below assume at least one folding round
+        if self.total_round == 0 || self.total_round > self.log_max_degree {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        match &self.final_value {
+            Some(value)
+                if value.coefficients().len() <= 1 << (self.log_max_degree - self.total_round) => {}
+            _ => return Err(VerificationError::MalformedProof("final polynomial")),
+        }
+        // The prover sends f with its first `total_round` variables fixed;
+        // the claimed value must agree with it on the remaining ones.
+        match &self.evaluation {
+            Some(poly)
+                if poly.coefficients().len() == 1 << (self.log_max_degree - self.total_round) =>
+            {
+                if poly.evaluate(&self.open_point[self.total_round..].to_vec()) != evaluation {
+                    return Err(VerificationError::EvaluationMismatch);
+                }
+            }
+            _ => return Err(VerificationError::MalformedProof("missing evaluation")),
+        }
+        if self.folding_root.len() != self.total_round - 1
+            || folding_proof.len() != self.total_round - 1
+        {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        let (folding_challenges, mut leaf_indices) = self.replay_transcript()?;
+        // Each round checks values of the next round's tree, so every opening
+        // is checked against its root before any of it is used.
+        let mut round_indices = vec![];
         for i in 0..self.total_round {
             let domain_size = self.interpolate_cosets[i].size();
             leaf_indices = leaf_indices
@@ -92,68 +150,71 @@ impl<T: MyField> One2ManyVerifier<T> {
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
-
             if i == 0 {
-                function_proof[i].verify_merkle_tree(&leaf_indices, 2, &self.function_root[i]);
+                function_proof.verify_merkle_tree(i, &leaf_indices, 2, &self.function_root)?;
             } else {
-                folding_proof[i - 1].verify_merkle_tree(
+                folding_proof[i - 1].verify_merkle_tree_multi(
+                    i,
                     &leaf_indices,
                     2,
+                    2,
                     &self.folding_root[i - 1],
-                );
+                )?;
             }
+            round_indices.push(leaf_indices.clone());
+        }
 
-            let challenge = self.oracle.folding_challenges[i];
-            let get_folding_value = if i == 0 {
-                &function_proof[i].proof_values
+        // Entry `index` of `column` in the next round's shared tree.
+        let next_value = |round: usize, index: usize, column: usize| {
+            let len = self.folding_root[round].leave_number;
+            folding_proof[round].column_entry(index, len, column, 2)
+        };
+        for (i, leaf_indices) in round_indices.iter().enumerate() {
+            // The first round reads the committed polynomial as both the
+            // folding and the function; later rounds read the folding from
+            // column 0 and the function from column 1 of one shared query.
+            let (query, function_column) = if i == 0 {
+                (function_proof, 0)
             } else {
-                &folding_proof[i - 1].proof_values
+                (&folding_proof[i - 1], 1)
             };
 
-            let function_values = if i != 0 {
-                let function_query_result = &function_proof[i];
-                function_query_result.verify_merkle_tree(&leaf_indices, 2, &self.function_root[i]);
-                Some(&function_query_result.proof_values)
-            } else {
-                None
-            };
-            for j in &leaf_indices {
-                let x = get_folding_value[j];
-                let nx = get_folding_value[&(j + domain_size / 2)];
-                let v =
-                    x + nx + challenge * (x - nx) * self.interpolate_cosets[i].element_inv_at(*j);
+            let challenge = folding_challenges[i];
+            for (position, j) in leaf_indices.iter().enumerate() {
+                let inv = self.interpolate_cosets[i].element_inv_at(*j);
+                // Each column holds x and -x.
+                let folding = query.column(position, 0, 2);
+                let (x, nx) = (folding[0], folding[1]);
+                let mut v = x + nx + challenge * (x - nx) * inv;
+                let function = query.column(position, function_column, 2);
+                let (fx, fnx) = (function[0], function[1]);
                 if i != 0 {
-                    let x = function_values.as_ref().unwrap()[j];
-                    let nx = function_values.as_ref().unwrap()[&(j + domain_size / 2)];
-                    let v = (v * challenge + (x + nx)) * challenge
-                        + (x - nx) * self.interpolate_cosets[i].element_inv_at(*j);
-                    if i == self.total_round - 1 {
-                        let x = self.interpolate_cosets[i + 1].element_at(*j);
-                        if v != self.final_value.as_ref().unwrap().evaluation_at(x) {
-                            return false;
-                        }
-                    } else if v != folding_proof[i].proof_values[j] {
-                        return false;
-                    }
+                    v = (v * challenge + (fx + fnx)) * challenge + (fx - fnx) * inv;
+                }
+                let expected = if i == self.total_round - 1 {
+                    let x = self.interpolate_cosets[i + 1].element_at(*j);
+                    self.final_value.as_ref().unwrap().evaluation_at(x)
                 } else {
-                    if v != folding_proof[i].proof_values[j] {
-                        return false;
-                    }
+                    next_value(i, *j, 0)?
+                };
+                if v != expected {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *j,
+                    });
                 }
-                let x = function_proof[i].proof_values[j];
-                let nx = function_proof[i].proof_values[&(j + domain_size / 2)];
-                let v = x
-                    + nx
-                    + self.open_point[i] * (x - nx) * self.interpolate_cosets[i].element_inv_at(*j);
-                if i < self.total_round - 1 {
-                    assert_eq!(v, function_proof[i + 1].proof_values[j] * T::from_int(2));
+                let v = fx + fnx + self.open_point[i] * (fx - fnx) * inv;
+                let expected = if i < self.total_round - 1 {
+                    next_value(i, *j, 1)?
                 } else {
                     let x = self.interpolate_cosets[i + 1].element_at(*j);
-                    let poly_v = self.evaluation.as_ref().unwrap().evaluate_as_polynomial(x);
-                    assert_eq!(v, poly_v * T::from_int(2));
+                    self.evaluation.as_ref().unwrap().evaluate_as_polynomial(x)
+                };
+                if v != expected * T::from_int(2) {
+                    return Err(VerificationError::EvaluationMismatch);
                 }
             }
         }
-        true
+        Ok(())
     }
 }
@@ -1,97 +1,275 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+use once_cell::sync::Lazy;
+
 use super::{field::MyField, polynomial::Polynomial};
-use crate::batch_bit_reverse;
+use crate::storage::{Buffer, Storage};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-#[derive(Debug, Clone, Copy)]
-struct Radix2Domain<T: MyField> {
-    order: usize,
+// Butterflies of one layer are split across threads once they are this wide.
+pub(crate) const PARALLEL_BUTTERFLIES: usize = 1 << 10;
+
+// Domains up to this size keep every power of their root of unity; larger
+// ones split exponents into two halves looked up separately, so the tables
+// take O(sqrt n) space and a lookup costs one multiplication.
+const DIRECT_TABLE_LOG: u32 = 22;
+
+// Powers of the primitive `order`-th root of unity. Every domain of that size
+// shares one table; inverse twiddles are read from it backwards.
+#[derive(Debug)]
+struct FftTables<T: MyField> {
     omega: T,
+    order: usize,
+    order_inv: T,
+    split: u32,
+    low: Vec<T>,
+    high: Vec<T>,
 }
 
-impl<T: MyField> Radix2Domain<T> {
-    #[inline]
-    pub fn new(order: usize, omega: T) -> Self {
-        Radix2Domain { order, omega }
-    }
+type TableCache = HashMap<(TypeId, usize), Weak<dyn Any + Send + Sync>>;
 
-    #[inline]
-    pub fn order(&self) -> usize {
-        self.order
-    }
+static TABLES: Lazy<Mutex<TableCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
-    #[inline]
-    pub fn omega(&self) -> T {
-        self.omega
+impl<T: MyField> FftTables<T> {
+    // A table stays cached for as long as some domain of its size is alive.
+    fn get(order: usize) -> Arc<Self> {
+        let key = (TypeId::of::<T>(), order);
+        let mut cache = TABLES.lock().unwrap();
+        if let Some(tables) = cache.get(&key).and_then(Weak::upgrade) {
+            return tables.downcast().unwrap();
+        }
+        let log_order = order.ilog2();
+        let split = if log_order <= DIRECT_TABLE_LOG {
+            log_order
+        } else {
+            log_order.div_ceil(2)
+        };
+        let tables = Arc::new(Self::build(order, split));
+        cache.retain(|_, tables| tables.strong_count() > 0);
+        let weak: Weak<dyn Any + Send + Sync> = Arc::downgrade(&tables) as _;
+        cache.insert(key, weak);
+        tables
     }
 
-    #[inline]
-    pub fn fft(&self, a: &mut Vec<T>) {
-        _fft(a, self.omega);
+    fn build(order: usize, split: u32) -> Self {
+        let omega = T::get_generator(order);
+        let high = if 1 << split == order {
+            vec![]
+        } else {
+            powers(omega.pow(1 << split), order >> split)
+        };
+        FftTables {
+            omega,
+            order,
+            order_inv: T::from_int(order as u64).inverse(),
+            split,
+            low: powers(omega, 1 << split),
+            high,
+        }
     }
 
     #[inline]
-    pub fn ifft(&self, a: &mut Vec<T>) {
-        _fft(a, self.omega.inverse());
-        let t = T::from_int(self.order as u64).inverse();
-        for i in a {
-            *i *= t;
+    fn power(&self, exponent: usize) -> T {
+        if self.high.is_empty() {
+            self.low[exponent]
+        } else {
+            self.high[exponent >> self.split] * self.low[exponent & ((1 << self.split) - 1)]
         }
     }
 
     #[inline]
-    pub fn coset_fft(&self, a: &mut Vec<T>, shift: T) {
-        multiply_by_coset(a, shift);
-        self.fft(a);
+    fn twiddle(&self, exponent: usize, inverse: bool) -> T {
+        if inverse {
+            self.power((self.order - exponent) & (self.order - 1))
+        } else {
+            self.power(exponent)
+        }
     }
+}
 
-    #[inline]
-    pub fn coset_ifft(&self, a: &mut Vec<T>, shift: T) {
-        self.ifft(a);
-        multiply_by_coset(a, shift.inverse());
-    }
+// a[i] *= base^i, restarting from `base.pow` at every chunk so chunks are independent.
+fn scale_by_powers<T: MyField>(a: &mut [T], base: T) {
+    crate::cfg_chunks_mut!(a, PARALLEL_BUTTERFLIES)
+        .enumerate()
+        .for_each(|(k, chunk)| {
+            let mut t = base.pow(k * PARALLEL_BUTTERFLIES);
+            for x in chunk {
+                *x *= t;
+                t *= base;
+            }
+        });
 }
 
-fn multiply_by_coset<T: MyField>(a: &mut Vec<T>, shift: T) {
-    let mut t = shift;
-    for i in 1..a.len() {
-        a[i] *= t;
-        t *= shift;
-    }
+fn powers<T: MyField>(base: T, n: usize) -> Vec<T> {
+    let mut res = vec![T::from_int(1); n];
+    scale_by_powers(&mut res, base);
+    res
 }
 
-fn _fft<T: MyField>(a: &mut Vec<T>, omega: T) {
+pub(crate) fn bit_reverse_permute<T>(a: &mut [T]) {
     let n = a.len();
-    let log_n = n.ilog2() as usize;
-    let rank = batch_bit_reverse(log_n);
+    if n <= 2 {
+        return;
+    }
+    let shift = usize::BITS - n.ilog2();
     for i in 0..n {
-        if i < rank[i] {
-            (a[i], a[rank[i]]) = (a[rank[i]], a[i]);
+        let j = i.reverse_bits() >> shift;
+        if i < j {
+            a.swap(i, j);
         }
     }
-    let mut log_m = 0usize;
-    for _i in 0..log_n {
-        let w_m = omega.pow(n >> (log_m + 1));
-        let m = 1 << log_m;
-        for j in (0..n).step_by(m * 2) {
-            let mut w = T::from_int(1);
-            for k in 0..m {
-                let t = w * a[j + k + m];
-                a[j + k + m] = a[j + k] - t;
-                a[j + k] += t;
-                w *= w_m;
-            }
+}
+
+// The half-size 1 layer, whose only twiddle is 1; it is the same for both
+// butterfly shapes and covers the layer left over when log n is odd.
+fn radix2_layer<T: MyField>(a: &mut [T]) {
+    crate::cfg_chunks_mut!(a, 2).for_each(|pair| {
+        let (x, y) = (pair[0], pair[1]);
+        pair[0] = x + y;
+        pair[1] = x - y;
+    });
+}
+
+// Calls `f(j, a0, a1, a2, a3)` on the four quarters of every block of 4m.
+fn for_each_quad<T, F>(a: &mut [T], m: usize, f: F)
+where
+    T: MyField,
+    F: Fn(usize, &mut T, &mut T, &mut T, &mut T) + Send + Sync,
+{
+    crate::cfg_chunks_mut!(a, 4 * m).for_each(|chunk| {
+        let (q01, q23) = chunk.split_at_mut(2 * m);
+        let (q0, q1) = q01.split_at_mut(m);
+        let (q2, q3) = q23.split_at_mut(m);
+        let kernel = |(j, (((a0, a1), a2), a3))| f(j, a0, a1, a2, a3);
+        if m >= PARALLEL_BUTTERFLIES {
+            crate::cfg_iter_mut!(q0)
+                .zip(q1)
+                .zip(q2)
+                .zip(q3)
+                .enumerate()
+                .for_each(kernel);
+        } else {
+            q0.iter_mut()
+                .zip(q1)
+                .zip(q2)
+                .zip(q3)
+                .enumerate()
+                .for_each(kernel);
         }
-        log_m += 1;
+    });
+}
+
+// Radix-4 decimation in time: the radix-2 layers of half-size m and 2m in one
+// pass. Takes bit-reversed input and produces natural order.
+fn dit<T: MyField>(a: &mut [T], tables: &FftTables<T>, inverse: bool) {
+    let n = a.len();
+    let log_n = n.ilog2();
+    let mut log_m = 0;
+    if log_n % 2 == 1 {
+        radix2_layer(a);
+        log_m = 1;
+    }
+    while log_m < log_n {
+        let m = 1 << log_m;
+        let stride = n / (4 * m);
+        for_each_quad(a, m, |j, a0, a1, a2, a3| {
+            let w1 = tables.twiddle(2 * j * stride, inverse);
+            let w2 = tables.twiddle(j * stride, inverse);
+            let w3 = tables.twiddle((j + m) * stride, inverse);
+            let t = w1 * *a1;
+            let (b0, b1) = (*a0 + t, *a0 - t);
+            let t = w1 * *a3;
+            let (b2, b3) = (*a2 + t, *a2 - t);
+            let t = w2 * b2;
+            (*a0, *a2) = (b0 + t, b0 - t);
+            let t = w3 * b3;
+            (*a1, *a3) = (b1 + t, b1 - t);
+        });
+        log_m += 2;
     }
 }
 
-use std::sync::Arc;
+// Radix-4 decimation in frequency, the mirror of `dit`: natural-order input,
+// bit-reversed output.
+fn dif<T: MyField>(a: &mut [T], tables: &FftTables<T>, inverse: bool) {
+    let n = a.len();
+    let mut log_m = n.ilog2();
+    while log_m >= 2 {
+        log_m -= 2;
+        let m = 1 << log_m;
+        let stride = n / (4 * m);
+        for_each_quad(a, m, |j, a0, a1, a2, a3| {
+            let w1 = tables.twiddle(2 * j * stride, inverse);
+            let w2 = tables.twiddle(j * stride, inverse);
+            let w3 = tables.twiddle((j + m) * stride, inverse);
+            let (b0, b2) = (*a0 + *a2, (*a0 - *a2) * w2);
+            let (b1, b3) = (*a1 + *a3, (*a1 - *a3) * w3);
+            (*a0, *a1) = (b0 + b1, (b0 - b1) * w1);
+            (*a2, *a3) = (b2 + b3, (b2 - b3) * w1);
+        });
+    }
+    if log_m == 1 {
+        radix2_layer(a);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Radix2Domain<T: MyField> {
+    tables: Arc<FftTables<T>>,
+}
+
+impl<T: MyField> Radix2Domain<T> {
+    #[inline]
+    pub fn new(order: usize) -> Self {
+        Radix2Domain {
+            tables: FftTables::get(order),
+        }
+    }
+
+    #[inline]
+    pub fn order(&self) -> usize {
+        self.tables.order
+    }
+
+    #[inline]
+    pub fn omega(&self) -> T {
+        self.tables.omega
+    }
+
+    #[inline]
+    pub fn fft(&self, a: &mut [T]) {
+        bit_reverse_permute(a);
+        dit(a, &self.tables, false);
+    }
+
+    #[inline]
+    pub fn fft_bit_reversed(&self, a: &mut [T]) {
+        dif(a, &self.tables, false);
+    }
+
+    #[inline]
+    pub fn ifft(&self, a: &mut [T]) {
+        bit_reverse_permute(a);
+        self.ifft_bit_reversed(a);
+    }
+
+    #[inline]
+    pub fn ifft_bit_reversed(&self, a: &mut [T]) {
+        dit(a, &self.tables, true);
+        let t = self.tables.order_inv;
+        crate::cfg_iter_mut!(a).for_each(|x| *x *= t);
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Coset<T: MyField> {
-    elements: Arc<Vec<T>>,
-    elements_inv: Arc<Vec<T>>,
     fft_eval_domain: Radix2Domain<T>,
     shift: T,
+    shift_inv: T,
+    unit_shift: bool,
 }
 
 impl<T: MyField> Coset<T> {
@@ -104,43 +282,33 @@ impl<T: MyField> Coset<T> {
             }
             d << 1
         };
-        let domain = Radix2Domain::new(degree, T::get_generator(degree));
+        let domain = Radix2Domain::new(degree);
         let mut coeff1 = poly1.coefficients().clone();
-        let len = coeff1.len();
-        coeff1.append(&mut (len..degree).into_iter().map(|_| T::from_int(0)).collect());
+        coeff1.resize(degree, T::from_int(0));
         let mut coeff2 = poly2.coefficients().clone();
-        let len = coeff2.len();
-        coeff2.append(&mut (len..degree).into_iter().map(|_| T::from_int(0)).collect());
-        domain.fft(&mut coeff1);
-        domain.fft(&mut coeff2);
-        for i in 0..degree {
-            coeff1[i] *= coeff2[i];
-        }
-        domain.ifft(&mut coeff1);
-        let poly = Polynomial::new(coeff1);
-        poly
+        coeff2.resize(degree, T::from_int(0));
+        // Pointwise products do not care about the order, so skip both permutations.
+        domain.fft_bit_reversed(&mut coeff1);
+        domain.fft_bit_reversed(&mut coeff2);
+        crate::cfg_iter_mut!(coeff1)
+            .zip(&coeff2)
+            .for_each(|(x, &y)| *x *= y);
+        domain.ifft_bit_reversed(&mut coeff1);
+        Polynomial::new(coeff1)
     }
 
     pub fn new(order: usize, shift: T) -> Self {
         assert!(!shift.is_zero());
-        let omega = T::get_generator(order);
-        let elements = std::iter::successors(Some(shift), |&last| Some(last * omega))
-            .take(order)
-            .collect();
-        let omega_inv = omega.pow(order - 1);
-        let elements_inv = std::iter::successors(Some(shift), |&last| Some(last * omega_inv))
-            .take(order)
-            .collect();
         Coset {
-            elements: Arc::new(elements),
-            elements_inv: Arc::new(elements_inv),
-            fft_eval_domain: Radix2Domain::new(order, omega),
+            fft_eval_domain: Radix2Domain::new(order),
             shift,
+            shift_inv: shift.inverse(),
+            unit_shift: shift == T::from_int(1),
         }
     }
 
     pub fn order(&self) -> usize {
-        self.fft_eval_domain.order
+        self.fft_eval_domain.order()
     }
 
     pub fn pow(&self, index: usize) -> Coset<T> {
@@ -150,35 +318,72 @@ impl<T: MyField> Coset<T> {
     }
 
     pub fn generator(&self) -> T {
-        self.fft_eval_domain.omega
+        self.fft_eval_domain.omega()
     }
 
     pub fn element_at(&self, index: usize) -> T {
-        self.elements[index]
+        let w = self.fft_eval_domain.tables.power(index);
+        if self.unit_shift {
+            w
+        } else {
+            self.shift * w
+        }
     }
 
     pub fn element_inv_at(&self, index: usize) -> T {
-        self.elements_inv[index]
+        let w = self.fft_eval_domain.tables.twiddle(index, true);
+        if self.unit_shift {
+            w
+        } else {
+            self.shift_inv * w
+        }
     }
 
     pub fn all_elements_inv(&self) -> Vec<T> {
-        (*self.elements_inv).clone()
+        (0..self.size()).map(|i| self.element_inv_at(i)).collect()
     }
 
     pub fn all_elements(&self) -> Vec<T> {
-        (*self.elements).clone()
+        (0..self.size()).map(|i| self.element_at(i)).collect()
     }
 
     pub fn size(&self) -> usize {
         self.fft_eval_domain.order()
     }
 
+    fn pad_and_shift(&self, coeff: &mut Vec<T>) {
+        assert!(coeff.len() <= self.size());
+        coeff.resize(self.size(), T::from_int(0));
+        if !self.unit_shift {
+            scale_by_powers(coeff, self.shift);
+        }
+    }
+
     pub fn fft(&self, mut coeff: Vec<T>) -> Vec<T> {
-        let n = self.size() - coeff.len();
-        for _i in 0..n {
-            coeff.push(T::from_int(0));
+        self.pad_and_shift(&mut coeff);
+        self.fft_eval_domain.fft(&mut coeff);
+        coeff
+    }
+
+    // `fft` into a buffer placed by `storage`, for codewords too large to hold in memory.
+    pub fn fft_into(&self, coeff: &[T], storage: &Storage) -> Buffer<T> {
+        assert!(coeff.len() <= self.size());
+        let zero = T::from_int(0);
+        let mut res = Buffer::from_fn(storage, self.size(), |i| {
+            coeff.get(i).copied().unwrap_or(zero)
+        });
+        if !self.unit_shift {
+            scale_by_powers(&mut res, self.shift);
         }
-        self.fft_eval_domain.coset_fft(&mut coeff, self.shift);
+        self.fft_eval_domain.fft(&mut res);
+        res
+    }
+
+    // Evaluations in bit-reversed order: entry i is the value at
+    // `element_at(reverse_bits(i))`.
+    pub fn fft_bit_reversed(&self, mut coeff: Vec<T>) -> Vec<T> {
+        self.pad_and_shift(&mut coeff);
+        self.fft_eval_domain.fft_bit_reversed(&mut coeff);
         coeff
     }
 
@@ -187,10 +392,25 @@ impl<T: MyField> Coset<T> {
             return vec![evals[0]];
         };
         assert_eq!(self.size(), evals.len());
-        self.fft_eval_domain.coset_ifft(&mut evals, self.shift);
+        self.fft_eval_domain.ifft(&mut evals);
+        self.unshift(&mut evals);
         evals
     }
 
+    // Inverse of `fft_bit_reversed`.
+    pub fn ifft_bit_reversed(&self, mut evals: Vec<T>) -> Vec<T> {
+        assert_eq!(self.size(), evals.len());
+        self.fft_eval_domain.ifft_bit_reversed(&mut evals);
+        self.unshift(&mut evals);
+        evals
+    }
+
+    fn unshift(&self, coeff: &mut [T]) {
+        if !self.unit_shift {
+            scale_by_powers(coeff, self.shift_inv);
+        }
+    }
+
     pub fn shift(&self) -> T {
         self.shift
     }
@@ -198,7 +418,9 @@ impl<T: MyField> Coset<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::algebra::field::{ft255::Ft255, mersenne61_ext::Mersenne61Ext};
+    use crate::algebra::field::{
+        ft255::Ft255, goldilocks64::Goldilocks64, mersenne61_ext::Mersenne61Ext,
+    };
 
     use super::*;
 
@@ -245,6 +467,10 @@ mod tests {
             assert_eq!(elements[i] * omega, elements[i + 1]);
         }
         assert_eq!(*elements.last().unwrap() * omega, elements[0]);
+        let elements_inv = coset.all_elements_inv();
+        for (x, x_inv) in elements.iter().zip(elements_inv.iter()) {
+            assert_eq!(*x * *x_inv, Ft255::from_int(1));
+        }
     }
 
     #[test]
@@ -257,4 +483,71 @@ mod tests {
             assert_eq!(*i, coset.all_elements()[idx + coset_square.size()].pow(2));
         }
     }
+
+    #[test]
+    fn matches_naive_evaluation() {
+        // odd and even log sizes exercise the leftover radix-2 layer
+        for log_n in 0..9 {
+            let n = 1 << log_n;
+            let coset = Coset::new(n, Goldilocks64::random_element());
+            let poly = Polynomial::random_polynomial(n);
+            let evals = coset.fft(poly.coefficients().clone());
+            for (i, x) in coset.all_elements().into_iter().enumerate() {
+                assert_eq!(evals[i], poly.evaluation_at(x));
+            }
+            assert_eq!(coset.ifft(evals), *poly.coefficients());
+        }
+    }
+
+    #[test]
+    fn bit_reversed_order() {
+        for log_n in 1..9 {
+            let n: usize = 1 << log_n;
+            for shift in [Mersenne61Ext::from_int(1), Mersenne61Ext::random_element()] {
+                let coset = Coset::new(n, shift);
+                let coeff = (0..n)
+                    .map(|_| Mersenne61Ext::random_element())
+                    .collect::<Vec<_>>();
+                let natural = coset.fft(coeff.clone());
+                let reversed = coset.fft_bit_reversed(coeff.clone());
+                for (i, x) in reversed.iter().enumerate() {
+                    assert_eq!(*x, natural[i.reverse_bits() >> (usize::BITS - log_n)]);
+                }
+                assert_eq!(coset.ifft_bit_reversed(reversed), coeff);
+            }
+        }
+    }
+
+    #[test]
+    fn shared_tables() {
+        let coset = Coset::new(64, Goldilocks64::random_element());
+        let other = Coset::new(32, Goldilocks64::from_int(1));
+        assert!(Arc::ptr_eq(
+            &coset.pow(2).fft_eval_domain.tables,
+            &other.fft_eval_domain.tables
+        ));
+        let ext = Coset::new(32, Mersenne61Ext::from_int(1));
+        assert_eq!(ext.size(), other.size());
+    }
+
+    #[test]
+    fn split_tables() {
+        let order = 1 << 7;
+        let split = FftTables::<Goldilocks64>::build(order, 3);
+        let direct = FftTables::<Goldilocks64>::get(order);
+        for e in 0..order {
+            assert_eq!(split.twiddle(e, false), direct.omega.pow(e));
+            assert_eq!(split.twiddle(e, true), direct.twiddle(e, true));
+        }
+        let coeff = (0..order)
+            .map(|_| Goldilocks64::random_element())
+            .collect::<Vec<_>>();
+        let (mut a, mut b) = (coeff.clone(), coeff);
+        Radix2Domain {
+            tables: Arc::new(split),
+        }
+        .fft(&mut a);
+        Radix2Domain { tables: direct }.fft(&mut b);
+        assert_eq!(a, b);
+    }
 }
@@ -1,4 +1,6 @@
 use crate::merkle_tree::MERKLE_ROOT_SIZE;
+use crate::poseidon2::Poseidon2Field;
+use crate::storage::Plain;
 use rand::RngCore;
 use std::{
     fmt::Debug,
@@ -7,6 +9,8 @@ use std::{
 
 pub mod bn254;
 pub mod ft255;
+pub mod goldilocks64;
+pub mod goldilocks64ext;
 pub mod m31_ext;
 pub mod mersenne61_ext;
 
@@ -27,14 +31,20 @@ pub trait MyField:
     + std::marker::Send
     + std::marker::Sync
     + 'static
+    + Poseidon2Field
+    + Plain
 {
     const FIELD_NAME: &'static str;
     const LOG_ORDER: u64;
+    // floor(log2 |F|), the field size soundness estimates work with.
+    const FIELD_BITS: usize;
     fn from_int(x: u64) -> Self;
     fn random_element() -> Self;
     fn inverse(&self) -> Self;
     fn is_zero(&self) -> bool;
     fn to_bytes(&self) -> Vec<u8>;
+    // Inverse of `to_bytes`; rejects wrong lengths and non-canonical encodings.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
     fn from_hash(hash: [u8; MERKLE_ROOT_SIZE]) -> Self;
     fn root_of_unity() -> Self;
     fn inverse_2() -> Self;
@@ -119,6 +129,21 @@ pub trait FftField: AnotherField + From<Self::FftBaseField> {
     type FftBaseField: FftField<BaseField = Self::BaseField>;
 }
 
+// A field containing `B`, so that a codeword committed over `B` can be folded
+// with challenges drawn from `Self`. Every field extends itself; the only
+// proper subfield implemented is Goldilocks64, as the other base fields have
+// no two-adic subgroup to encode over.
+pub trait ExtensionOf<B: MyField>: MyField + From<B> {
+    fn mul_base(&self, rhs: B) -> Self;
+}
+
+impl<T: MyField> ExtensionOf<T> for T {
+    #[inline(always)]
+    fn mul_base(&self, rhs: T) -> T {
+        *self * rhs
+    }
+}
+
 #[inline]
 pub fn as_bytes_vec<T: MyField>(s: &[T]) -> Vec<u8> {
     let mut res = vec![];
@@ -183,6 +208,16 @@ mod field_tests {
         }
     }
 
+    pub fn bytes_round_trip<T: MyField>() {
+        for _i in 0..10 {
+            let a = T::random_element();
+            let bytes = a.to_bytes();
+            assert_eq!(bytes.len(), std::mem::size_of::<T>());
+            assert_eq!(T::from_bytes(&bytes), Some(a));
+            assert_eq!(T::from_bytes(&bytes[1..]), None);
+        }
+    }
+
     pub fn pow_and_generator<T: MyField>() {
         assert_eq!(T::get_generator(1), T::from_int(1));
         let x = T::get_generator(1 << 28);
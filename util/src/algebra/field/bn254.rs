@@ -3,6 +3,9 @@ use halo2curves::{
     ff::{Field, PrimeField},
 };
 
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
 use super::MyField;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -65,6 +68,7 @@ const INVERSE_2: Bn254Fr = Bn254Fr(Fr::TWO_INV);
 impl MyField for Bn254Fr {
     const FIELD_NAME: &'static str = "Ft255";
     const LOG_ORDER: u64 = 41;
+    const FIELD_BITS: usize = 253;
 
     fn inverse_2() -> Self {
         INVERSE_2
@@ -81,7 +85,7 @@ impl MyField for Bn254Fr {
 
     #[inline]
     fn from_hash(hash: [u8; crate::merkle_tree::MERKLE_ROOT_SIZE]) -> Self {
-        Self(Fr::from_bytes(hash[..].try_into().unwrap()).unwrap())
+        Self(Fr::random(ChaCha20Rng::from_seed(hash)))
     }
 
     #[inline]
@@ -103,4 +107,10 @@ impl MyField for Bn254Fr {
     fn to_bytes(&self) -> Vec<u8> {
         self.0.to_bytes().into()
     }
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; 32] = bytes.try_into().ok()?;
+        Option::from(Fr::from_bytes(&bytes)).map(Self)
+    }
 }
@@ -1,5 +1,7 @@
 use ff::{Field as Fd, PrimeField};
 use ff_derive_num::Num;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 
 use super::MyField;
@@ -26,6 +28,7 @@ const INVERSE_2: Ft255 = Ft255([
 impl MyField for Ft255 {
     const FIELD_NAME: &'static str = "Ft255";
     const LOG_ORDER: u64 = 41;
+    const FIELD_BITS: usize = 254;
     #[inline(always)]
     fn root_of_unity() -> Self {
         ROOT_OF_UNITY
@@ -40,20 +43,7 @@ impl MyField for Ft255 {
     }
     #[inline(always)]
     fn from_hash(hash: [u8; crate::merkle_tree::MERKLE_ROOT_SIZE]) -> Self {
-        Ft255([
-            (0..8)
-                .into_iter()
-                .fold(0, |acc, x| (acc << 8) + hash[x] as u64),
-            (8..16)
-                .into_iter()
-                .fold(0, |acc, x| (acc << 8) + hash[x] as u64),
-            (16..24)
-                .into_iter()
-                .fold(0, |acc, x| (acc << 8) + hash[x] as u64),
-            (24..32)
-                .into_iter()
-                .fold(0, |acc, x| (acc << 8) + hash[x] as u64),
-        ])
+        Ft255::random(ChaCha20Rng::from_seed(hash))
     }
     #[inline(always)]
     fn is_zero(&self) -> bool {
@@ -74,12 +64,16 @@ impl MyField for Ft255 {
     }
     #[inline(always)]
     fn to_bytes(&self) -> Vec<u8> {
-        let x = self
-            .0
-            .iter()
-            .flat_map(|x| x.to_le_bytes().to_vec())
-            .collect();
-        x
+        self.to_repr().as_ref().to_vec()
+    }
+    #[inline(always)]
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut repr = <Ft255 as PrimeField>::Repr::default();
+        if bytes.len() != repr.as_ref().len() {
+            return None;
+        }
+        repr.as_mut().copy_from_slice(bytes);
+        Option::from(Ft255::from_repr(repr))
     }
 }
 
@@ -93,6 +87,7 @@ mod tests {
         add_and_sub::<Ft255>();
         mult_and_inverse::<Ft255>();
         assigns::<Ft255>();
+        bytes_round_trip::<Ft255>();
         pow_and_generator::<Ft255>();
     }
 }
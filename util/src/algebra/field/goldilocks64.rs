@@ -0,0 +1,291 @@
+use super::{AnotherField, FftField, MyField};
+use rand::{Rng, RngCore};
+
+// p = 2^64 - 2^32 + 1; values are always kept canonical.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Goldilocks64 {
+    v: u64,
+}
+
+pub(super) const MOD: u64 = 0xffff_ffff_0000_0001;
+// 2^64 - p, so 2^64 = EPSILON (mod p)
+const EPSILON: u64 = 0xffff_ffff;
+
+#[inline]
+pub(super) fn reduce128(x: u128) -> u64 {
+    let lo = x as u64;
+    let hi = (x >> 64) as u64;
+    let hi_hi = hi >> 32;
+    let hi_lo = hi & EPSILON;
+    // 2^96 = -1 (mod p)
+    let (mut t0, borrow) = lo.overflowing_sub(hi_hi);
+    if borrow {
+        t0 = t0.wrapping_sub(EPSILON);
+    }
+    let (mut res, carry) = t0.overflowing_add(hi_lo * EPSILON);
+    if carry {
+        res = res.wrapping_add(EPSILON);
+    }
+    if res >= MOD {
+        res - MOD
+    } else {
+        res
+    }
+}
+
+impl Goldilocks64 {
+    pub const fn new(v: u64) -> Self {
+        Goldilocks64 { v: v % MOD }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.v
+    }
+}
+
+impl std::ops::Neg for Goldilocks64 {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Self {
+            v: if self.v == 0 { 0 } else { MOD - self.v },
+        }
+    }
+}
+
+impl std::ops::Add for Goldilocks64 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        let (mut v, carry) = self.v.overflowing_add(rhs.v);
+        if carry {
+            v = v.wrapping_add(EPSILON);
+        } else if v >= MOD {
+            v -= MOD;
+        }
+        Self { v }
+    }
+}
+
+impl std::ops::AddAssign for Goldilocks64 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::Sub for Goldilocks64 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        let (mut v, borrow) = self.v.overflowing_sub(rhs.v);
+        if borrow {
+            v = v.wrapping_sub(EPSILON);
+        }
+        Self { v }
+    }
+}
+
+impl std::ops::SubAssign for Goldilocks64 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::ops::Mul for Goldilocks64 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            v: reduce128(self.v as u128 * rhs.v as u128),
+        }
+    }
+}
+
+impl std::ops::MulAssign for Goldilocks64 {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl std::fmt::Display for Goldilocks64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.v)
+    }
+}
+
+impl std::cmp::PartialEq for Goldilocks64 {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.v == rhs.v
+    }
+}
+
+impl From<u32> for Goldilocks64 {
+    fn from(x: u32) -> Self {
+        Goldilocks64 { v: x as u64 }
+    }
+}
+
+impl Goldilocks64 {
+    // 7^((p - 1) / 2^32)
+    const ROOT_OF_UNITY: Goldilocks64 = Goldilocks64 {
+        v: 1753635133440165772,
+    };
+    const INVERSE_2: Goldilocks64 = Goldilocks64 {
+        v: 9223372034707292161,
+    };
+}
+
+impl MyField for Goldilocks64 {
+    const FIELD_NAME: &'static str = "Goldilocks64";
+    const LOG_ORDER: u64 = 32;
+    const FIELD_BITS: usize = 63;
+    #[inline(always)]
+    fn root_of_unity() -> Self {
+        Goldilocks64::ROOT_OF_UNITY
+    }
+    #[inline(always)]
+    fn inverse_2() -> Self {
+        Goldilocks64::INVERSE_2
+    }
+
+    #[inline]
+    fn from_int(x: u64) -> Self {
+        Goldilocks64::new(x)
+    }
+
+    #[inline]
+    fn from_hash(hash: [u8; crate::merkle_tree::MERKLE_ROOT_SIZE]) -> Self {
+        Goldilocks64 {
+            v: reduce128(u128::from_le_bytes(hash[..16].try_into().unwrap())),
+        }
+    }
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        self.v == 0
+    }
+
+    #[inline]
+    fn random_element() -> Self {
+        let mut rng = rand::thread_rng();
+        Goldilocks64 {
+            v: rng.gen_range(0..MOD),
+        }
+    }
+
+    #[inline(always)]
+    fn inverse(&self) -> Self {
+        MyField::pow(self, (MOD - 2) as usize)
+    }
+
+    #[inline]
+    fn to_bytes(&self) -> Vec<u8> {
+        self.v.to_le_bytes().to_vec()
+    }
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let v = u64::from_le_bytes(bytes.try_into().ok()?);
+        if v >= MOD {
+            return None;
+        }
+        Some(Goldilocks64 { v })
+    }
+}
+
+impl AnotherField for Goldilocks64 {
+    const NAME: &'static str = "Goldilocks64";
+    const SIZE: usize = 8;
+    const INV_2: Self = Goldilocks64::INVERSE_2;
+    const ZERO: Self = Goldilocks64 { v: 0 };
+    const UNIT: Self = Goldilocks64 { v: 1 };
+    type BaseField = Goldilocks64;
+
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn one() -> Self {
+        Self::UNIT
+    }
+
+    fn random(mut rng: impl RngCore) -> Self {
+        Goldilocks64 {
+            v: rng.gen_range(0..MOD),
+        }
+    }
+
+    fn exp(&self, exponent: usize) -> Self {
+        MyField::pow(self, exponent)
+    }
+
+    fn inv(&self) -> Option<Self> {
+        if self.is_zero() {
+            None
+        } else {
+            Some(self.inverse())
+        }
+    }
+
+    fn add_base_elem(&self, rhs: Self) -> Self {
+        *self + rhs
+    }
+
+    fn add_assign_base_elem(&mut self, rhs: Self) {
+        *self += rhs;
+    }
+
+    fn mul_base_elem(&self, rhs: Self) -> Self {
+        *self * rhs
+    }
+
+    fn mul_assign_base_elem(&mut self, rhs: Self) {
+        *self *= rhs;
+    }
+
+    fn from_uniform_bytes(bytes: &[u8; 32]) -> Self {
+        Goldilocks64 {
+            v: reduce128(u128::from_le_bytes(bytes[..16].try_into().unwrap())),
+        }
+    }
+
+    fn serialize_into(&self, buffer: &mut [u8]) {
+        buffer[..Self::SIZE].copy_from_slice(&self.v.to_le_bytes());
+    }
+
+    fn deserialize_from(buffer: &[u8]) -> Self {
+        Self::from_bytes(&buffer[..Self::SIZE]).expect("non-canonical field element")
+    }
+}
+
+impl FftField for Goldilocks64 {
+    const LOG_ORDER: u32 = 32;
+    const ROOT_OF_UNITY: Self = Goldilocks64::ROOT_OF_UNITY;
+    type FftBaseField = Goldilocks64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::field_tests::*;
+    use super::*;
+
+    #[test]
+    fn test() {
+        add_and_sub::<Goldilocks64>();
+        mult_and_inverse::<Goldilocks64>();
+        assigns::<Goldilocks64>();
+        bytes_round_trip::<Goldilocks64>();
+        pow_and_generator::<Goldilocks64>();
+    }
+
+    #[test]
+    fn reduction() {
+        let a = Goldilocks64::new(MOD - 1);
+        assert_eq!(a * a, Goldilocks64::from_int(1));
+        assert_eq!(a + a, Goldilocks64::new(MOD - 2));
+        assert_eq!(Goldilocks64::from_int(0) - a, Goldilocks64::from_int(1));
+        let x = Goldilocks64::from_int(1 << 32);
+        assert_eq!(x * x * x, -Goldilocks64::from_int(1));
+    }
+
+    #[test]
+    fn non_canonical_bytes() {
+        assert_eq!(Goldilocks64::from_bytes(&MOD.to_le_bytes()), None);
+    }
+}
@@ -0,0 +1,288 @@
+use super::goldilocks64::{reduce128, Goldilocks64, MOD};
+use super::{AnotherField, ExtensionOf, FftField, MyField};
+use rand::{Rng, RngCore};
+
+// Goldilocks64[X] / (X^2 - 7)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Goldilocks64Ext {
+    real: Goldilocks64,
+    image: Goldilocks64,
+}
+
+const NON_RESIDUE: Goldilocks64 = Goldilocks64::new(7);
+
+impl Goldilocks64Ext {
+    pub const fn new(real: Goldilocks64, image: Goldilocks64) -> Self {
+        Goldilocks64Ext { real, image }
+    }
+}
+
+impl std::ops::Neg for Goldilocks64Ext {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Self {
+            real: -self.real,
+            image: -self.image,
+        }
+    }
+}
+
+impl std::ops::Add for Goldilocks64Ext {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real + rhs.real,
+            image: self.image + rhs.image,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Goldilocks64Ext {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::Sub for Goldilocks64Ext {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real - rhs.real,
+            image: self.image - rhs.image,
+        }
+    }
+}
+
+impl std::ops::SubAssign for Goldilocks64Ext {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::ops::Mul for Goldilocks64Ext {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real * rhs.real + NON_RESIDUE * self.image * rhs.image,
+            image: self.real * rhs.image + self.image * rhs.real,
+        }
+    }
+}
+
+impl std::ops::MulAssign for Goldilocks64Ext {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl std::fmt::Display for Goldilocks64Ext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "real: {}, image: {}", self.real, self.image)
+    }
+}
+
+impl std::cmp::PartialEq for Goldilocks64Ext {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.real == rhs.real && self.image == rhs.image
+    }
+}
+
+impl From<u32> for Goldilocks64Ext {
+    fn from(x: u32) -> Self {
+        Goldilocks64Ext::from(Goldilocks64::from(x))
+    }
+}
+
+impl From<Goldilocks64> for Goldilocks64Ext {
+    fn from(x: Goldilocks64) -> Self {
+        Goldilocks64Ext {
+            real: x,
+            image: Goldilocks64::ZERO,
+        }
+    }
+}
+
+impl MyField for Goldilocks64Ext {
+    const FIELD_NAME: &'static str = "Goldilocks64Ext";
+    const LOG_ORDER: u64 = 32;
+    const FIELD_BITS: usize = 127;
+    #[inline(always)]
+    fn root_of_unity() -> Self {
+        Goldilocks64Ext::from(Goldilocks64::root_of_unity())
+    }
+    #[inline(always)]
+    fn inverse_2() -> Self {
+        Goldilocks64Ext::from(Goldilocks64::inverse_2())
+    }
+
+    #[inline]
+    fn from_int(x: u64) -> Self {
+        Goldilocks64Ext::from(Goldilocks64::from_int(x))
+    }
+
+    #[inline]
+    fn from_hash(hash: [u8; crate::merkle_tree::MERKLE_ROOT_SIZE]) -> Self {
+        Goldilocks64Ext::from_uniform_bytes(&hash)
+    }
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        self.real.is_zero() && self.image.is_zero()
+    }
+
+    #[inline]
+    fn random_element() -> Self {
+        Goldilocks64Ext {
+            real: Goldilocks64::random_element(),
+            image: Goldilocks64::random_element(),
+        }
+    }
+
+    // (a + bX)^-1 = (a - bX) / (a^2 - 7b^2)
+    #[inline(always)]
+    fn inverse(&self) -> Self {
+        let norm = self.real * self.real - NON_RESIDUE * self.image * self.image;
+        let inv = norm.inverse();
+        Goldilocks64Ext {
+            real: self.real * inv,
+            image: -self.image * inv,
+        }
+    }
+
+    #[inline]
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut x = self.real.to_bytes();
+        x.extend_from_slice(&self.image.to_bytes());
+        x
+    }
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 16 {
+            return None;
+        }
+        Some(Goldilocks64Ext {
+            real: Goldilocks64::from_bytes(&bytes[..8])?,
+            image: Goldilocks64::from_bytes(&bytes[8..])?,
+        })
+    }
+}
+
+impl AnotherField for Goldilocks64Ext {
+    const NAME: &'static str = "Goldilocks64Ext";
+    const SIZE: usize = 16;
+    const INV_2: Self = Goldilocks64Ext::new(Goldilocks64::INV_2, Goldilocks64::ZERO);
+    const ZERO: Self = Goldilocks64Ext::new(Goldilocks64::ZERO, Goldilocks64::ZERO);
+    const UNIT: Self = Goldilocks64Ext::new(Goldilocks64::UNIT, Goldilocks64::ZERO);
+    type BaseField = Goldilocks64;
+
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn one() -> Self {
+        Self::UNIT
+    }
+
+    fn random(mut rng: impl RngCore) -> Self {
+        Goldilocks64Ext {
+            real: Goldilocks64::new(rng.gen_range(0..MOD)),
+            image: Goldilocks64::new(rng.gen_range(0..MOD)),
+        }
+    }
+
+    fn exp(&self, exponent: usize) -> Self {
+        MyField::pow(self, exponent)
+    }
+
+    fn inv(&self) -> Option<Self> {
+        if self.is_zero() {
+            None
+        } else {
+            Some(self.inverse())
+        }
+    }
+
+    fn add_base_elem(&self, rhs: Goldilocks64) -> Self {
+        Goldilocks64Ext {
+            real: self.real + rhs,
+            image: self.image,
+        }
+    }
+
+    fn add_assign_base_elem(&mut self, rhs: Goldilocks64) {
+        self.real += rhs;
+    }
+
+    fn mul_base_elem(&self, rhs: Goldilocks64) -> Self {
+        Goldilocks64Ext {
+            real: self.real * rhs,
+            image: self.image * rhs,
+        }
+    }
+
+    fn mul_assign_base_elem(&mut self, rhs: Goldilocks64) {
+        *self = self.mul_base_elem(rhs);
+    }
+
+    fn from_uniform_bytes(bytes: &[u8; 32]) -> Self {
+        Goldilocks64Ext {
+            real: Goldilocks64::new(reduce128(u128::from_le_bytes(
+                bytes[..16].try_into().unwrap(),
+            ))),
+            image: Goldilocks64::new(reduce128(u128::from_le_bytes(
+                bytes[16..].try_into().unwrap(),
+            ))),
+        }
+    }
+
+    fn serialize_into(&self, buffer: &mut [u8]) {
+        self.real.serialize_into(&mut buffer[..8]);
+        self.image.serialize_into(&mut buffer[8..Self::SIZE]);
+    }
+
+    fn deserialize_from(buffer: &[u8]) -> Self {
+        Self::from_bytes(&buffer[..Self::SIZE]).expect("non-canonical field element")
+    }
+}
+
+impl FftField for Goldilocks64Ext {
+    const LOG_ORDER: u32 = 32;
+    const ROOT_OF_UNITY: Self = Goldilocks64Ext::new(
+        <Goldilocks64 as FftField>::ROOT_OF_UNITY,
+        Goldilocks64::ZERO,
+    );
+    type FftBaseField = Goldilocks64;
+}
+
+impl ExtensionOf<Goldilocks64> for Goldilocks64Ext {
+    #[inline(always)]
+    fn mul_base(&self, rhs: Goldilocks64) -> Self {
+        self.mul_base_elem(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::field_tests::*;
+    use super::*;
+
+    #[test]
+    fn test() {
+        add_and_sub::<Goldilocks64Ext>();
+        mult_and_inverse::<Goldilocks64Ext>();
+        assigns::<Goldilocks64Ext>();
+        bytes_round_trip::<Goldilocks64Ext>();
+        pow_and_generator::<Goldilocks64Ext>();
+    }
+
+    #[test]
+    fn base_elements() {
+        let a = Goldilocks64Ext::random_element();
+        let b = Goldilocks64::random_element();
+        assert_eq!(a.mul_base_elem(b), a * Goldilocks64Ext::from(b));
+        assert_eq!(a.add_base_elem(b), a + Goldilocks64Ext::from(b));
+        let x = Goldilocks64Ext::new(Goldilocks64::ZERO, Goldilocks64::UNIT);
+        assert_eq!(x * x, Goldilocks64Ext::from_int(7));
+    }
+}
@@ -2,11 +2,13 @@ use once_cell::sync::OnceCell;
 use p3_field::{
     extension::Complex, AbstractExtensionField, AbstractField, Field, PrimeField32, TwoAdicField,
 };
-use rand::Rng;
+use p3_mersenne_31::Mersenne31;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 
 use super::MyField;
 
-type F = p3_field::extension::BinomialExtensionField<Complex<p3_mersenne_31::Mersenne31>, 2>;
+type F = p3_field::extension::BinomialExtensionField<Complex<Mersenne31>, 2>;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct M31ext(F);
@@ -68,6 +70,7 @@ static INVERSE_2: OnceCell<M31ext> = OnceCell::new();
 impl MyField for M31ext {
     const FIELD_NAME: &'static str = "M31ext";
     const LOG_ORDER: u64 = F::TWO_ADICITY as u64 - 1;
+    const FIELD_BITS: usize = 123;
     #[inline(always)]
     fn inverse_2() -> Self {
         INVERSE_2
@@ -88,8 +91,8 @@ impl MyField for M31ext {
 
     #[inline]
     fn from_hash(hash: [u8; crate::merkle_tree::MERKLE_ROOT_SIZE]) -> Self {
-        todo!()
-        // Self(F::deserialize(&hash.into()))
+        let mut rng = ChaCha20Rng::from_seed(hash);
+        Self(rng.gen::<F>())
     }
 
     #[inline]
@@ -109,7 +112,7 @@ impl MyField for M31ext {
 
     #[inline]
     fn to_bytes(&self) -> Vec<u8> {
-        let s: &[Complex<p3_mersenne_31::Mersenne31>] = self.0.as_base_slice();
+        let s: &[Complex<Mersenne31>] = self.0.as_base_slice();
         s.iter()
             .flat_map(|x| {
                 x.real()
@@ -121,6 +124,26 @@ impl MyField for M31ext {
             })
             .collect()
     }
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 16 {
+            return None;
+        }
+        let mut limbs = vec![];
+        for chunk in bytes.chunks(4) {
+            let x = u32::from_be_bytes(chunk.try_into().unwrap());
+            if x >= Mersenne31::ORDER_U32 {
+                return None;
+            }
+            limbs.push(Mersenne31::from_canonical_u32(x));
+        }
+        let base = limbs
+            .chunks(2)
+            .map(|x| Complex::<Mersenne31>::from_base_slice(x))
+            .collect::<Vec<_>>();
+        Some(Self(F::from_base_slice(&base)))
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -133,6 +156,7 @@ mod tests {
         add_and_sub::<M31ext>();
         mult_and_inverse::<M31ext>();
         assigns::<M31ext>();
+        bytes_round_trip::<M31ext>();
         pow_and_generator::<M31ext>();
     }
 }
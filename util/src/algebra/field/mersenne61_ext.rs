@@ -130,6 +130,7 @@ impl Mersenne61Ext {
 impl MyField for Mersenne61Ext {
     const FIELD_NAME: &'static str = "Mersenne61Ext";
     const LOG_ORDER: u64 = 62;
+    const FIELD_BITS: usize = 121;
     #[inline(always)]
     fn root_of_unity() -> Self {
         Mersenne61Ext::ROOT_OF_UNITY
@@ -187,9 +188,23 @@ impl MyField for Mersenne61Ext {
 
     #[inline]
     fn to_bytes(&self) -> Vec<u8> {
-        let x = self.real.to_le_bytes().to_vec();
+        let mut x = self.real.to_le_bytes().to_vec();
+        x.extend_from_slice(&self.image.to_le_bytes());
         x
     }
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 16 {
+            return None;
+        }
+        let real = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let image = u64::from_le_bytes(bytes[8..].try_into().unwrap());
+        if real >= MOD || image >= MOD {
+            return None;
+        }
+        Some(Mersenne61Ext { real, image })
+    }
 }
 
 #[cfg(test)]
@@ -202,6 +217,14 @@ mod tests {
         add_and_sub::<Mersenne61Ext>();
         mult_and_inverse::<Mersenne61Ext>();
         assigns::<Mersenne61Ext>();
+        bytes_round_trip::<Mersenne61Ext>();
         pow_and_generator::<Mersenne61Ext>();
     }
+
+    #[test]
+    fn non_canonical_bytes() {
+        let mut bytes = MOD.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(Mersenne61Ext::from_bytes(&bytes), None);
+    }
 }
@@ -11,7 +11,7 @@ pub struct Radix2Group<F: FftField> {
 
 impl<F: FftField> Radix2Group<F> {
     pub fn new(log_order: u32) -> Self {
-        let omega = F::ROOT_OF_UNITY.exp(1usize << ((F::LOG_ORDER as u32) - log_order));
+        let omega = F::ROOT_OF_UNITY.exp(1usize << (<F as FftField>::LOG_ORDER - log_order));
         let elements = std::iter::successors(Some(F::one()), |&last| Some(last * omega))
             .take(1 << log_order)
             .collect();
@@ -45,14 +45,14 @@ impl<F: FftField> Radix2Group<F> {
 
     fn batch_bit_reverse(log_n: usize) -> Vec<usize> {
         let n = 1 << log_n;
-        let mut res = (0..n).into_iter().map(|_| 0).collect::<Vec<usize>>();
+        let mut res = vec![0; n];
         for i in 0..n {
             res[i] = (res[i >> 1] >> 1) | ((i & 1) << (log_n - 1));
         }
         res
     }
 
-    fn _fft(coeff: &mut Vec<F>, omega: F) {
+    fn _fft(coeff: &mut [F], omega: F) {
         let n = coeff.len();
         let log_n = n.ilog2() as usize;
         let rank = Self::batch_bit_reverse(log_n);
@@ -61,12 +61,11 @@ impl<F: FftField> Radix2Group<F> {
                 (coeff[i], coeff[rank[i]]) = (coeff[rank[i]], coeff[i]);
             }
         }
-        let mut log_m = 0usize;
-        for _i in 0..log_n {
+        for log_m in 0..log_n {
             let w_m = omega.exp(n >> (log_m + 1));
             let m = 1 << log_m;
             for j in (0..n).step_by(m * 2) {
-                let mut w = F::from(1);
+                let mut w = F::one();
                 for k in 0..m {
                     let t = w * coeff[j + k + m];
                     coeff[j + k + m] = coeff[j + k] - t;
@@ -74,7 +73,6 @@ impl<F: FftField> Radix2Group<F> {
                     w *= w_m;
                 }
             }
-            log_m += 1;
         }
     }
 
@@ -90,7 +88,7 @@ impl<F: FftField> Radix2Group<F> {
 
     pub fn ifft(&self, mut evals: Vec<F>) -> Vec<F> {
         assert_eq!(self.size(), evals.len());
-        Self::_fft(&mut evals, self.omega.exp((1 << self.size()) - 1));
+        Self::_fft(&mut evals, self.omega.exp(self.size() - 1));
         let t = F::from(self.size() as u32).inv().unwrap();
         evals.iter_mut().for_each(|x| *x *= t);
         evals
@@ -100,12 +98,7 @@ impl<F: FftField> Radix2Group<F> {
 #[cfg(test)]
 mod tests {
 
-    use crate::algebra::field::{
-        AnotherField,
-        MyField,
-        goldilocks64::Goldilocks64,
-        goldilocks64ext::Goldilocks64Ext,
-    };
+    use crate::algebra::field::{goldilocks64::Goldilocks64, AnotherField};
 
     use super::*;
     #[test]
@@ -1,7 +1,9 @@
-use crate::batch_bit_reverse;
+use crate::storage::{Buffer, Storage};
 
-use super::coset::Coset;
+use super::coset::{bit_reverse_permute, Coset, PARALLEL_BUTTERFLIES};
 use super::field::MyField;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 #[derive(Debug, Clone)]
 pub struct Polynomial<T: MyField> {
@@ -9,11 +11,15 @@ pub struct Polynomial<T: MyField> {
 }
 
 impl<T: MyField> Polynomial<T> {
+    // Drops trailing zero coefficients; zero itself is the single coefficient 0.
     pub fn new(mut coefficients: Vec<T>) -> Polynomial<T> {
         let zero = T::from_int(0);
-        while *coefficients.last().unwrap() == zero {
+        while coefficients.last() == Some(&zero) {
             coefficients.pop();
         }
+        if coefficients.is_empty() {
+            coefficients.push(zero);
+        }
         Polynomial { coefficients }
     }
 
@@ -92,6 +98,24 @@ pub struct MultilinearPolynomial<T: MyField> {
     coefficients: Vec<T>,
 }
 
+// Turns coefficients into the values on the hypercube, in place.
+fn hypercube_in_place<T: MyField>(res: &mut [T]) {
+    bit_reverse_permute(res);
+    for i in 0..res.len().ilog2() {
+        let m = 1 << i;
+        crate::cfg_chunks_mut!(res, m * 2).for_each(|chunk| {
+            let (lo, hi) = chunk.split_at_mut(m);
+            if m >= PARALLEL_BUTTERFLIES {
+                crate::cfg_iter_mut!(hi)
+                    .zip(&*lo)
+                    .for_each(|(y, &x)| *y += x);
+            } else {
+                hi.iter_mut().zip(lo.iter()).for_each(|(y, &x)| *y += x);
+            }
+        });
+    }
+}
+
 impl<T: MyField> MultilinearPolynomial<T> {
     pub fn coefficients(&self) -> &Vec<T> {
         &self.coefficients
@@ -102,34 +126,27 @@ impl<T: MyField> MultilinearPolynomial<T> {
         assert_eq!(len, 1 << point.len());
         for v in point.into_iter() {
             len >>= 1;
-            for i in 0..len {
-                poly_hypercube[i] *= T::from_int(1) - v;
-                let tmp = poly_hypercube[i + len] * v;
-                poly_hypercube[i] += tmp;
-            }
+            let (lo, hi) = poly_hypercube.split_at_mut(len);
+            crate::cfg_iter_mut!(lo)
+                .zip(&hi[..len])
+                .for_each(|(x, &y)| {
+                    *x *= T::from_int(1) - v;
+                    *x += y * v;
+                });
         }
         poly_hypercube[0]
     }
 
     pub fn evaluate_hypercube(&self) -> Vec<T> {
-        let log_n = self.variable_num();
-        let n = self.coefficients.len();
-        let rank = batch_bit_reverse(log_n);
         let mut res = self.coefficients.clone();
-        for i in 0..n {
-            if i < rank[i] {
-                (res[i], res[rank[i]]) = (res[rank[i]], res[i]);
-            }
-        }
-        for i in 0..log_n {
-            let m = 1 << i;
-            for j in (0..n).step_by(m * 2) {
-                for k in 0..m {
-                    let tmp = res[j + k];
-                    res[j + k + m] += tmp;
-                }
-            }
-        }
+        hypercube_in_place(&mut res);
+        res
+    }
+
+    // `evaluate_hypercube` into a buffer placed by `storage`.
+    pub fn evaluate_hypercube_into(&self, storage: &Storage) -> Buffer<T> {
+        let mut res = Buffer::from_fn(storage, self.coefficients.len(), |i| self.coefficients[i]);
+        hypercube_in_place(&mut res);
         res
     }
 
@@ -289,6 +306,10 @@ mod test {
             point.reverse();
             assert_eq!(i.1, poly.evaluate(&point));
         }
+        assert_eq!(
+            *poly.evaluate_hypercube_into(&Storage::Memory),
+            poly.evaluate_hypercube()
+        );
 
         let point = (0..8).map(|_| Mersenne61Ext::random_element()).collect();
         let v = poly.evaluate(&point);
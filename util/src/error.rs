@@ -0,0 +1,55 @@
+use std::fmt;
+
+// Reasons a verifier rejects a proof. Rounds and indices refer to the
+// folding round and the queried position in that round's domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    MalformedProof(&'static str),
+    MerklePathInvalid { round: usize, index: usize },
+    FoldingMismatch { round: usize, index: usize },
+    SumcheckMismatch { round: usize },
+    DeepEvalMismatch { index: usize },
+    EvaluationMismatch,
+    InsufficientWork,
+    // The commitment was made under other `PcsParams` than the verifier's.
+    ParameterMismatch,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::MalformedProof(what) => write!(f, "malformed proof: {}", what),
+            VerificationError::MerklePathInvalid { round, index } => {
+                write!(
+                    f,
+                    "invalid merkle path in round {} at index {}",
+                    round, index
+                )
+            }
+            VerificationError::FoldingMismatch { round, index } => {
+                write!(f, "folding mismatch in round {} at index {}", round, index)
+            }
+            VerificationError::SumcheckMismatch { round } => {
+                write!(f, "sumcheck mismatch in round {}", round)
+            }
+            VerificationError::DeepEvalMismatch { index } => {
+                write!(
+                    f,
+                    "DEEP evaluation {} does not fold to the final value",
+                    index
+                )
+            }
+            VerificationError::EvaluationMismatch => {
+                write!(f, "claimed evaluation does not fold to the final value")
+            }
+            VerificationError::InsufficientWork => {
+                write!(f, "proof-of-work nonce does not meet the grinding bits")
+            }
+            VerificationError::ParameterMismatch => {
+                write!(f, "commitment was made under different parameters")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
@@ -1,34 +1,61 @@
-use crate::merkle_tree::MERKLE_ROOT_SIZE;
+use crate::merkle_tree::{random_salts, MerkleCap, TreeHash, SALT_SIZE};
 use crate::query_result::QueryResult;
 use crate::{
     algebra::field::{as_bytes_vec, MyField},
     merkle_tree::MerkleTreeProver,
+    storage::{Buffer, Storage},
 };
 
 #[derive(Clone)]
 pub struct InterpolateValue<T: MyField> {
-    pub value: Vec<T>,
+    pub value: Buffer<T>,
     leaf_size: usize,
+    // Set for a hiding tree: leaf i is hashed together with salt i.
+    salts: Option<Buffer<[u8; SALT_SIZE]>>,
     merkle_tree: MerkleTreeProver,
 }
 
 impl<T: MyField> InterpolateValue<T> {
-    pub fn new(value: Vec<T>, leaf_size: usize) -> Self {
+    pub fn new(hash: TreeHash, value: Vec<T>, leaf_size: usize) -> Self {
+        Self::from_buffer(hash, Buffer::from(value), leaf_size)
+    }
+
+    // The Merkle layers are placed in the same storage as `value`.
+    pub fn from_buffer(hash: TreeHash, value: Buffer<T>, leaf_size: usize) -> Self {
+        Self::build(hash, value, leaf_size, None)
+    }
+
+    // Every leaf gets a fresh random salt, revealed only when it is opened, so
+    // the root and sibling hashes say nothing about unopened values.
+    pub fn from_buffer_salted(hash: TreeHash, value: Buffer<T>, leaf_size: usize) -> Self {
+        let salts = random_salts(value.len() / leaf_size, &value.storage());
+        Self::build(hash, value, leaf_size, Some(salts))
+    }
+
+    fn build(
+        hash: TreeHash,
+        value: Buffer<T>,
+        leaf_size: usize,
+        salts: Option<Buffer<[u8; SALT_SIZE]>>,
+    ) -> Self {
         let len = value.len() / leaf_size;
-        let merkle_tree = MerkleTreeProver::new(
-            (0..len)
-                .map(|i| {
-                    as_bytes_vec(
-                        &(0..leaf_size)
-                            .map(|j| value[i + len * j])
-                            .collect::<Vec<_>>(),
-                    )
-                })
-                .collect(),
-        );
+        let leaf = |i: usize| {
+            as_bytes_vec(
+                &(0..leaf_size)
+                    .map(|j| value[i + len * j])
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let merkle_tree = match &salts {
+            Some(salts) => {
+                MerkleTreeProver::from_salted_leaves(hash, salts, &value.storage(), leaf)
+            }
+            None => MerkleTreeProver::from_leaves(hash, len, &value.storage(), leaf),
+        };
         Self {
             value,
             leaf_size,
+            salts,
             merkle_tree,
         }
     }
@@ -37,25 +64,133 @@ impl<T: MyField> InterpolateValue<T> {
         self.merkle_tree.leave_num()
     }
 
-    pub fn commit(&self) -> [u8; MERKLE_ROOT_SIZE] {
-        self.merkle_tree.commit()
+    // The cap of the tree's `TreeHash::cap_bits`.
+    pub fn commit(&self) -> MerkleCap {
+        self.merkle_tree
+            .commit_cap(self.merkle_tree.hash().cap_bits())
     }
 
     pub fn query(&self, leaf_indices: &Vec<usize>) -> QueryResult<T> {
+        let proof_bytes = self
+            .merkle_tree
+            .open_to_cap(leaf_indices, self.merkle_tree.hash().cap_bits());
+        self.query_with(leaf_indices, proof_bytes)
+    }
+
+    // Openings of the same leaves of several trees of one shape, e.g. a
+    // codeword and its mask, through `MerkleTreeProver::open_many`. Each
+    // result carries its own tree's share of the proof.
+    pub fn query_many(oracles: &[&Self], leaf_indices: &[usize]) -> Vec<QueryResult<T>> {
+        let trees = oracles.iter().map(|x| &x.merkle_tree).collect::<Vec<_>>();
+        let proof_bytes =
+            MerkleTreeProver::open_many(&trees, leaf_indices, trees[0].hash().cap_bits());
+        let len = proof_bytes.len() / oracles.len();
+        oracles
+            .iter()
+            .enumerate()
+            .map(|(k, oracle)| {
+                oracle.query_with(leaf_indices, proof_bytes[k * len..(k + 1) * len].to_vec())
+            })
+            .collect()
+    }
+
+    fn query_with(&self, leaf_indices: &[usize], proof_bytes: Vec<u8>) -> QueryResult<T> {
         let len = self.merkle_tree.leave_num();
         assert_eq!(len * self.leaf_size, self.value.len());
-        let proof_values = leaf_indices
+        let values = leaf_indices
             .iter()
-            .flat_map(|j: &usize| {
-                (0..self.leaf_size)
-                    .map(|i| (j.clone() + len * i, self.value[j.clone() + len * i]))
-                    .collect::<Vec<_>>()
-            })
+            .flat_map(|j| (0..self.leaf_size).map(move |i| self.value[j + len * i]))
             .collect();
-        let proof_bytes = self.merkle_tree.open(&leaf_indices);
-        QueryResult {
-            proof_bytes,
-            proof_values,
+        let salts = self.salts.as_ref().map_or(vec![], |salts| {
+            leaf_indices.iter().map(|&j| salts[j]).collect()
+        });
+        QueryResult::new(proof_bytes, leaf_indices.to_vec(), values, salts)
+    }
+}
+
+// Several codewords over the same domain committed in one tree. Leaf i holds
+// what `InterpolateValue` leaf i would hold for every codeword in turn, so
+// offset `column * leaf_size + j` of an opened leaf is entry `i + j * len` of
+// codeword `column`.
+#[derive(Clone)]
+pub struct MultiInterpolateValue<T: MyField> {
+    pub values: Vec<Vec<T>>,
+    leaf_size: usize,
+    salts: Option<Buffer<[u8; SALT_SIZE]>>,
+    merkle_tree: MerkleTreeProver,
+}
+
+impl<T: MyField> MultiInterpolateValue<T> {
+    pub fn new(hash: TreeHash, values: Vec<Vec<T>>, leaf_size: usize) -> Self {
+        Self::build(hash, values, leaf_size, None)
+    }
+
+    // As `InterpolateValue::from_buffer_salted`, one salt per shared leaf.
+    pub fn new_salted(hash: TreeHash, values: Vec<Vec<T>>, leaf_size: usize) -> Self {
+        let salts = random_salts(values[0].len() / leaf_size, &Storage::Memory);
+        Self::build(hash, values, leaf_size, Some(salts))
+    }
+
+    fn build(
+        hash: TreeHash,
+        values: Vec<Vec<T>>,
+        leaf_size: usize,
+        salts: Option<Buffer<[u8; SALT_SIZE]>>,
+    ) -> Self {
+        let size = values[0].len();
+        assert!(values.iter().all(|x| x.len() == size));
+        let len = size / leaf_size;
+        let leaf = |i: usize| {
+            as_bytes_vec(
+                &values
+                    .iter()
+                    .flat_map(|value| (0..leaf_size).map(move |j| value[i + len * j]))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let merkle_tree = match &salts {
+            Some(salts) => {
+                MerkleTreeProver::from_salted_leaves(hash, salts, &Storage::Memory, leaf)
+            }
+            None => MerkleTreeProver::from_leaves(hash, len, &Storage::Memory, leaf),
+        };
+        Self {
+            values,
+            leaf_size,
+            salts,
+            merkle_tree,
         }
     }
+
+    pub fn width(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn leave_num(&self) -> usize {
+        self.merkle_tree.leave_num()
+    }
+
+    pub fn commit(&self) -> MerkleCap {
+        self.merkle_tree
+            .commit_cap(self.merkle_tree.hash().cap_bits())
+    }
+
+    pub fn query(&self, leaf_indices: &Vec<usize>) -> QueryResult<T> {
+        let len = self.merkle_tree.leave_num();
+        let values = leaf_indices
+            .iter()
+            .flat_map(|j| {
+                self.values
+                    .iter()
+                    .flat_map(move |value| (0..self.leaf_size).map(move |i| value[j + len * i]))
+            })
+            .collect();
+        let proof_bytes = self
+            .merkle_tree
+            .open_to_cap(leaf_indices, self.merkle_tree.hash().cap_bits());
+        let salts = self.salts.as_ref().map_or(vec![], |salts| {
+            leaf_indices.iter().map(|&j| salts[j]).collect()
+        });
+        QueryResult::new(proof_bytes, leaf_indices.clone(), values, salts)
+    }
 }
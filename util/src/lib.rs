@@ -1,16 +1,22 @@
 pub mod algebra {
     pub mod coset;
     pub mod field;
+    pub mod mul_group;
     pub mod polynomial;
 }
+pub mod error;
 pub mod interpolation;
 pub mod merkle_tree;
+pub mod parallel;
+pub mod pcs;
+pub mod poseidon2;
 pub mod query_result;
-pub mod random_oracle;
+pub mod schedule;
+pub mod security;
+pub mod serialization;
+pub mod storage;
+pub mod transcript;
 
-pub const CODE_RATE: usize = 3;
-pub const SECURITY_BITS: usize = 100;
-pub const STEP: usize = 1;
 pub fn split_n(mut n: usize) -> Vec<usize> {
     let mut res = vec![];
     let mut i = 1;
@@ -25,12 +31,3 @@ pub fn split_n(mut n: usize) -> Vec<usize> {
     res.sort_by(|x, y| y.trailing_zeros().cmp(&x.trailing_zeros()));
     res
 }
-
-fn batch_bit_reverse(log_n: usize) -> Vec<usize> {
-    let n = 1 << log_n;
-    let mut res = (0..n).into_iter().map(|_| 0).collect::<Vec<usize>>();
-    for i in 0..n {
-        res[i] = (res[i >> 1] >> 1) | ((i & 1) << (log_n - 1));
-    }
-    res
-}
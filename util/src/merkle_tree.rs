@@ -1,60 +1,437 @@
-use rs_merkle::{Hasher, MerkleProof, MerkleTree};
+use std::{fmt::Debug, marker::PhantomData};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use sha2::Digest;
+
+use crate::algebra::field::{as_bytes_vec, MyField};
+use crate::error::VerificationError;
+use crate::poseidon2::{self, Poseidon2Sponge};
+use crate::storage::{Buffer, Storage};
+
+pub const MERKLE_ROOT_SIZE: usize = 32;
+
+// What a tree is committed by: the lowest layer with at most 2^cap_bits nodes,
+// or just the root when cap_bits is 0. Openings stop at the cap, so each path
+// loses the layers above it.
+pub type MerkleCap = Vec<[u8; MERKLE_ROOT_SIZE]>;
+
+// Random bytes hashed in front of each leaf of a hiding tree.
+pub const SALT_SIZE: usize = 16;
+
+// Hash used for leaves and inner nodes. Every implementation produces
+// MERKLE_ROOT_SIZE-byte digests so roots stay interchangeable in transcripts
+// and proofs.
+pub trait MerkleHasher: Debug {
+    fn hash(data: &[u8]) -> [u8; MERKLE_ROOT_SIZE];
+
+    // Parent of the children of an inner node. The last node of a layer may
+    // have fewer children than the arity; a lone child is carried up
+    // unchanged.
+    fn hash_nodes(children: &[[u8; MERKLE_ROOT_SIZE]]) -> [u8; MERKLE_ROOT_SIZE] {
+        match children {
+            [child] => *child,
+            _ => Self::hash(&children.concat()),
+        }
+    }
+
+    // Digest of a leaf of a salted tree; by default the salt is hashed in
+    // front of the leaf bytes.
+    fn hash_salted(salt: &[u8; SALT_SIZE], leaf: &[u8]) -> [u8; MERKLE_ROOT_SIZE] {
+        Self::hash(&[salt.as_slice(), leaf].concat())
+    }
+
+    // Whether `node` is something `hash` can output. Siblings read from a
+    // proof are checked with this before they are hashed.
+    fn is_digest(_node: &[u8; MERKLE_ROOT_SIZE]) -> bool {
+        true
+    }
+}
+
+// A `MerkleHasher` picked at run time. Trees keep one of these instead of a
+// type parameter, so trees over different hashes share one type. It also
+// carries the shape trees built with it have: the number of children of an
+// inner node and the cap height they are committed and opened at.
+#[derive(Clone, Copy)]
+pub struct TreeHash {
+    leaf: fn(&[u8]) -> [u8; MERKLE_ROOT_SIZE],
+    salted: fn(&[u8; SALT_SIZE], &[u8]) -> [u8; MERKLE_ROOT_SIZE],
+    node: fn(&[[u8; MERKLE_ROOT_SIZE]]) -> [u8; MERKLE_ROOT_SIZE],
+    digest: fn(&[u8; MERKLE_ROOT_SIZE]) -> bool,
+    arity: usize,
+    cap_bits: usize,
+}
+
+impl TreeHash {
+    pub fn of<H: MerkleHasher>() -> Self {
+        TreeHash {
+            leaf: H::hash,
+            salted: H::hash_salted,
+            node: H::hash_nodes,
+            digest: H::is_digest,
+            arity: 2,
+            cap_bits: 0,
+        }
+    }
+
+    pub fn with_arity(self, arity: usize) -> Self {
+        assert!(arity >= 2 && arity.is_power_of_two());
+        TreeHash { arity, ..self }
+    }
+
+    pub fn with_cap(self, cap_bits: usize) -> Self {
+        TreeHash { cap_bits, ..self }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn cap_bits(&self) -> usize {
+        self.cap_bits
+    }
+
+    // Number of nodes in every layer of a tree over `leave_num` leaves, from
+    // the leaves up to the root.
+    fn layer_sizes(&self, leave_num: usize) -> Vec<usize> {
+        let mut sizes = vec![leave_num];
+        while *sizes.last().unwrap() > 1 {
+            sizes.push(sizes.last().unwrap().div_ceil(self.arity));
+        }
+        sizes
+    }
+
+    // Index of the layer holding the cap: the lowest one with at most
+    // 2^cap_bits nodes, so trees shallower than the cap are capped at their
+    // leaves.
+    fn cap_layer(&self, sizes: &[usize]) -> usize {
+        let cap_size = u32::try_from(self.cap_bits)
+            .ok()
+            .and_then(|bits| 1usize.checked_shl(bits))
+            .unwrap_or(usize::MAX);
+        sizes.iter().position(|&size| size <= cap_size).unwrap()
+    }
+}
+
+impl Debug for TreeHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TreeHash")
+    }
+}
+
+// The hashes a scheme can be configured with, see `PcsParams`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashChoice {
+    #[default]
+    Blake3,
+    Sha256,
+    Keccak256,
+    // Poseidon2 over the field the tree commits to.
+    Poseidon2,
+}
+
+impl HashChoice {
+    // The hash of trees over elements of `T`.
+    pub fn tree_hash<T: MyField>(self) -> TreeHash {
+        match self {
+            HashChoice::Blake3 => TreeHash::of::<Blake3Algorithm>(),
+            HashChoice::Sha256 => TreeHash::of::<Sha256Algorithm>(),
+            HashChoice::Keccak256 => TreeHash::of::<Keccak256Algorithm>(),
+            HashChoice::Poseidon2 => TreeHash::of::<Poseidon2Algorithm<T>>(),
+        }
+    }
+}
+
+// Fresh random salts for `leave_num` leaves, placed in `storage`.
+pub fn random_salts(leave_num: usize, storage: &Storage) -> Buffer<[u8; SALT_SIZE]> {
+    Buffer::from_fn(storage, leave_num, |_| rand::random())
+}
 
 #[derive(Debug, Clone)]
 pub struct Blake3Algorithm {}
 
-impl Hasher for Blake3Algorithm {
-    type Hash = [u8; MERKLE_ROOT_SIZE];
-
+impl MerkleHasher for Blake3Algorithm {
     fn hash(data: &[u8]) -> [u8; MERKLE_ROOT_SIZE] {
         blake3::hash(data).into()
     }
 }
 
-pub const MERKLE_ROOT_SIZE: usize = 32;
+#[derive(Debug, Clone)]
+pub struct Sha256Algorithm {}
+
+impl MerkleHasher for Sha256Algorithm {
+    fn hash(data: &[u8]) -> [u8; MERKLE_ROOT_SIZE] {
+        sha2::Sha256::digest(data).into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keccak256Algorithm {}
+
+impl MerkleHasher for Keccak256Algorithm {
+    fn hash(data: &[u8]) -> [u8; MERKLE_ROOT_SIZE] {
+        sha3::Keccak256::digest(data).into()
+    }
+}
+
+// Poseidon2 over the committed field. Leaves are read as canonical encodings
+// of `T` and sponge-hashed; two children are joined with the two-to-one
+// compression, more are sponge-hashed like a leaf. A digest packs
+// MERKLE_ROOT_SIZE / size elements. Leaves are always encoded field elements,
+// so one that does not parse is a bug and panics; siblings taken from a proof
+// are rejected by `is_digest` unless every element in them is canonical.
+#[derive(Debug, Clone)]
+pub struct Poseidon2Algorithm<T: MyField> {
+    _field: PhantomData<T>,
+}
+
+impl<T: MyField> Poseidon2Algorithm<T> {
+    fn element_size() -> usize {
+        let size = T::from_int(0).to_bytes().len();
+        assert_eq!(MERKLE_ROOT_SIZE % size, 0);
+        size
+    }
+
+    fn parse(data: &[u8]) -> Option<Vec<T>> {
+        let chunks = data.chunks_exact(Self::element_size());
+        if chunks.remainder().is_empty() {
+            chunks.map(T::from_bytes).collect()
+        } else {
+            None
+        }
+    }
+
+    fn elements(data: &[u8]) -> Vec<T> {
+        Self::parse(data).expect("poseidon2 input is not a sequence of canonical field elements")
+    }
+
+    fn digest(elements: Vec<T>) -> [u8; MERKLE_ROOT_SIZE] {
+        as_bytes_vec(&elements).try_into().unwrap()
+    }
+}
+
+// A salt is not a field encoding, so it is absorbed as bytes after the leaf
+// elements.
+impl<T: MyField> MerkleHasher for Poseidon2Algorithm<T> {
+    fn hash(data: &[u8]) -> [u8; MERKLE_ROOT_SIZE] {
+        let len = MERKLE_ROOT_SIZE / Self::element_size();
+        Self::digest(poseidon2::hash(&Self::elements(data), len))
+    }
+
+    fn hash_nodes(children: &[[u8; MERKLE_ROOT_SIZE]]) -> [u8; MERKLE_ROOT_SIZE] {
+        match children {
+            [child] => *child,
+            [left, right] => Self::digest(poseidon2::compress(
+                &Self::elements(left),
+                &Self::elements(right),
+            )),
+            _ => Self::hash(&children.concat()),
+        }
+    }
+
+    fn hash_salted(salt: &[u8; SALT_SIZE], leaf: &[u8]) -> [u8; MERKLE_ROOT_SIZE] {
+        let len = MERKLE_ROOT_SIZE / Self::element_size();
+        let mut sponge = Poseidon2Sponge::new();
+        sponge.absorb(&Self::elements(leaf));
+        sponge.absorb_bytes(salt);
+        Self::digest(sponge.squeeze_n(len))
+    }
+
+    fn is_digest(node: &[u8; MERKLE_ROOT_SIZE]) -> bool {
+        Self::parse(node).is_some()
+    }
+}
+
+// Every layer from the leaf hashes up to the root, stored back to back in one
+// buffer; `offsets[l]` is where layer l starts. In mapped mode the buffer is
+// a file, so a tree never needs all of its nodes resident at once.
 #[derive(Clone)]
 pub struct MerkleTreeProver {
-    pub merkle_tree: MerkleTree<Blake3Algorithm>,
-    leave_num: usize,
+    nodes: Buffer<[u8; MERKLE_ROOT_SIZE]>,
+    offsets: Vec<usize>,
+    hash: TreeHash,
 }
 
 #[derive(Debug, Clone)]
 pub struct MerkleTreeVerifier {
-    pub merkle_root: [u8; MERKLE_ROOT_SIZE],
+    pub merkle_cap: MerkleCap,
     pub leave_number: usize,
+    hash: TreeHash,
 }
 
 impl MerkleTreeProver {
-    pub fn new(leaf_values: Vec<Vec<u8>>) -> Self {
-        let leaves = leaf_values
-            .iter()
-            .map(|x| Blake3Algorithm::hash(x))
-            .collect::<Vec<_>>();
-        let merkle_tree = MerkleTree::<Blake3Algorithm>::from_leaves(&leaves);
+    pub fn new(hash: TreeHash, leaf_values: Vec<Vec<u8>>) -> Self {
+        Self::from_leaves(hash, leaf_values.len(), &Storage::Memory, |i| {
+            &leaf_values[i]
+        })
+    }
+
+    // Builds the tree from leaf `i` = `leaf(i)`, streaming leaves straight
+    // into their hashes.
+    pub fn from_leaves<L, F>(hash: TreeHash, leave_num: usize, storage: &Storage, leaf: F) -> Self
+    where
+        L: AsRef<[u8]>,
+        F: Fn(usize) -> L + Send + Sync,
+    {
+        Self::from_leaf_hashes(hash, leave_num, storage, |i| (hash.leaf)(leaf(i).as_ref()))
+    }
+
+    // As `from_leaves`, with leaf `i` hashed together with `salts[i]`. The
+    // salts are revealed only for opened leaves, so the root and the sibling
+    // hashes of a proof say nothing about the other leaves.
+    pub fn from_salted_leaves<L, F>(
+        hash: TreeHash,
+        salts: &[[u8; SALT_SIZE]],
+        storage: &Storage,
+        leaf: F,
+    ) -> Self
+    where
+        L: AsRef<[u8]>,
+        F: Fn(usize) -> L + Send + Sync,
+    {
+        Self::from_leaf_hashes(hash, salts.len(), storage, |i| {
+            (hash.salted)(&salts[i], leaf(i).as_ref())
+        })
+    }
+
+    // Leaf hashes are filled in parallel, then every layer from the one
+    // below it.
+    fn from_leaf_hashes<F>(
+        hash: TreeHash,
+        leave_num: usize,
+        storage: &Storage,
+        leaf_hash: F,
+    ) -> Self
+    where
+        F: Fn(usize) -> [u8; MERKLE_ROOT_SIZE] + Send + Sync,
+    {
+        assert!(leave_num > 0);
+        let mut offsets = vec![0];
+        for size in hash.layer_sizes(leave_num) {
+            offsets.push(offsets.last().unwrap() + size);
+        }
+        let mut nodes = Buffer::from_fn(storage, *offsets.last().unwrap(), |i| {
+            if i < leave_num {
+                leaf_hash(i)
+            } else {
+                [0; MERKLE_ROOT_SIZE]
+            }
+        });
+        for l in 1..offsets.len() - 1 {
+            let (lower, upper) = nodes.split_at_mut(offsets[l]);
+            let children = &lower[offsets[l - 1]..];
+            crate::cfg_iter_mut!(upper[..offsets[l + 1] - offsets[l]])
+                .enumerate()
+                .for_each(|(i, node)| {
+                    let end = children.len().min((i + 1) * hash.arity);
+                    *node = (hash.node)(&children[i * hash.arity..end]);
+                });
+        }
         Self {
-            merkle_tree,
-            leave_num: leaf_values.len(),
+            nodes,
+            offsets,
+            hash,
         }
     }
 
+    fn layer(&self, l: usize) -> &[[u8; MERKLE_ROOT_SIZE]] {
+        &self.nodes[self.offsets[l]..self.offsets[l + 1]]
+    }
+
+    fn layer_num(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn hash(&self) -> TreeHash {
+        self.hash
+    }
+
     pub fn leave_num(&self) -> usize {
-        self.leave_num
+        self.offsets[1]
     }
 
     pub fn commit(&self) -> [u8; MERKLE_ROOT_SIZE] {
-        self.merkle_tree.root().unwrap()
+        self.layer(self.layer_num() - 1)[0]
+    }
+
+    // The lowest layer with at most 2^cap_bits nodes, or every leaf hash of a
+    // tree with fewer leaves.
+    pub fn commit_cap(&self, cap_bits: usize) -> MerkleCap {
+        let hash = self.hash.with_cap(cap_bits);
+        let sizes = hash.layer_sizes(self.leave_num());
+        self.layer(hash.cap_layer(&sizes)).to_vec()
     }
 
+    // Sibling hashes layer by layer up to the root, skipping nodes the
+    // verifier can compute.
     pub fn open(&self, leaf_indices: &Vec<usize>) -> Vec<u8> {
-        self.merkle_tree.proof(leaf_indices).to_bytes()
+        self.open_to_cap(leaf_indices, 0)
+    }
+
+    // As `open`, stopping below the cap of `commit_cap(cap_bits)`.
+    pub fn open_to_cap(&self, leaf_indices: &Vec<usize>, cap_bits: usize) -> Vec<u8> {
+        let positions = self.sibling_positions(leaf_indices, cap_bits);
+        let mut proof_bytes = Vec::with_capacity(positions.len() * MERKLE_ROOT_SIZE);
+        for p in positions {
+            proof_bytes.extend_from_slice(&self.nodes[p]);
+        }
+        proof_bytes
+    }
+
+    // One proof for the same leaves of several trees of one shape: the
+    // siblings are worked out once and each tree's hashes follow in order,
+    // so the proof splits into one `open_to_cap` per tree.
+    pub fn open_many(trees: &[&Self], leaf_indices: &[usize], cap_bits: usize) -> Vec<u8> {
+        let first = trees[0];
+        assert!(trees.iter().all(|tree| tree.offsets == first.offsets));
+        let positions = first.sibling_positions(leaf_indices, cap_bits);
+        let mut proof_bytes = Vec::with_capacity(trees.len() * positions.len() * MERKLE_ROOT_SIZE);
+        for tree in trees {
+            for &p in &positions {
+                proof_bytes.extend_from_slice(&tree.nodes[p]);
+            }
+        }
+        proof_bytes
+    }
+
+    // Positions in `nodes` of the siblings a proof for sorted `leaf_indices`
+    // carries: in every layer below the cap, the children of each touched
+    // parent that are neither opened nor computed from opened leaves.
+    fn sibling_positions(&self, leaf_indices: &[usize], cap_bits: usize) -> Vec<usize> {
+        let arity = self.hash.arity;
+        let sizes = self.hash.layer_sizes(self.leave_num());
+        let cap = self.hash.with_cap(cap_bits).cap_layer(&sizes);
+        let mut indices = leaf_indices.to_vec();
+        indices.dedup();
+        let mut positions = vec![];
+        for (l, &size) in sizes[..cap].iter().enumerate() {
+            let mut known = indices.iter().peekable();
+            let mut parents = vec![];
+            while let Some(&i) = known.next() {
+                let parent = i / arity;
+                let mut next = Some(i);
+                for c in parent * arity..size.min((parent + 1) * arity) {
+                    if next == Some(c) {
+                        next = known.next_if(|&&j| j / arity == parent).copied();
+                    } else {
+                        positions.push(self.offsets[l] + c);
+                    }
+                }
+                parents.push(parent);
+            }
+            indices = parents;
+        }
+        positions
     }
 }
 
 impl MerkleTreeVerifier {
-    pub fn new(leave_number: usize, merkle_root: &[u8; MERKLE_ROOT_SIZE]) -> Self {
+    // `merkle_cap` is the cap of `hash.cap_bits()`, a single root by default.
+    pub fn new(hash: TreeHash, leave_number: usize, merkle_cap: &[[u8; MERKLE_ROOT_SIZE]]) -> Self {
         Self {
             leave_number,
-            merkle_root: merkle_root.clone(),
+            merkle_cap: merkle_cap.to_vec(),
+            hash,
         }
     }
 
@@ -64,41 +441,165 @@ impl MerkleTreeVerifier {
         indices: &Vec<usize>,
         leaves: &Vec<Vec<u8>>,
     ) -> bool {
-        let proof = MerkleProof::<Blake3Algorithm>::try_from(proof_bytes).unwrap();
-        let leaves_to_prove: Vec<[u8; MERKLE_ROOT_SIZE]> =
-            leaves.iter().map(|x| Blake3Algorithm::hash(x)).collect();
-        proof.verify(
-            self.merkle_root,
-            indices,
-            &leaves_to_prove,
-            self.leave_number,
-        )
+        let leaves_to_prove = leaves.iter().map(|x| (self.hash.leaf)(x)).collect();
+        self.verify_hashes(&proof_bytes, indices, leaves_to_prove)
+    }
+
+    // Checks leaves of a tree built by `from_salted_leaves` against their
+    // revealed salts.
+    pub fn verify_salted(
+        &self,
+        proof_bytes: Vec<u8>,
+        indices: &Vec<usize>,
+        salts: &[[u8; SALT_SIZE]],
+        leaves: &Vec<Vec<u8>>,
+    ) -> bool {
+        if salts.len() != leaves.len() {
+            return false;
+        }
+        let leaves_to_prove = salts
+            .iter()
+            .zip(leaves)
+            .map(|(salt, x)| (self.hash.salted)(salt, x))
+            .collect();
+        self.verify_hashes(&proof_bytes, indices, leaves_to_prove)
+    }
+
+    // Checks a proof of `MerkleTreeProver::open_many`; `leaves[k]` are the
+    // opened leaves of the tree of `verifiers[k]` and `salts[k]` their salts,
+    // empty unless that tree is salted.
+    pub fn verify_many(
+        verifiers: &[Self],
+        proof_bytes: Vec<u8>,
+        indices: &[usize],
+        salts: &[&[[u8; SALT_SIZE]]],
+        leaves: &[Vec<Vec<u8>>],
+    ) -> bool {
+        if verifiers.is_empty()
+            || verifiers.len() != leaves.len()
+            || verifiers.len() != salts.len()
+            || proof_bytes.len() % (verifiers.len() * MERKLE_ROOT_SIZE) != 0
+        {
+            return false;
+        }
+        let len = proof_bytes.len() / verifiers.len();
+        verifiers
+            .iter()
+            .zip(salts.iter().zip(leaves))
+            .enumerate()
+            .all(|(k, (verifier, (salts, leaves)))| {
+                let leaves_to_prove = match salts {
+                    [] => leaves.iter().map(|x| (verifier.hash.leaf)(x)).collect(),
+                    _ if salts.len() != leaves.len() => return false,
+                    _ => salts
+                        .iter()
+                        .zip(leaves)
+                        .map(|(salt, x)| (verifier.hash.salted)(salt, x))
+                        .collect(),
+                };
+                verifier.verify_hashes(
+                    &proof_bytes[k * len..(k + 1) * len],
+                    indices,
+                    leaves_to_prove,
+                )
+            })
+    }
+
+    fn verify_hashes(
+        &self,
+        proof_bytes: &[u8],
+        indices: &[usize],
+        leaves_to_prove: Vec<[u8; MERKLE_ROOT_SIZE]>,
+    ) -> bool {
+        if indices.len() != leaves_to_prove.len()
+            || indices.last().is_some_and(|&i| i >= self.leave_number)
+            || indices.windows(2).any(|x| x[0] >= x[1])
+            || proof_bytes.len() % MERKLE_ROOT_SIZE != 0
+        {
+            return false;
+        }
+        let sizes = self.hash.layer_sizes(self.leave_number);
+        let cap = self.hash.cap_layer(&sizes);
+        if self.merkle_cap.len() != sizes[cap] {
+            return false;
+        }
+        let nodes = indices.iter().cloned().zip(leaves_to_prove).collect();
+        match fold_to_cap(self.hash, &sizes[..cap], nodes, proof_bytes) {
+            Some(nodes) => nodes.iter().all(|(i, node)| self.merkle_cap[*i] == *node),
+            None => false,
+        }
     }
 }
 
-pub struct MerkleRoot;
-impl MerkleRoot {
+// Hashes the known `nodes` of the lowest layer up through the layers of
+// `sizes`, taking missing children from `proof_bytes` in the order
+// `MerkleTreeProver::sibling_positions` lists them. Fails unless the proof is
+// used up exactly.
+fn fold_to_cap(
+    hash: TreeHash,
+    sizes: &[usize],
+    mut nodes: Vec<(usize, [u8; MERKLE_ROOT_SIZE])>,
+    proof_bytes: &[u8],
+) -> Option<Vec<(usize, [u8; MERKLE_ROOT_SIZE])>> {
+    let mut siblings = proof_bytes
+        .chunks_exact(MERKLE_ROOT_SIZE)
+        .map(|x| <[u8; MERKLE_ROOT_SIZE]>::try_from(x).unwrap())
+        .map(|x| Some(x).filter(hash.digest));
+    for &size in sizes {
+        let mut known = nodes.into_iter().peekable();
+        let mut parents = vec![];
+        while let Some((i, node)) = known.next() {
+            let parent = i / hash.arity;
+            let mut next = Some((i, node));
+            let mut children = Vec::with_capacity(hash.arity);
+            for c in parent * hash.arity..size.min((parent + 1) * hash.arity) {
+                match next {
+                    Some((j, node)) if j == c => {
+                        children.push(node);
+                        next = known.next_if(|(j, _)| j / hash.arity == parent);
+                    }
+                    _ => children.push(siblings.next()??),
+                }
+            }
+            parents.push((parent, (hash.node)(&children)));
+        }
+        nodes = parents;
+    }
+    match siblings.next() {
+        Some(_) => None,
+        None => Some(nodes),
+    }
+}
+
+pub struct MerkleRoot<H: MerkleHasher = Blake3Algorithm> {
+    _hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> MerkleRoot<H> {
+    // Root of a binary tree over `total_leaves_count` leaves implied by an
+    // opening of leaf `index`; fails if the path does not have the tree's shape.
     pub fn get_root(
         proof_bytes: Vec<u8>,
         index: usize,
         leaf: Vec<u8>,
         total_leaves_count: usize,
-    ) -> [u8; MERKLE_ROOT_SIZE] {
-        let proof = MerkleProof::<Blake3Algorithm>::try_from(proof_bytes).unwrap();
-        let leaf_hashes = vec![Blake3Algorithm::hash(&leaf)];
-        proof
-            .root(&vec![index], &leaf_hashes, total_leaves_count)
-            .unwrap()
+    ) -> Result<[u8; MERKLE_ROOT_SIZE], VerificationError> {
+        let hash = TreeHash::of::<H>();
+        let sizes = hash.layer_sizes(total_leaves_count);
+        let nodes = vec![(index, H::hash(&leaf))];
+        match fold_to_cap(hash, &sizes[..sizes.len() - 1], nodes, &proof_bytes) {
+            Some(root) => Ok(root[0].1),
+            None => Err(VerificationError::MalformedProof("merkle path")),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::algebra::field::{as_bytes_vec, mersenne61_ext::Mersenne61Ext, MyField};
+    use crate::algebra::field::{mersenne61_ext::Mersenne61Ext, MyField};
 
-    #[test]
-    fn commit_and_open() {
+    fn check_commit_and_open<H: MerkleHasher>() {
         let leaf_values = vec![
             as_bytes_vec(&[Mersenne61Ext::from_int(1), Mersenne61Ext::from_int(2)]),
             as_bytes_vec(&[Mersenne61Ext::from_int(3), Mersenne61Ext::from_int(4)]),
@@ -109,16 +610,265 @@ mod tests {
             as_bytes_vec(&[Mersenne61Ext::from_int(13), Mersenne61Ext::from_int(14)]),
         ];
         let leave_number = leaf_values.len();
-        let prover = MerkleTreeProver::new(leaf_values);
+        let prover = MerkleTreeProver::new(TreeHash::of::<H>(), leaf_values);
         let root = prover.commit();
-        let verifier = MerkleTreeVerifier::new(leave_number, &root);
+        let verifier = MerkleTreeVerifier::new(TreeHash::of::<H>(), leave_number, &[root]);
         let leaf_indices = vec![2, 3];
         let proof_bytes = prover.open(&leaf_indices);
         let open_values = vec![
             as_bytes_vec(&[Mersenne61Ext::from_int(5), Mersenne61Ext::from_int(6)]),
             as_bytes_vec(&[Mersenne61Ext::from_int(7), Mersenne61Ext::from_int(8)]),
         ];
-        assert!(verifier.verify(proof_bytes, &leaf_indices, &open_values));
+        assert!(verifier.verify(proof_bytes.clone(), &leaf_indices, &open_values));
+        assert!(!verifier.verify(proof_bytes.clone(), &vec![2, 7], &open_values));
+        assert!(!verifier.verify(vec![0; 5], &leaf_indices, &open_values));
+        assert!(!verifier.verify(proof_bytes, &leaf_indices, &open_values[..1].to_vec()));
+    }
+
+    #[test]
+    fn commit_and_open() {
+        check_commit_and_open::<Blake3Algorithm>();
+        check_commit_and_open::<Sha256Algorithm>();
+        check_commit_and_open::<Keccak256Algorithm>();
+        check_commit_and_open::<Poseidon2Algorithm<Mersenne61Ext>>();
+    }
+
+    fn check_salted_commit_and_open<H: MerkleHasher>() {
+        let leaf = |i: usize| as_bytes_vec(&[Mersenne61Ext::from_int(i as u64 % 2)]);
+        let leave_num = 6;
+        let salts = random_salts(leave_num, &Storage::Memory);
+        let hash = TreeHash::of::<H>();
+        let prover = MerkleTreeProver::from_salted_leaves(hash, &salts, &Storage::Memory, leaf);
+        let resalted = MerkleTreeProver::from_salted_leaves(
+            hash,
+            &random_salts(leave_num, &Storage::Memory),
+            &Storage::Memory,
+            leaf,
+        );
+        assert_ne!(prover.commit(), resalted.commit());
+        assert_ne!(
+            prover.commit(),
+            MerkleTreeProver::from_leaves(hash, leave_num, &Storage::Memory, leaf).commit()
+        );
+
+        let verifier = MerkleTreeVerifier::new(hash, leave_num, &[prover.commit()]);
+        let leaf_indices = vec![1, 4];
+        let proof_bytes = prover.open(&leaf_indices);
+        let open_salts = leaf_indices.iter().map(|&i| salts[i]).collect::<Vec<_>>();
+        let leaves = leaf_indices.iter().map(|&i| leaf(i)).collect();
+        assert!(verifier.verify_salted(proof_bytes.clone(), &leaf_indices, &open_salts, &leaves));
+        assert!(!verifier.verify(proof_bytes.clone(), &leaf_indices, &leaves));
+        let mut wrong_salts = open_salts.clone();
+        wrong_salts[1][0] ^= 1;
+        assert!(!verifier.verify_salted(proof_bytes.clone(), &leaf_indices, &wrong_salts, &leaves));
+        assert!(!verifier.verify_salted(proof_bytes, &leaf_indices, &open_salts[..1], &leaves));
+    }
+
+    #[test]
+    fn salted_commit_and_open() {
+        check_salted_commit_and_open::<Blake3Algorithm>();
+        check_salted_commit_and_open::<Sha256Algorithm>();
+        check_salted_commit_and_open::<Keccak256Algorithm>();
+        check_salted_commit_and_open::<Poseidon2Algorithm<Mersenne61Ext>>();
+    }
+
+    #[test]
+    fn mapped_tree() {
+        let leave_num = (1 << 15) + 5;
+        let leaf = |i: usize| (i as u64).to_le_bytes();
+        let hash = TreeHash::of::<Blake3Algorithm>();
+        let memory = MerkleTreeProver::from_leaves(hash, leave_num, &Storage::Memory, leaf);
+        let mapped = MerkleTreeProver::from_leaves(
+            hash,
+            leave_num,
+            &Storage::Mapped(std::env::temp_dir()),
+            leaf,
+        );
+        assert_eq!(
+            mapped.nodes.storage(),
+            Storage::Mapped(std::env::temp_dir())
+        );
+        assert_eq!(mapped.commit(), memory.commit());
+        let leaf_indices = vec![0, 1, 77, leave_num - 1];
+        let proof_bytes = mapped.open(&leaf_indices);
+        assert_eq!(proof_bytes, memory.open(&leaf_indices));
+        let verifier = MerkleTreeVerifier::new(hash, leave_num, &[mapped.commit()]);
+        let leaves = leaf_indices.iter().map(|&i| leaf(i).to_vec()).collect();
+        assert!(verifier.verify(proof_bytes, &leaf_indices, &leaves));
+    }
+
+    #[test]
+    fn cap_commit_and_open() {
+        let leaf = |i: usize| (i as u64).to_le_bytes();
+        for leave_num in [1, 7, 16, 33] {
+            let hash = TreeHash::of::<Blake3Algorithm>();
+            let prover = MerkleTreeProver::from_leaves(hash, leave_num, &Storage::Memory, leaf);
+            let mut leaf_indices = vec![0, 3 % leave_num, leave_num - 1];
+            leaf_indices.dedup();
+            let leaves = leaf_indices.iter().map(|&i| leaf(i).to_vec()).collect();
+            let full = prover.open(&leaf_indices).len();
+            for cap_bits in 0..8 {
+                let cap = prover.commit_cap(cap_bits);
+                assert!(cap.len() <= 1 << cap_bits);
+                if leave_num.is_power_of_two() {
+                    assert_eq!(cap.len(), leave_num.min(1 << cap_bits));
+                }
+                let proof_bytes = prover.open_to_cap(&leaf_indices, cap_bits);
+                assert!(proof_bytes.len() <= full);
+                let capped = hash.with_cap(cap_bits);
+                let verifier = MerkleTreeVerifier::new(capped, leave_num, &cap);
+                assert!(verifier.verify(proof_bytes.clone(), &leaf_indices, &leaves));
+                // the cap height is part of what the verifier checks against
+                let other = MerkleTreeVerifier::new(capped.with_cap(cap_bits + 1), leave_num, &cap);
+                if prover.commit_cap(cap_bits + 1) != cap {
+                    assert!(!other.verify(proof_bytes.clone(), &leaf_indices, &leaves));
+                }
+                let mut forged = cap.clone();
+                forged[0][0] ^= 1;
+                let verifier = MerkleTreeVerifier::new(capped, leave_num, &forged);
+                assert!(!verifier.verify(proof_bytes, &leaf_indices, &leaves));
+            }
+            assert_eq!(prover.commit_cap(0), vec![prover.commit()]);
+        }
+    }
+
+    #[test]
+    fn tree_shape() {
+        let leaf = |i: usize| [i as u8];
+        let h = |x: &[u8]| Blake3Algorithm::hash(x);
+        let hash = TreeHash::of::<Blake3Algorithm>();
+        // an unpaired last node is carried up unchanged
+        let binary = MerkleTreeProver::from_leaves(hash, 3, &Storage::Memory, leaf);
+        let left = h(&[h(&[0]), h(&[1])].concat());
+        assert_eq!(binary.commit(), h(&[left, h(&[2])].concat()));
+        let quaternary =
+            MerkleTreeProver::from_leaves(hash.with_arity(4), 6, &Storage::Memory, leaf);
+        let children = (0..4).map(|i| h(&[i])).collect::<Vec<_>>();
+        let rest = h(&[h(&[4]), h(&[5])].concat());
+        assert_eq!(
+            quaternary.commit(),
+            h(&[h(&children.concat()), rest].concat())
+        );
+
+        for i in 0..3 {
+            let root = MerkleRoot::<Blake3Algorithm>::get_root(
+                binary.open(&vec![i]),
+                i,
+                leaf(i).to_vec(),
+                3,
+            );
+            assert_eq!(root, Ok(binary.commit()));
+        }
+        assert_eq!(
+            MerkleRoot::<Blake3Algorithm>::get_root(vec![], 0, leaf(0).to_vec(), 3),
+            Err(VerificationError::MalformedProof("merkle path"))
+        );
+    }
+
+    #[test]
+    fn arity_commit_and_open() {
+        let leaf = |i: usize| (i as u64).to_le_bytes();
+        for arity in [2, 4, 8] {
+            for leave_num in [1, 5, 16, 37] {
+                let hash = TreeHash::of::<Blake3Algorithm>().with_arity(arity);
+                let prover = MerkleTreeProver::from_leaves(hash, leave_num, &Storage::Memory, leaf);
+                let mut leaf_indices = vec![
+                    0,
+                    1 % leave_num,
+                    3 % leave_num,
+                    leave_num / 2,
+                    leave_num - 1,
+                ];
+                leaf_indices.sort();
+                leaf_indices.dedup();
+                let leaves = leaf_indices.iter().map(|&i| leaf(i).to_vec()).collect();
+                for cap_bits in 0..4 {
+                    let cap = prover.commit_cap(cap_bits);
+                    assert!(cap.len() <= 1 << cap_bits);
+                    let proof_bytes = prover.open_to_cap(&leaf_indices, cap_bits);
+                    let verifier =
+                        MerkleTreeVerifier::new(hash.with_cap(cap_bits), leave_num, &cap);
+                    assert!(verifier.verify(proof_bytes.clone(), &leaf_indices, &leaves));
+                    let mut forged = proof_bytes.clone();
+                    forged.extend_from_slice(&[0; MERKLE_ROOT_SIZE]);
+                    assert!(!verifier.verify(forged, &leaf_indices, &leaves));
+                    if let Some(byte) = proof_bytes.first() {
+                        let mut forged = proof_bytes.clone();
+                        forged[0] = byte ^ 1;
+                        assert!(!verifier.verify(forged, &leaf_indices, &leaves));
+                    }
+                    let binary = MerkleTreeVerifier::new(
+                        hash.with_arity(2).with_cap(cap_bits),
+                        leave_num,
+                        &cap,
+                    );
+                    if arity != 2 && leave_num > 2 && cap.len() == 1 {
+                        assert!(!binary.verify(proof_bytes, &leaf_indices, &leaves));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multi_tree_openings() {
+        let hash = TreeHash::of::<Blake3Algorithm>().with_arity(4).with_cap(1);
+        let leave_num = 21;
+        let trees = (0..3)
+            .map(|k| {
+                MerkleTreeProver::from_leaves(hash, leave_num, &Storage::Memory, |i| {
+                    [k as u8, i as u8]
+                })
+            })
+            .collect::<Vec<_>>();
+        let leaf_indices = vec![2, 3, 9, 20];
+        let proof_bytes = MerkleTreeProver::open_many(
+            &trees.iter().collect::<Vec<_>>(),
+            &leaf_indices,
+            hash.cap_bits(),
+        );
+        assert_eq!(
+            proof_bytes,
+            trees
+                .iter()
+                .flat_map(|tree| tree.open_to_cap(&leaf_indices, hash.cap_bits()))
+                .collect::<Vec<_>>()
+        );
+        let verifiers = trees
+            .iter()
+            .map(|tree| MerkleTreeVerifier::new(hash, leave_num, &tree.commit_cap(1)))
+            .collect::<Vec<_>>();
+        let mut leaves = (0..3)
+            .map(|k| {
+                leaf_indices
+                    .iter()
+                    .map(|&i| vec![k as u8, i as u8])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let salts: [&[[u8; SALT_SIZE]]; 3] = [&[], &[], &[]];
+        assert!(MerkleTreeVerifier::verify_many(
+            &verifiers,
+            proof_bytes.clone(),
+            &leaf_indices,
+            &salts,
+            &leaves
+        ));
+        leaves.swap(0, 1);
+        assert!(!MerkleTreeVerifier::verify_many(
+            &verifiers,
+            proof_bytes.clone(),
+            &leaf_indices,
+            &salts,
+            &leaves
+        ));
+        assert!(!MerkleTreeVerifier::verify_many(
+            &verifiers[..2],
+            proof_bytes,
+            &leaf_indices,
+            &salts[..2],
+            &leaves[..2]
+        ));
     }
 
     #[test]
@@ -130,4 +880,54 @@ mod tests {
             hex_string
         );
     }
+
+    #[test]
+    fn sha256_and_keccak256() {
+        assert_eq!(
+            "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7",
+            hex::encode(Sha256Algorithm::hash("data".as_bytes()))
+        );
+        assert_eq!(
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            hex::encode(Keccak256Algorithm::hash(&[]))
+        );
+    }
+
+    #[test]
+    fn poseidon2_digests() {
+        let leaf = as_bytes_vec(&[Mersenne61Ext::from_int(1), Mersenne61Ext::from_int(2)]);
+        let digest = Poseidon2Algorithm::<Mersenne61Ext>::hash(&leaf);
+        assert!(digest
+            .chunks(16)
+            .all(|x| Mersenne61Ext::from_bytes(x).is_some()));
+        assert_ne!(
+            digest,
+            Poseidon2Algorithm::<Mersenne61Ext>::hash(&leaf[..16])
+        );
+        assert!(Poseidon2Algorithm::<Mersenne61Ext>::is_digest(&digest));
+        assert!(!Poseidon2Algorithm::<Mersenne61Ext>::is_digest(
+            &[0xff; MERKLE_ROOT_SIZE]
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn poseidon2_non_canonical_leaf() {
+        Poseidon2Algorithm::<Mersenne61Ext>::hash(&[0xff; 16]);
+    }
+
+    #[test]
+    fn poseidon2_non_canonical_sibling() {
+        let leaf = |i: usize| as_bytes_vec(&[Mersenne61Ext::from_int(i as u64)]);
+        let hash = HashChoice::Poseidon2.tree_hash::<Mersenne61Ext>();
+        let prover = MerkleTreeProver::from_leaves(hash, 4, &Storage::Memory, leaf);
+        let verifier = MerkleTreeVerifier::new(hash, 4, &[prover.commit()]);
+        let leaf_indices = vec![1];
+        let leaves = vec![leaf(1)];
+        let proof_bytes = prover.open(&leaf_indices);
+        assert!(verifier.verify(proof_bytes.clone(), &leaf_indices, &leaves));
+        let mut forged = proof_bytes;
+        forged[..MERKLE_ROOT_SIZE].copy_from_slice(&[0xff; MERKLE_ROOT_SIZE]);
+        assert!(!verifier.verify(forged, &leaf_indices, &leaves));
+    }
 }
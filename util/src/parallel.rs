@@ -0,0 +1,81 @@
+// Iterators for the prover hot loops. With the `parallel` feature of the
+// calling crate they expand to rayon's parallel iterators, otherwise to the
+// sequential ones; callers import `rayon::prelude::*` under the same feature.
+// Every use maps elements independently or reduces with field addition, so
+// both builds produce bit-identical proofs.
+
+#[macro_export]
+macro_rules! cfg_into_iter {
+    ($e:expr) => {{
+        #[cfg(feature = "parallel")]
+        let it = $e.into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let it = $e.into_iter();
+        it
+    }};
+}
+
+#[macro_export]
+macro_rules! cfg_iter {
+    ($e:expr) => {{
+        #[cfg(feature = "parallel")]
+        let it = $e.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let it = $e.iter();
+        it
+    }};
+}
+
+#[macro_export]
+macro_rules! cfg_iter_mut {
+    ($e:expr) => {{
+        #[cfg(feature = "parallel")]
+        let it = $e.par_iter_mut();
+        #[cfg(not(feature = "parallel"))]
+        let it = $e.iter_mut();
+        it
+    }};
+}
+
+#[macro_export]
+macro_rules! cfg_chunks_mut {
+    ($e:expr, $size:expr) => {{
+        #[cfg(feature = "parallel")]
+        let it = $e.par_chunks_mut($size);
+        #[cfg(not(feature = "parallel"))]
+        let it = $e.chunks_mut($size);
+        it
+    }};
+}
+
+// A rayon pool of a given size, or just the calling thread without the
+// `parallel` feature.
+pub struct Pool {
+    #[cfg(feature = "parallel")]
+    pool: rayon::ThreadPool,
+}
+
+impl Pool {
+    pub fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        #[cfg(feature = "parallel")]
+        let res = self.pool.install(op);
+        #[cfg(not(feature = "parallel"))]
+        let res = op();
+        res
+    }
+}
+
+// Calls `f` with each pool size the thread-scaling benchmarks and tests sweep;
+// the sequential build only has the calling thread.
+pub fn for_each_pool(mut f: impl FnMut(usize, &Pool)) {
+    #[cfg(feature = "parallel")]
+    for threads in [1, 2, 4, 8] {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        f(threads, &Pool { pool });
+    }
+    #[cfg(not(feature = "parallel"))]
+    f(1, &Pool {});
+}
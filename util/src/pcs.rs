@@ -0,0 +1,301 @@
+use crate::algebra::field::MyField;
+use crate::error::VerificationError;
+use crate::merkle_tree::{HashChoice, MerkleCap, TreeHash};
+use crate::schedule::FoldingSchedule;
+use crate::security::{Regime, SecurityParams};
+use crate::serialization::{
+    deserialize_with, serialize_with, Reader, Serializable, SerializationError, Writer,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Everything prover and verifier must agree on besides the instance size.
+// Commitments carry the parameters they were made under, and verifiers reject
+// a commitment whose parameters differ from their own, so schemes configured
+// differently can be used side by side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcsParams {
+    // The codeword is 2^code_rate times longer than the message.
+    pub code_rate: usize,
+    // Variables folded per round, unless `schedule` is set.
+    pub step: usize,
+    pub query_num: usize,
+    // Leading zero bits of proof of work demanded before the queries.
+    pub grinding_bits: usize,
+    pub hash: HashChoice,
+    // Children of every inner Merkle node, a power of two. Wider trees are
+    // shallower but carry more siblings per layer in a path.
+    pub merkle_arity: usize,
+    // Trees are committed by their lowest layer of at most 2^cap_bits nodes
+    // instead of the root, which takes the layers above it off every
+    // authentication path.
+    pub cap_bits: usize,
+    // Folding stops with this many variables left; the remaining polynomial
+    // of degree below 2^final_poly_bits is sent in the clear. Larger values
+    // trade a longer final polynomial for fewer rounds of roots and queries.
+    pub final_poly_bits: usize,
+    // Explicit variables folded per round; it must fold exactly the
+    // variables of the instance it is used with, less `final_poly_bits`.
+    pub schedule: Option<FoldingSchedule>,
+}
+
+// 100 bits of conjectured security with 16 bits of grinding.
+impl Default for PcsParams {
+    fn default() -> Self {
+        PcsParams {
+            code_rate: 3,
+            step: 1,
+            query_num: 28,
+            grinding_bits: 16,
+            hash: HashChoice::Blake3,
+            merkle_arity: 2,
+            cap_bits: 0,
+            final_poly_bits: 0,
+            schedule: None,
+        }
+    }
+}
+
+impl PcsParams {
+    // The same parameters with the fewest queries reaching `bits` of security
+    // for `variable_num` variables over `T`.
+    pub fn with_security<T: MyField>(
+        self,
+        variable_num: usize,
+        bits: usize,
+        regime: Regime,
+    ) -> Self {
+        PcsParams {
+            query_num: SecurityParams::new::<T>(variable_num, &self, regime)
+                .target(bits)
+                .query_num,
+            ..self
+        }
+    }
+
+    // How `total_round` variables are folded down to `final_poly_bits`:
+    // `schedule` if set, otherwise `step` per round with a shorter last round
+    // for any remainder.
+    pub fn folding(&self, total_round: usize) -> FoldingSchedule {
+        assert!(
+            self.final_poly_bits < total_round,
+            "final polynomial leaves nothing to fold"
+        );
+        let folded = total_round - self.final_poly_bits;
+        match self.schedule {
+            Some(schedule) => {
+                assert_eq!(
+                    schedule.total(),
+                    folded,
+                    "folding schedule does not match the round count"
+                );
+                schedule
+            }
+            None => FoldingSchedule::uniform(folded, self.step),
+        }
+    }
+
+    pub fn tree_hash<T: MyField>(&self) -> TreeHash {
+        self.hash
+            .tree_hash::<T>()
+            .with_arity(self.merkle_arity)
+            .with_cap(self.cap_bits)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetupParams {
+    pub variable_num: usize,
+    pub pcs: PcsParams,
+}
+
+// A single Merkle cap together with the parameters it was committed under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commitment {
+    pub merkle_root: MerkleCap,
+    pub params: PcsParams,
+}
+
+fn write_usize(writer: &mut Writer, x: usize) {
+    writer.write_u32(u32::try_from(x).expect("parameter does not fit in u32"));
+}
+
+impl Serializable for PcsParams {
+    fn write_to(&self, writer: &mut Writer) {
+        write_usize(writer, self.code_rate);
+        write_usize(writer, self.step);
+        write_usize(writer, self.query_num);
+        write_usize(writer, self.grinding_bits);
+        writer.write_u8(self.hash as u8);
+        write_usize(writer, self.merkle_arity);
+        write_usize(writer, self.cap_bits);
+        write_usize(writer, self.final_poly_bits);
+        match &self.schedule {
+            None => writer.write_u8(0),
+            Some(schedule) => {
+                writer.write_u8(1);
+                writer.write(schedule);
+            }
+        }
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        Ok(PcsParams {
+            code_rate: reader.read_u32()? as usize,
+            step: reader.read_u32()? as usize,
+            query_num: reader.read_u32()? as usize,
+            grinding_bits: reader.read_u32()? as usize,
+            hash: match reader.read_u8()? {
+                0 => HashChoice::Blake3,
+                1 => HashChoice::Sha256,
+                2 => HashChoice::Keccak256,
+                3 => HashChoice::Poseidon2,
+                _ => return Err(SerializationError::NonCanonical("hash choice")),
+            },
+            merkle_arity: match reader.read_u32()? as usize {
+                arity if arity >= 2 && arity.is_power_of_two() => arity,
+                _ => return Err(SerializationError::NonCanonical("merkle arity")),
+            },
+            cap_bits: reader.read_u32()? as usize,
+            final_poly_bits: reader.read_u32()? as usize,
+            schedule: match reader.read_u8()? {
+                0 => None,
+                1 => Some(reader.read()?),
+                _ => return Err(SerializationError::NonCanonical("folding schedule")),
+            },
+        })
+    }
+}
+
+impl Serializable for Commitment {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_cap(&self.merkle_root);
+        writer.write(&self.params);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        Ok(Commitment {
+            merkle_root: reader.read_cap()?,
+            params: reader.read()?,
+        })
+    }
+}
+
+impl Serialize for Commitment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Commitment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
+// Common interface of the multilinear commitment schemes. Single-polynomial
+// schemes use `MultilinearPolynomial<T>` and `T` for `Polynomial` and
+// `Evaluation`; batch schemes commit and open several polynomials at once.
+pub trait MultilinearPCS<T: MyField>: Sized {
+    type Params;
+    type Polynomial;
+    type Evaluation;
+    type Commitment: Clone;
+    type ProverData;
+    type Proof;
+
+    fn setup(params: Self::Params) -> Self;
+
+    fn commit(&self, poly: Self::Polynomial) -> (Self::Commitment, Self::ProverData);
+
+    fn open(&self, data: &Self::ProverData, point: &[T]) -> (Self::Evaluation, Self::Proof);
+
+    fn verify(
+        &self,
+        commitment: &Self::Commitment,
+        point: &[T],
+        eval: &Self::Evaluation,
+        proof: &Self::Proof,
+    ) -> Result<(), VerificationError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use crate::merkle_tree::MERKLE_ROOT_SIZE;
+
+    #[test]
+    fn default_params() {
+        let params = PcsParams::default();
+        assert_eq!(
+            params.with_security::<Mersenne61Ext>(20, 100, Regime::Conjectured),
+            params
+        );
+    }
+
+    #[test]
+    fn commitment_round_trip() {
+        let commitment = Commitment {
+            merkle_root: vec![[3; MERKLE_ROOT_SIZE]; 2],
+            params: PcsParams {
+                step: 2,
+                hash: HashChoice::Keccak256,
+                merkle_arity: 4,
+                cap_bits: 1,
+                final_poly_bits: 4,
+                ..PcsParams::default()
+            },
+        };
+        let bytes = commitment.encode();
+        assert_eq!(Commitment::decode(&bytes), Ok(commitment.clone()));
+
+        let mut hash = bytes.clone();
+        hash[bytes.len() - 14] = 3;
+        assert_eq!(
+            Commitment::decode(&hash).unwrap().params.hash,
+            HashChoice::Poseidon2
+        );
+        hash[bytes.len() - 14] = 4;
+        assert_eq!(
+            Commitment::decode(&hash),
+            Err(SerializationError::NonCanonical("hash choice"))
+        );
+        let mut arity = bytes.clone();
+        arity[bytes.len() - 13] = 3;
+        assert_eq!(
+            Commitment::decode(&arity),
+            Err(SerializationError::NonCanonical("merkle arity"))
+        );
+
+        let scheduled = Commitment {
+            params: PcsParams {
+                schedule: Some(FoldingSchedule::new(&[4, 3, 1])),
+                ..commitment.params
+            },
+            ..commitment.clone()
+        };
+        let bytes = scheduled.encode();
+        assert_eq!(Commitment::decode(&bytes), Ok(scheduled));
+        assert_ne!(bytes.len(), commitment.encode().len());
+    }
+
+    #[test]
+    fn folding() {
+        let params = PcsParams {
+            step: 3,
+            ..PcsParams::default()
+        };
+        assert_eq!(params.folding(10).steps(), vec![3, 3, 3, 1]);
+        let schedule = FoldingSchedule::new(&[4, 3, 1]);
+        let params = PcsParams {
+            schedule: Some(schedule),
+            ..params
+        };
+        assert_eq!(params.folding(8), schedule);
+        let params = PcsParams {
+            final_poly_bits: 2,
+            ..params
+        };
+        assert_eq!(params.folding(10), schedule);
+    }
+}
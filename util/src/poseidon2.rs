@@ -0,0 +1,772 @@
+use once_cell::sync::Lazy;
+
+use crate::algebra::field::{
+    bn254::Bn254Fr, ft255::Ft255, goldilocks64::Goldilocks64, goldilocks64ext::Goldilocks64Ext,
+    m31_ext::M31ext, mersenne61_ext::Mersenne61Ext, MyField,
+};
+
+// Round constants of one Poseidon2 instance. The internal matrix is
+// 1 + diag(diagonal), i.e. x_i <- sum(x) + diagonal[i] * x_i.
+pub struct Poseidon2Constants<T> {
+    external: Vec<Vec<T>>,
+    internal: Vec<T>,
+    diagonal: Vec<T>,
+}
+
+impl<T: MyField> Poseidon2Constants<T> {
+    // The instance the reference scripts of the Poseidon2 paper generate:
+    // round constants are read off a Grain LFSR seeded with the instance
+    // parameters, first full rounds, then partial rounds, then the last full
+    // rounds. Widths 2 and 3 use the paper's fixed internal matrices; wider
+    // instances keep drawing diagonals from the LFSR until every power up to
+    // 2 * WIDTH of the internal matrix has an irreducible characteristic
+    // polynomial. The scripts only cover prime fields; over extension fields
+    // each constant is drawn here coordinate by coordinate in `basis()`.
+    pub fn generate() -> Self {
+        assert!(
+            sbox_is_permutation::<T>(),
+            "S-box degree shares a factor with the order of the multiplicative group"
+        );
+        let mut grain = Grain::new::<T>();
+        let half = T::FULL_ROUNDS / 2;
+        let mut external = grain.rounds(half);
+        let internal = grain.elements(T::PARTIAL_ROUNDS);
+        external.extend(grain.rounds(T::FULL_ROUNDS - half));
+        let diagonal = match T::WIDTH {
+            2 => vec![T::from_int(1), T::from_int(2)],
+            3 => vec![T::from_int(1), T::from_int(1), T::from_int(2)],
+            _ => loop {
+                let diagonal = grain.elements::<T>(T::WIDTH);
+                if internal_matrix_is_sound(&diagonal) {
+                    break diagonal.into_iter().map(|x| x - T::from_int(1)).collect();
+                }
+            },
+        };
+        Poseidon2Constants {
+            external,
+            internal,
+            diagonal,
+        }
+    }
+}
+
+pub trait Poseidon2Field: Sized {
+    // WIDTH is 2, 3 or a multiple of 4.
+    const WIDTH: usize;
+    const RATE: usize;
+    // Must be coprime to the order of the multiplicative group.
+    const SBOX_DEGREE: usize;
+    const FULL_ROUNDS: usize;
+    const PARTIAL_ROUNDS: usize;
+    // The characteristic, as little-endian 64-bit limbs.
+    const PRIME: &'static [u64];
+    // A basis over the prime field; just one for a prime field.
+    fn basis() -> Vec<Self>;
+    fn constants() -> &'static Poseidon2Constants<Self>;
+}
+
+// gcd(SBOX_DEGREE, |F| - 1) = 1, so x -> x^SBOX_DEGREE is a bijection.
+pub fn sbox_is_permutation<T: MyField>() -> bool {
+    let d = T::SBOX_DEGREE as u128;
+    let p = T::PRIME
+        .iter()
+        .rev()
+        .fold(0, |acc, &limb| ((acc << 64) | limb as u128) % d);
+    let order = (0..T::basis().len()).fold(1, |acc, _| acc * p % d);
+    let (mut a, mut b) = (d, (order + d - 1) % d);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a == 1
+}
+
+// The Grain LFSR the reference scripts draw constants from, in
+// self-shrinking mode. The 80-bit state starts with the field type (prime),
+// the S-box type (x^d), the bit length of p, the width and the numbers of
+// full and partial rounds, padded with ones; bit i of `state` is the i-th
+// oldest.
+struct Grain {
+    state: u128,
+    prime_bits: usize,
+}
+
+impl Grain {
+    fn new<T: MyField>() -> Self {
+        let top = T::PRIME.last().unwrap();
+        let prime_bits = 64 * T::PRIME.len() - top.leading_zeros() as usize;
+        let fields = [
+            (1, 2),
+            (0, 4),
+            (prime_bits, 12),
+            (T::WIDTH, 12),
+            (T::FULL_ROUNDS, 10),
+            (T::PARTIAL_ROUNDS, 10),
+        ];
+        let mut bits = vec![];
+        for (x, len) in fields {
+            bits.extend((0..len).rev().map(|j| (x >> j) & 1 == 1));
+        }
+        bits.resize(80, true);
+        let state = bits
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, &bit)| acc | (bit as u128) << i);
+        let mut grain = Grain { state, prime_bits };
+        for _ in 0..160 {
+            grain.step();
+        }
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let bit = |i: u32| self.state >> i & 1;
+        let new = bit(62) ^ bit(51) ^ bit(38) ^ bit(23) ^ bit(13) ^ bit(0);
+        self.state = self.state >> 1 | new << 79;
+        new == 1
+    }
+
+    // Of each pair of steps, the second is output if the first is one.
+    fn next_bit(&mut self) -> bool {
+        while !self.step() {
+            self.step();
+        }
+        self.step()
+    }
+
+    // Integers of `prime_bits` bits, most significant first, until one is
+    // below p.
+    fn coordinate(&mut self, prime: &[u64]) -> Vec<u64> {
+        loop {
+            let mut x = vec![0u64; prime.len()];
+            for i in (0..self.prime_bits).rev() {
+                x[i / 64] |= (self.next_bit() as u64) << (i % 64);
+            }
+            if x.iter().rev().lt(prime.iter().rev()) {
+                return x;
+            }
+        }
+    }
+
+    fn elements<T: MyField>(&mut self, n: usize) -> Vec<T> {
+        let basis = T::basis();
+        (0..n)
+            .map(|_| {
+                basis.iter().fold(T::from_int(0), |acc, &b| {
+                    acc + from_limbs::<T>(&self.coordinate(T::PRIME)) * b
+                })
+            })
+            .collect()
+    }
+
+    fn rounds<T: MyField>(&mut self, n: usize) -> Vec<Vec<T>> {
+        (0..n).map(|_| self.elements(T::WIDTH)).collect()
+    }
+}
+
+// The integer with little-endian limbs `x` as an element of the prime field.
+fn from_limbs<T: MyField>(x: &[u64]) -> T {
+    let radix = T::from_int(1 << 16).pow(4);
+    x.iter()
+        .rev()
+        .fold(T::from_int(0), |acc, &limb| acc * radix + T::from_int(limb))
+}
+
+// The coordinate unit vectors of an extension of `degree` over the prime
+// field, built from their encodings; `one(bytes, i)` sets coordinate i to 1.
+fn extension_basis<T: MyField>(degree: usize, one: impl Fn(&mut [u8], usize)) -> Vec<T> {
+    let len = T::from_int(0).to_bytes().len();
+    (0..degree)
+        .map(|i| {
+            let mut bytes = vec![0; len];
+            one(&mut bytes, i);
+            T::from_bytes(&bytes).unwrap()
+        })
+        .collect()
+}
+
+// Whether the characteristic polynomials of M, M^2, ..., M^(2 WIDTH) are all
+// irreducible, for M the all-ones matrix with `diagonal` on its diagonal.
+// This is the reference scripts' condition ruling out invariant subspaces
+// that last through the partial rounds.
+fn internal_matrix_is_sound<T: MyField>(diagonal: &[T]) -> bool {
+    let n = diagonal.len();
+    let m = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| if i == j { diagonal[i] } else { T::from_int(1) })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut power = m.clone();
+    for _ in 0..2 * n {
+        if !is_irreducible(&characteristic_polynomial(&power)) {
+            return false;
+        }
+        power = matrix_mul(&m, &power);
+    }
+    true
+}
+
+fn matrix_mul<T: MyField>(a: &[Vec<T>], b: &[Vec<T>]) -> Vec<Vec<T>> {
+    a.iter()
+        .map(|row| {
+            (0..b.len())
+                .map(|j| {
+                    row.iter()
+                        .zip(b)
+                        .fold(T::from_int(0), |acc, (&x, col)| acc + x * col[j])
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Faddeev-LeVerrier; coefficients lowest degree first, monic.
+fn characteristic_polynomial<T: MyField>(m: &[Vec<T>]) -> Vec<T> {
+    let n = m.len();
+    let mut c = vec![T::from_int(0); n + 1];
+    c[n] = T::from_int(1);
+    let mut mk = vec![vec![T::from_int(0); n]; n];
+    for k in 1..=n {
+        for (i, row) in mk.iter_mut().enumerate() {
+            row[i] += c[n - k + 1];
+        }
+        mk = matrix_mul(m, &mk);
+        let trace = (0..n).fold(T::from_int(0), |acc, i| acc + mk[i][i]);
+        c[n - k] = -trace * T::from_int(k as u64).inverse();
+    }
+    c
+}
+
+// Polynomials below are coefficient vectors, lowest degree first.
+fn trim<T: MyField>(mut a: Vec<T>) -> Vec<T> {
+    while a.last().is_some_and(|x| x.is_zero()) {
+        a.pop();
+    }
+    a
+}
+
+fn poly_rem<T: MyField>(mut a: Vec<T>, f: &[T]) -> Vec<T> {
+    let n = f.len() - 1;
+    let lead = f[n].inverse();
+    while a.len() > n {
+        let c = a.pop().unwrap() * lead;
+        let shift = a.len() - n;
+        for (x, &y) in a[shift..].iter_mut().zip(f) {
+            *x -= c * y;
+        }
+    }
+    a
+}
+
+fn poly_mul_mod<T: MyField>(a: &[T], b: &[T], f: &[T]) -> Vec<T> {
+    let mut product = vec![T::from_int(0); a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            product[i + j] += x * y;
+        }
+    }
+    poly_rem(product, f)
+}
+
+fn poly_sub<T: MyField>(a: &[T], b: &[T]) -> Vec<T> {
+    let zero = T::from_int(0);
+    trim(
+        (0..a.len().max(b.len()))
+            .map(|i| *a.get(i).unwrap_or(&zero) - *b.get(i).unwrap_or(&zero))
+            .collect(),
+    )
+}
+
+fn poly_gcd<T: MyField>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
+    let (mut a, mut b) = (trim(a), trim(b));
+    while !b.is_empty() {
+        let r = trim(poly_rem(a, &b));
+        a = b;
+        b = r;
+    }
+    a
+}
+
+// g^|F| mod f, as p-th powers taken once per basis element.
+fn frobenius<T: MyField>(g: &[T], f: &[T]) -> Vec<T> {
+    let mut g = g.to_vec();
+    for _ in 0..T::basis().len() {
+        let mut power = vec![T::from_int(1)];
+        for limb in T::PRIME.iter().rev() {
+            for i in (0..64).rev() {
+                power = poly_mul_mod(&power, &power, f);
+                if limb >> i & 1 == 1 {
+                    power = poly_mul_mod(&power, &g, f);
+                }
+            }
+        }
+        g = power;
+    }
+    g
+}
+
+// Rabin's test: a monic f of degree n > 1 is irreducible iff x^(q^n) = x
+// mod f and x^(q^(n/r)) - x is coprime to f for every prime r dividing n.
+fn is_irreducible<T: MyField>(f: &[T]) -> bool {
+    let n = f.len() - 1;
+    let x = vec![T::from_int(0), T::from_int(1)];
+    let mut powers = vec![x.clone()];
+    for j in 0..n {
+        powers.push(frobenius(&powers[j], f));
+    }
+    if !poly_sub(&powers[n], &x).is_empty() {
+        return false;
+    }
+    (2..=n)
+        .filter(|&r| n.is_multiple_of(r) && (2..r).all(|s| !r.is_multiple_of(s)))
+        .all(|r| poly_gcd(f.to_vec(), poly_sub(&powers[n / r], &x)).len() == 1)
+}
+
+#[inline]
+fn sbox<T: MyField>(x: T) -> T {
+    x.pow(T::SBOX_DEGREE)
+}
+
+// circ(2 M4, M4, ..., M4) for widths divisible by 4 (just M4 for width 4),
+// circ(2, 1[, 1]) otherwise.
+fn external_linear_layer<T: MyField>(state: &mut [T]) {
+    let width = state.len();
+    if width < 4 {
+        let sum = state.iter().fold(T::from_int(0), |acc, &x| acc + x);
+        state.iter_mut().for_each(|x| *x += sum);
+        return;
+    }
+    for chunk in state.chunks_exact_mut(4) {
+        let [a, b, c, d] = [chunk[0], chunk[1], chunk[2], chunk[3]];
+        let m = |x: [u64; 4]| {
+            T::from_int(x[0]) * a
+                + T::from_int(x[1]) * b
+                + T::from_int(x[2]) * c
+                + T::from_int(x[3]) * d
+        };
+        chunk.copy_from_slice(&[
+            m([5, 7, 1, 3]),
+            m([4, 6, 1, 1]),
+            m([1, 3, 5, 7]),
+            m([1, 1, 4, 6]),
+        ]);
+    }
+    if width == 4 {
+        return;
+    }
+    let sums = (0..4)
+        .map(|j| {
+            (j..width)
+                .step_by(4)
+                .fold(T::from_int(0), |acc, i| acc + state[i])
+        })
+        .collect::<Vec<_>>();
+    for (i, x) in state.iter_mut().enumerate() {
+        *x += sums[i % 4];
+    }
+}
+
+fn internal_linear_layer<T: MyField>(state: &mut [T], diagonal: &[T]) {
+    let sum = state.iter().fold(T::from_int(0), |acc, &x| acc + x);
+    for (x, &d) in state.iter_mut().zip(diagonal) {
+        *x = *x * d + sum;
+    }
+}
+
+pub fn permute<T: MyField>(state: &mut [T]) {
+    assert_eq!(state.len(), T::WIDTH);
+    let constants = T::constants();
+    let half = T::FULL_ROUNDS / 2;
+    external_linear_layer(state);
+    for round in &constants.external[..half] {
+        for (x, &c) in state.iter_mut().zip(round) {
+            *x = sbox(*x + c);
+        }
+        external_linear_layer(state);
+    }
+    for &c in &constants.internal {
+        state[0] = sbox(state[0] + c);
+        internal_linear_layer(state, &constants.diagonal);
+    }
+    for round in &constants.external[half..] {
+        for (x, &c) in state.iter_mut().zip(round) {
+            *x = sbox(*x + c);
+        }
+        external_linear_layer(state);
+    }
+}
+
+// Duplex sponge adding up to `RATE` elements into the state per permutation.
+// Every `absorb` call is prefixed with its length plus one, so a sequence of
+// calls is encoded injectively and interleaved squeezes are unambiguous.
+#[derive(Debug, Clone)]
+pub struct Poseidon2Sponge<T: MyField> {
+    state: Vec<T>,
+    input: Vec<T>,
+    output: Vec<T>,
+}
+
+impl<T: MyField> Default for Poseidon2Sponge<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: MyField> Poseidon2Sponge<T> {
+    pub fn new() -> Self {
+        Poseidon2Sponge {
+            state: vec![T::from_int(0); T::WIDTH],
+            input: vec![],
+            output: vec![],
+        }
+    }
+
+    fn duplex(&mut self) {
+        for (x, &y) in self.state.iter_mut().zip(&self.input) {
+            *x += y;
+        }
+        permute(&mut self.state);
+        self.input.clear();
+        self.output = self.state[..T::RATE].to_vec();
+    }
+
+    pub fn absorb(&mut self, elements: &[T]) {
+        self.output.clear();
+        for &x in std::iter::once(&T::from_int(elements.len() as u64 + 1)).chain(elements) {
+            self.input.push(x);
+            if self.input.len() == T::RATE {
+                self.duplex();
+                self.output.clear();
+            }
+        }
+    }
+
+    // Bytes go in as 16-bit limbs, which is injective in every supported field.
+    pub fn absorb_bytes(&mut self, bytes: &[u8]) {
+        let elements = std::iter::once(T::from_int(bytes.len() as u64))
+            .chain(
+                bytes
+                    .chunks(2)
+                    .map(|x| T::from_int(x.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64))),
+            )
+            .collect::<Vec<_>>();
+        self.absorb(&elements);
+    }
+
+    pub fn squeeze(&mut self) -> T {
+        if !self.input.is_empty() || self.output.is_empty() {
+            self.duplex();
+        }
+        self.output.remove(0)
+    }
+
+    pub fn squeeze_n(&mut self, n: usize) -> Vec<T> {
+        (0..n).map(|_| self.squeeze()).collect()
+    }
+}
+
+pub fn hash<T: MyField>(input: &[T], output_len: usize) -> Vec<T> {
+    let mut sponge = Poseidon2Sponge::new();
+    sponge.absorb(input);
+    sponge.squeeze_n(output_len)
+}
+
+// Two-to-one compression: one permutation of `left || right || 0`, truncated
+// to the length of `left`.
+pub fn compress<T: MyField>(left: &[T], right: &[T]) -> Vec<T> {
+    assert_eq!(left.len(), right.len());
+    assert!(left.len() * 2 <= T::WIDTH);
+    let mut state = vec![T::from_int(0); T::WIDTH];
+    state[..left.len()].copy_from_slice(left);
+    state[left.len()..left.len() * 2].copy_from_slice(right);
+    permute(&mut state);
+    state.truncate(left.len());
+    state
+}
+
+impl Poseidon2Field for Goldilocks64 {
+    const WIDTH: usize = 12;
+    const RATE: usize = 8;
+    const SBOX_DEGREE: usize = 7;
+    const FULL_ROUNDS: usize = 8;
+    const PARTIAL_ROUNDS: usize = 22;
+    const PRIME: &'static [u64] = &[0xffff_ffff_0000_0001];
+    fn basis() -> Vec<Self> {
+        vec![Self::from_int(1)]
+    }
+    fn constants() -> &'static Poseidon2Constants<Self> {
+        static CONSTANTS: Lazy<Poseidon2Constants<Goldilocks64>> =
+            Lazy::new(Poseidon2Constants::generate);
+        &CONSTANTS
+    }
+}
+
+impl Poseidon2Field for Goldilocks64Ext {
+    const WIDTH: usize = 8;
+    const RATE: usize = 4;
+    const SBOX_DEGREE: usize = 11;
+    const FULL_ROUNDS: usize = 8;
+    const PARTIAL_ROUNDS: usize = 22;
+    const PRIME: &'static [u64] = &[0xffff_ffff_0000_0001];
+    fn basis() -> Vec<Self> {
+        extension_basis::<Self>(2, |bytes, i| bytes[8 * i] = 1)
+    }
+    fn constants() -> &'static Poseidon2Constants<Self> {
+        static CONSTANTS: Lazy<Poseidon2Constants<Goldilocks64Ext>> =
+            Lazy::new(Poseidon2Constants::generate);
+        &CONSTANTS
+    }
+}
+
+impl Poseidon2Field for Mersenne61Ext {
+    const WIDTH: usize = 8;
+    const RATE: usize = 4;
+    const SBOX_DEGREE: usize = 17;
+    const FULL_ROUNDS: usize = 8;
+    const PARTIAL_ROUNDS: usize = 22;
+    const PRIME: &'static [u64] = &[(1 << 61) - 1];
+    fn basis() -> Vec<Self> {
+        extension_basis::<Self>(2, |bytes, i| bytes[8 * i] = 1)
+    }
+    fn constants() -> &'static Poseidon2Constants<Self> {
+        static CONSTANTS: Lazy<Poseidon2Constants<Mersenne61Ext>> =
+            Lazy::new(Poseidon2Constants::generate);
+        &CONSTANTS
+    }
+}
+
+impl Poseidon2Field for M31ext {
+    const WIDTH: usize = 8;
+    const RATE: usize = 4;
+    const SBOX_DEGREE: usize = 13;
+    const FULL_ROUNDS: usize = 8;
+    const PARTIAL_ROUNDS: usize = 22;
+    const PRIME: &'static [u64] = &[(1 << 31) - 1];
+    fn basis() -> Vec<Self> {
+        extension_basis::<Self>(4, |bytes, i| bytes[4 * i + 3] = 1)
+    }
+    fn constants() -> &'static Poseidon2Constants<Self> {
+        static CONSTANTS: Lazy<Poseidon2Constants<M31ext>> =
+            Lazy::new(Poseidon2Constants::generate);
+        &CONSTANTS
+    }
+}
+
+impl Poseidon2Field for Bn254Fr {
+    const WIDTH: usize = 3;
+    const RATE: usize = 2;
+    const SBOX_DEGREE: usize = 5;
+    const FULL_ROUNDS: usize = 8;
+    const PARTIAL_ROUNDS: usize = 56;
+    const PRIME: &'static [u64] = &[
+        0x43e1_f593_f000_0001,
+        0x2833_e848_79b9_7091,
+        0xb850_45b6_8181_585d,
+        0x3064_4e72_e131_a029,
+    ];
+    fn basis() -> Vec<Self> {
+        vec![Self::from_int(1)]
+    }
+    fn constants() -> &'static Poseidon2Constants<Self> {
+        static CONSTANTS: Lazy<Poseidon2Constants<Bn254Fr>> =
+            Lazy::new(Poseidon2Constants::generate);
+        &CONSTANTS
+    }
+}
+
+impl Poseidon2Field for Ft255 {
+    const WIDTH: usize = 3;
+    const RATE: usize = 2;
+    const SBOX_DEGREE: usize = 5;
+    const FULL_ROUNDS: usize = 8;
+    const PARTIAL_ROUNDS: usize = 56;
+    const PRIME: &'static [u64] = &[
+        0x02a4_f200_0000_0001,
+        0xef73_c790_8659_5f30,
+        0xfda9_df04_b957_5969,
+        0x663c_799b_6e4d_2900,
+    ];
+    fn basis() -> Vec<Self> {
+        vec![Self::from_int(1)]
+    }
+    fn constants() -> &'static Poseidon2Constants<Self> {
+        static CONSTANTS: Lazy<Poseidon2Constants<Ft255>> = Lazy::new(Poseidon2Constants::generate);
+        &CONSTANTS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_permutation<T: MyField>() {
+        let input = (0..T::WIDTH)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let mut a = input.clone();
+        let mut b = input.clone();
+        permute(&mut a);
+        permute(&mut b);
+        assert_eq!(a, b);
+        assert_ne!(a, input);
+        b = input.clone();
+        b[T::WIDTH - 1] += T::from_int(1);
+        permute(&mut b);
+        assert!(a.iter().zip(&b).all(|(x, y)| x != y));
+
+        let x = hash(&input[..3], 2);
+        assert_eq!(x, hash(&input[..3], 2));
+        assert_ne!(x, hash(&input[..2], 2));
+        assert_ne!(hash::<T>(&[], 2), hash(&[T::from_int(0)], 2));
+    }
+
+    fn check_sponge<T: MyField>() {
+        let a = T::random_element();
+        let b = T::random_element();
+        let mut s1 = Poseidon2Sponge::new();
+        s1.absorb(&[a, b]);
+        let mut s2 = Poseidon2Sponge::new();
+        s2.absorb(&[a]);
+        s2.absorb(&[b]);
+        let x = s1.squeeze();
+        assert_ne!(x, s2.squeeze());
+        assert_ne!(x, s1.squeeze());
+
+        let mut s3 = Poseidon2Sponge::new();
+        s3.absorb(&[a, b]);
+        assert_eq!(s3.squeeze_n(T::RATE + 1)[0], x);
+        let mut s4 = Poseidon2Sponge::<T>::new();
+        s4.absorb_bytes(&[1]);
+        let mut s5 = Poseidon2Sponge::<T>::new();
+        s5.absorb_bytes(&[1, 0]);
+        assert_ne!(s4.squeeze(), s5.squeeze());
+
+        let digest = compress(&[a], &[b]);
+        assert_eq!(digest.len(), 1);
+        assert_eq!(digest, compress(&[a], &[b]));
+        assert_ne!(digest, compress(&[b], &[a]));
+    }
+
+    // An element from the hex strings of its coordinates in `basis()`.
+    fn from_hex<T: MyField>(coordinates: &[&str]) -> T {
+        coordinates
+            .iter()
+            .zip(T::basis())
+            .fold(T::from_int(0), |acc, (x, b)| {
+                let digits = x.trim_start_matches("0x").as_bytes();
+                let limbs = digits
+                    .rchunks(16)
+                    .map(|limb| {
+                        u64::from_str_radix(std::str::from_utf8(limb).unwrap(), 16).unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                acc + from_limbs::<T>(&limbs) * b
+            })
+    }
+
+    // Checks the permutation of (0, 1, ..., WIDTH - 1).
+    fn permutation_vector<T: MyField>(expected: &[&[&str]]) {
+        let mut state = (0..T::WIDTH as u64).map(T::from_int).collect::<Vec<_>>();
+        permute(&mut state);
+        let expected = expected.iter().map(|x| from_hex(x)).collect::<Vec<T>>();
+        assert_eq!(state, expected);
+    }
+
+    #[test]
+    fn sponge() {
+        check_sponge::<Goldilocks64>();
+        check_sponge::<Goldilocks64Ext>();
+        check_sponge::<Mersenne61Ext>();
+        check_sponge::<M31ext>();
+        check_sponge::<Bn254Fr>();
+        check_sponge::<Ft255>();
+    }
+
+    #[test]
+    fn sbox_degrees() {
+        assert!(sbox_is_permutation::<Goldilocks64>());
+        assert!(sbox_is_permutation::<Goldilocks64Ext>());
+        assert!(sbox_is_permutation::<Mersenne61Ext>());
+        assert!(sbox_is_permutation::<M31ext>());
+        assert!(sbox_is_permutation::<Bn254Fr>());
+        assert!(sbox_is_permutation::<Ft255>());
+    }
+
+    // Test vectors of the reference implementation accompanying the Poseidon2
+    // paper (github.com/HorizenLabs/poseidon2), which covers prime fields only.
+    #[test]
+    fn reference_vectors() {
+        permutation_vector::<Goldilocks64>(&[
+            &["0x01eaef96bdf1c0c1"],
+            &["0x1f0d2cc525b2540c"],
+            &["0x6282c1dfe1e0358d"],
+            &["0xe780d721f698e1e6"],
+            &["0x280c0b6f753d833b"],
+            &["0x1b942dd5023156ab"],
+            &["0x43f0df3fcccb8398"],
+            &["0xe8e8190585489025"],
+            &["0x56bdbf72f77ada22"],
+            &["0x7911c32bf9dcd705"],
+            &["0xec467926508fbe67"],
+            &["0x6a50450ddf85a6ed"],
+        ]);
+        permutation_vector::<Bn254Fr>(&[
+            &["0x0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033"],
+            &["0x303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570"],
+            &["0x1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8"],
+        ]);
+    }
+
+    // Outputs of this implementation for the instances the reference does not
+    // cover. They were generated here, not cross-checked against another
+    // implementation, and only guard against unintended changes.
+    #[test]
+    fn regression_vectors() {
+        permutation_vector::<Goldilocks64Ext>(&[
+            &["0xf0ec9478d5ebb9f8", "0x8e746062c6ca2d9b"],
+            &["0x8dff1d76c8d356ee", "0x8ca065f783d7906c"],
+            &["0x53de7ceb662ecee0", "0xc5e2059184d2bc8a"],
+            &["0xa1518f0d50f31037", "0x5e10e0978ecb8893"],
+            &["0x678e982398acc462", "0x492b0a59fca179c5"],
+            &["0x637b26f0269c408d", "0xcbdbb681f7d41012"],
+            &["0x3c670e85c693a1ec", "0xdec7597c15d1c657"],
+            &["0x61c58dc8728cd41f", "0x7b0f927ae1feae3"],
+        ]);
+        permutation_vector::<Mersenne61Ext>(&[
+            &["0x3eacddaf9605e35", "0x1c49c1c2917dd72a"],
+            &["0x1bcea5b0eba49371", "0x1de2a0c0f4f49258"],
+            &["0x1d22ea8071e84766", "0x9cd3a6888ec8c64"],
+            &["0x63a0900f19fa8cf", "0x5ae89188e4e9e17"],
+            &["0x18732c1591ed86a3", "0x5c3ce72acc49d51"],
+            &["0x1dd01474e4c7e640", "0x1da619ed85b956ff"],
+            &["0xc9f269ae6526c8b", "0x279a247525b04b5"],
+            &["0x242457a765d43d5", "0x1901acbebb37a119"],
+        ]);
+        permutation_vector::<M31ext>(&[
+            &["0x27b8580e", "0xb172d86", "0x339203da", "0x794a2e21"],
+            &["0x72ac9bcf", "0x585c0974", "0x38e35677", "0x5408bfed"],
+            &["0x44bd9344", "0x2fc0efc4", "0x1901cd54", "0x7fd8e2b"],
+            &["0x158da556", "0xa925f72", "0x311365ea", "0x3841692b"],
+            &["0x30a8dea4", "0x283e746c", "0x3605130f", "0xc077525"],
+            &["0x1a165c2a", "0x6f5eec82", "0x744c69c7", "0x4b7c750"],
+            &["0x12a76225", "0x66c0c41c", "0x50b3af0e", "0x4d9e7b63"],
+            &["0x5250a4d4", "0x332b33eb", "0x5c8a329b", "0x14ea36eb"],
+        ]);
+        permutation_vector::<Ft255>(&[
+            &["0x22b03ad8141cfbd3e4532db00f1e4eb6481090c0925e5f02e80398d891eba82"],
+            &["0x26a0b2c4c5f2ca052478f0dfd24936e120a5e914847fabb2beced7773e824f0a"],
+            &["0x618deb7c340700cd77b6c01e8afb12021c08e4ef130f248132c13d1e8f4f8d96"],
+        ]);
+    }
+
+    #[test]
+    fn permutation() {
+        check_permutation::<Goldilocks64>();
+        check_permutation::<Goldilocks64Ext>();
+        check_permutation::<Mersenne61Ext>();
+        check_permutation::<M31ext>();
+        check_permutation::<Bn254Fr>();
+        check_permutation::<Ft255>();
+    }
+}
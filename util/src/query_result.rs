@@ -1,45 +1,412 @@
+use crate::algebra::coset::Coset;
 use crate::algebra::field::{as_bytes_vec, MyField};
-use crate::merkle_tree::MerkleTreeVerifier;
-use std::collections::HashMap;
+use crate::error::VerificationError;
+use crate::merkle_tree::{MerkleTreeVerifier, SALT_SIZE};
+use crate::serialization::{
+    deserialize_with, sequence_size, serialize_with, Reader, Serializable, SerializationError,
+    Writer, LENGTH_SIZE,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::mem::size_of;
 
-#[derive(Clone)]
+// The opened leaves of one tree. Leaf `leaf_indices[k]` holds
+// `values[k * w..(k + 1) * w]` for the common leaf width `w`, in the order
+// they were hashed: offset `j` of an `InterpolateValue` leaf `i` is codeword
+// entry `i + j * leave_num`, and a `MultiInterpolateValue` leaf lists each
+// codeword's offsets in turn.
+#[derive(Clone, Debug, PartialEq)]
 pub struct QueryResult<T: MyField> {
     pub proof_bytes: Vec<u8>,
-    // Cauchy: Why use hashmap rather than Vec here?
-    pub proof_values: HashMap<usize, T>,
+    // Strictly increasing.
+    leaf_indices: Vec<usize>,
+    pub values: Vec<T>,
+    // Salts of the opened leaves in `leaf_indices` order; empty unless the
+    // tree is salted.
+    pub salts: Vec<[u8; SALT_SIZE]>,
 }
 
 impl<T: MyField> QueryResult<T> {
+    pub fn new(
+        proof_bytes: Vec<u8>,
+        leaf_indices: Vec<usize>,
+        values: Vec<T>,
+        salts: Vec<[u8; SALT_SIZE]>,
+    ) -> Self {
+        debug_assert!(leaf_indices.windows(2).all(|w| w[0] < w[1]));
+        QueryResult {
+            proof_bytes,
+            leaf_indices,
+            values,
+            salts,
+        }
+    }
+
+    pub fn leaf_indices(&self) -> &[usize] {
+        &self.leaf_indices
+    }
+
     pub fn verify_merkle_tree(
         &self,
+        round: usize,
         leaf_indices: &Vec<usize>,
         leaf_size: usize,
         merkle_verifier: &MerkleTreeVerifier,
-    ) -> bool {
-        let len = merkle_verifier.leave_number;
+    ) -> Result<(), VerificationError> {
+        self.verify_merkle_tree_multi(round, leaf_indices, leaf_size, 1, merkle_verifier)
+    }
 
-        let leaves: Vec<Vec<u8>> = leaf_indices
-            .iter()
-            .map(|x| {
-                as_bytes_vec(
-                    &(0..leaf_size)
-                        .map(|j| {
-                            self.proof_values
-                                .get(&(x.clone() + j * len))
-                                .unwrap()
-                                .clone()
-                        })
-                        .collect::<Vec<_>>(),
-                )
+    // Checks a tree built by `MultiInterpolateValue` over `width` codewords.
+    // The opened leaves must be exactly `leaf_indices`.
+    pub fn verify_merkle_tree_multi(
+        &self,
+        round: usize,
+        leaf_indices: &Vec<usize>,
+        leaf_size: usize,
+        width: usize,
+        merkle_verifier: &MerkleTreeVerifier,
+    ) -> Result<(), VerificationError> {
+        self.check_shape(leaf_indices, leaf_size * width)?;
+        let leaves = self.leaves();
+        let valid = if self.salts.is_empty() {
+            merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves)
+        } else {
+            merkle_verifier.verify_salted(
+                self.proof_bytes.clone(),
+                leaf_indices,
+                &self.salts,
+                &leaves,
+            )
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(VerificationError::MerklePathInvalid {
+                round,
+                index: leaf_indices.first().cloned().unwrap_or_default(),
             })
+        }
+    }
+
+    // Checks results of `InterpolateValue::query_many`, the k-th against the
+    // tree of `merkle_verifiers[k]`, all opened at `leaf_indices`.
+    pub fn verify_merkle_trees(
+        queries: &[&Self],
+        round: usize,
+        leaf_indices: &Vec<usize>,
+        leaf_size: usize,
+        merkle_verifiers: &[MerkleTreeVerifier],
+    ) -> Result<(), VerificationError> {
+        for query in queries {
+            query.check_shape(leaf_indices, leaf_size)?;
+        }
+        let proof_bytes = queries
+            .iter()
+            .flat_map(|query| query.proof_bytes.iter().copied())
             .collect();
-        let res = merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves);
-        assert!(res);
-        res
+        let salts = queries
+            .iter()
+            .map(|query| query.salts.as_slice())
+            .collect::<Vec<_>>();
+        let leaves = queries
+            .iter()
+            .map(|query| query.leaves())
+            .collect::<Vec<_>>();
+        if MerkleTreeVerifier::verify_many(
+            merkle_verifiers,
+            proof_bytes,
+            leaf_indices,
+            &salts,
+            &leaves,
+        ) {
+            Ok(())
+        } else {
+            Err(VerificationError::MerklePathInvalid {
+                round,
+                index: leaf_indices.first().cloned().unwrap_or_default(),
+            })
+        }
+    }
+
+    fn check_shape(
+        &self,
+        leaf_indices: &Vec<usize>,
+        leaf_width: usize,
+    ) -> Result<(), VerificationError> {
+        if self.leaf_indices != *leaf_indices {
+            return Err(VerificationError::MalformedProof("query indices"));
+        }
+        if self.values.len() != leaf_indices.len() * leaf_width {
+            return Err(VerificationError::MalformedProof("query value count"));
+        }
+        if !self.salts.is_empty() && self.salts.len() != leaf_indices.len() {
+            return Err(VerificationError::MalformedProof("salt count"));
+        }
+        Ok(())
+    }
+
+    fn leaves(&self) -> Vec<Vec<u8>> {
+        (0..self.leaf_indices.len())
+            .map(|k| as_bytes_vec(self.leaf(k)))
+            .collect()
+    }
+
+    pub fn leaf_width(&self) -> usize {
+        if self.leaf_indices.is_empty() {
+            0
+        } else {
+            self.values.len() / self.leaf_indices.len()
+        }
+    }
+
+    // The values of the `position`-th opened leaf.
+    pub fn leaf(&self, position: usize) -> &[T] {
+        let width = self.leaf_width();
+        &self.values[position * width..(position + 1) * width]
+    }
+
+    pub fn position(&self, leaf: usize) -> Result<usize, VerificationError> {
+        self.leaf_indices
+            .binary_search(&leaf)
+            .map_err(|_| VerificationError::MalformedProof("missing query value"))
+    }
+
+    // Value at `offset` of leaf `leaf`; callers walking `leaf_indices` in
+    // order should index `leaf(position)` instead.
+    pub fn get(&self, leaf: usize, offset: usize) -> Result<T, VerificationError> {
+        let position = self.position(leaf)?;
+        self.leaf(position)
+            .get(offset)
+            .cloned()
+            .ok_or(VerificationError::MalformedProof("missing query value"))
+    }
+
+    // Codeword entry `index` of a single-codeword tree with `leave_num` leaves.
+    pub fn entry(&self, index: usize, leave_num: usize) -> Result<T, VerificationError> {
+        self.get(index % leave_num, index / leave_num)
+    }
+
+    // The entries of codeword `column` in the `position`-th opened leaf of a
+    // `MultiInterpolateValue` tree with `leaf_size` entries per codeword.
+    pub fn column(&self, position: usize, column: usize, leaf_size: usize) -> &[T] {
+        &self.leaf(position)[column * leaf_size..(column + 1) * leaf_size]
     }
 
+    // Entry `index` of codeword `column` of such a tree with `leave_num` leaves.
+    pub fn column_entry(
+        &self,
+        index: usize,
+        leave_num: usize,
+        column: usize,
+        leaf_size: usize,
+    ) -> Result<T, VerificationError> {
+        let offset = index / leave_num;
+        if offset >= leaf_size {
+            return Err(VerificationError::MalformedProof("missing query value"));
+        }
+        self.get(index % leave_num, column * leaf_size + offset)
+    }
+
+    // Folds the `position`-th leaf down to one value, see `fold`.
+    pub fn fold(&self, position: usize, cosets: &[Coset<T>], challenges: &[T]) -> T {
+        fold(
+            self.leaf(position).to_vec(),
+            self.leaf_indices[position],
+            cosets,
+            challenges,
+        )
+    }
+
+    // Exact length of the encoding produced by `write_to`.
     pub fn proof_size(&self) -> usize {
-        self.proof_bytes.len() + self.proof_values.len() * size_of::<T>()
+        LENGTH_SIZE
+            + self.proof_bytes.len()
+            + sequence_size(self.leaf_indices.len(), INDEX_SIZE)
+            + sequence_size(self.values.len(), size_of::<T>())
+            + sequence_size(self.salts.len(), SALT_SIZE)
+    }
+}
+
+// Folds the `2^k` values of leaf `leaf` of a codeword over `cosets[0]`, one
+// challenge per coset: values at offsets `l` and `l + size / 2` become
+// `x + nx + c * (x - nx) / w` with `w` the coset element of offset `l`.
+pub fn fold<T: MyField>(
+    mut values: Vec<T>,
+    leaf: usize,
+    cosets: &[Coset<T>],
+    challenges: &[T],
+) -> T {
+    assert_eq!(values.len(), 1 << challenges.len());
+    let len = cosets[0].size() / values.len();
+    for (coset, &challenge) in cosets.iter().zip(challenges) {
+        let size = values.len() / 2;
+        values = (0..size)
+            .map(|l| {
+                let x = values[l];
+                let nx = values[l + size];
+                x + nx + challenge * (x - nx) * coset.element_inv_at(leaf + l * len)
+            })
+            .collect();
+    }
+    values[0]
+}
+
+const INDEX_SIZE: usize = 4;
+
+// Leaf indices must be strictly increasing and the values must split evenly
+// into leaves; the decoder rejects anything else.
+impl<T: MyField> Serializable for QueryResult<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_bytes(&self.proof_bytes);
+        writer.write_length(self.leaf_indices.len());
+        for &i in &self.leaf_indices {
+            writer.write_u32(u32::try_from(i).expect("index does not fit in u32"));
+        }
+        writer.write_fields(&self.values);
+        writer.write_salts(&self.salts);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let proof_bytes = reader.read_bytes()?;
+        let len = reader.read_length(INDEX_SIZE)?;
+        let mut leaf_indices = Vec::with_capacity(len);
+        for _ in 0..len {
+            let index = reader.read_u32()? as usize;
+            if leaf_indices.last().is_some_and(|&x| x >= index) {
+                return Err(SerializationError::NonCanonical("query result indices"));
+            }
+            leaf_indices.push(index);
+        }
+        let values = reader.read_fields()?;
+        if (len == 0 && !values.is_empty()) || (len != 0 && values.len() % len != 0) {
+            return Err(SerializationError::NonCanonical("query result values"));
+        }
+        Ok(QueryResult::new(
+            proof_bytes,
+            leaf_indices,
+            values,
+            reader.read_salts()?,
+        ))
+    }
+}
+
+impl<T: MyField> Serialize for QueryResult<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for QueryResult<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use crate::interpolation::{InterpolateValue, MultiInterpolateValue};
+    use crate::merkle_tree::HashChoice;
+
+    #[test]
+    fn leaf_lookup_and_fold() {
+        let coset = Coset::new(1 << 6, Mersenne61Ext::random_element());
+        let cosets = vec![coset.clone(), coset.pow(2)];
+        let codeword = (0..64)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let oracle = InterpolateValue::new(
+            HashChoice::Blake3.tree_hash::<Mersenne61Ext>(),
+            codeword.clone(),
+            4,
+        );
+        let query = oracle.query(&vec![1, 6, 9]);
+        assert_eq!(query.leaf_width(), 4);
+        assert_eq!(query.leaf(1)[2], codeword[6 + 2 * 16]);
+        assert_eq!(query.entry(6 + 2 * 16, 16), Ok(codeword[38]));
+        assert!(query.get(7, 0).is_err());
+        assert!(query.get(6, 4).is_err());
+        let decoded = QueryResult::<Mersenne61Ext>::decode(&query.encode()).unwrap();
+        assert_eq!(decoded.position(9), Ok(2));
+        assert_eq!(decoded.entry(6 + 2 * 16, 16), Ok(codeword[38]));
+
+        let challenges = [
+            Mersenne61Ext::random_element(),
+            Mersenne61Ext::random_element(),
+        ];
+        let mut folded = codeword;
+        for (coset, &challenge) in cosets.iter().zip(&challenges) {
+            let len = folded.len() / 2;
+            folded = (0..len)
+                .map(|i| {
+                    let (x, nx) = (folded[i], folded[i + len]);
+                    x + nx + challenge * (x - nx) * coset.element_inv_at(i)
+                })
+                .collect();
+        }
+        assert_eq!(query.fold(2, &cosets, &challenges), folded[9]);
+    }
+
+    #[test]
+    fn shared_openings() {
+        let hash = HashChoice::Blake3
+            .tree_hash::<Mersenne61Ext>()
+            .with_arity(4);
+        let oracles = (0..2)
+            .map(|_| {
+                let codeword = (0..64)
+                    .map(|_| Mersenne61Ext::random_element())
+                    .collect::<Vec<_>>();
+                InterpolateValue::from_buffer_salted(hash, codeword.into(), 2)
+            })
+            .collect::<Vec<_>>();
+        let leaf_indices = vec![0, 5, 6, 31];
+        let queries = InterpolateValue::query_many(&[&oracles[0], &oracles[1]], &leaf_indices);
+        for (query, oracle) in queries.iter().zip(&oracles) {
+            assert_eq!(*query, oracle.query(&leaf_indices));
+        }
+        let verifiers = oracles
+            .iter()
+            .map(|oracle| MerkleTreeVerifier::new(hash, 32, &oracle.commit()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            QueryResult::verify_merkle_trees(
+                &[&queries[0], &queries[1]],
+                0,
+                &leaf_indices,
+                2,
+                &verifiers
+            ),
+            Ok(())
+        );
+        assert!(QueryResult::verify_merkle_trees(
+            &[&queries[1], &queries[0]],
+            0,
+            &leaf_indices,
+            2,
+            &verifiers
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn column_lookup() {
+        let codewords = (0..2)
+            .map(|_| {
+                (0..32)
+                    .map(|_| Mersenne61Ext::random_element())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let oracle = MultiInterpolateValue::new(
+            HashChoice::Blake3.tree_hash::<Mersenne61Ext>(),
+            codewords.clone(),
+            2,
+        );
+        let query = oracle.query(&vec![3, 10]);
+        assert_eq!(query.column(1, 1, 2), [codewords[1][10], codewords[1][26]]);
+        assert_eq!(query.column_entry(26, 16, 0, 2), Ok(codewords[0][26]));
+        assert_eq!(query.column_entry(3, 16, 1, 2), Ok(codewords[1][3]));
+        assert!(query.column_entry(35, 16, 0, 2).is_err());
     }
 }
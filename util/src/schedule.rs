@@ -0,0 +1,158 @@
+use crate::serialization::{Reader, Serializable, SerializationError, Writer};
+
+// More rounds than any domain this crate can build.
+pub const MAX_ROUNDS: usize = 64;
+
+// Variables folded in each round, e.g. `[4, 3, 1]` folds by 16, then 8, then
+// 2. Round `i` starts from the domain with `offset(i)` variables already
+// folded. The fixed capacity keeps `PcsParams` `Copy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldingSchedule {
+    len: u8,
+    steps: [u8; MAX_ROUNDS],
+}
+
+impl FoldingSchedule {
+    pub fn new(steps: &[usize]) -> Self {
+        assert!(steps.len() <= MAX_ROUNDS, "too many folding rounds");
+        let mut res = FoldingSchedule {
+            len: steps.len() as u8,
+            steps: [0; MAX_ROUNDS],
+        };
+        for (i, &step) in steps.iter().enumerate() {
+            assert!(step > 0 && step <= u8::MAX as usize, "invalid folding step");
+            res.steps[i] = step as u8;
+        }
+        res
+    }
+
+    // `total` variables folded `step` at a time; the last round folds
+    // whatever is left when `step` does not divide `total`.
+    pub fn uniform(total: usize, step: usize) -> Self {
+        assert!(step > 0, "invalid folding step");
+        let mut steps = vec![step; total / step];
+        if total % step != 0 {
+            steps.push(total % step);
+        }
+        Self::new(&steps)
+    }
+
+    // Every schedule folding `total` variables, in lexicographic order.
+    pub fn all(total: usize) -> Vec<Self> {
+        fn extend(prefix: &mut Vec<usize>, left: usize, res: &mut Vec<FoldingSchedule>) {
+            if left == 0 {
+                res.push(FoldingSchedule::new(prefix));
+                return;
+            }
+            for step in 1..=left {
+                prefix.push(step);
+                extend(prefix, left - step, res);
+                prefix.pop();
+            }
+        }
+        let mut res = vec![];
+        extend(&mut vec![], total, &mut res);
+        res
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn step(&self, round: usize) -> usize {
+        assert!(round < self.rounds());
+        self.steps[round] as usize
+    }
+
+    // Variables folded before `round`; `offset(rounds())` is the total.
+    pub fn offset(&self, round: usize) -> usize {
+        assert!(round <= self.rounds());
+        self.steps[..round].iter().map(|&x| x as usize).sum()
+    }
+
+    // Leaf width, in variables, of the codeword that round `layer` folds.
+    // The layer left after the last round keeps that round's width, capped
+    // by its `size` so that a large last step still leaves whole leaves.
+    pub fn leaf_step(&self, layer: usize, size: usize) -> usize {
+        let step = self.step(layer.min(self.rounds() - 1));
+        step.min(size.trailing_zeros() as usize)
+    }
+
+    pub fn total(&self) -> usize {
+        self.offset(self.rounds())
+    }
+
+    pub fn max_step(&self) -> usize {
+        self.steps.iter().copied().max().unwrap_or(0) as usize
+    }
+
+    pub fn steps(&self) -> Vec<usize> {
+        (0..self.rounds()).map(|i| self.step(i)).collect()
+    }
+}
+
+impl Serializable for FoldingSchedule {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_u8(self.len);
+        for i in 0..self.rounds() {
+            writer.write_u8(self.steps[i]);
+        }
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let len = reader.read_u8()? as usize;
+        if len > MAX_ROUNDS {
+            return Err(SerializationError::NonCanonical("folding schedule"));
+        }
+        let mut steps = vec![];
+        for _ in 0..len {
+            match reader.read_u8()? {
+                0 => return Err(SerializationError::NonCanonical("folding schedule")),
+                step => steps.push(step as usize),
+            }
+        }
+        Ok(FoldingSchedule::new(&steps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform() {
+        let schedule = FoldingSchedule::uniform(10, 3);
+        assert_eq!(schedule.steps(), vec![3, 3, 3, 1]);
+        assert_eq!(schedule.offset(2), 6);
+        assert_eq!(schedule.total(), 10);
+        assert_eq!(schedule.max_step(), 3);
+        assert_eq!(schedule.leaf_step(1, 1 << 7), 3);
+        assert_eq!(schedule.leaf_step(4, 1 << 3), 1);
+        assert_eq!(FoldingSchedule::new(&[1, 5]).leaf_step(2, 1 << 3), 3);
+        assert_eq!(FoldingSchedule::uniform(8, 4).steps(), vec![4, 4]);
+        assert_eq!(FoldingSchedule::uniform(0, 2).rounds(), 0);
+    }
+
+    #[test]
+    fn all() {
+        for total in 1..8 {
+            let schedules = FoldingSchedule::all(total);
+            assert_eq!(schedules.len(), 1 << (total - 1));
+            assert!(schedules.iter().all(|x| x.total() == total));
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let schedule = FoldingSchedule::new(&[4, 3, 1]);
+        let bytes = schedule.encode();
+        assert_eq!(FoldingSchedule::decode(&bytes), Ok(schedule));
+
+        let mut zero = bytes.clone();
+        zero[2] = 0;
+        assert_eq!(
+            FoldingSchedule::decode(&zero),
+            Err(SerializationError::NonCanonical("folding schedule"))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::algebra::field::MyField;
+use crate::pcs::PcsParams;
+
+// How far from the code a word may be while the FRI-style tests still catch
+// it, which fixes how much each query is worth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Regime {
+    // Proven up to the unique decoding radius (1 - rho) / 2.
+    UniqueDecoding,
+    // Proven up to the Johnson bound 1 - sqrt(rho) [BCIKS20].
+    JohnsonBound,
+    // Conjectured up to list decoding capacity 1 - rho [ethSTARK].
+    Conjectured,
+}
+
+// Johnson bound slack: the proximity used is 1 - sqrt(rho) * (1 + 1 / 2m).
+const JOHNSON_M: f64 = 16.0;
+
+// One run of the low-degree test: a codeword of rate 2^-code_rate over a
+// domain of 2^domain_bits points, folded 2^step to one, `rounds` times, over a
+// field of 2^field_bits elements. A schedule with varying steps is bounded by
+// its largest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecurityParams {
+    pub field_bits: usize,
+    pub code_rate: usize,
+    pub domain_bits: usize,
+    pub step: usize,
+    pub rounds: usize,
+    pub grinding_bits: usize,
+    pub regime: Regime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Security {
+    pub query_num: usize,
+    // Below the target when the folding phase error dominates, which more
+    // queries cannot fix.
+    pub bits: f64,
+}
+
+impl SecurityParams {
+    // `variable_num` variables over `T` folded down to `final_poly_bits`
+    // under `params`.
+    pub fn new<T: MyField>(variable_num: usize, params: &PcsParams, regime: Regime) -> Self {
+        let folding = params.folding(variable_num);
+        SecurityParams {
+            field_bits: T::FIELD_BITS,
+            code_rate: params.code_rate,
+            domain_bits: folding.total() + params.final_poly_bits + params.code_rate,
+            step: folding.max_step(),
+            rounds: folding.rounds(),
+            grinding_bits: params.grinding_bits,
+            regime,
+        }
+    }
+
+    // -log2 of the chance that one query misses a word at the regime's
+    // proximity.
+    pub fn query_bits(&self) -> f64 {
+        let rate = 0.5_f64.powi(self.code_rate as i32);
+        match self.regime {
+            Regime::UniqueDecoding => -((1.0 + rate) / 2.0).log2(),
+            Regime::JohnsonBound => -(rate.sqrt() * (1.0 + 0.5 / JOHNSON_M)).log2(),
+            Regime::Conjectured => self.code_rate as f64,
+        }
+    }
+
+    // -log2 of the chance that some folding challenge lands a far word close
+    // to the code, summed over all rounds.
+    pub fn folding_bits(&self) -> f64 {
+        let log_domain = self.domain_bits as f64;
+        let per_challenge = match self.regime {
+            Regime::UniqueDecoding | Regime::Conjectured => log_domain,
+            Regime::JohnsonBound => {
+                2.0 * log_domain + 7.0 * (JOHNSON_M + 0.5).log2() + 1.5 * self.code_rate as f64
+                    - 3.0_f64.log2()
+            }
+        };
+        let challenges = (self.rounds * ((1 << self.step) - 1)) as f64;
+        self.field_bits as f64 - per_challenge - challenges.log2()
+    }
+
+    pub fn achieved_bits(&self, query_num: usize) -> f64 {
+        let query = query_num as f64 * self.query_bits() + self.grinding_bits as f64;
+        -(0.5_f64.powf(query) + 0.5_f64.powf(self.folding_bits())).log2()
+    }
+
+    // The fewest queries whose error, with the grinding, is 2^-bits.
+    pub fn target(&self, bits: usize) -> Security {
+        let remaining = bits.saturating_sub(self.grinding_bits) as f64;
+        let query_num = (remaining / self.query_bits()).ceil() as usize;
+        Security {
+            query_num,
+            bits: self.achieved_bits(query_num),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::{goldilocks64ext::Goldilocks64Ext, mersenne61_ext::Mersenne61Ext};
+
+    const SECURITY_BITS: usize = 100;
+
+    #[test]
+    fn query_counts() {
+        let pcs = PcsParams::default();
+        let params = |regime| SecurityParams::new::<Mersenne61Ext>(20, &pcs, regime);
+        let conjectured = params(Regime::Conjectured).target(SECURITY_BITS);
+        assert_eq!(
+            conjectured.query_num,
+            (SECURITY_BITS - pcs.grinding_bits).div_ceil(pcs.code_rate)
+        );
+        let unique = params(Regime::UniqueDecoding).target(SECURITY_BITS);
+        let expected = ((SECURITY_BITS - pcs.grinding_bits) as f32
+            / (2.0 / (1.0 + 0.5_f32.powi(pcs.code_rate as i32))).log2())
+        .ceil() as usize;
+        assert_eq!(unique.query_num, expected);
+        let johnson = params(Regime::JohnsonBound).target(SECURITY_BITS);
+        assert!(conjectured.query_num < johnson.query_num);
+        assert!(johnson.query_num < unique.query_num);
+    }
+
+    #[test]
+    fn domain_size() {
+        // 20 variables in steps of 3 leave a last round of 2, and the final
+        // polynomial's variables are never folded
+        for (step, final_poly_bits) in [(3, 0), (3, 4), (4, 2)] {
+            let pcs = PcsParams {
+                step,
+                final_poly_bits,
+                ..PcsParams::default()
+            };
+            let params = SecurityParams::new::<Mersenne61Ext>(20, &pcs, Regime::Conjectured);
+            assert_eq!(params.domain_bits, 20 + pcs.code_rate);
+        }
+    }
+
+    #[test]
+    fn achieved_bits() {
+        let pcs = PcsParams {
+            step: 2,
+            ..PcsParams::default()
+        };
+        let params = SecurityParams::new::<Goldilocks64Ext>(20, &pcs, Regime::Conjectured);
+        let security = params.target(SECURITY_BITS);
+        assert_eq!(security.bits, params.achieved_bits(security.query_num));
+        assert!(params.achieved_bits(security.query_num - 1) < security.bits);
+        assert!(security.bits <= params.folding_bits());
+
+        // the Johnson bound folding error is beyond what queries can make up
+        let johnson = SecurityParams {
+            regime: Regime::JohnsonBound,
+            ..params
+        };
+        let security = johnson.target(SECURITY_BITS);
+        assert!(security.bits < SECURITY_BITS as f64);
+        assert!(security.bits <= johnson.folding_bits());
+        assert_eq!(johnson.target(0).query_num, 0);
+    }
+}
@@ -0,0 +1,451 @@
+use std::fmt;
+use std::mem::size_of;
+
+use serde::{de, Deserializer, Serializer};
+
+use crate::algebra::{field::MyField, polynomial::Polynomial};
+use crate::merkle_tree::{MerkleCap, MERKLE_ROOT_SIZE, SALT_SIZE};
+
+// Wire format, all integers little-endian:
+//   top level   = FORMAT_VERSION (u8) || payload
+//   length      = u32
+//   nonce       = u64
+//   sequence    = length || items
+//   field       = MyField::to_bytes, size_of::<T>() bytes, canonical
+//   merkle root = MERKLE_ROOT_SIZE raw bytes
+//   merkle cap  = sequence of merkle roots
+//   salt        = SALT_SIZE raw bytes
+pub const FORMAT_VERSION: u8 = 8;
+pub const VERSION_SIZE: usize = 1;
+pub const LENGTH_SIZE: usize = 4;
+pub const NONCE_SIZE: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerializationError {
+    UnexpectedEnd,
+    TrailingBytes(usize),
+    UnsupportedVersion(u8),
+    NonCanonicalField,
+    NonCanonical(&'static str),
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            SerializationError::TrailingBytes(n) => write!(f, "{} trailing bytes", n),
+            SerializationError::UnsupportedVersion(v) => {
+                write!(f, "unsupported format version {}", v)
+            }
+            SerializationError::NonCanonicalField => {
+                write!(f, "non-canonical field element encoding")
+            }
+            SerializationError::NonCanonical(what) => write!(f, "non-canonical {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+pub fn sequence_size(len: usize, item_size: usize) -> usize {
+    LENGTH_SIZE + len * item_size
+}
+
+pub fn field_sequence_size<T: MyField>(len: usize) -> usize {
+    sequence_size(len, size_of::<T>())
+}
+
+// Encoded length of a sequence of caps.
+pub fn caps_size(caps: &[MerkleCap]) -> usize {
+    caps.iter().fold(LENGTH_SIZE, |acc, x| {
+        acc + sequence_size(x.len(), MERKLE_ROOT_SIZE)
+    })
+}
+
+#[derive(Default)]
+pub struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Writer { bytes: vec![] }
+    }
+
+    pub fn write_u8(&mut self, x: u8) {
+        self.bytes.push(x);
+    }
+
+    pub fn write_u32(&mut self, x: u32) {
+        self.bytes.extend_from_slice(&x.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, x: u64) {
+        self.bytes.extend_from_slice(&x.to_le_bytes());
+    }
+
+    pub fn write_length(&mut self, len: usize) {
+        self.write_u32(u32::try_from(len).expect("length does not fit in u32"));
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_length(bytes.len());
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn write_root(&mut self, root: &[u8; MERKLE_ROOT_SIZE]) {
+        self.bytes.extend_from_slice(root);
+    }
+
+    pub fn write_roots(&mut self, roots: &[[u8; MERKLE_ROOT_SIZE]]) {
+        self.write_length(roots.len());
+        for root in roots {
+            self.write_root(root);
+        }
+    }
+
+    pub fn write_cap(&mut self, cap: &[[u8; MERKLE_ROOT_SIZE]]) {
+        self.write_roots(cap);
+    }
+
+    pub fn write_caps(&mut self, caps: &[MerkleCap]) {
+        self.write_length(caps.len());
+        for cap in caps {
+            self.write_cap(cap);
+        }
+    }
+
+    pub fn write_salts(&mut self, salts: &[[u8; SALT_SIZE]]) {
+        self.write_length(salts.len());
+        for salt in salts {
+            self.bytes.extend_from_slice(salt);
+        }
+    }
+
+    pub fn write_field<T: MyField>(&mut self, element: &T) {
+        self.bytes.extend_from_slice(&element.to_bytes());
+    }
+
+    pub fn write_fields<T: MyField>(&mut self, elements: &[T]) {
+        self.write_length(elements.len());
+        for element in elements {
+            self.write_field(element);
+        }
+    }
+
+    pub fn write_polynomial<T: MyField>(&mut self, poly: &Polynomial<T>) {
+        self.write_fields(poly.coefficients());
+    }
+
+    pub fn write<S: Serializable>(&mut self, item: &S) {
+        item.write_to(self);
+    }
+
+    pub fn write_all<S: Serializable>(&mut self, items: &[S]) {
+        self.write_length(items.len());
+        for item in items {
+            item.write_to(self);
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SerializationError> {
+        if self.bytes.len() < n {
+            return Err(SerializationError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SerializationError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SerializationError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, SerializationError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // Lengths are checked against the remaining input before anything is
+    // allocated, so a forged prefix cannot trigger a huge allocation.
+    pub fn read_length(&mut self, item_size: usize) -> Result<usize, SerializationError> {
+        let len = self.read_u32()? as usize;
+        if len.saturating_mul(item_size) > self.bytes.len() {
+            return Err(SerializationError::UnexpectedEnd);
+        }
+        Ok(len)
+    }
+
+    pub fn read_bytes(&mut self) -> Result<Vec<u8>, SerializationError> {
+        let len = self.read_length(1)?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub fn read_root(&mut self) -> Result<[u8; MERKLE_ROOT_SIZE], SerializationError> {
+        Ok(self.take(MERKLE_ROOT_SIZE)?.try_into().unwrap())
+    }
+
+    pub fn read_roots(&mut self) -> Result<Vec<[u8; MERKLE_ROOT_SIZE]>, SerializationError> {
+        let len = self.read_length(MERKLE_ROOT_SIZE)?;
+        (0..len).map(|_| self.read_root()).collect()
+    }
+
+    pub fn read_cap(&mut self) -> Result<MerkleCap, SerializationError> {
+        self.read_roots()
+    }
+
+    pub fn read_caps(&mut self) -> Result<Vec<MerkleCap>, SerializationError> {
+        let len = self.read_length(LENGTH_SIZE)?;
+        (0..len).map(|_| self.read_cap()).collect()
+    }
+
+    pub fn read_salts(&mut self) -> Result<Vec<[u8; SALT_SIZE]>, SerializationError> {
+        let len = self.read_length(SALT_SIZE)?;
+        (0..len)
+            .map(|_| Ok(self.take(SALT_SIZE)?.try_into().unwrap()))
+            .collect()
+    }
+
+    pub fn read_field<T: MyField>(&mut self) -> Result<T, SerializationError> {
+        T::from_bytes(self.take(size_of::<T>())?).ok_or(SerializationError::NonCanonicalField)
+    }
+
+    pub fn read_fields<T: MyField>(&mut self) -> Result<Vec<T>, SerializationError> {
+        let len = self.read_length(size_of::<T>())?;
+        (0..len).map(|_| self.read_field()).collect()
+    }
+
+    // Mirrors `Polynomial::new`, which never leaves a trailing zero coefficient
+    // and writes zero as the single coefficient 0.
+    pub fn read_polynomial<T: MyField>(&mut self) -> Result<Polynomial<T>, SerializationError> {
+        let coefficients = self.read_fields::<T>()?;
+        match coefficients.as_slice() {
+            [_] => Ok(Polynomial { coefficients }),
+            [.., x] if *x != T::from_int(0) => Ok(Polynomial { coefficients }),
+            _ => Err(SerializationError::NonCanonical("polynomial")),
+        }
+    }
+
+    pub fn read<S: Serializable>(&mut self) -> Result<S, SerializationError> {
+        S::read_from(self)
+    }
+
+    pub fn read_all<S: Serializable>(&mut self) -> Result<Vec<S>, SerializationError> {
+        let len = self.read_length(0)?;
+        let mut items = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            items.push(S::read_from(self)?);
+        }
+        Ok(items)
+    }
+
+    pub fn finish(self) -> Result<(), SerializationError> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            n => Err(SerializationError::TrailingBytes(n)),
+        }
+    }
+}
+
+pub trait Serializable: Sized {
+    fn write_to(&self, writer: &mut Writer);
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError>;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_u8(FORMAT_VERSION);
+        self.write_to(&mut writer);
+        writer.finish()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, SerializationError> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_u8()?;
+        if version != FORMAT_VERSION {
+            return Err(SerializationError::UnsupportedVersion(version));
+        }
+        let res = Self::read_from(&mut reader)?;
+        reader.finish()?;
+        Ok(res)
+    }
+}
+
+// serde support: every type is carried as the byte string produced by `encode`.
+pub fn serialize_with<S: Serializable, Ser: Serializer>(
+    item: &S,
+    serializer: Ser,
+) -> Result<Ser::Ok, Ser::Error> {
+    serializer.serialize_bytes(&item.encode())
+}
+
+pub fn deserialize_with<'de, S: Serializable, De: Deserializer<'de>>(
+    deserializer: De,
+) -> Result<S, De::Error> {
+    struct BytesVisitor;
+
+    impl<'de> de::Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "an encoded proof object")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = vec![];
+            while let Some(x) = seq.next_element()? {
+                bytes.push(x);
+            }
+            Ok(bytes)
+        }
+    }
+
+    let bytes = deserializer.deserialize_bytes(BytesVisitor)?;
+    S::decode(&bytes).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use crate::merkle_tree::SALT_SIZE;
+    use crate::query_result::QueryResult;
+
+    fn random_query_result() -> QueryResult<Mersenne61Ext> {
+        QueryResult::new(
+            (0..77).map(|x| x as u8).collect(),
+            vec![3, 5, 17],
+            (0..6).map(|_| Mersenne61Ext::random_element()).collect(),
+            vec![[7; SALT_SIZE], [9; SALT_SIZE], [11; SALT_SIZE]],
+        )
+    }
+
+    #[test]
+    fn query_result_round_trip() {
+        let query_result = random_query_result();
+        let bytes = query_result.encode();
+        assert_eq!(bytes.len(), VERSION_SIZE + query_result.proof_size());
+        let decoded = QueryResult::<Mersenne61Ext>::decode(&bytes).unwrap();
+        assert_eq!(decoded, query_result);
+        assert_eq!(decoded.leaf_width(), 2);
+        assert_eq!(decoded.encode(), bytes);
+    }
+
+    #[test]
+    fn strict_decoding() {
+        let bytes = random_query_result().encode();
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            QueryResult::<Mersenne61Ext>::decode(&trailing).err(),
+            Some(SerializationError::TrailingBytes(1))
+        );
+        assert_eq!(
+            QueryResult::<Mersenne61Ext>::decode(&bytes[..bytes.len() - 1]).err(),
+            Some(SerializationError::UnexpectedEnd)
+        );
+
+        let mut version = bytes.clone();
+        version[0] = FORMAT_VERSION + 1;
+        assert_eq!(
+            QueryResult::<Mersenne61Ext>::decode(&version).err(),
+            Some(SerializationError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+
+        // The first value's real part sits right after the indices.
+        let offset = VERSION_SIZE + LENGTH_SIZE + 77 + LENGTH_SIZE + 3 * 4 + LENGTH_SIZE;
+        let mut field = bytes.clone();
+        field[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            QueryResult::<Mersenne61Ext>::decode(&field).err(),
+            Some(SerializationError::NonCanonicalField)
+        );
+    }
+
+    #[test]
+    fn zero_polynomial() {
+        let zero = Mersenne61Ext::from_int(0);
+        let poly = Polynomial::new(vec![zero; 4]);
+        assert_eq!(poly.coefficients(), &vec![zero]);
+        let mut writer = Writer::new();
+        writer.write_polynomial(&poly);
+        let bytes = writer.finish();
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(
+            reader
+                .read_polynomial::<Mersenne61Ext>()
+                .unwrap()
+                .coefficients(),
+            &vec![zero]
+        );
+        assert_eq!(reader.finish(), Ok(()));
+
+        for coefficients in [vec![], vec![Mersenne61Ext::from_int(1), zero]] {
+            let mut writer = Writer::new();
+            writer.write_fields(&coefficients);
+            let bytes = writer.finish();
+            assert_eq!(
+                Reader::new(&bytes).read_polynomial::<Mersenne61Ext>().err(),
+                Some(SerializationError::NonCanonical("polynomial"))
+            );
+        }
+    }
+
+    #[test]
+    fn unsorted_indices_rejected() {
+        let mut writer = Writer::new();
+        writer.write_u8(FORMAT_VERSION);
+        writer.write_bytes(&[]);
+        writer.write_length(2);
+        for i in [5, 3] {
+            writer.write_u32(i);
+        }
+        writer.write_fields(&[Mersenne61Ext::from_int(1); 2]);
+        assert_eq!(
+            QueryResult::<Mersenne61Ext>::decode(&writer.finish()).err(),
+            Some(SerializationError::NonCanonical("query result indices"))
+        );
+    }
+
+    #[test]
+    fn ragged_leaves_rejected() {
+        let mut writer = Writer::new();
+        writer.write_u8(FORMAT_VERSION);
+        writer.write_bytes(&[]);
+        writer.write_length(2);
+        for i in [3, 5] {
+            writer.write_u32(i);
+        }
+        writer.write_fields(&[Mersenne61Ext::from_int(1); 3]);
+        writer.write_salts(&[]);
+        assert_eq!(
+            QueryResult::<Mersenne61Ext>::decode(&writer.finish()).err(),
+            Some(SerializationError::NonCanonical("query result values"))
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+
+use memmap2::MmapMut;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::algebra::field::{
+    bn254::Bn254Fr, ft255::Ft255, goldilocks64::Goldilocks64, goldilocks64ext::Goldilocks64Ext,
+    m31_ext::M31ext, mersenne61_ext::Mersenne61Ext,
+};
+use crate::merkle_tree::{MERKLE_ROOT_SIZE, SALT_SIZE};
+
+// Buffers below this size stay in memory even in mapped mode.
+const MAPPED_MIN_BYTES: usize = 1 << 20;
+
+// Buffers are filled this many elements at a time.
+const FILL_CHUNK: usize = 1 << 12;
+
+// Where the prover keeps codewords and Merkle layers. `Mapped` backs every
+// large buffer with an unlinked temporary file in the given directory, so
+// resident memory is bounded by the page cache instead of the codeword size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Storage {
+    #[default]
+    Memory,
+    Mapped(PathBuf),
+}
+
+/// Types that may be stored as raw bytes in a mapped file.
+///
+/// # Safety
+/// Every bit pattern written from a valid value must read back as that value
+/// in any process: the type holds only integers, with no pointers or other
+/// state tied to its address. Every `MyField` must be `Plain`, so each new
+/// field type has to be checked and listed here.
+pub unsafe trait Plain: Copy + Send + Sync + 'static {}
+
+// SAFETY: elements are fixed arrays of integers, in Montgomery form for the
+// 254- and 255-bit fields, without any indirection.
+unsafe impl Plain for Goldilocks64 {}
+
+unsafe impl Plain for Goldilocks64Ext {}
+
+unsafe impl Plain for Mersenne61Ext {}
+
+unsafe impl Plain for M31ext {}
+
+unsafe impl Plain for Bn254Fr {}
+
+unsafe impl Plain for Ft255 {}
+
+unsafe impl Plain for [u8; MERKLE_ROOT_SIZE] {}
+
+unsafe impl Plain for [u8; SALT_SIZE] {}
+
+pub struct Buffer<T: Plain> {
+    inner: Inner<T>,
+}
+
+enum Inner<T: Plain> {
+    Memory(Vec<T>),
+    Mapped {
+        map: MmapMut,
+        len: usize,
+        dir: PathBuf,
+        _file: File,
+        _marker: PhantomData<T>,
+    },
+}
+
+impl<T: Plain> Buffer<T> {
+    // Fills every entry with `f(index)`; in mapped mode entries are written
+    // straight to the file chunk by chunk.
+    pub fn from_fn<F>(storage: &Storage, len: usize, f: F) -> Self
+    where
+        F: Fn(usize) -> T + Send + Sync,
+    {
+        let dir = match storage {
+            Storage::Mapped(dir) if len * size_of::<T>() >= MAPPED_MIN_BYTES => dir,
+            _ => {
+                return Self::from(crate::cfg_into_iter!(0..len).map(f).collect::<Vec<_>>());
+            }
+        };
+        let file = tempfile::tempfile_in(dir).expect("cannot create buffer file");
+        file.set_len((len * size_of::<T>()) as u64)
+            .expect("cannot size buffer file");
+        let map = unsafe { MmapMut::map_mut(&file) }.expect("cannot map buffer file");
+        let mut res = Buffer {
+            inner: Inner::Mapped {
+                map,
+                len,
+                dir: dir.clone(),
+                _file: file,
+                _marker: PhantomData,
+            },
+        };
+        crate::cfg_chunks_mut!(res, FILL_CHUNK)
+            .enumerate()
+            .for_each(|(k, chunk)| {
+                for (j, x) in chunk.iter_mut().enumerate() {
+                    *x = f(k * FILL_CHUNK + j);
+                }
+            });
+        res
+    }
+
+    // Keeps the first `len` entries; a mapped file keeps its size.
+    pub fn truncate(&mut self, len: usize) {
+        match &mut self.inner {
+            Inner::Memory(value) => value.truncate(len),
+            Inner::Mapped { len: old, .. } => *old = len.min(*old),
+        }
+    }
+
+    // Same storage as `self`, so folded layers follow the codeword they come from.
+    pub fn storage(&self) -> Storage {
+        match &self.inner {
+            Inner::Memory(_) => Storage::Memory,
+            Inner::Mapped { dir, .. } => Storage::Mapped(dir.clone()),
+        }
+    }
+}
+
+impl<T: Plain> From<Vec<T>> for Buffer<T> {
+    fn from(value: Vec<T>) -> Self {
+        Buffer {
+            inner: Inner::Memory(value),
+        }
+    }
+}
+
+impl<T: Plain> Deref for Buffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match &self.inner {
+            Inner::Memory(value) => value,
+            Inner::Mapped { map, len, .. } => unsafe {
+                std::slice::from_raw_parts(map.as_ptr() as *const T, *len)
+            },
+        }
+    }
+}
+
+impl<T: Plain> DerefMut for Buffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match &mut self.inner {
+            Inner::Memory(value) => value,
+            Inner::Mapped { map, len, .. } => unsafe {
+                std::slice::from_raw_parts_mut(map.as_mut_ptr() as *mut T, *len)
+            },
+        }
+    }
+}
+
+impl<T: Plain> Clone for Buffer<T> {
+    fn clone(&self) -> Self {
+        match &self.inner {
+            Inner::Memory(value) => Self::from(value.clone()),
+            Inner::Mapped { .. } => Self::from_fn(&self.storage(), self.len(), |i| self[i]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::{mersenne61_ext::Mersenne61Ext, MyField};
+
+    #[test]
+    fn mapped_matches_memory() {
+        let storage = Storage::Mapped(std::env::temp_dir());
+        let len = MAPPED_MIN_BYTES / size_of::<Mersenne61Ext>() + 3;
+        let f = |i: usize| Mersenne61Ext::from_int(i as u64) * Mersenne61Ext::from_int(7);
+        let memory = Buffer::from_fn(&Storage::Memory, len, f);
+        let mut mapped = Buffer::from_fn(&storage, len, f);
+        assert_eq!(mapped.storage(), storage);
+        assert_eq!(*mapped, *memory);
+        mapped[len - 1] = Mersenne61Ext::from_int(1);
+        let copy = mapped.clone();
+        assert_eq!(copy.storage(), storage);
+        assert_eq!(copy[len - 1], Mersenne61Ext::from_int(1));
+        assert_eq!(copy[..len - 1], memory[..len - 1]);
+        mapped.truncate(3);
+        assert_eq!(*mapped, memory[..3]);
+        // small buffers never touch the disk
+        assert_eq!(Buffer::from_fn(&storage, 3, f).storage(), Storage::Memory);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::algebra::field::{as_bytes_vec, MyField};
+use crate::merkle_tree::MERKLE_ROOT_SIZE;
+use crate::poseidon2::Poseidon2Sponge;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+const ABSORB: u8 = 0;
+const SQUEEZE: u8 = 1;
+const GRIND: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    state: [u8; MERKLE_ROOT_SIZE],
+}
+
+impl Transcript {
+    pub fn new(label: &[u8]) -> Self {
+        Transcript {
+            state: blake3::hash(label).into(),
+        }
+    }
+
+    pub fn append_bytes(&mut self, bytes: &[u8]) {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[ABSORB]);
+        hasher.update(&self.state);
+        hasher.update(&(bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+        self.state = hasher.finalize().into();
+    }
+
+    pub fn append_root(&mut self, root: &[u8; MERKLE_ROOT_SIZE]) {
+        self.append_bytes(root);
+    }
+
+    // A single root is absorbed exactly as by `append_root`.
+    pub fn append_cap(&mut self, cap: &[[u8; MERKLE_ROOT_SIZE]]) {
+        self.append_bytes(&cap.concat());
+    }
+
+    pub fn append_field<T: MyField>(&mut self, element: &T) {
+        self.append_bytes(&element.to_bytes());
+    }
+
+    pub fn append_fields<T: MyField>(&mut self, elements: &[T]) {
+        self.append_bytes(&as_bytes_vec(elements));
+    }
+
+    fn squeeze(&mut self) -> [u8; MERKLE_ROOT_SIZE] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[SQUEEZE]);
+        hasher.update(&self.state);
+        self.state = hasher.finalize().into();
+        self.state
+    }
+
+    pub fn challenge<T: MyField>(&mut self) -> T {
+        T::from_hash(self.squeeze())
+    }
+
+    pub fn challenges<T: MyField>(&mut self, n: usize) -> Vec<T> {
+        (0..n).map(|_| self.challenge()).collect()
+    }
+
+    // Uniform index in [0, bound), rejecting the biased tail of the u64 range.
+    pub fn challenge_index(&mut self, bound: usize) -> usize {
+        assert!(bound > 0);
+        let bound = bound as u64;
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let hash = self.squeeze();
+            let x = u64::from_le_bytes(hash[..8].try_into().unwrap());
+            if x < zone {
+                return (x % bound) as usize;
+            }
+        }
+    }
+
+    pub fn query_list(&mut self, query_num: usize, bound: usize) -> Vec<usize> {
+        (0..query_num)
+            .map(|_| self.challenge_index(bound))
+            .collect()
+    }
+
+    fn has_work(&self, bits: usize, nonce: u64) -> bool {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[GRIND]);
+        hasher.update(&self.state);
+        hasher.update(&nonce.to_le_bytes());
+        let hash: [u8; MERKLE_ROOT_SIZE] = hasher.finalize().into();
+        u64::from_be_bytes(hash[..8].try_into().unwrap()).leading_zeros() as usize >= bits
+    }
+
+    // Proof of work before the queries: finds the smallest nonce whose hash
+    // with the state has `bits` leading zero bits and absorbs it, so every
+    // attempt at new query indices costs a cheating prover 2^bits hashes.
+    pub fn grind(&mut self, bits: usize) -> u64 {
+        assert!(bits < 64);
+        #[cfg(feature = "parallel")]
+        let nonce = (0..u64::MAX)
+            .into_par_iter()
+            .find_first(|&nonce| self.has_work(bits, nonce));
+        #[cfg(not(feature = "parallel"))]
+        let nonce = (0..u64::MAX).find(|&nonce| self.has_work(bits, nonce));
+        let nonce = nonce.unwrap();
+        self.append_bytes(&nonce.to_le_bytes());
+        nonce
+    }
+
+    // The verifier side of `grind`: absorbs `nonce` and reports whether it
+    // carries enough work.
+    pub fn check_grinding(&mut self, bits: usize, nonce: u64) -> bool {
+        let valid = bits < 64 && self.has_work(bits, nonce);
+        self.append_bytes(&nonce.to_le_bytes());
+        valid
+    }
+}
+
+// Fiat-Shamir transcript over the Poseidon2 sponge of `T`, for verifiers that
+// are themselves proven over `T`. Challenges are always elements of `T`.
+#[derive(Debug, Clone)]
+pub struct Poseidon2Transcript<T: MyField> {
+    sponge: Poseidon2Sponge<T>,
+}
+
+impl<T: MyField> Poseidon2Transcript<T> {
+    pub fn new(label: &[u8]) -> Self {
+        let mut sponge = Poseidon2Sponge::new();
+        sponge.absorb_bytes(label);
+        Poseidon2Transcript { sponge }
+    }
+
+    pub fn append_bytes(&mut self, bytes: &[u8]) {
+        self.sponge.absorb_bytes(bytes);
+    }
+
+    pub fn append_root(&mut self, root: &[u8; MERKLE_ROOT_SIZE]) {
+        self.append_bytes(root);
+    }
+
+    // A single root is absorbed exactly as by `append_root`.
+    pub fn append_cap(&mut self, cap: &[[u8; MERKLE_ROOT_SIZE]]) {
+        self.append_bytes(&cap.concat());
+    }
+
+    pub fn append_field(&mut self, element: &T) {
+        self.sponge.absorb(&[*element]);
+    }
+
+    pub fn append_fields(&mut self, elements: &[T]) {
+        self.sponge.absorb(elements);
+    }
+
+    pub fn challenge(&mut self) -> T {
+        self.sponge.squeeze()
+    }
+
+    pub fn challenges(&mut self, n: usize) -> Vec<T> {
+        self.sponge.squeeze_n(n)
+    }
+
+    // Uniform index in [0, bound) for bound <= 2^30, taken from the low 30
+    // bits of the first limb of a squeezed element. Every supported base
+    // field has at least 31 bits, so those bits are close to uniform.
+    pub fn challenge_index(&mut self, bound: usize) -> usize {
+        const BITS: u32 = 30;
+        assert!(bound > 0 && bound <= 1 << BITS);
+        let zone = (1u64 << BITS) - (1u64 << BITS) % bound as u64;
+        loop {
+            let bytes = self.sponge.squeeze().to_bytes();
+            let x = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as u64 & ((1 << BITS) - 1);
+            if x < zone {
+                return (x % bound as u64) as usize;
+            }
+        }
+    }
+
+    pub fn query_list(&mut self, query_num: usize, bound: usize) -> Vec<usize> {
+        (0..query_num)
+            .map(|_| self.challenge_index(bound))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::mersenne61_ext::Mersenne61Ext;
+
+    #[test]
+    fn prover_and_verifier_agree() {
+        let mut prover = Transcript::new(b"test");
+        let mut verifier = Transcript::new(b"test");
+        let root = [7u8; MERKLE_ROOT_SIZE];
+        let eval = Mersenne61Ext::random_element();
+        prover.append_root(&root);
+        prover.append_field(&eval);
+        verifier.append_root(&root);
+        verifier.append_field(&eval);
+        let a: Mersenne61Ext = prover.challenge();
+        let b: Mersenne61Ext = verifier.challenge();
+        assert_eq!(a, b);
+        assert_eq!(
+            prover.query_list(10, 1 << 12),
+            verifier.query_list(10, 1 << 12)
+        );
+    }
+
+    #[test]
+    fn absorbed_data_changes_challenges() {
+        let mut t1 = Transcript::new(b"test");
+        let mut t2 = Transcript::new(b"test");
+        t1.append_field(&Mersenne61Ext::from_int(1));
+        t2.append_field(&Mersenne61Ext::from_int(2));
+        let a: Mersenne61Ext = t1.challenge();
+        let b: Mersenne61Ext = t2.challenge();
+        assert_ne!(a, b);
+
+        let mut t3 = Transcript::new(b"other");
+        t3.append_field(&Mersenne61Ext::from_int(1));
+        let c: Mersenne61Ext = t3.challenge();
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn query_indices_in_range() {
+        let mut transcript = Transcript::new(b"test");
+        for bound in [1, 3, 7, 1 << 10, 1000] {
+            assert!(transcript
+                .query_list(50, bound)
+                .into_iter()
+                .all(|x| x < bound));
+        }
+    }
+
+    #[test]
+    fn grinding() {
+        let mut prover = Transcript::new(b"test");
+        let mut verifier = Transcript::new(b"test");
+        let nonce = prover.grind(8);
+        assert!(verifier.clone().check_grinding(8, nonce));
+        assert!(verifier.check_grinding(0, nonce));
+        assert_eq!(prover.query_list(10, 1000), verifier.query_list(10, 1000));
+
+        let mut transcript = Transcript::new(b"test");
+        // the smallest valid nonce is found, so smaller ones all fail
+        assert!((0..nonce).all(|n| !transcript.clone().check_grinding(8, n)));
+        assert_eq!(transcript.grind(0), 0);
+    }
+
+    #[test]
+    fn poseidon2_transcript() {
+        let mut prover = Poseidon2Transcript::<Mersenne61Ext>::new(b"test");
+        let mut verifier = Poseidon2Transcript::<Mersenne61Ext>::new(b"test");
+        let root = [7u8; MERKLE_ROOT_SIZE];
+        let eval = Mersenne61Ext::random_element();
+        for t in [&mut prover, &mut verifier] {
+            t.append_root(&root);
+            t.append_field(&eval);
+        }
+        assert_eq!(prover.challenge(), verifier.challenge());
+        assert_eq!(prover.query_list(10, 1000), verifier.query_list(10, 1000));
+
+        let mut reference = Poseidon2Transcript::<Mersenne61Ext>::new(b"test");
+        reference.append_root(&root);
+        reference.append_field(&eval);
+        let mut other = Poseidon2Transcript::<Mersenne61Ext>::new(b"test");
+        other.append_root(&root);
+        other.append_field(&(eval + Mersenne61Ext::from_int(1)));
+        let mut empty = reference.clone();
+        empty.append_fields(&[]);
+        let a = reference.challenge();
+        assert_ne!(a, other.challenge());
+        assert_ne!(a, empty.challenge());
+        assert!(prover.query_list(50, 7).into_iter().all(|x| x < 7));
+    }
+}
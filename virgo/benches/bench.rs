@@ -2,28 +2,26 @@ extern crate criterion;
 
 use criterion::*;
 
-use util::{
-    algebra::{
-        coset::Coset, field::mersenne61_ext::Mersenne61Ext, field::MyField,
-        polynomial::MultilinearPolynomial,
-    },
-    random_oracle::RandomOracle,
+use util::algebra::{
+    coset::Coset, field::mersenne61_ext::Mersenne61Ext, field::MyField,
+    polynomial::MultilinearPolynomial,
 };
 use virgo::{prover::FriProver, verifier::FriVerifier};
 
-use util::{CODE_RATE, SECURITY_BITS, STEP};
+use util::parallel::for_each_pool;
+use util::pcs::PcsParams;
 
 fn commit(criterion: &mut Criterion, variable_num: usize) {
     let total_round = variable_num;
+    let params = PcsParams::default();
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
     let mut interpolate_cosets = vec![Coset::new(
-        1 << (variable_num + CODE_RATE),
+        1 << (variable_num + params.code_rate),
         Mersenne61Ext::random_element(),
     )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let random_oracle = RandomOracle::new(total_round, SECURITY_BITS / CODE_RATE);
     let vector_interpolation_coset = Coset::new(1 << variable_num, Mersenne61Ext::random_element());
     criterion.bench_function(&format!("bench virgo commit {}", variable_num), move |b| {
         b.iter_batched(
@@ -34,8 +32,7 @@ fn commit(criterion: &mut Criterion, variable_num: usize) {
                     &interpolate_cosets,
                     &vector_interpolation_coset,
                     p,
-                    &random_oracle,
-                    STEP,
+                    params,
                 );
                 prover.commit_first_polynomial();
             },
@@ -52,34 +49,35 @@ fn bench_commit(c: &mut Criterion) {
 
 fn open(criterion: &mut Criterion, variable_num: usize) {
     let total_round = variable_num;
+    let params = PcsParams::default();
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
     let mut interpolate_cosets = vec![Coset::new(
-        1 << (variable_num + CODE_RATE),
+        1 << (variable_num + params.code_rate),
         Mersenne61Ext::random_element(),
     )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let random_oracle = RandomOracle::new(total_round, SECURITY_BITS / CODE_RATE);
     let vector_interpolation_coset = Coset::new(1 << variable_num, Mersenne61Ext::random_element());
     let prover = FriProver::new(
         variable_num,
         &interpolate_cosets,
         &vector_interpolation_coset,
         polynomial,
-        &random_oracle,
-        STEP,
+        params,
     );
     let commit = prover.commit_first_polynomial();
-    let mut verifier = FriVerifier::new(
+    let open_point = (0..total_round)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
+    let verifier = FriVerifier::new(
         total_round,
         &interpolate_cosets,
         &vector_interpolation_coset,
         commit,
-        &random_oracle,
-        STEP,
+        &open_point,
+        params,
     );
-    let open_point = verifier.get_open_point();
     criterion.bench_function(&format!("virgo prove {}", variable_num), |b| {
         b.iter_batched(
             || (prover.clone(), verifier.clone()),
@@ -102,41 +100,45 @@ fn bench_open(c: &mut Criterion) {
 
 fn verify(criterion: &mut Criterion, variable_num: usize) {
     let total_round = variable_num;
+    let params = PcsParams::default();
     let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
     let mut interpolate_cosets = vec![Coset::new(
-        1 << (variable_num + CODE_RATE),
+        1 << (variable_num + params.code_rate),
         Mersenne61Ext::random_element(),
     )];
     for i in 1..variable_num + 1 {
         interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
     }
-    let random_oracle = RandomOracle::new(variable_num, SECURITY_BITS / CODE_RATE);
     let vector_interpolation_coset = Coset::new(1 << variable_num, Mersenne61Ext::random_element());
     let mut prover = FriProver::new(
         variable_num,
         &interpolate_cosets,
         &vector_interpolation_coset,
-        polynomial,
-        &random_oracle,
-        STEP,
+        polynomial.clone(),
+        params,
     );
     let commit = prover.commit_first_polynomial();
+    let open_point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
+    let evaluation = polynomial.evaluate(&open_point);
     let mut verifier = FriVerifier::new(
         variable_num,
         &interpolate_cosets,
         &vector_interpolation_coset,
         commit,
-        &random_oracle,
-        STEP,
+        &open_point,
+        params,
     );
-    let open_point = verifier.get_open_point();
     prover.commit_functions(&mut verifier, &open_point);
     prover.prove();
     prover.commit_foldings(&mut verifier);
     let (folding_proofs, function_proofs, v_value) = prover.query();
     criterion.bench_function(&format!("virgo verify {}", variable_num), |b| {
         b.iter(|| {
-            assert!(verifier.verify(&folding_proofs, &v_value, &function_proofs));
+            assert!(verifier
+                .verify(&folding_proofs, &v_value, &function_proofs, evaluation)
+                .is_ok());
         })
     });
 }
@@ -147,10 +149,59 @@ fn bench_verify(c: &mut Criterion) {
     }
 }
 
+// Prover scaling by thread count; proofs are identical for every pool size.
+fn bench_open_threads(c: &mut Criterion) {
+    let variable_num = 16;
+    let params = PcsParams::default();
+    let polynomial = MultilinearPolynomial::<Mersenne61Ext>::random_polynomial(variable_num);
+    let mut interpolate_cosets = vec![Coset::new(
+        1 << (variable_num + params.code_rate),
+        Mersenne61Ext::random_element(),
+    )];
+    for i in 1..variable_num + 1 {
+        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+    }
+    let vector_interpolation_coset = Coset::new(1 << variable_num, Mersenne61Ext::random_element());
+    let open_point = (0..variable_num)
+        .map(|_| Mersenne61Ext::random_element())
+        .collect::<Vec<_>>();
+    for_each_pool(|threads, pool| {
+        c.bench_function(
+            &format!("virgo prove {:02} threads {}", variable_num, threads),
+            |b| {
+                b.iter(|| {
+                    pool.install(|| {
+                        let mut prover = FriProver::new(
+                            variable_num,
+                            &interpolate_cosets,
+                            &vector_interpolation_coset,
+                            polynomial.clone(),
+                            params,
+                        );
+                        let commit = prover.commit_first_polynomial();
+                        let mut verifier = FriVerifier::new(
+                            variable_num,
+                            &interpolate_cosets,
+                            &vector_interpolation_coset,
+                            commit,
+                            &open_point,
+                            params,
+                        );
+                        prover.commit_functions(&mut verifier, &open_point);
+                        prover.prove();
+                        prover.commit_foldings(&mut verifier);
+                        prover.query();
+                    })
+                })
+            },
+        );
+    });
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_commit, bench_open, bench_verify
+    targets = bench_commit, bench_open, bench_verify, bench_open_threads
 }
 
 criterion_main!(benches);
@@ -1,58 +1,189 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use util::{
+    algebra::{field::MyField, polynomial::Polynomial},
+    merkle_tree::{MerkleCap, MERKLE_ROOT_SIZE},
+    query_result::QueryResult,
+    serialization::{
+        caps_size, deserialize_with, field_sequence_size, sequence_size, serialize_with, Reader,
+        Serializable, SerializationError, Writer, LENGTH_SIZE, NONCE_SIZE, VERSION_SIZE,
+    },
+};
+
+pub mod pcs;
 pub mod prover;
 pub mod verifier;
 
+// Non-interactive transcript of an opening: everything `commit_functions`,
+// `commit_foldings` and `query` would hand to the verifier.
+#[derive(Clone)]
+pub struct Proof<T: MyField> {
+    pub h_root: MerkleCap,
+    pub evaluation: T,
+    pub folding_root: Vec<MerkleCap>,
+    pub final_poly: Polynomial<T>,
+    pub folding_proofs: Vec<QueryResult<T>>,
+    pub function_proofs: Vec<QueryResult<T>>,
+    pub v_values: HashMap<usize, T>,
+    pub pow_nonce: u64,
+}
+
+const INDEX_SIZE: usize = 4;
+
+impl<T: MyField> Proof<T> {
+    // Exact length of `encode`.
+    pub fn size(&self) -> usize {
+        VERSION_SIZE
+            + sequence_size(self.h_root.len(), MERKLE_ROOT_SIZE)
+            + size_of::<T>()
+            + caps_size(&self.folding_root)
+            + field_sequence_size::<T>(self.final_poly.coefficients().len())
+            + self
+                .folding_proofs
+                .iter()
+                .chain(&self.function_proofs)
+                .fold(2 * LENGTH_SIZE, |acc, x| acc + x.proof_size())
+            + sequence_size(self.v_values.len(), INDEX_SIZE + size_of::<T>())
+            + NONCE_SIZE
+    }
+}
+
+// `v_values` is written in increasing order of index, so every proof has a
+// single encoding.
+impl<T: MyField> Serializable for Proof<T> {
+    fn write_to(&self, writer: &mut Writer) {
+        writer.write_cap(&self.h_root);
+        writer.write_field(&self.evaluation);
+        writer.write_caps(&self.folding_root);
+        writer.write_polynomial(&self.final_poly);
+        writer.write_all(&self.folding_proofs);
+        writer.write_all(&self.function_proofs);
+        let mut v_values = self.v_values.iter().collect::<Vec<_>>();
+        v_values.sort_by_key(|(&index, _)| index);
+        writer.write_length(v_values.len());
+        for (&index, value) in v_values {
+            writer.write_u32(u32::try_from(index).expect("index does not fit in u32"));
+            writer.write_field(value);
+        }
+        writer.write_u64(self.pow_nonce);
+    }
+
+    fn read_from(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let h_root = reader.read_cap()?;
+        let evaluation = reader.read_field()?;
+        let folding_root = reader.read_caps()?;
+        let final_poly = reader.read_polynomial()?;
+        let folding_proofs = reader.read_all()?;
+        let function_proofs = reader.read_all()?;
+        let mut v_values = HashMap::new();
+        let mut last = None;
+        for _ in 0..reader.read_length(INDEX_SIZE + size_of::<T>())? {
+            let index = reader.read_u32()? as usize;
+            if last.is_some_and(|last| index <= last) {
+                return Err(SerializationError::NonCanonical("v value indices"));
+            }
+            last = Some(index);
+            v_values.insert(index, reader.read_field()?);
+        }
+        Ok(Proof {
+            h_root,
+            evaluation,
+            folding_root,
+            final_poly,
+            folding_proofs,
+            function_proofs,
+            v_values,
+            pow_nonce: reader.read_u64()?,
+        })
+    }
+}
+
+impl<T: MyField> Serialize for Proof<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with(self, serializer)
+    }
+}
+
+impl<'de, T: MyField> Deserialize<'de> for Proof<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_with(deserializer)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{prover::FriProver, verifier::FriVerifier};
+    use crate::{pcs::Virgo, prover::FriProver, verifier::FriVerifier, Proof, INDEX_SIZE};
     use csv::Writer;
     use std::mem::size_of;
     use util::{
         algebra::{
-            coset::Coset, field::mersenne61_ext::Mersenne61Ext, field::MyField,
-            polynomial::MultilinearPolynomial,
+            coset::Coset,
+            field::goldilocks64ext::Goldilocks64Ext,
+            field::mersenne61_ext::Mersenne61Ext,
+            field::MyField,
+            polynomial::{MultilinearPolynomial, Polynomial},
         },
-        merkle_tree::MERKLE_ROOT_SIZE,
-        random_oracle::RandomOracle,
+        error::VerificationError,
+        merkle_tree::{Blake3Algorithm, HashChoice, MerkleHasher, MERKLE_ROOT_SIZE},
+        parallel::for_each_pool,
+        pcs::{Commitment, MultilinearPCS, PcsParams, SetupParams},
+        schedule::FoldingSchedule,
+        serialization::{Serializable, SerializationError, NONCE_SIZE},
     };
 
-    use util::{CODE_RATE, SECURITY_BITS, STEP};
     fn output_proof_size(variable_num: usize) -> usize {
         let total_round = variable_num;
+        let params = PcsParams::default();
         let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
         let mut interpolate_cosets = vec![Coset::new(
-            1 << (variable_num + CODE_RATE),
+            1 << (variable_num + params.code_rate),
             Mersenne61Ext::random_element(),
         )];
         for i in 1..variable_num + 1 {
             interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
         }
-        let random_oracle = RandomOracle::new(total_round, SECURITY_BITS / CODE_RATE);
         let vector_interpolation_coset =
             Coset::new(1 << variable_num, Mersenne61Ext::random_element());
         let mut prover = FriProver::new(
             total_round,
             &interpolate_cosets,
             &vector_interpolation_coset,
-            polynomial,
-            &random_oracle,
-            STEP,
+            polynomial.clone(),
+            params,
         );
         let commit = prover.commit_first_polynomial();
+        // cauchy: why vector of points rather than a single point?
+        let open_point = (0..total_round)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
         let mut verifier = FriVerifier::new(
             total_round,
             &interpolate_cosets,
             &vector_interpolation_coset,
             commit,
-            &random_oracle,
-            STEP,
+            &open_point,
+            params,
         );
-        // cauchy: why vector of points rather than a single point?
-        let open_point = verifier.get_open_point();
         prover.commit_functions(&mut verifier, &open_point);
         prover.prove();
         prover.commit_foldings(&mut verifier);
         let (folding_proofs, function_proofs, v_value) = prover.query();
-        assert!(verifier.verify(&folding_proofs, &v_value, &function_proofs));
+        let evaluation = polynomial.evaluate(&open_point);
+        assert_eq!(
+            verifier.verify(
+                &folding_proofs,
+                &v_value,
+                &function_proofs,
+                evaluation + Mersenne61Ext::from_int(1)
+            ),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        assert_eq!(
+            verifier.verify(&folding_proofs, &v_value, &function_proofs, evaluation),
+            Ok(())
+        );
         folding_proofs.iter().map(|x| x.proof_size()).sum::<usize>()
             + (variable_num + 1) * MERKLE_ROOT_SIZE
             + size_of::<Mersenne61Ext>()
@@ -60,6 +191,260 @@ mod tests {
                 .iter()
                 .map(|x| x.proof_size())
                 .sum::<usize>()
+            + NONCE_SIZE
+    }
+
+    fn check_pcs_interface<T: MyField>(hash: HashChoice) {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 8,
+                hash,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = Virgo::<T>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| T::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial.clone());
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, polynomial.evaluate(&point));
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+        assert_eq!(
+            pcs.verify(&commit, &point, &(eval + T::from_int(1)), &proof),
+            Err(VerificationError::EvaluationMismatch)
+        );
+        let mut other_point = point.clone();
+        other_point[0] += T::from_int(1);
+        assert!(pcs.verify(&commit, &other_point, &eval, &proof).is_err());
+        assert_eq!(
+            pcs.verify(&commit, &point[1..], &eval, &proof),
+            Err(VerificationError::MalformedProof("open point"))
+        );
+        let keccak = Virgo::<T>::setup(SetupParams {
+            pcs: PcsParams {
+                hash: HashChoice::Keccak256,
+                ..params.pcs
+            },
+            ..params
+        });
+        assert_eq!(
+            keccak.verify(&commit, &point, &eval, &proof),
+            Err(VerificationError::ParameterMismatch)
+        );
+    }
+
+    #[test]
+    fn pcs_interface() {
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Goldilocks64Ext>(HashChoice::Blake3);
+        check_pcs_interface::<Mersenne61Ext>(HashChoice::Poseidon2);
+    }
+
+    #[test]
+    fn serialization() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = Virgo::<Mersenne61Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::random_polynomial(params.variable_num);
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let commit: Commitment =
+            bincode::deserialize(&bincode::serialize(&commit).unwrap()).unwrap();
+        let (eval, proof) = pcs.open(&data, &point);
+
+        let bytes = proof.encode();
+        assert_eq!(bytes.len(), proof.size());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&trailing).err(),
+            Some(SerializationError::TrailingBytes(1))
+        );
+        // The last v value and its index, ahead of the nonce.
+        let n = bytes.len() - NONCE_SIZE - size_of::<Mersenne61Ext>();
+        let mut non_canonical = bytes.clone();
+        non_canonical[n..n + size_of::<Mersenne61Ext>()].fill(0xff);
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&non_canonical).err(),
+            Some(SerializationError::NonCanonicalField)
+        );
+        let (last, previous) = (
+            n - INDEX_SIZE,
+            n - 2 * INDEX_SIZE - size_of::<Mersenne61Ext>(),
+        );
+        let mut repeated = bytes.clone();
+        repeated.copy_within(previous..previous + INDEX_SIZE, last);
+        assert_eq!(
+            Proof::<Mersenne61Ext>::decode(&repeated).err(),
+            Some(SerializationError::NonCanonical("v value indices"))
+        );
+
+        let proof: Proof<Mersenne61Ext> =
+            bincode::deserialize(&bincode::serialize(&proof).unwrap()).unwrap();
+        assert_eq!(proof.encode(), bytes);
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+    }
+
+    // Digest of a fixed opening made by the sequential build. The parallel
+    // build must reproduce it byte for byte in every pool size.
+    const SEQUENTIAL_PROOF: &str =
+        "d8257b9209baf159300efaafbbf689ce96167159ea12be342d2bf70cebf1489e";
+
+    #[test]
+    fn proof_matches_sequential_fixture() {
+        let params = SetupParams {
+            variable_num: 12,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = Virgo::<Goldilocks64Ext>::setup(params);
+        let polynomial = MultilinearPolynomial::new(
+            (0..1u64 << params.variable_num)
+                .map(|i| Goldilocks64Ext::from_int(i * i + 1))
+                .collect(),
+        );
+        let point = (0..params.variable_num as u64)
+            .map(|i| Goldilocks64Ext::from_int(3 * i + 7))
+            .collect::<Vec<_>>();
+        let prove = || {
+            let (commit, data) = pcs.commit(polynomial.clone());
+            let mut bytes = commit.encode();
+            bytes.extend(pcs.open(&data, &point).1.encode());
+            Blake3Algorithm::hash(&bytes)
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        };
+        for_each_pool(|_, pool| assert_eq!(pool.install(prove), SEQUENTIAL_PROOF));
+    }
+
+    #[test]
+    fn zero_polynomial() {
+        let params = SetupParams {
+            variable_num: 8,
+            pcs: PcsParams {
+                query_num: 10,
+                grinding_bits: 0,
+                ..PcsParams::default()
+            },
+        };
+        let pcs = Virgo::<Mersenne61Ext>::setup(params);
+        let polynomial =
+            MultilinearPolynomial::new(vec![Mersenne61Ext::from_int(0); 1 << params.variable_num]);
+        let point = (0..params.variable_num)
+            .map(|_| Mersenne61Ext::random_element())
+            .collect::<Vec<_>>();
+        let (commit, data) = pcs.commit(polynomial);
+        let (eval, proof) = pcs.open(&data, &point);
+        assert_eq!(eval, Mersenne61Ext::from_int(0));
+        let proof = Proof::<Mersenne61Ext>::decode(&proof.encode()).unwrap();
+        assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+    }
+
+    #[test]
+    fn folding_schedules() {
+        let variable_num = 5;
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        // uniform steps, most of them leaving a remainder round
+        let uniform = (1..variable_num + 1).map(|step| PcsParams { step, ..params });
+        let scheduled = FoldingSchedule::all(variable_num)
+            .into_iter()
+            .map(|schedule| PcsParams {
+                schedule: Some(schedule),
+                ..params
+            });
+        for pcs in uniform.chain(scheduled) {
+            let pcs = Virgo::<Mersenne61Ext>::setup(SetupParams { variable_num, pcs });
+            let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+            let point = (0..variable_num)
+                .map(|_| Mersenne61Ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = pcs.commit(polynomial);
+            let (eval, proof) = pcs.open(&data, &point);
+            assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+        }
+    }
+
+    #[test]
+    fn early_termination() {
+        let variable_num = 6;
+        let params = PcsParams {
+            query_num: 10,
+            grinding_bits: 0,
+            ..PcsParams::default()
+        };
+        for final_poly_bits in 0..variable_num {
+            for step in 1..3 {
+                let pcs = Virgo::<Mersenne61Ext>::setup(SetupParams {
+                    variable_num,
+                    pcs: PcsParams {
+                        step,
+                        final_poly_bits,
+                        ..params
+                    },
+                });
+                let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+                let point = (0..variable_num)
+                    .map(|_| Mersenne61Ext::random_element())
+                    .collect::<Vec<_>>();
+                let (commit, data) = pcs.commit(polynomial);
+                let (eval, mut proof) = pcs.open(&data, &point);
+                assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+
+                proof.final_poly = Polynomial::random_polynomial((1 << final_poly_bits) + 1);
+                assert_eq!(
+                    pcs.verify(&commit, &point, &eval, &proof),
+                    Err(VerificationError::MalformedProof("final polynomial"))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn merkle_caps() {
+        let variable_num = 8;
+        for cap_bits in 0..8 {
+            let pcs = Virgo::<Mersenne61Ext>::setup(SetupParams {
+                variable_num,
+                pcs: PcsParams {
+                    query_num: 10,
+                    grinding_bits: 0,
+                    step: 2,
+                    cap_bits,
+                    ..PcsParams::default()
+                },
+            });
+            let polynomial = MultilinearPolynomial::random_polynomial(variable_num);
+            let point = (0..variable_num)
+                .map(|_| Mersenne61Ext::random_element())
+                .collect::<Vec<_>>();
+            let (commit, data) = pcs.commit(polynomial);
+            assert!(commit.merkle_root.len() <= 1 << cap_bits);
+            let (eval, mut proof) = pcs.open(&data, &point);
+            assert_eq!(pcs.verify(&commit, &point, &eval, &proof), Ok(()));
+
+            proof.h_root[0][0] ^= 1;
+            assert!(pcs.verify(&commit, &point, &eval, &proof).is_err());
+        }
     }
 
     #[test]
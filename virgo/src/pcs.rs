@@ -0,0 +1,96 @@
+use util::{
+    algebra::{coset::Coset, field::MyField, polynomial::MultilinearPolynomial},
+    error::VerificationError,
+    pcs::{Commitment, MultilinearPCS, SetupParams},
+    transcript::Transcript,
+};
+
+use crate::{prover::FriProver, verifier::FriVerifier, Proof};
+
+pub struct Virgo<T: MyField> {
+    params: SetupParams,
+    interpolate_cosets: Vec<Coset<T>>,
+    vector_interpolation_coset: Coset<T>,
+}
+
+impl<T: MyField> MultilinearPCS<T> for Virgo<T> {
+    type Params = SetupParams;
+    type Polynomial = MultilinearPolynomial<T>;
+    type Evaluation = T;
+    type Commitment = Commitment;
+    type ProverData = FriProver<T>;
+    type Proof = Proof<T>;
+
+    // Both coset shifts are derived from a fixed transcript so that prover
+    // and verifier agree on them without further communication.
+    fn setup(params: SetupParams) -> Self {
+        let mut transcript = Transcript::new(b"virgo setup");
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (params.variable_num + params.pcs.code_rate),
+            transcript.challenge(),
+        )];
+        for i in 1..params.variable_num + 1 {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        let vector_interpolation_coset =
+            Coset::new(1 << params.variable_num, transcript.challenge());
+        Virgo {
+            params,
+            interpolate_cosets,
+            vector_interpolation_coset,
+        }
+    }
+
+    fn commit(&self, poly: MultilinearPolynomial<T>) -> (Commitment, FriProver<T>) {
+        let prover = FriProver::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            &self.vector_interpolation_coset,
+            poly,
+            self.params.pcs,
+        );
+        (prover.commit_first_polynomial(), prover)
+    }
+
+    fn open(&self, data: &FriProver<T>, point: &[T]) -> (T, Proof<T>) {
+        let proof = data.clone().generate_proof(point.to_vec());
+        (proof.evaluation, proof)
+    }
+
+    fn verify(
+        &self,
+        commitment: &Commitment,
+        point: &[T],
+        eval: &T,
+        proof: &Proof<T>,
+    ) -> Result<(), VerificationError> {
+        let schedule = self.params.pcs.folding(self.params.variable_num);
+        let rounds = schedule.rounds();
+        if proof.folding_root.len() != rounds {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        let mut verifier = FriVerifier::new(
+            self.params.variable_num,
+            &self.interpolate_cosets,
+            &self.vector_interpolation_coset,
+            commitment.clone(),
+            point,
+            self.params.pcs,
+        );
+        verifier.set_h_root(proof.h_root.clone());
+        verifier.set_evaluation(proof.evaluation);
+        for (i, root) in proof.folding_root.iter().enumerate() {
+            let size = self.interpolate_cosets[schedule.offset(i + 1)].size();
+            let leave_number = size >> schedule.leaf_step(i + 1, size);
+            verifier.receive_folding_root(leave_number, root.clone());
+        }
+        verifier.set_final_poly(proof.final_poly.clone());
+        verifier.set_pow_nonce(proof.pow_nonce);
+        verifier.verify(
+            &proof.folding_proofs,
+            &proof.v_values,
+            &proof.function_proofs,
+            *eval,
+        )
+    }
+}
@@ -1,12 +1,16 @@
 use std::collections::HashMap;
 
 use super::verifier::FriVerifier;
+use crate::Proof;
 use util::{
     algebra::polynomial::{MultilinearPolynomial, Polynomial, VanishingPolynomial},
-    merkle_tree::MERKLE_ROOT_SIZE,
-    random_oracle::RandomOracle,
+    merkle_tree::{MerkleCap, TreeHash},
+    pcs::{Commitment, PcsParams},
+    schedule::FoldingSchedule,
+    transcript::Transcript,
 };
 
+use util::cfg_into_iter;
 use util::query_result::QueryResult;
 use util::{
     algebra::{
@@ -16,6 +20,9 @@ use util::{
     merkle_tree::MerkleTreeProver,
 };
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
 #[derive(Clone)]
 struct InterpolateValue<T: MyField> {
     value: Vec<T>,
@@ -24,9 +31,10 @@ struct InterpolateValue<T: MyField> {
 }
 
 impl<T: MyField> InterpolateValue<T> {
-    fn new(value: Vec<T>, leaf_size: usize) -> Self {
+    fn new(hash: TreeHash, value: Vec<T>, leaf_size: usize) -> Self {
         let len = value.len() / leaf_size;
         let merkle_tree = MerkleTreeProver::new(
+            hash,
             (0..len)
                 .map(|i| {
                     as_bytes_vec(
@@ -48,26 +56,22 @@ impl<T: MyField> InterpolateValue<T> {
         self.merkle_tree.leave_num()
     }
 
-    fn commit(&self) -> [u8; MERKLE_ROOT_SIZE] {
-        self.merkle_tree.commit()
+    fn commit(&self) -> MerkleCap {
+        self.merkle_tree
+            .commit_cap(self.merkle_tree.hash().cap_bits())
     }
 
     fn query(&self, leaf_indices: &Vec<usize>) -> QueryResult<T> {
         let len = self.merkle_tree.leave_num();
         assert_eq!(len * self.leaf_size, self.value.len());
-        let proof_values = leaf_indices
+        let values = leaf_indices
             .iter()
-            .flat_map(|j: &usize| {
-                (0..self.leaf_size)
-                    .map(|i| (j.clone() + len * i, self.value[j.clone() + len * i]))
-                    .collect::<Vec<_>>()
-            })
+            .flat_map(|j| (0..self.leaf_size).map(move |i| self.value[j + len * i]))
             .collect();
-        let proof_bytes = self.merkle_tree.open(&leaf_indices);
-        QueryResult {
-            proof_bytes,
-            proof_values,
-        }
+        let proof_bytes = self
+            .merkle_tree
+            .open_to_cap(&leaf_indices, self.merkle_tree.hash().cap_bits());
+        QueryResult::new(proof_bytes, leaf_indices.clone(), values, vec![])
     }
 }
 
@@ -82,10 +86,13 @@ pub struct FriProver<T: MyField> {
     poly_u: Polynomial<T>,
     polynomial: MultilinearPolynomial<T>,
     foldings: Vec<InterpolateValue<T>>,
-    oracle: RandomOracle<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    schedule: FoldingSchedule,
+    rlc: Option<T>,
     evaluation: Option<T>,
     final_poly: Option<Polynomial<T>>,
-    step: usize,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> FriProver<T> {
@@ -94,37 +101,57 @@ impl<T: MyField> FriProver<T> {
         fri_cosets: &Vec<Coset<T>>,
         vector_interpolation_coset: &Coset<T>,
         polynomial: MultilinearPolynomial<T>,
-        oracle: &RandomOracle<T>,
-        step: usize,
+        params: PcsParams,
     ) -> FriProver<T> {
         assert_eq!(
             vector_interpolation_coset.size(),
             1 << polynomial.variable_num()
         );
+        let schedule = params.folding(total_round);
         let interpolation = vector_interpolation_coset.ifft(polynomial.coefficients().clone());
+        let function_u = InterpolateValue::new(
+            params.tree_hash::<T>(),
+            fri_cosets[0].fft(interpolation.clone()),
+            1 << schedule.step(0),
+        );
+        let mut transcript = Transcript::new(b"virgo");
+        transcript.append_cap(&function_u.commit());
         FriProver {
             total_round,
             vector_interpolation_coset: vector_interpolation_coset.clone(),
             fri_cosets: fri_cosets.clone(),
             function_h: None,
-            function_u: InterpolateValue::new(fri_cosets[0].fft(interpolation.clone()), 1 << step),
+            function_u,
             interpolation_v: None,
             poly_u: Polynomial::new(interpolation),
             polynomial,
             foldings: vec![],
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            schedule,
+            rlc: None,
             evaluation: None,
             final_poly: None,
-            step,
+            pow_nonce: 0,
         }
     }
 
-    pub fn commit_first_polynomial(&self) -> [u8; MERKLE_ROOT_SIZE] {
-        self.function_u.commit()
+    pub fn commit_first_polynomial(&self) -> Commitment {
+        Commitment {
+            merkle_root: self.function_u.commit(),
+            params: self.params,
+        }
     }
 
     pub fn commit_functions(&mut self, verifier: &mut FriVerifier<T>, open_point: &Vec<T>) {
+        self.open_functions(open_point);
+        verifier.set_h_root(self.function_h.as_ref().unwrap().commit());
+        verifier.set_evaluation(self.evaluation.unwrap());
+    }
+
+    fn open_functions(&mut self, open_point: &Vec<T>) {
         assert_eq!(open_point.len(), self.total_round);
+        self.transcript.append_fields(open_point);
         let mut public_vector = vec![T::from_int(1)];
         for i in open_point {
             let len = public_vector.len();
@@ -139,27 +166,30 @@ impl<T: MyField> FriProver<T> {
             .over_vanish_polynomial(&VanishingPolynomial::new(&self.vector_interpolation_coset));
         assert!(h.degree() < self.vector_interpolation_coset.size());
         let function_h = InterpolateValue::new(
+            self.params.tree_hash::<T>(),
             self.fri_cosets[0].fft(h.coefficients().clone()),
-            1 << self.step,
+            1 << self.schedule.step(0),
         );
-        verifier.set_h_root(function_h.commit());
+        self.transcript.append_cap(&function_h.commit());
         self.function_h = Some(function_h);
         self.interpolation_v = Some(self.fri_cosets[0].fft(poly_v.coefficients().clone()));
         let evaluation = self.polynomial.evaluate(open_point);
+        self.transcript.append_field(&evaluation);
+        self.rlc = Some(self.transcript.challenge());
         self.evaluation = Some(evaluation);
-        verifier.set_evaluation(evaluation);
     }
 
     pub fn commit_foldings(&self, verifier: &mut FriVerifier<T>) {
-        for i in 0..(self.total_round / self.step) {
+        for i in 0..self.schedule.rounds() {
             verifier.receive_folding_root(self.foldings[i].leave_num(), self.foldings[i].commit());
         }
         verifier.set_final_poly(self.final_poly.clone().unwrap());
+        verifier.set_pow_nonce(self.pow_nonce);
         // verifier.set_final_value(self.final_value.unwrap())
     }
 
     fn initial_interpolation(&self) -> Vec<T> {
-        let rlc = self.oracle.rlc;
+        let rlc = self.rlc.unwrap();
         let u = &self.function_u.value;
         let mut res = u.clone();
         let h = &self.function_h.as_ref().unwrap().value;
@@ -185,34 +215,33 @@ impl<T: MyField> FriProver<T> {
 
     fn evaluation_next_domain(&self, round: usize, challenge: Vec<T>) -> Vec<T> {
         let mut res = vec![];
+        let offset = self.schedule.offset(round);
         if round == 0 {
             let mut function = self.initial_interpolation();
-            for j in 0..self.step {
-                let mut tmp_res = vec![];
-                let coset = &self.fri_cosets[round * self.step + j];
+            for j in 0..self.schedule.step(round) {
+                let coset = &self.fri_cosets[offset + j];
                 let len = coset.size();
-                for i in 0..(len / 2) {
-                    let x = function[i];
-                    let nx = function[i + len / 2];
-                    let new_v = (x + nx) + challenge[j] * (x - nx) * coset.element_inv_at(i);
-                    tmp_res.push(new_v);
-                }
-                function = tmp_res;
+                function = cfg_into_iter!(0..(len / 2))
+                    .map(|i| {
+                        let x = function[i];
+                        let nx = function[i + len / 2];
+                        (x + nx) + challenge[j] * (x - nx) * coset.element_inv_at(i)
+                    })
+                    .collect();
             }
             res = function;
         } else {
             let mut last_folding = self.foldings.last().unwrap().value.clone();
-            for j in 0..self.step {
-                let mut tmp_res = vec![];
-                let coset = &self.fri_cosets[round * self.step + j];
+            for j in 0..self.schedule.step(round) {
+                let coset = &self.fri_cosets[offset + j];
                 let len = coset.size();
-                for i in 0..(len / 2) {
-                    let x = last_folding[i];
-                    let nx = last_folding[i + len / 2];
-                    let new_v = (x + nx) + challenge[j] * (x - nx) * coset.element_inv_at(i);
-                    tmp_res.push(new_v);
-                }
-                last_folding = tmp_res;
+                last_folding = cfg_into_iter!(0..(len / 2))
+                    .map(|i| {
+                        let x = last_folding[i];
+                        let nx = last_folding[i + len / 2];
+                        (x + nx) + challenge[j] * (x - nx) * coset.element_inv_at(i)
+                    })
+                    .collect();
             }
             res = last_folding;
         }
@@ -220,33 +249,46 @@ impl<T: MyField> FriProver<T> {
     }
 
     pub fn prove(&mut self) {
-        for i in 0..self.total_round / self.step {
-            let mut challenge = vec![];
-            for j in 0..self.step {
-                challenge.push(self.oracle.folding_challenges[i * self.step + j]);
-            }
-            // let challenge = self.oracle.folding_challenges[i];
+        let rounds = self.schedule.rounds();
+        for i in 0..rounds {
+            let challenge = self.transcript.challenges(self.schedule.step(i));
             let next_evalutation = self.evaluation_next_domain(i, challenge);
-            let interpolate_value = InterpolateValue::new(next_evalutation.clone(), 1 << self.step);
+            // Leaves group what the next round folds together; the last
+            // folding is never opened.
+            let interpolate_value = InterpolateValue::new(
+                self.params.tree_hash::<T>(),
+                next_evalutation.clone(),
+                1 << self.schedule.leaf_step(i + 1, next_evalutation.len()),
+            );
+            self.transcript.append_cap(&interpolate_value.commit());
             self.foldings.push(interpolate_value);
 
-            if i == self.total_round / self.step - 1 {
-                self.final_poly = Some(Polynomial::new(
-                    self.fri_cosets[(i + 1) * self.step].ifft(next_evalutation.clone()),
-                ));
+            if i == rounds - 1 {
+                // Only the low coefficients survive folding down to
+                // `final_poly_bits` variables.
+                let mut coefficients =
+                    self.fri_cosets[self.schedule.total()].ifft(next_evalutation.clone());
+                coefficients.truncate(1 << self.params.final_poly_bits);
+                let final_poly = Polynomial::new(coefficients);
+                self.transcript.append_fields(final_poly.coefficients());
+                self.final_poly = Some(final_poly);
             }
         }
+        self.pow_nonce = self.transcript.grind(self.params.grinding_bits);
     }
 
-    pub fn query(&self) -> (Vec<QueryResult<T>>, Vec<QueryResult<T>>, HashMap<usize, T>) {
+    pub fn query(&mut self) -> (Vec<QueryResult<T>>, Vec<QueryResult<T>>, HashMap<usize, T>) {
         let mut folding_res = vec![];
         let mut functions_res = None;
-        let mut leaf_indices = self.oracle.query_list.clone();
+        let mut leaf_indices = self.transcript.query_list(
+            self.params.query_num,
+            self.fri_cosets[0].size() >> self.schedule.step(0),
+        );
         let mut v_value = None;
-        let leaf_size = 1 << self.step as usize;
+        let leaf_size = 1 << self.schedule.step(0);
 
-        for i in 0..self.total_round / self.step {
-            let len = self.fri_cosets[i * self.step].size() / (1 << self.step);
+        for i in 0..self.schedule.rounds() {
+            let len = self.fri_cosets[self.schedule.offset(i)].size() >> self.schedule.step(i);
             leaf_indices = leaf_indices.iter_mut().map(|v| *v % len).collect();
             leaf_indices.sort();
             leaf_indices.dedup();
@@ -276,4 +318,20 @@ impl<T: MyField> FriProver<T> {
         }
         (folding_res, functions_res.unwrap(), v_value.unwrap())
     }
+
+    pub fn generate_proof(mut self, point: Vec<T>) -> Proof<T> {
+        self.open_functions(&point);
+        self.prove();
+        let (folding_proofs, function_proofs, v_values) = self.query();
+        Proof {
+            h_root: self.function_h.as_ref().unwrap().commit(),
+            evaluation: self.evaluation.unwrap(),
+            folding_root: self.foldings.iter().map(|x| x.commit()).collect(),
+            final_poly: self.final_poly.clone().unwrap(),
+            folding_proofs,
+            function_proofs,
+            v_values,
+            pow_nonce: self.pow_nonce,
+        }
+    }
 }
@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 
 use util::algebra::polynomial::{Polynomial, VanishingPolynomial};
-use util::merkle_tree::MERKLE_ROOT_SIZE;
-use util::query_result::QueryResult;
-use util::random_oracle::RandomOracle;
+use util::error::VerificationError;
+use util::pcs::{Commitment, PcsParams};
+use util::query_result::{fold, QueryResult};
+use util::schedule::FoldingSchedule;
+use util::transcript::Transcript;
 use util::{
     algebra::{coset::Coset, field::MyField},
-    merkle_tree::MerkleTreeVerifier,
+    merkle_tree::{MerkleCap, MerkleTreeVerifier},
 };
 
 #[derive(Clone)]
@@ -17,12 +19,15 @@ pub struct FriVerifier<T: MyField> {
     u_root: MerkleTreeVerifier,
     h_root: Option<MerkleTreeVerifier>,
     folding_root: Vec<MerkleTreeVerifier>,
-    oracle: RandomOracle<T>,
+    transcript: Transcript,
+    params: PcsParams,
+    commit_params: PcsParams,
+    schedule: FoldingSchedule,
     vanishing_polynomial: VanishingPolynomial<T>,
     final_poly: Option<Polynomial<T>>,
     evaluation: Option<T>,
-    open_point: Option<Vec<T>>,
-    step: usize,
+    open_point: Vec<T>,
+    pow_nonce: u64,
 }
 
 impl<T: MyField> FriVerifier<T> {
@@ -30,26 +35,33 @@ impl<T: MyField> FriVerifier<T> {
         total_round: usize,
         coset: &Vec<Coset<T>>,
         vector_interpolation_coset: &Coset<T>,
-        polynomial_commitment: [u8; MERKLE_ROOT_SIZE],
-        oracle: &RandomOracle<T>,
-        step: usize,
+        commit: Commitment,
+        open_point: &[T],
+        params: PcsParams,
     ) -> Self {
+        let schedule = params.folding(total_round);
+        let mut transcript = Transcript::new(b"virgo");
+        transcript.append_cap(&commit.merkle_root);
         FriVerifier {
             total_round,
             interpolate_cosets: coset.clone(),
             vector_interpolation_coset: vector_interpolation_coset.clone(),
-            u_root: MerkleTreeVerifier {
-                leave_number: coset[0].size() / (1 << step),
-                merkle_root: polynomial_commitment,
-            },
+            u_root: MerkleTreeVerifier::new(
+                params.tree_hash::<T>(),
+                coset[0].size() / (1 << schedule.step(0)),
+                &commit.merkle_root,
+            ),
             h_root: None,
             folding_root: vec![],
-            oracle: oracle.clone(),
+            transcript,
+            params,
+            commit_params: commit.params,
+            schedule,
             vanishing_polynomial: VanishingPolynomial::new(vector_interpolation_coset),
             final_poly: None,
-            open_point: None,
+            open_point: open_point.to_vec(),
             evaluation: None,
-            step,
+            pow_nonce: 0,
         }
     }
 
@@ -57,142 +69,159 @@ impl<T: MyField> FriVerifier<T> {
         self.evaluation = Some(v);
     }
 
-    pub fn get_open_point(&mut self) -> Vec<T> {
-        let point = (0..self.total_round)
-            .map(|_| T::random_element())
-            .collect::<Vec<T>>();
-        self.open_point = Some(point.clone());
-        point
+    pub fn set_h_root(&mut self, h_root: MerkleCap) {
+        self.h_root = Some(MerkleTreeVerifier::new(
+            self.params.tree_hash::<T>(),
+            self.interpolate_cosets[0].size() / (1 << self.schedule.step(0)),
+            &h_root,
+        ));
     }
 
-    pub fn set_h_root(&mut self, h_root: [u8; MERKLE_ROOT_SIZE]) {
-        self.h_root = Some(MerkleTreeVerifier {
-            merkle_root: h_root,
-            leave_number: self.interpolate_cosets[0].size() / (1 << self.step),
-        });
-    }
-
-    pub fn receive_folding_root(
-        &mut self,
-        leave_number: usize,
-        folding_root: [u8; MERKLE_ROOT_SIZE],
-    ) {
-        self.folding_root.push(MerkleTreeVerifier {
+    pub fn receive_folding_root(&mut self, leave_number: usize, folding_root: MerkleCap) {
+        self.folding_root.push(MerkleTreeVerifier::new(
+            self.params.tree_hash::<T>(),
             leave_number,
-            merkle_root: folding_root,
-        });
+            &folding_root,
+        ));
     }
 
     pub fn set_final_poly(&mut self, poly: Polynomial<T>) {
         self.final_poly = Some(poly);
     }
 
+    pub fn set_pow_nonce(&mut self, nonce: u64) {
+        self.pow_nonce = nonce;
+    }
+
+    // Derives the batching coefficient, folding challenges and queries in the prover's order.
+    fn replay_transcript(&self) -> Result<(T, Vec<T>, Vec<usize>), VerificationError> {
+        let mut transcript = self.transcript.clone();
+        transcript.append_fields(&self.open_point);
+        transcript.append_cap(&self.h_root.as_ref().unwrap().merkle_cap);
+        transcript.append_field(&self.evaluation.unwrap());
+        let rlc = transcript.challenge();
+        let mut folding_challenges = vec![];
+        for i in 0..self.schedule.rounds() {
+            folding_challenges.append(&mut transcript.challenges(self.schedule.step(i)));
+            transcript.append_cap(&self.folding_root[i].merkle_cap);
+        }
+        transcript.append_fields(self.final_poly.as_ref().unwrap().coefficients());
+        if !transcript.check_grinding(self.params.grinding_bits, self.pow_nonce) {
+            return Err(VerificationError::InsufficientWork);
+        }
+        let query_list = transcript.query_list(
+            self.params.query_num,
+            self.interpolate_cosets[0].size() >> self.schedule.step(0),
+        );
+        Ok((rlc, folding_challenges, query_list))
+    }
+
     pub fn verify(
         &self,
         folding_proofs: &Vec<QueryResult<T>>,
         v_values: &HashMap<usize, T>,
         function_proofs: &Vec<QueryResult<T>>,
-    ) -> bool {
-        let mut leaf_indices = self.oracle.query_list.clone();
-        let rlc = self.oracle.rlc;
+        evaluation: T,
+    ) -> Result<(), VerificationError> {
+        if self.commit_params != self.params {
+            return Err(VerificationError::ParameterMismatch);
+        }
+        if self.open_point.len() != self.total_round {
+            return Err(VerificationError::MalformedProof("open point"));
+        }
+        let rounds = self.schedule.rounds();
+        if self.h_root.is_none() || self.evaluation.is_none() || self.final_poly.is_none() {
+            return Err(VerificationError::MalformedProof("missing commitment"));
+        }
+        if self.final_poly.as_ref().unwrap().coefficients().len() > 1 << self.params.final_poly_bits
+        {
+            return Err(VerificationError::MalformedProof("final polynomial"));
+        }
+        if self.evaluation != Some(evaluation) {
+            return Err(VerificationError::EvaluationMismatch);
+        }
+        if self.folding_root.len() != rounds
+            || folding_proofs.len() != rounds - 1
+            || function_proofs.len() != 2
+        {
+            return Err(VerificationError::MalformedProof("round count"));
+        }
+        let (rlc, folding_challenges, mut leaf_indices) = self.replay_transcript()?;
         let h_size = T::from_int(self.vector_interpolation_coset.size() as u64);
-        for i in 0..self.total_round / self.step {
+        for i in 0..rounds {
             // cauchy: verify mt and define get_folding_value fn outside step loop
-            let len = self.interpolate_cosets[i * self.step].size() / (1 << self.step);
+            let step = self.schedule.step(i);
+            let offset = self.schedule.offset(i);
+            let len = self.interpolate_cosets[offset].size() >> step;
             leaf_indices = leaf_indices.iter_mut().map(|v| *v % len).collect();
             leaf_indices.sort();
             leaf_indices.dedup();
 
             if i == 0 {
-                assert!(function_proofs[0].verify_merkle_tree(
+                function_proofs[0].verify_merkle_tree(i, &leaf_indices, 1 << step, &self.u_root)?;
+                function_proofs[1].verify_merkle_tree(
+                    i,
                     &leaf_indices,
-                    1 << self.step,
-                    &self.u_root
-                ));
-                assert!(function_proofs[1].verify_merkle_tree(
-                    &leaf_indices,
-                    1 << self.step,
-                    &self.h_root.as_ref().unwrap()
-                ));
+                    1 << step,
+                    self.h_root.as_ref().unwrap(),
+                )?;
             } else {
                 folding_proofs[i - 1].verify_merkle_tree(
+                    i,
                     &leaf_indices,
-                    1 << self.step,
+                    1 << step,
                     &self.folding_root[i - 1],
-                );
+                )?;
             }
 
-            let get_folding_value = |index: &usize| {
-                if i == 0 {
-                    let u = function_proofs[0].proof_values[index];
-                    let h = function_proofs[1].proof_values[index];
-                    let v = v_values[index];
-                    let x = self.interpolate_cosets[i * self.step].element_at(*index);
-                    let x_inv = self.interpolate_cosets[i * self.step].element_inv_at(*index);
+            // Entry `j` of the `position`-th leaf of the initial combination.
+            let initial_value = |position: usize, j: usize| -> Result<T, VerificationError> {
+                let index = leaf_indices[position] + j * len;
+                let u = function_proofs[0].leaf(position)[j];
+                let h = function_proofs[1].leaf(position)[j];
+                let v = *v_values
+                    .get(&index)
+                    .ok_or(VerificationError::MalformedProof("missing v value"))?;
+                let x = self.interpolate_cosets[offset].element_at(index);
+                let x_inv = self.interpolate_cosets[offset].element_inv_at(index);
 
-                    let mut res = u;
-                    let mut acc = rlc;
-                    res += h * acc;
-                    acc *= rlc;
-                    res += acc
-                        * (u * v * h_size
-                            - self.vanishing_polynomial.evaluation_at(x) * h * h_size
-                            - self.evaluation.unwrap())
-                        * x_inv;
-                    res
-                } else {
-                    folding_proofs[i - 1].proof_values[index]
-                }
+                let mut res = u;
+                let mut acc = rlc;
+                res += h * acc;
+                acc *= rlc;
+                res += acc
+                    * (u * v * h_size
+                        - self.vanishing_polynomial.evaluation_at(x) * h * h_size
+                        - self.evaluation.unwrap())
+                    * x_inv;
+                Ok(res)
             };
 
-            // cauchy: get folding values for multi steps
-            for k in &leaf_indices {
-                let mut x;
-                let mut nx;
-                let mut verify_values = vec![];
-                let mut verify_inds = vec![];
-                for j in 0..(1 << self.step) {
-                    // Init verify values, which is the total values in the first step
-                    let ind = k + j * len;
-                    verify_values.push(get_folding_value(&ind));
-                    verify_inds.push(ind);
-                }
-
-                for j in 0..self.step {
-                    let challenge = self.oracle.folding_challenges[i * self.step + j];
-                    let size = verify_values.len();
-                    let mut tmp_values = vec![];
-                    let mut tmp_inds = vec![];
-                    for l in 0..size / 2 {
-                        x = verify_values[l];
-                        nx = verify_values[l + size / 2];
-                        tmp_values.push(
-                            x + nx
-                                + challenge
-                                    * (x - nx)
-                                    * self.interpolate_cosets[i * self.step + j]
-                                        .element_inv_at(verify_inds[l]),
-                        );
-                        tmp_inds.push(verify_inds[l]);
-                    }
-                    verify_values = tmp_values;
-                    verify_inds = tmp_inds;
-                }
-                assert_eq!(verify_values.len(), 1);
-
-                let v = verify_values[0];
-                if i < self.total_round / self.step - 1 {
-                    if v != folding_proofs[i].proof_values[k] {
-                        panic!("{}", i)
-                    }
+            let cosets = &self.interpolate_cosets[offset..];
+            let challenges = &folding_challenges[offset..offset + step];
+            for (position, k) in leaf_indices.iter().enumerate() {
+                let v = if i == 0 {
+                    let values = (0..1 << step)
+                        .map(|j| initial_value(position, j))
+                        .collect::<Result<Vec<_>, VerificationError>>()?;
+                    fold(values, *k, cosets, challenges)
+                } else {
+                    folding_proofs[i - 1].fold(position, cosets, challenges)
+                };
+                let expected = if i < rounds - 1 {
+                    folding_proofs[i].entry(*k, self.folding_root[i].leave_number)?
                 } else {
-                    let point = self.interpolate_cosets[(i + 1) * self.step].element_at(*k);
-                    if v != self.final_poly.as_ref().unwrap().evaluation_at(point) {
-                        panic!()
-                    }
+                    let point = self.interpolate_cosets[offset + step].element_at(*k);
+                    self.final_poly.as_ref().unwrap().evaluation_at(point)
+                };
+                if v != expected {
+                    return Err(VerificationError::FoldingMismatch {
+                        round: i,
+                        index: *k,
+                    });
                 }
             }
         }
-        true
+        Ok(())
     }
 }
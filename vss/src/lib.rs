@@ -6,7 +6,8 @@ use util::{
         field::{as_bytes_vec, MyField},
         polynomial::MultilinearPolynomial,
     },
-    merkle_tree::{MerkleRoot, MerkleTreeProver},
+    error::VerificationError,
+    merkle_tree::{HashChoice, MerkleRoot, MerkleTreeProver},
 };
 
 struct Dealer<T: MyField> {
@@ -44,8 +45,10 @@ impl<T: MyField> Dealer<T> {
                 let b = (sharing[j] + sharing[j + len]) * T::inverse_2();
                 line.push((k, b));
             }
-            let tree =
-                MerkleTreeProver::new(line.iter().map(|x| as_bytes_vec(&[x.0, x.1])).collect());
+            let tree = MerkleTreeProver::new(
+                HashChoice::Blake3.into(),
+                line.iter().map(|x| as_bytes_vec(&[x.0, x.1])).collect(),
+            );
             let r = T::from_hash(tree.commit());
             self.fiat_shamir.push(tree);
             for j in 0..len {
@@ -84,15 +87,15 @@ struct Proof<T: MyField> {
 }
 
 impl<T: MyField> Proof<T> {
-    pub fn get_challenge(&self, index: usize, round: usize) -> T {
+    pub fn get_challenge(&self, index: usize, round: usize) -> Result<T, VerificationError> {
         let num = 1 << (self.lines.len() - round);
         let root = MerkleRoot::get_root(
             self.merkle_paths[round].clone(),
             index & (num - 1),
             as_bytes_vec(&[self.lines[round].0, self.lines[round].1]),
             num,
-        );
-        T::from_hash(root)
+        )?;
+        Ok(T::from_hash(root))
     }
 }
 
@@ -112,7 +115,7 @@ impl<T: MyField> Party<T> {
 
         assert_eq!(share, lines[0].0 * point + lines[0].1);
         for i in 0..lines.len() {
-            let r = proof.get_challenge(self.index, i);
+            let r = proof.get_challenge(self.index, i).unwrap();
             point *= point;
             if i == lines.len() - 1 {
                 assert_eq!(lines[i].0 * r + lines[i].1, proof.final_value);